@@ -0,0 +1,107 @@
+/*
+ * C interface of gpscan, built with the gpscan-capi feature:
+ *
+ *     cargo rustc --release --lib --features gpscan-capi --crate-type cdylib
+ *
+ * The callback is called on the thread that calls gpscan_scan().
+ */
+#ifndef GPSCAN_H
+#define GPSCAN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return values of gpscan_scan(), the same as the exit codes of the command-line tool */
+#define GPSCAN_SUCCESS 0
+#define GPSCAN_USAGE_ERROR 1
+#define GPSCAN_INVALID_ROOT 2
+#define GPSCAN_OUTPUT_ERROR 3
+#define GPSCAN_TOO_MANY_ERRORS 7
+/* The callback returned non-zero */
+#define GPSCAN_STOPPED (-1)
+
+/* Value of gpscan_entry.modified when the modification time is unknown */
+#define GPSCAN_TIME_UNKNOWN INT64_MIN
+
+typedef struct gpscan_options gpscan_options;
+
+typedef enum gpscan_event {
+    /* A folder is entered, before any of its children */
+    GPSCAN_ENTER_DIR = 0,
+    /* A file that is not skipped */
+    GPSCAN_FILE = 1,
+    /* All children of the most recently entered folder have been reported */
+    GPSCAN_LEAVE_DIR = 2,
+    /* An entry is skipped because it could not be read; only path is set */
+    GPSCAN_ERROR = 3,
+} gpscan_event;
+
+typedef enum gpscan_kind {
+    GPSCAN_KIND_FILE = 0,
+    GPSCAN_KIND_DIR = 1,
+    GPSCAN_KIND_SYMLINK = 2,
+    GPSCAN_KIND_JUNCTION = 3,
+    GPSCAN_KIND_OTHER = 4,
+} gpscan_kind;
+
+/* An entry passed to the callback. The strings are only valid during the call. */
+typedef struct gpscan_entry {
+    gpscan_event event;
+    gpscan_kind kind;
+    /* Full path of the entry */
+    const char *path;
+    /* Name written to the output (UTF-8) */
+    const char *name;
+    /* Depth below the scan root, which has depth 0 */
+    size_t depth;
+    /* Size according to the selected size measure; 0 for folders */
+    uint64_t size;
+    /* Apparent size in bytes */
+    uint64_t apparent_size;
+    /* Modification time in seconds since the Unix epoch, or GPSCAN_TIME_UNKNOWN */
+    int64_t modified;
+} gpscan_entry;
+
+/* Called for each entry; returning non-zero stops the scan */
+typedef int (*gpscan_callback)(const gpscan_entry *entry, void *user_data);
+
+/* Creates scan options with the defaults of the library */
+gpscan_options *gpscan_options_new(void);
+/* Frees options created with gpscan_options_new(); NULL is ignored */
+void gpscan_options_free(gpscan_options *options);
+
+/* Uses the apparent size instead of disk usage */
+void gpscan_options_set_apparent_size(gpscan_options *options, bool value);
+/* Crosses file system boundaries during the scan */
+void gpscan_options_set_cross_mount_points(gpscan_options *options, bool value);
+/* Reports zero-byte files */
+void gpscan_options_set_include_zero_files(gpscan_options *options, bool value);
+/* Reports empty folders */
+void gpscan_options_set_include_empty_folders(gpscan_options *options, bool value);
+/* Skips files and folders whose name matches the pattern (* and ? wildcards) */
+int gpscan_options_add_exclude(gpscan_options *options, const char *pattern);
+
+/*
+ * Scans the directory at path, calling callback for each entry in depth-first order.
+ * options may be NULL for the defaults. Returns GPSCAN_SUCCESS, GPSCAN_STOPPED, or the
+ * exit code of the command-line tool for the failure, described by gpscan_last_error().
+ */
+int gpscan_scan(const char *path, const gpscan_options *options, gpscan_callback callback,
+                void *user_data);
+
+/*
+ * Returns the message of the last failure on this thread, or NULL. The string is valid
+ * until the next call into the library on the same thread.
+ */
+const char *gpscan_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GPSCAN_H */
@@ -0,0 +1,173 @@
+// External crates
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+
+// Standard library imports
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use crate::filters;
+use crate::model::FileNode;
+use crate::options::Options;
+use crate::platform::open_noatime;
+use crate::scan::sort_nodes;
+
+/// Archive formats whose contents can be listed during a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Detects the format from the file name extension.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Value of the `archive` attribute of the folder representing the archive.
+    fn as_str(self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// Lists the archive at `path` as a folder named `name`.
+///
+/// File sizes are the uncompressed sizes recorded in the archive. The folder is
+/// marked with an `archive` attribute holding the format.
+pub(crate) fn read_archive(
+    path: &Path,
+    name: String,
+    format: ArchiveFormat,
+) -> io::Result<FileNode> {
+    let mut root = FileNode::folder(name);
+    root.attributes
+        .push(("archive".to_string(), format.as_str().to_string()));
+
+    let file = BufReader::new(open_noatime(path)?);
+    match format {
+        ArchiveFormat::Tar => read_tar(file, &mut root)?,
+        ArchiveFormat::TarGz => read_tar(GzDecoder::new(file), &mut root)?,
+        ArchiveFormat::Zip => read_zip(file, &mut root)?,
+    }
+    Ok(root)
+}
+
+/// Applies the filters and the sort order of `options` to the members of an archive,
+/// as the traversal does for directory entries.
+pub(crate) fn filter_members(folder: &mut FileNode, options: &Options) {
+    folder.children.retain_mut(|child| {
+        if filters::skip_by_name(&child.name, options).is_some() {
+            return false;
+        }
+        if child.is_folder() {
+            filter_members(child, options);
+            filters::skip_folder(child.children.len(), options).is_none()
+        } else {
+            filters::skip_file(child.size, options).is_none()
+        }
+    });
+
+    sort_nodes(&mut folder.children, options.sort_order);
+}
+
+fn read_tar<R: Read>(input: R, root: &mut FileNode) -> io::Result<()> {
+    let mut archive = tar::Archive::new(input);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        // Links and special files take no space in the archive
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            continue;
+        }
+
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let modified = header
+            .mtime()
+            .ok()
+            .and_then(|secs| DateTime::<Utc>::from_timestamp(secs as i64, 0));
+        add_member(root, &path, entry_type.is_dir(), entry.size(), modified);
+    }
+    Ok(())
+}
+
+fn read_zip<R: Read + io::Seek>(input: R, root: &mut FileNode) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(input).map_err(io::Error::other)?;
+    for i in 0..archive.len() {
+        // The raw entry is enough for listing and skips decompression
+        let member = archive.by_index_raw(i).map_err(io::Error::other)?;
+        let modified = member.last_modified().and_then(|t| {
+            NaiveDate::from_ymd_opt(t.year().into(), t.month().into(), t.day().into())?
+                .and_hms_opt(t.hour().into(), t.minute().into(), t.second().into())
+                .map(|time| time.and_utc())
+        });
+        add_member(
+            root,
+            member.name(),
+            member.is_dir(),
+            member.size(),
+            modified,
+        );
+    }
+    Ok(())
+}
+
+/// Inserts an archive member into the tree, creating missing parent folders.
+fn add_member(
+    root: &mut FileNode,
+    path: &str,
+    is_dir: bool,
+    size: u64,
+    modified: Option<DateTime<Utc>>,
+) {
+    let components: Vec<&str> = path
+        .split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != "." && *c != "..")
+        .collect();
+    let Some((last, parents)) = components.split_last() else {
+        return;
+    };
+
+    let mut folder = root;
+    for component in parents {
+        folder = child_folder(folder, component);
+    }
+
+    if is_dir {
+        child_folder(folder, last).modified = modified;
+    } else {
+        let mut file = FileNode::file(*last, size);
+        file.modified = modified;
+        folder.children.push(file);
+    }
+}
+
+fn child_folder<'a>(parent: &'a mut FileNode, name: &str) -> &'a mut FileNode {
+    let index = match parent
+        .children
+        .iter()
+        .position(|child| child.is_folder() && child.name == name)
+    {
+        Some(index) => index,
+        None => {
+            parent.children.push(FileNode::folder(name));
+            parent.children.len() - 1
+        }
+    };
+    &mut parent.children[index]
+}
@@ -1,14 +1,196 @@
-use clap::{Arg, ArgMatches, Command};
+use chrono::DateTime;
+use clap::{Arg, ArgGroup, ArgMatches, Command};
+
+// Standard library imports
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::{default_config_path, load_profile};
+use crate::daemon::parse_interval;
+use crate::exit_code;
+use crate::inode_filter::InodeFilter;
+use crate::options::RootName;
+use crate::platform::{is_stream, user_id};
+use crate::split::parse_size;
 
 /// Parses command-line arguments using clap.
+///
+/// With `--profile`, the options of the profile are parsed first, so that the
+/// command line can override them.
 pub fn parse_args() -> ArgMatches {
+    let args: Vec<OsString> = env::args_os().collect();
+    let matches = parse_from(&args);
+    let Some(name) = matches.get_one::<String>("profile") else {
+        return matches;
+    };
+    if matches.subcommand().is_some() {
+        return matches;
+    }
+
+    let config_path = matches
+        .get_one::<String>("config")
+        .map(PathBuf::from)
+        .or_else(default_config_path);
+    let profile = config_path
+        .ok_or_else(|| "No configuration file found (use --config)".to_string())
+        .and_then(|path| load_profile(&path, name, &command()))
+        .unwrap_or_else(|e| usage_error(&e));
+
+    let mut profile_args = vec![args[0].clone()];
+    if !matches.contains_id("directory")
+        && !matches.get_flag("all-volumes")
+        && !matches.get_flag("all-drives")
+    {
+        match profile.root {
+            Some(root) => profile_args.push(root.into()),
+            None => usage_error(&format!("Profile '{}' does not set a root directory", name)),
+        }
+    }
+    profile_args.extend(profile.args.into_iter().map(OsString::from));
+    profile_args.extend(args.into_iter().skip(1));
+    parse_from(&profile_args)
+}
+
+fn parse_from(args: &[OsString]) -> ArgMatches {
+    let matches = command().try_get_matches_from(args).unwrap_or_else(|e| {
+        let _ = e.print();
+        // Help and version requests are not errors
+        std::process::exit(if e.use_stderr() {
+            exit_code::USAGE_ERROR
+        } else {
+            exit_code::SUCCESS
+        })
+    });
+    check_stdout(&matches);
+    check_all_drives(&matches);
+    check_max_memory(&matches);
+    check_encrypt(&matches);
+    matches
+}
+
+/// Rejects `-o -` (stdout), and pipes or devices such as `-o /dev/stdout`, for the modes
+/// that write more than one file or replace the output file.
+fn check_stdout(matches: &ArgMatches) {
+    let target = match matches.get_one::<String>("output") {
+        Some(output) if output == "-" => "stdout (-o -)".to_string(),
+        Some(output) if is_stream(Path::new(output)) => format!("a pipe or device ({})", output),
+        _ => return,
+    };
+    let mut modes: Vec<&str> = ["watch", "daemon", "all-drives"]
+        .into_iter()
+        .filter(|&mode| matches.get_flag(mode))
+        .collect();
+    if matches.contains_id("split-size") {
+        modes.push("split-size");
+    }
+    if let Some(mode) = modes.first() {
+        usage_error(&format!("--{} cannot write to {}", mode, target));
+    }
+}
+
+/// Rejects `--all-drives` outside Windows, where there are no drive letters.
+fn check_all_drives(matches: &ArgMatches) {
+    if cfg!(not(windows)) && matches.get_flag("all-drives") {
+        usage_error("--all-drives is only available on Windows (use --all-volumes)");
+    }
+}
+
+/// Rejects `--max-memory` for the options that hold the whole tree in memory.
+fn check_max_memory(matches: &ArgMatches) {
+    if matches.contains_id("max-memory")
+        && matches.get_one::<String>("sort").map(String::as_str) == Some("size")
+    {
+        usage_error(
+            "--max-memory cannot be combined with --sort size, which holds the tree in memory",
+        );
+    }
+}
+
+/// Checks the `--encrypt` recipients before the scan, so that a typo does not waste it.
+fn check_encrypt(matches: &ArgMatches) {
+    let Some(values) = matches.get_many::<String>("encrypt") else {
+        return;
+    };
+    #[cfg(feature = "encrypt")]
+    if let Err(e) = crate::encrypt::Encryption::parse(values.map(String::as_str)) {
+        usage_error(&e);
+    }
+    #[cfg(not(feature = "encrypt"))]
+    {
+        let _ = values;
+        usage_error("Cannot encrypt the output: gpscan was built without the encrypt feature");
+    }
+}
+
+/// Parses `exact` or `bloom:SIZE`, with SIZE as in [`parse_size`].
+fn parse_inode_filter(value: &str) -> Result<InodeFilter, String> {
+    if value == "exact" {
+        return Ok(InodeFilter::Exact);
+    }
+    let size = value
+        .strip_prefix("bloom:")
+        .ok_or_else(|| format!("invalid inode filter (use exact or bloom:SIZE): {}", value))?;
+    parse_size(size).map(InodeFilter::Bloom)
+}
+
+/// Parses a percentage such as `1%` or `0.5%` into a fraction of more than 0, up to 1.
+fn parse_sample_rate(value: &str) -> Result<f64, String> {
+    value
+        .strip_suffix('%')
+        .and_then(|percent| percent.trim().parse::<f64>().ok())
+        .map(|percent| percent / 100.0)
+        .filter(|rate| *rate > 0.0 && *rate <= 1.0)
+        .ok_or_else(|| {
+            format!(
+                "invalid sample rate (use a percentage up to 100%, such as 1%): {}",
+                value
+            )
+        })
+}
+
+/// Parses a time as a duration before now, such as `7d`, or as an RFC 3339 timestamp.
+fn parse_cutoff(value: &str) -> Result<SystemTime, String> {
+    if let Ok(age) = parse_interval(value) {
+        return SystemTime::now()
+            .checked_sub(age)
+            .ok_or_else(|| format!("cutoff is too far in the past: {}", value));
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(SystemTime::from)
+        .map_err(|_| {
+            format!(
+                "invalid cutoff (use a duration such as 7d or an RFC 3339 time): {}",
+                value
+            )
+        })
+}
+
+/// Parses a user ID, or looks up the ID of a user name.
+fn parse_user(value: &str) -> Result<u32, String> {
+    if let Ok(uid) = value.parse() {
+        return Ok(uid);
+    }
+    user_id(value).ok_or_else(|| format!("unknown user: {}", value))
+}
+
+fn usage_error(message: &str) -> ! {
+    let _ = command()
+        .error(clap::error::ErrorKind::InvalidValue, message)
+        .print();
+    std::process::exit(exit_code::USAGE_ERROR)
+}
+
+/// Builds the command-line interface.
+fn command() -> Command {
     let bold_underline = "\x1b[1;4m";
     let bold = "\x1b[1m";
     let reset = "\x1b[0m";
 
     Command::new("gpscan")
         .version(clap::crate_version!())
-        .about(&format!(
+        .about(format!(
             "\n\n{}Program:{} {}gpscan{} (GrandPerspective XML Scan Dump)\n\
             Version: {}\n\
             Source:  https://github.com/kojix2/gpscan",
@@ -20,18 +202,89 @@ pub fn parse_args() -> ArgMatches {
         ))
         .arg(
             Arg::new("directory")
-                .help("The directory to scan (required)")
+                .help("The directory to scan (required unless set by --profile, --all-volumes, or --all-drives)")
                 .index(1)
-                .required(true),
+                .required_unless_present_any(["profile", "all-volumes", "all-drives"]),
         )
         .arg(
             Arg::new("output")
                 .short('o')
                 .long("output")
                 .value_name("FILE")
-                .help("Output file (default: stdout)")
+                .help("Output file, or - for stdout, with {hostname}, {date}, and {timestamp} expanded (default: stdout)")
                 .num_args(1),
         )
+        .arg(
+            Arg::new("output-fd")
+                .long("output-fd")
+                .value_name("FD")
+                .help("Write the output to an open file descriptor, such as 3 with 3>file (Unix only)")
+                .value_parser(clap::value_parser!(i32).range(0..))
+                .conflicts_with_all(["output", "output-url"]),
+        )
+        .arg(
+            Arg::new("output-url")
+                .long("output-url")
+                .value_name("URL")
+                .help("Upload the gzip-compressed output to an http(s):// or s3:// URL")
+                .conflicts_with("output")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("fallback-output")
+                .long("fallback-output")
+                .value_name("FILE")
+                .help("Continue the output in this file if the disk of the output file fills up")
+                .requires("output")
+                .conflicts_with_all(["split-size", "watch", "daemon", "all-volumes", "all-drives"])
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .help("Compress the output with gzip [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("checksum")
+                .long("checksum")
+                .value_name("ALGORITHM")
+                .help("Print the digest of the output and write it to <output>.sha256")
+                .value_parser(["sha256"])
+                .conflicts_with_all(["output-url", "split-size"]),
+        )
+        .arg(
+            Arg::new("encrypt")
+                .long("encrypt")
+                .value_name("RECIPIENT")
+                .help("Encrypt the output with age to age:<public key> (repeatable), or to a password from GPSCAN_PASSWORD with 'password'")
+                .action(clap::ArgAction::Append)
+                .conflicts_with("split-size"),
+        )
+        .arg(
+            Arg::new("split-size")
+                .long("split-size")
+                .value_name("SIZE")
+                .help("Split the output into numbered files of about SIZE (e.g. 500M, 1G), after compression")
+                .value_parser(parse_size)
+                .requires("output")
+                .conflicts_with_all(["watch", "daemon", "all-volumes", "all-drives"]),
+        )
+        .arg(
+            Arg::new("max-memory")
+                .long("max-memory")
+                .value_name("SIZE")
+                .help("Limit the memory used for hard link and loop detection only, not listings (e.g. 512M, 1G)")
+                .value_parser(parse_size)
+                .conflicts_with("folder-totals"),
+        )
+        .arg(
+            Arg::new("inode-filter")
+                .long("inode-filter")
+                .value_name("FILTER")
+                .help("Remember files for hard link detection in an exact set or a fixed-size Bloom filter (exact, bloom:SIZE)")
+                .value_parser(parse_inode_filter),
+        )
         .arg(
             Arg::new("apparent-size")
                 .short('A')
@@ -39,6 +292,60 @@ pub fn parse_args() -> ArgMatches {
                 .help("Use apparent size instead of disk usage [false]")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("both-sizes")
+                .long("both-sizes")
+                .help("Also record the other size measure of each file (logicalSize or physicalSize) [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("allocation-unit")
+                .long("allocation-unit")
+                .help("Round disk usage up to the block or cluster size of each file system [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dir-entry-size")
+                .long("dir-entry-size")
+                .help("Count the size of each directory itself, as a file named \".\" in it [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("all-volumes")
+                .long("all-volumes")
+                .help("Scan every mounted volume, into one dump or one per {volume} in the output name [false]")
+                .conflicts_with_all(["directory", "watch", "daemon", "baseline", "report", "metrics-textfile"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("all-drives")
+                .long("all-drives")
+                .help("Scan every fixed drive on Windows, into one dump per drive [false]")
+                .requires("output")
+                .conflicts_with_all(["directory", "all-volumes", "watch", "daemon", "baseline", "report", "metrics-textfile"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("removable-drives")
+                .long("removable-drives")
+                .help("Also scan removable drives with --all-drives [false]")
+                .requires("all-drives")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("network-drives")
+                .long("network-drives")
+                .help("Also scan network drives with --all-drives [false]")
+                .requires("all-drives")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("parallel-volumes")
+                .long("parallel-volumes")
+                .help("Scan volumes on different disks at the same time with --all-volumes (disks are only known on Linux) [false]")
+                .requires("volume-modes")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("mounts")
                 .short('m')
@@ -46,6 +353,50 @@ pub fn parse_args() -> ArgMatches {
                 .help("Cross filesystem boundaries during scan [false]")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("follow-root-symlink")
+                .long("follow-root-symlink")
+                .value_name("BOOL")
+                .help("Scan the target of a root directory that is a symbolic link, named after the path as given; =false rejects such a root [true]")
+                .value_parser(clap::value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true"),
+        )
+        .arg(
+            Arg::new("comment")
+                .long("comment")
+                .value_name("TEXT")
+                .help("Record a description of the scan in the ScanInfo of the dump")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("snapshot")
+                .long("snapshot")
+                .value_name("NAME")
+                .help("Scan the named APFS snapshot of the volume instead of the live files (macOS)")
+                .requires("directory")
+                .conflicts_with_all(["watch", "daemon"])
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("mark-subvolumes")
+                .long("mark-subvolumes")
+                .help("Add subvolume=\"true\" to the folders that are btrfs subvolumes [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("record-junctions")
+                .long("record-junctions")
+                .help("Write Windows junctions as empty folders with junction=\"true\" instead of skipping them [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("local-only")
+                .long("local-only")
+                .help("Skip network file systems (NFS, SMB, FUSE) instead of warning about them [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("include-zero-files")
                 .short('z')
@@ -60,13 +411,527 @@ pub fn parse_args() -> ArgMatches {
                 .help("Include empty folders in scan [false]")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .value_name("PATTERN")
+                .help("Skip files and folders whose name matches PATTERN (* and ? wildcards)")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("exclude-user")
+                .long("exclude-user")
+                .value_name("USER")
+                .help("Skip files owned by USER, a user name or ID")
+                .value_parser(parse_user)
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("only-user")
+                .long("only-user")
+                .value_name("USER")
+                .help("Skip files not owned by USER, a user name or ID; may be repeated")
+                .value_parser(parse_user)
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("filter-cmd")
+                .long("filter-cmd")
+                .value_name("COMMAND")
+                .help("Skip the entries that COMMAND answers skip for; it reads their paths on stdin, a folder at a time"),
+        )
+        .arg(
+            Arg::new("scan-archives")
+                .long("scan-archives")
+                .help("List the contents of .tar, .tar.gz, and .zip files as folders [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-atime")
+                .long("no-atime")
+                .help("Write the Unix epoch instead of the access time of each entry [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("times")
+                .long("times")
+                .value_name("SET")
+                .help("Times to write for each entry, leaving out unknown times (default: all, with the Unix epoch for unknown times)")
+                .value_parser(["none", "mtime", "all"]),
+        )
+        .arg(
+            Arg::new("strict-names")
+                .long("strict-names")
+                .help("Fail on names with characters not allowed in XML instead of replacing them [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("log-each-skip")
+                .long("log-each-skip")
+                .help("Log every skipped entry instead of the first few and periodic summaries [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("embed-warnings")
+                .long("embed-warnings")
+                .help("Write skipped entries and read errors into the dump as XML comments [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("folder-totals")
+                .long("folder-totals")
+                .help("Add cumulative sizes and file and folder counts to each folder [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("root-name")
+                .long("root-name")
+                .value_name("NAME")
+                .help("Name of the root folder: path (as given), absolute, basename, or custom:NAME")
+                .value_parser(|s: &str| s.parse::<RootName>())
+                .default_value("path"),
+        )
+        .arg(
+            Arg::new("relative-root")
+                .long("relative-root")
+                .value_name("LABEL")
+                .help("Name the root folder LABEL (. if omitted) instead of its path, for dumps that do not depend on where the data is mounted")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(".")
+                .conflicts_with_all(["root-name", "absolute-paths"]),
+        )
+        .arg(
+            Arg::new("placeholders")
+                .long("placeholders")
+                .value_name("MODE")
+                .help("How to report online-only cloud files")
+                .value_parser(["keep", "zero", "mark"])
+                .default_value("keep"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("ORDER")
+                .help("Order of entries within a folder")
+                .value_parser(["bytes", "name", "natural", "size", "mtime", "none"])
+                .default_value("bytes"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .value_name("N")
+                .help("Retry transient I/O errors up to N times")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("retry-delay")
+                .long("retry-delay")
+                .value_name("MS")
+                .help("Delay between retries in milliseconds")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Exit with status 4 if any entries were skipped due to errors [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("writer-thread")
+                .long("writer-thread")
+                .help("Write the output on a separate thread from the traversal [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-errors")
+                .long("max-errors")
+                .value_name("N")
+                .help("Stop the scan after N entries could not be read, and exit with status 7")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("warn-dir-entries")
+                .long("warn-dir-entries")
+                .value_name("N")
+                .help("Warn about directories with more than N entries")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("skip-dir-entries")
+                .long("skip-dir-entries")
+                .value_name("N")
+                .help("Leave out the entries of directories with more than N entries")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Keep watching for changes and rewrite the output file when the tree changed [false]")
+                .requires("output")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("watch-interval")
+                .long("watch-interval")
+                .value_name("SECONDS")
+                .help("Minimum time between snapshots in watch mode")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("60"),
+        )
+        .arg(
+            Arg::new("daemon")
+                .long("daemon")
+                .help("Keep running and scan every --interval, writing timestamped output files [false]")
+                .requires("output")
+                .conflicts_with("watch")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("interval")
+                .long("interval")
+                .value_name("DURATION")
+                .help("Time between scans in daemon mode (e.g. 30m, 6h, 1d)")
+                .value_parser(parse_interval)
+                .default_value("1d"),
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .value_name("N")
+                .help("Delete all but the N most recent output files in daemon mode")
+                .value_parser(clap::value_parser!(usize))
+                .requires("daemon"),
+        )
+        .arg(
+            Arg::new("classify")
+                .long("classify")
+                .value_name("METHOD")
+                .help("Record the kind of each file (image, video, ...) in a kind attribute and print the kinds report")
+                .value_parser(["extension", "magic"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("extension"),
+        )
+        .arg(
+            Arg::new("uniform-types")
+                .long("uniform-types")
+                .help("Record the uniform type identifier of each file (e.g. public.jpeg) in a type attribute [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("absolute-paths")
+                .long("absolute-paths")
+                .help("Record the full path of each file and folder in a path attribute [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .value_name("KIND")
+                .help("Print the total size and number of files per group after the scan")
+                .value_parser(["extensions", "owners", "kinds"])
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("largest-files")
+                .long("largest-files")
+                .value_name("N")
+                .help("Record the N largest files in a <LargestFiles> section of the dump")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("split-size"),
+        )
+        .arg(
+            Arg::new("print-largest-files")
+                .long("print-largest-files")
+                .help("Print the --largest-files after the scan instead of recording them [false]")
+                .requires("largest-files")
+                .conflicts_with_all(["watch", "daemon", "all-volumes", "all-drives"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("profile-dirs")
+                .long("profile-dirs")
+                .value_name("N")
+                .help("Measure the scan time of each directory and print the N slowest (20 if omitted)")
+                .value_parser(clap::value_parser!(usize))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("20")
+                .conflicts_with_all(["watch", "daemon", "all-volumes", "all-drives"]),
+        )
+        .arg(
+            Arg::new("sample")
+                .long("sample")
+                .value_name("RATE")
+                .help("Scan only about RATE of the files (e.g. 1%), chosen by their paths, and scale up their sizes for a quick estimate")
+                .value_parser(parse_sample_rate)
+                .conflicts_with_all(["both-sizes", "scan-archives", "trust-dir-mtime"]),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .value_name("N")
+                .help("Check N random entries again after the scan and record how many changed during it (1000 if omitted)")
+                .value_parser(clap::value_parser!(usize))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("1000"),
+        )
+        .arg(
+            Arg::new("verify-totals")
+                .long("verify-totals")
+                .help("Compare the scanned total with the used space of the volume and explain the difference [false]")
+                .conflicts_with_all(["watch", "daemon", "all-volumes", "all-drives"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("report-format")
+                .long("report-format")
+                .value_name("FORMAT")
+                .help("Format of the --report tables")
+                .value_parser(["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("baseline")
+                .long("baseline")
+                .value_name("FILE")
+                .help("Print the folders that grew the most since the scan dump FILE"),
+        )
+        .arg(
+            Arg::new("baseline-count")
+                .long("baseline-count")
+                .value_name("N")
+                .help("Number of folders to print with --baseline")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("trust-dir-mtime")
+                .long("trust-dir-mtime")
+                .value_name("CUTOFF")
+                .help("Copy folders whose directory was not modified since CUTOFF (e.g. 7d or 2024-06-01T00:00:00Z) from the --dir-cache dump")
+                .value_parser(parse_cutoff)
+                .requires("dir-cache")
+                .conflicts_with_all(["watch", "daemon", "all-volumes", "all-drives"]),
+        )
+        .arg(
+            Arg::new("dir-cache")
+                .long("dir-cache")
+                .value_name("FILE")
+                .help("Previous scan dump of the same directory for --trust-dir-mtime")
+                .requires("trust-dir-mtime"),
+        )
+        .arg(
+            Arg::new("metrics-textfile")
+                .long("metrics-textfile")
+                .value_name("FILE")
+                .help("Write Prometheus metrics of each scan to FILE (for the node_exporter textfile collector)"),
+        )
+        .arg(
+            Arg::new("metrics-listen")
+                .long("metrics-listen")
+                .value_name("ADDR")
+                .help("Serve Prometheus metrics of the latest scan at http://ADDR/metrics in daemon mode")
+                .requires("daemon"),
+        )
+        .arg(
+            Arg::new("notify-url")
+                .long("notify-url")
+                .value_name("URL")
+                .help("POST a JSON summary to URL when a scan finishes or fails"),
+        )
+        .arg(
+            Arg::new("notify-cmd")
+                .long("notify-cmd")
+                .value_name("COMMAND")
+                .help("Run COMMAND with GPSCAN_* summary variables when a scan finishes or fails"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Use the root and options of a profile from the configuration file"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Configuration file with [profiles.NAME] tables (default: ~/.config/gpscan/config.toml)"),
+        )
+        .arg(
+            Arg::new("lock")
+                .long("lock")
+                .help("Exit with status 6 if another gpscan process is scanning the same directory [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("lock-wait")
+                .long("lock-wait")
+                .help("Like --lock, but wait for the other process to finish [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("progress")
+                .short('p')
+                .long("progress")
+                .help("Show scan progress on stderr [false]")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("progress-json")
+                .long("progress-json")
+                .value_name("PATH")
+                .help("Write progress records as JSON lines to stderr, or to PATH such as /dev/fd/3")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("-")
+                .conflicts_with("progress"),
+        )
         .arg(
             Arg::new("quiet")
                 .short('q')
                 .long("quiet")
                 .help("Suppress all informational messages [false]")
+                .global(true)
                 .action(clap::ArgAction::SetTrue),
         )
+        .subcommand(
+            Command::new("validate")
+                .about("Check that a scan dump is complete and well-formed")
+                .arg(
+                    Arg::new("file")
+                        .help("The scan dump to validate (plain or gzip-compressed)")
+                        .index(1)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Combine several scan dumps into one tree under a synthetic root")
+                .arg(
+                    Arg::new("files")
+                        .help("The scan dumps to merge (plain or gzip-compressed)")
+                        .value_name("FILE")
+                        .num_args(1..)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output file, or - for stdout, with {hostname}, {date}, and {timestamp} expanded (default: stdout)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("output-fd")
+                        .long("output-fd")
+                        .value_name("FD")
+                        .help("Write the output to an open file descriptor, such as 3 with 3>file (Unix only)")
+                        .value_parser(clap::value_parser!(i32).range(0..))
+                        .conflicts_with("output"),
+                )
+                .arg(
+                    Arg::new("root-name")
+                        .long("root-name")
+                        .value_name("NAME")
+                        .help("Name of the synthetic root folder")
+                        .default_value("merged"),
+                ),
+        )
+        .subcommand(
+            Command::new("top")
+                .about("List the largest files and directories of a directory or scan dump")
+                .arg(
+                    Arg::new("input")
+                        .help("The directory to scan, or an existing scan dump")
+                        .value_name("PATH")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("count")
+                        .short('n')
+                        .long("count")
+                        .value_name("N")
+                        .help("Number of files and directories to list")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("20"),
+                )
+                .arg(
+                    Arg::new("apparent-size")
+                        .short('A')
+                        .long("apparent-size")
+                        .help("Use apparent size instead of disk usage when scanning [false]")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("mounts")
+                        .short('m')
+                        .long("mounts")
+                        .help("Cross filesystem boundaries when scanning [false]")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Convert ncdu JSON exports or du output into a scan dump")
+                .arg(
+                    Arg::new("file")
+                        .help("The file to convert, or - for stdin (may be gzip-compressed)")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_name("FORMAT")
+                        .help("Format of the input")
+                        .value_parser(["ncdu", "du"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output file, or - for stdout, with {hostname}, {date}, and {timestamp} expanded (default: stdout)")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("output-fd")
+                        .long("output-fd")
+                        .value_name("FD")
+                        .help("Write the output to an open file descriptor, such as 3 with 3>file (Unix only)")
+                        .value_parser(clap::value_parser!(i32).range(0..))
+                        .conflicts_with("output"),
+                )
+                .arg(
+                    Arg::new("apparent-size")
+                        .short('A')
+                        .long("apparent-size")
+                        .help("Use the apparent size of ncdu entries instead of disk usage [false]")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("block-size")
+                        .long("block-size")
+                        .value_name("BYTES")
+                        .help("Unit of the sizes in du output (use 1 for du -b)")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("1024"),
+                ),
+        )
+        // A group, as clap does not require --all-volumes while the directory it conflicts
+        // with is given
+        .group(ArgGroup::new("volume-modes").arg("all-volumes"))
+        .subcommand_negates_reqs(true)
         .arg_required_else_help(true)
-        .get_matches()
+        // Options from a profile are followed by those on the command line
+        .args_override_self(true)
 }
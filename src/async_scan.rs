@@ -0,0 +1,62 @@
+// Standard library imports
+use std::future::Future;
+use std::io;
+use std::panic;
+use std::path::Path;
+
+use crate::cancel::CancellationToken;
+use crate::error::{Error, Result};
+use crate::model::ScanResult;
+use crate::options::Options;
+use crate::scan::{scan_to_result, ScanStats};
+
+/// Scans the directory at `root_path` into a [`ScanResult`] without blocking the async runtime.
+///
+/// The scan runs as [`scan_to_result`] on tokio's blocking thread pool, so it must be awaited
+/// within a tokio runtime. Dropping the future before it completes cancels the scan, unless
+/// the options carry their own [`CancellationToken`], which is then left to the caller.
+///
+/// ```no_run
+/// # async fn handler() -> gpscan::Result<()> {
+/// use gpscan::{scan_async, Options};
+///
+/// let (result, stats) = scan_async("/srv", &Options::default()).await?;
+/// println!("{} bytes, {} errors", result.root.total_size(), stats.errors);
+/// # Ok(())
+/// # }
+/// ```
+pub fn scan_async(
+    root_path: impl AsRef<Path>,
+    options: &Options,
+) -> impl Future<Output = Result<(ScanResult, ScanStats)>> + Send + 'static {
+    let root_path = root_path.as_ref().to_path_buf();
+    let mut options = options.clone();
+    let mut guard = CancelOnDrop(None);
+    if options.cancellation.is_none() {
+        let token = CancellationToken::new();
+        options.cancellation = Some(token.clone());
+        guard.0 = Some(token);
+    }
+
+    async move {
+        let task = tokio::task::spawn_blocking(move || scan_to_result(&root_path, &options));
+        let outcome = match task.await {
+            Ok(outcome) => outcome,
+            Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(Error::Io(io::Error::other(e))),
+        };
+        guard.0.take();
+        outcome
+    }
+}
+
+/// Cancels the scan if the future is dropped before the scan completes.
+struct CancelOnDrop(Option<CancellationToken>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(token) = &self.0 {
+            token.cancel();
+        }
+    }
+}
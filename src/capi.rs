@@ -0,0 +1,365 @@
+//! C interface for embedding the scanner in applications written in other languages.
+//!
+//! The declarations are in `include/gpscan.h`. Build the library with
+//! `cargo rustc --release --lib --features gpscan-capi --crate-type cdylib` (or `staticlib`).
+#![allow(non_camel_case_types)]
+
+// Standard library imports
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exit_code;
+use crate::options::Options;
+use crate::scan::{scan_with_visitor, ScanEntry, ScanVisitor};
+use crate::source::EntryKind;
+
+/// Returned by [`gpscan_scan`] when the callback stopped the scan.
+pub const GPSCAN_STOPPED: c_int = -1;
+
+/// Value of [`gpscan_entry::modified`] when the modification time is unknown.
+pub const GPSCAN_TIME_UNKNOWN: i64 = i64::MIN;
+
+/// Scan options, created with [`gpscan_options_new`].
+pub struct gpscan_options(Options);
+
+/// Why the callback is called.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum gpscan_event {
+    /// A folder is entered, before any of its children.
+    GPSCAN_ENTER_DIR = 0,
+    /// A file that is not skipped.
+    GPSCAN_FILE = 1,
+    /// All children of the most recently entered folder have been reported.
+    GPSCAN_LEAVE_DIR = 2,
+    /// An entry is skipped because it could not be read; only `path` is set.
+    GPSCAN_ERROR = 3,
+}
+
+/// Type of a file system entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum gpscan_kind {
+    GPSCAN_KIND_FILE = 0,
+    GPSCAN_KIND_DIR = 1,
+    GPSCAN_KIND_SYMLINK = 2,
+    GPSCAN_KIND_JUNCTION = 3,
+    GPSCAN_KIND_OTHER = 4,
+}
+
+/// An entry passed to the callback. The strings are only valid during the call.
+#[repr(C)]
+#[derive(Debug)]
+pub struct gpscan_entry {
+    pub event: gpscan_event,
+    pub kind: gpscan_kind,
+    /// Full path of the entry.
+    pub path: *const c_char,
+    /// Name written to the output (UTF-8).
+    pub name: *const c_char,
+    /// Depth below the scan root, which has depth 0.
+    pub depth: usize,
+    /// Size according to the selected size measure; 0 for folders.
+    pub size: u64,
+    /// Apparent size in bytes.
+    pub apparent_size: u64,
+    /// Modification time in seconds since the Unix epoch, or `GPSCAN_TIME_UNKNOWN`.
+    pub modified: i64,
+}
+
+/// Called for each entry; returning non-zero stops the scan.
+pub type gpscan_callback =
+    Option<unsafe extern "C" fn(entry: *const gpscan_entry, user_data: *mut c_void) -> c_int>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Creates scan options with the defaults of the library. Free them with [`gpscan_options_free`].
+#[no_mangle]
+pub extern "C" fn gpscan_options_new() -> *mut gpscan_options {
+    Box::into_raw(Box::new(gpscan_options(Options::default())))
+}
+
+/// Frees options created with [`gpscan_options_new`].
+///
+/// # Safety
+///
+/// `options` must be null or returned by [`gpscan_options_new`] and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn gpscan_options_free(options: *mut gpscan_options) {
+    if !options.is_null() {
+        drop(Box::from_raw(options));
+    }
+}
+
+/// Uses the apparent size instead of disk usage.
+///
+/// # Safety
+///
+/// `options` must be valid options returned by [`gpscan_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn gpscan_options_set_apparent_size(
+    options: *mut gpscan_options,
+    value: bool,
+) {
+    if let Some(options) = options.as_mut() {
+        options.0.apparent_size = value;
+    }
+}
+
+/// Crosses file system boundaries during the scan.
+///
+/// # Safety
+///
+/// `options` must be valid options returned by [`gpscan_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn gpscan_options_set_cross_mount_points(
+    options: *mut gpscan_options,
+    value: bool,
+) {
+    if let Some(options) = options.as_mut() {
+        options.0.cross_mount_points = value;
+    }
+}
+
+/// Reports zero-byte files.
+///
+/// # Safety
+///
+/// `options` must be valid options returned by [`gpscan_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn gpscan_options_set_include_zero_files(
+    options: *mut gpscan_options,
+    value: bool,
+) {
+    if let Some(options) = options.as_mut() {
+        options.0.include_zero_files = value;
+    }
+}
+
+/// Reports empty folders.
+///
+/// # Safety
+///
+/// `options` must be valid options returned by [`gpscan_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn gpscan_options_set_include_empty_folders(
+    options: *mut gpscan_options,
+    value: bool,
+) {
+    if let Some(options) = options.as_mut() {
+        options.0.include_empty_folders = value;
+    }
+}
+
+/// Skips files and folders whose name matches the pattern (`*` and `?` wildcards).
+/// Returns 0, or `GPSCAN_USAGE_ERROR` if the arguments are invalid.
+///
+/// # Safety
+///
+/// `options` must be valid options returned by [`gpscan_options_new`], and `pattern`
+/// a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn gpscan_options_add_exclude(
+    options: *mut gpscan_options,
+    pattern: *const c_char,
+) -> c_int {
+    let Some(options) = options.as_mut() else {
+        return usage_error("options is null");
+    };
+    if pattern.is_null() {
+        return usage_error("pattern is null");
+    }
+    match CStr::from_ptr(pattern).to_str() {
+        Ok(pattern) => {
+            options.0.excludes.push(pattern.to_string());
+            exit_code::SUCCESS
+        }
+        Err(_) => usage_error("pattern is not valid UTF-8"),
+    }
+}
+
+/// Scans the directory at `path`, calling `callback` for each entry in depth-first order.
+///
+/// Returns 0 on success, `GPSCAN_STOPPED` if the callback returned non-zero, or the exit
+/// code of the command-line tool for the failure, whose message is then available from
+/// [`gpscan_last_error`].
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string, `options` null or valid options returned by
+/// [`gpscan_options_new`], and `callback` a function that is safe to call with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn gpscan_scan(
+    path: *const c_char,
+    options: *const gpscan_options,
+    callback: gpscan_callback,
+    user_data: *mut c_void,
+) -> c_int {
+    if path.is_null() {
+        return usage_error("path is null");
+    }
+    let Some(callback) = callback else {
+        return usage_error("callback is null");
+    };
+    let Some(root_path) = path_from_c(CStr::from_ptr(path)) else {
+        return usage_error("path is not valid UTF-8");
+    };
+    let default_options = Options::default();
+    let options = options
+        .as_ref()
+        .map_or(&default_options, |options| &options.0);
+
+    let mut visitor = CallbackVisitor {
+        callback,
+        user_data,
+        stopped: false,
+    };
+    match scan_with_visitor(&root_path, options, &mut visitor) {
+        _ if visitor.stopped => GPSCAN_STOPPED,
+        Ok(stats) if stats.too_many_errors => {
+            set_last_error("Too many errors");
+            exit_code::TOO_MANY_ERRORS
+        }
+        Ok(_) => exit_code::SUCCESS,
+        Err(e) => {
+            set_last_error(&e.to_string());
+            e.exit_code()
+        }
+    }
+}
+
+/// Returns the message of the last failure on this thread, or null. The string is valid
+/// until the next call into the library on the same thread.
+#[no_mangle]
+pub extern "C" fn gpscan_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Passes the entries of a scan to the C callback.
+struct CallbackVisitor {
+    callback: unsafe extern "C" fn(*const gpscan_entry, *mut c_void) -> c_int,
+    user_data: *mut c_void,
+    stopped: bool,
+}
+
+impl CallbackVisitor {
+    fn call(&mut self, event: gpscan_event, entry: &ScanEntry) -> io::Result<()> {
+        let path = path_to_c(&entry.path);
+        let name = string_to_c(entry.name.as_bytes());
+        let entry = gpscan_entry {
+            event,
+            kind: kind_to_c(entry.metadata.kind),
+            path: path.as_ptr(),
+            name: name.as_ptr(),
+            depth: entry.depth,
+            size: entry.size,
+            apparent_size: entry.metadata.apparent_size,
+            modified: entry.metadata.modified.map_or(GPSCAN_TIME_UNKNOWN, seconds),
+        };
+        if unsafe { (self.callback)(&entry, self.user_data) } != 0 {
+            self.stopped = true;
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "Scan stopped by the callback",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ScanVisitor for CallbackVisitor {
+    fn enter_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.call(gpscan_event::GPSCAN_ENTER_DIR, entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.call(gpscan_event::GPSCAN_FILE, entry)
+    }
+
+    fn leave_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.call(gpscan_event::GPSCAN_LEAVE_DIR, entry)
+    }
+
+    fn error(&mut self, path: &Path, _error: &io::Error) {
+        // The return value is ignored, since errors cannot stop the scan
+        let path = path_to_c(path);
+        let entry = gpscan_entry {
+            event: gpscan_event::GPSCAN_ERROR,
+            kind: gpscan_kind::GPSCAN_KIND_OTHER,
+            path: path.as_ptr(),
+            name: c"".as_ptr(),
+            depth: 0,
+            size: 0,
+            apparent_size: 0,
+            modified: GPSCAN_TIME_UNKNOWN,
+        };
+        unsafe { (self.callback)(&entry, self.user_data) };
+    }
+}
+
+fn kind_to_c(kind: EntryKind) -> gpscan_kind {
+    match kind {
+        EntryKind::File => gpscan_kind::GPSCAN_KIND_FILE,
+        EntryKind::Dir => gpscan_kind::GPSCAN_KIND_DIR,
+        EntryKind::Symlink => gpscan_kind::GPSCAN_KIND_SYMLINK,
+        EntryKind::Junction => gpscan_kind::GPSCAN_KIND_JUNCTION,
+        EntryKind::Other => gpscan_kind::GPSCAN_KIND_OTHER,
+    }
+}
+
+fn seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+/// Converts a C path: raw bytes on Unix, UTF-8 elsewhere.
+fn path_from_c(path: &CStr) -> Option<PathBuf> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(PathBuf::from(std::ffi::OsStr::from_bytes(path.to_bytes())))
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_str().ok().map(PathBuf::from)
+    }
+}
+
+fn path_to_c(path: &Path) -> CString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        string_to_c(path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        string_to_c(path.to_string_lossy().as_bytes())
+    }
+}
+
+/// Entry names from other sources than the file system may contain NUL; they are cut there.
+fn string_to_c(bytes: &[u8]) -> CString {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    CString::new(&bytes[..end]).unwrap_or_default()
+}
+
+fn usage_error(message: &str) -> c_int {
+    set_last_error(message);
+    exit_code::USAGE_ERROR
+}
+
+fn set_last_error(message: &str) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(string_to_c(message.as_bytes())));
+}
@@ -0,0 +1,238 @@
+// Standard library imports
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Name of the attribute that records the [`FileKind`] of a file.
+pub const KIND_ATTRIBUTE: &str = "kind";
+
+/// Coarse kind of a file, for coloring a treemap without looking at every extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FileKind {
+    Image,
+    Video,
+    Audio,
+    Archive,
+    Code,
+    Document,
+    Other,
+}
+
+impl FileKind {
+    /// All kinds, in the order of the enum.
+    pub const ALL: [FileKind; 7] = [
+        FileKind::Image,
+        FileKind::Video,
+        FileKind::Audio,
+        FileKind::Archive,
+        FileKind::Code,
+        FileKind::Document,
+        FileKind::Other,
+    ];
+
+    /// Returns the name of the kind, as recorded in the `kind` attribute.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileKind::Image => "image",
+            FileKind::Video => "video",
+            FileKind::Audio => "audio",
+            FileKind::Archive => "archive",
+            FileKind::Code => "code",
+            FileKind::Document => "document",
+            FileKind::Other => "other",
+        }
+    }
+
+    /// Classifies a file by the extension of its name, ignoring case.
+    pub fn from_name(name: &str) -> FileKind {
+        let Some(extension) = Path::new(name).extension() else {
+            return FileKind::Other;
+        };
+        match extension.to_string_lossy().to_lowercase().as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "heic" | "heif"
+            | "avif" | "svg" | "ico" | "psd" | "raw" | "cr2" | "cr3" | "nef" | "arw" | "dng"
+            | "xcf" => FileKind::Image,
+            "mp4" | "m4v" | "mkv" | "mov" | "avi" | "wmv" | "webm" | "flv" | "mpg" | "mpeg"
+            | "m2ts" | "mts" | "3gp" | "vob" => FileKind::Video,
+            "mp3" | "m4a" | "aac" | "flac" | "wav" | "aif" | "aiff" | "ogg" | "oga" | "opus"
+            | "wma" | "alac" | "mid" | "midi" => FileKind::Audio,
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "tbz2" | "xz" | "txz" | "zst" | "7z" | "rar"
+            | "lz" | "lz4" | "lzma" | "cab" | "iso" | "dmg" | "img" | "jar" | "deb" | "rpm"
+            | "pkg" | "apk" => FileKind::Archive,
+            "rs" | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "m" | "mm" | "swift" | "go"
+            | "java" | "kt" | "scala" | "cs" | "py" | "rb" | "pl" | "php" | "js" | "mjs"
+            | "cjs" | "jsx" | "ts" | "tsx" | "css" | "scss" | "html" | "htm" | "sh" | "bash"
+            | "zsh" | "fish" | "ps1" | "bat" | "lua" | "r" | "jl" | "hs" | "ml" | "ex" | "exs"
+            | "erl" | "clj" | "dart" | "zig" | "nim" | "cr" | "sql" | "toml" | "yaml" | "yml"
+            | "json" | "xml" | "ipynb" => FileKind::Code,
+            "pdf" | "doc" | "docx" | "odt" | "rtf" | "txt" | "md" | "rst" | "tex" | "xls"
+            | "xlsx" | "ods" | "csv" | "ppt" | "pptx" | "odp" | "key" | "pages" | "numbers"
+            | "epub" | "mobi" => FileKind::Document,
+            _ => FileKind::Other,
+        }
+    }
+
+    /// Classifies a file by the magic bytes at its start, as read from `header`.
+    pub fn from_magic(header: &[u8]) -> FileKind {
+        const SIGNATURES: &[(usize, &[u8], FileKind)] = &[
+            (0, b"\x89PNG", FileKind::Image),
+            (0, b"\xff\xd8\xff", FileKind::Image),
+            (0, b"GIF8", FileKind::Image),
+            (0, b"II*\0", FileKind::Image),
+            (0, b"MM\0*", FileKind::Image),
+            (8, b"WEBP", FileKind::Image),
+            (8, b"AVI ", FileKind::Video),
+            (0, b"\x1a\x45\xdf\xa3", FileKind::Video),
+            (4, b"ftyp", FileKind::Video),
+            (8, b"WAVE", FileKind::Audio),
+            (0, b"ID3", FileKind::Audio),
+            (0, b"fLaC", FileKind::Audio),
+            (0, b"OggS", FileKind::Audio),
+            (0, b"PK\x03\x04", FileKind::Archive),
+            (0, b"\x1f\x8b", FileKind::Archive),
+            (0, b"\xfd7zXZ\0", FileKind::Archive),
+            (0, b"BZh", FileKind::Archive),
+            (0, b"\x28\xb5\x2f\xfd", FileKind::Archive),
+            (0, b"7z\xbc\xaf\x27\x1c", FileKind::Archive),
+            (0, b"Rar!", FileKind::Archive),
+            (0, b"%PDF", FileKind::Document),
+            (0, b"#!", FileKind::Code),
+        ];
+        SIGNATURES
+            .iter()
+            .find(|(offset, magic, _)| header.get(*offset..offset + magic.len()) == Some(magic))
+            .map_or(FileKind::Other, |(_, _, kind)| *kind)
+    }
+}
+
+impl FromStr for FileKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FileKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("invalid file kind: {}", s))
+    }
+}
+
+/// How files are classified into [`FileKind`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClassifyMode {
+    /// By the extension of the name only.
+    #[default]
+    Extension,
+    /// By the extension, reading the first bytes of files whose extension is not known.
+    Magic,
+}
+
+impl FromStr for ClassifyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "extension" => Ok(ClassifyMode::Extension),
+            "magic" => Ok(ClassifyMode::Magic),
+            _ => Err(format!("invalid classification mode: {}", s)),
+        }
+    }
+}
+
+/// Returns the uniform type identifier (UTI) of a file by the extension of its name, such
+/// as `public.jpeg`, or `None` if the extension is not known.
+pub fn uniform_type(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    let uti = match extension.as_str() {
+        "jpg" | "jpeg" => "public.jpeg",
+        "png" => "public.png",
+        "gif" => "com.compuserve.gif",
+        "bmp" => "com.microsoft.bmp",
+        "tif" | "tiff" => "public.tiff",
+        "heic" => "public.heic",
+        "heif" => "public.heif",
+        "webp" => "org.webmproject.webp",
+        "svg" => "public.svg-image",
+        "ico" => "com.microsoft.ico",
+        "psd" => "com.adobe.photoshop-image",
+        "dng" => "com.adobe.raw-image",
+        "mp4" => "public.mpeg-4",
+        "m4v" => "com.apple.m4v-video",
+        "mov" => "com.apple.quicktime-movie",
+        "avi" => "public.avi",
+        "mpg" | "mpeg" => "public.mpeg",
+        "mkv" => "org.matroska.mkv",
+        "webm" => "org.webmproject.webm",
+        "mp3" => "public.mp3",
+        "m4a" => "com.apple.m4a-audio",
+        "aac" => "public.aac-audio",
+        "wav" => "com.microsoft.waveform-audio",
+        "aif" | "aiff" => "public.aiff-audio",
+        "flac" => "org.xiph.flac",
+        "zip" => "public.zip-archive",
+        "tar" => "public.tar-archive",
+        "gz" | "tgz" => "org.gnu.gnu-zip-archive",
+        "bz2" => "public.bzip2-archive",
+        "7z" => "org.7-zip.7-zip-archive",
+        "dmg" => "com.apple.disk-image-udif",
+        "iso" => "public.iso-image",
+        "jar" => "com.sun.java-archive",
+        "pdf" => "com.adobe.pdf",
+        "txt" => "public.plain-text",
+        "rtf" => "public.rtf",
+        "md" => "net.daringfireball.markdown",
+        "csv" => "public.comma-separated-values-text",
+        "html" | "htm" => "public.html",
+        "xml" => "public.xml",
+        "json" => "public.json",
+        "yaml" | "yml" => "public.yaml",
+        "doc" => "com.microsoft.word.doc",
+        "docx" => "org.openxmlformats.wordprocessingml.document",
+        "xls" => "com.microsoft.excel.xls",
+        "xlsx" => "org.openxmlformats.spreadsheetml.sheet",
+        "ppt" => "com.microsoft.powerpoint.ppt",
+        "pptx" => "org.openxmlformats.presentationml.presentation",
+        "epub" => "org.idpf.epub-container",
+        "c" => "public.c-source",
+        "h" => "public.c-header",
+        "cc" | "cpp" | "cxx" => "public.c-plus-plus-source",
+        "hpp" => "public.c-plus-plus-header",
+        "m" => "public.objective-c-source",
+        "swift" => "public.swift-source",
+        "java" => "com.sun.java-source",
+        "py" => "public.python-script",
+        "rb" => "public.ruby-script",
+        "pl" => "public.perl-script",
+        "php" => "public.php-script",
+        "sh" => "public.shell-script",
+        "js" => "com.netscape.javascript-source",
+        "app" => "com.apple.application-bundle",
+        "exe" => "com.microsoft.windows-executable",
+        "dylib" => "com.apple.mach-o-dylib",
+        "o" => "public.object-code",
+        "log" => "com.apple.log",
+        _ => return None,
+    };
+    Some(uti)
+}
+
+/// Number of bytes read to recognize magic bytes.
+const MAGIC_LEN: usize = 16;
+
+/// Classifies the file `name` at `path`.
+///
+/// Files that cannot be read, such as archive members, are classified by name only.
+pub(crate) fn classify(path: &Path, name: &str, mode: ClassifyMode) -> FileKind {
+    let kind = FileKind::from_name(name);
+    if kind != FileKind::Other || mode != ClassifyMode::Magic {
+        return kind;
+    }
+    let mut header = Vec::with_capacity(MAGIC_LEN);
+    match File::open(path).and_then(|file| file.take(MAGIC_LEN as u64).read_to_end(&mut header)) {
+        Ok(_) => FileKind::from_magic(&header),
+        Err(_) => FileKind::Other,
+    }
+}
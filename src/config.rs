@@ -0,0 +1,125 @@
+// External crates
+use clap::Command;
+use toml::{Table, Value};
+
+// Standard library imports
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A named scan recipe from the `[profiles]` table of the configuration file.
+///
+/// ```toml
+/// [profiles.nas-weekly]
+/// root = "/mnt/nas"
+/// exclude = [".snapshot", "*.tmp"]
+/// output = "/var/lib/gpscan/nas.gpscan.gz"
+/// compress = true
+/// ```
+///
+/// Every key other than `root` is the long name of a command-line option.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Profile {
+    /// The directory to scan.
+    pub root: Option<String>,
+    /// The options of the profile as command-line arguments.
+    pub args: Vec<String>,
+}
+
+/// Returns the configuration file used when `--config` is not given:
+/// `$GPSCAN_CONFIG`, or `gpscan/config.toml` in the user's configuration directory.
+pub(crate) fn default_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("GPSCAN_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+            PathBuf::from(home).join(".config")
+        }
+    };
+    Some(config_dir.join("gpscan").join("config.toml"))
+}
+
+/// Reads the profile `name` from the configuration file at `path`.
+///
+/// Options are checked against `command`, so that a misspelled key is reported
+/// with the name of the profile.
+pub(crate) fn load_profile(path: &Path, name: &str, command: &Command) -> Result<Profile, String> {
+    let text = fs::read_to_string(path).map_err(|e| {
+        format!(
+            "Failed to read configuration file '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+    let config: Table = text
+        .parse()
+        .map_err(|e| format!("Invalid configuration file '{}': {}", path.display(), e))?;
+
+    let table = config
+        .get("profiles")
+        .and_then(|profiles| profiles.get(name))
+        .and_then(Value::as_table)
+        .ok_or_else(|| format!("Profile '{}' not found in '{}'", name, path.display()))?;
+    parse_profile(name, table, command)
+}
+
+fn parse_profile(name: &str, table: &Table, command: &Command) -> Result<Profile, String> {
+    let mut profile = Profile::default();
+    for (key, value) in table {
+        if key == "root" {
+            profile.root = Some(
+                value
+                    .as_str()
+                    .ok_or_else(|| format!("Profile '{}': root must be a string", name))?
+                    .to_string(),
+            );
+            continue;
+        }
+
+        let takes_value = match command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()))
+        {
+            Some(arg) => arg.get_action().takes_values(),
+            None => return Err(format!("Profile '{}': unknown option '{}'", name, key)),
+        };
+        let option = format!("--{}", key);
+
+        match value {
+            Value::Boolean(true) if !takes_value => profile.args.push(option),
+            Value::Boolean(false) if !takes_value => {}
+            Value::Array(values) if takes_value => {
+                for value in values {
+                    profile.args.push(option.clone());
+                    profile.args.push(option_value(name, key, value)?);
+                }
+            }
+            value if takes_value => {
+                profile.args.push(option);
+                profile.args.push(option_value(name, key, value)?);
+            }
+            _ => {
+                return Err(format!(
+                    "Profile '{}': {} is a flag and must be true or false",
+                    name, key
+                ))
+            }
+        }
+    }
+    Ok(profile)
+}
+
+fn option_value(name: &str, key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(n) => Ok(n.to_string()),
+        Value::Float(n) => Ok(n.to_string()),
+        _ => Err(format!(
+            "Profile '{}': invalid value for {}: {}",
+            name, key, value
+        )),
+    }
+}
@@ -0,0 +1,256 @@
+// External crates
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ArgMatches;
+use log::{error, info, warn};
+use sysinfo::System;
+
+// Standard library imports
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::filesystem::{
+    dump_formatter, placeholder_template, scan_with_metrics, write_dump_file, write_metrics,
+};
+use crate::notify::Notifier;
+use crate::options::Options;
+use crate::scan::ScanStats;
+
+/// Placeholder in the output name that is replaced with the scan time.
+const TIMESTAMP_PLACEHOLDER: &str = "{timestamp}";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+/// Placeholder in the output name that is replaced with the scan date.
+const DATE_PLACEHOLDER: &str = "{date}";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Placeholder in the output name that is replaced with the name of this host.
+const HOSTNAME_PLACEHOLDER: &str = "{hostname}";
+
+/// Scans `root_path` every `--interval`, writing each dump to a timestamped file.
+///
+/// Failed scans are logged and retried at the next interval. Runs until it is interrupted.
+pub(crate) fn run_daemon(
+    root_path: &Path,
+    options: &Options,
+    matches: &ArgMatches,
+) -> Result<ScanStats, Error> {
+    let template = timestamp_template(
+        matches
+            .get_one::<String>("output")
+            .expect("--daemon requires --output"),
+    );
+    let interval = *matches
+        .get_one::<Duration>("interval")
+        .expect("Interval has a default value");
+    let keep = matches.get_one::<usize>("keep").copied();
+    let notifier = Notifier::from_matches(matches);
+    let root = root_path.to_string_lossy();
+
+    // Metrics of the latest scan, as served at `--metrics-listen`
+    let latest = Arc::new(Mutex::new(String::new()));
+    if let Some(address) = matches.get_one::<String>("metrics-listen") {
+        let listener = TcpListener::bind(address).map_err(|e| {
+            Error::Output(io::Error::new(
+                e.kind(),
+                format!("Failed to listen on '{}': {}", address, e),
+            ))
+        })?;
+        info!("Serving metrics at http://{}/metrics", address);
+        let latest = Arc::clone(&latest);
+        thread::spawn(move || serve_metrics(listener, latest));
+    }
+
+    info!(
+        "Scanning {} every {}",
+        root_path.display(),
+        format_interval(interval)
+    );
+    loop {
+        let started = Instant::now();
+        let path = timestamped_path(&template, Utc::now());
+
+        let outcome = write_dump_file(matches, &path, |handle| {
+            scan_with_metrics(root_path, options, dump_formatter(matches, handle))
+        });
+        match &outcome {
+            Ok((stats, metrics)) => {
+                if stats.errors > 0 {
+                    warn!(
+                        "Wrote {} ({} entries skipped due to errors)",
+                        path.display(),
+                        stats.errors
+                    );
+                } else {
+                    info!("Wrote {}", path.display());
+                }
+                if let Err(e) = write_metrics(matches, metrics) {
+                    warn!("Failed to write metrics: {}", e);
+                }
+                *latest.lock().unwrap_or_else(|e| e.into_inner()) = metrics.to_prometheus();
+            }
+            Err(e) => error!("Scheduled scan failed: {}", e),
+        }
+        notifier.notify(
+            &root,
+            Some(&path.to_string_lossy()),
+            outcome.as_ref().map(|(_, metrics)| metrics),
+        );
+
+        if let Some(keep) = keep {
+            if let Err(e) = rotate(&template, keep) {
+                warn!("Failed to remove old dumps: {}", e);
+            }
+        }
+
+        thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+/// Answers HTTP requests for `/metrics` with the metrics of the latest scan.
+fn serve_metrics(listener: TcpListener, latest: Arc<Mutex<String>>) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| {
+            let body = latest.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            respond(stream, &body)
+        });
+        if let Err(e) = result {
+            warn!("Failed to serve metrics: {}", e);
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, metrics: &str) -> io::Result<()> {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+    // Skip the headers; requests to this endpoint have no body
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", metrics),
+        _ => ("404 Not Found", "Not found\n"),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Parses an interval such as `90s`, `30m`, `6h`, or `1d`.
+pub(crate) fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid interval: {}", value))?;
+    let seconds = match unit {
+        "s" | "" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid interval unit (use s, m, h, or d): {}",
+                value
+            ))
+        }
+    };
+    if number == 0 {
+        return Err("interval must be greater than zero".to_string());
+    }
+    Ok(Duration::from_secs(number * seconds))
+}
+
+fn format_interval(interval: Duration) -> String {
+    let seconds = interval.as_secs();
+    match seconds {
+        s if s % 86400 == 0 => format!("{}d", s / 86400),
+        s if s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// Adds a timestamp placeholder before the extension if the output name has none,
+/// so that `scan.gpscan` becomes `scan-{timestamp}.gpscan`.
+fn timestamp_template(output: &str) -> String {
+    placeholder_template(output, TIMESTAMP_PLACEHOLDER)
+}
+
+fn timestamped_path(template: &str, time: DateTime<Utc>) -> PathBuf {
+    PathBuf::from(expand_output_name(template, time))
+}
+
+/// Replaces `{hostname}`, `{date}`, and `{timestamp}` in the output name with the
+/// name of this host and the date and time (UTC) `time`.
+///
+/// Other placeholders, such as `{volume}`, are left as they are.
+pub(crate) fn expand_output_name(template: &str, time: DateTime<Utc>) -> String {
+    let mut name = template.to_string();
+    if name.contains(HOSTNAME_PLACEHOLDER) {
+        let host_name = System::host_name().unwrap_or_else(|| "localhost".to_string());
+        name = name.replace(HOSTNAME_PLACEHOLDER, &host_name);
+    }
+    name.replace(DATE_PLACEHOLDER, &time.format(DATE_FORMAT).to_string())
+        .replace(
+            TIMESTAMP_PLACEHOLDER,
+            &time.format(TIMESTAMP_FORMAT).to_string(),
+        )
+}
+
+/// Removes all but the `keep` most recent dumps written from `template`.
+fn rotate(template: &str, keep: usize) -> io::Result<()> {
+    let template = Path::new(template);
+    let directory = match template.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = template
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let Some((prefix, _)) = name.split_once(TIMESTAMP_PLACEHOLDER) else {
+        return Ok(());
+    };
+    // The placeholders expand to the same length at any time, so the timestamp of
+    // every dump is at the same position
+    let now = Utc::now();
+    let start = expand_output_name(prefix, now).len();
+    let end = start + now.format(TIMESTAMP_FORMAT).to_string().len();
+
+    let mut dumps = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let file_name = entry?.file_name().to_string_lossy().into_owned();
+        let time = file_name
+            .get(start..end)
+            .and_then(|t| NaiveDateTime::parse_from_str(t, TIMESTAMP_FORMAT).ok());
+        if let Some(time) = time {
+            if expand_output_name(&name, time.and_utc()) == file_name {
+                dumps.push((time, file_name));
+            }
+        }
+    }
+
+    dumps.sort();
+    let excess = dumps.len().saturating_sub(keep);
+    for (_, file_name) in dumps.into_iter().take(excess) {
+        let path = directory.join(file_name);
+        info!("Removing old dump {}", path.display());
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
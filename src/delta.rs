@@ -0,0 +1,143 @@
+// Standard library imports
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::model::{FileNode, NodeKind, ScanInfo};
+use crate::output::OutputFormatter;
+use crate::scan::ScanEntry;
+
+/// Cumulative sizes of folders, by path relative to the scan root (the root itself is `""`).
+pub type FolderSizes = HashMap<PathBuf, u64>;
+
+/// Change in the cumulative size of a folder between two scans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FolderDelta {
+    /// Path relative to the scan root.
+    pub path: PathBuf,
+    /// Size in the baseline, or 0 if the folder is new.
+    pub before: u64,
+    pub after: u64,
+}
+
+impl FolderDelta {
+    /// Returns the growth in bytes (negative if the folder shrank).
+    pub fn growth(&self) -> i128 {
+        self.after as i128 - self.before as i128
+    }
+}
+
+/// Returns the cumulative size of every folder in the tree below `root`.
+pub fn folder_sizes(root: &FileNode) -> FolderSizes {
+    let mut sizes = FolderSizes::new();
+    collect(root, Path::new(""), &mut sizes);
+    sizes
+}
+
+fn collect(node: &FileNode, path: &Path, sizes: &mut FolderSizes) -> u64 {
+    match node.kind {
+        NodeKind::File => node.size,
+        NodeKind::Folder => {
+            let size = node
+                .children
+                .iter()
+                .map(|child| collect(child, &path.join(&child.name), sizes))
+                .sum();
+            sizes.insert(path.to_path_buf(), size);
+            size
+        }
+    }
+}
+
+/// Returns the `n` folders that grew the most from `baseline` to `current`, largest growth first.
+///
+/// Folders are matched by their path relative to the scan root, so that scans of
+/// the same tree under a different root name can be compared. Entries of equal
+/// growth are ordered by path.
+pub fn largest_growth(baseline: &FolderSizes, current: &FolderSizes, n: usize) -> Vec<FolderDelta> {
+    let mut deltas: Vec<FolderDelta> = current
+        .iter()
+        .map(|(path, &after)| FolderDelta {
+            path: path.clone(),
+            before: baseline.get(path).copied().unwrap_or(0),
+            after,
+        })
+        .filter(|delta| delta.after > delta.before)
+        .collect();
+    deltas.sort_by(|a, b| {
+        Reverse(a.growth())
+            .cmp(&Reverse(b.growth()))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    deltas.truncate(n);
+    deltas
+}
+
+/// Collects the [`FolderSizes`] of a scan while passing everything on to another formatter.
+pub struct FolderSizeCollector<F> {
+    inner: F,
+    root: PathBuf,
+    /// Sizes of the open folders so far, innermost last.
+    open: Vec<u64>,
+    sizes: FolderSizes,
+}
+
+impl<F: OutputFormatter> FolderSizeCollector<F> {
+    pub fn new(inner: F) -> Self {
+        FolderSizeCollector {
+            inner,
+            root: PathBuf::new(),
+            open: Vec::new(),
+            sizes: FolderSizes::new(),
+        }
+    }
+
+    /// Returns the sizes of the completed folders.
+    pub fn into_sizes(self) -> FolderSizes {
+        self.sizes
+    }
+}
+
+impl<F: OutputFormatter> OutputFormatter for FolderSizeCollector<F> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.begin_scan(info)
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        if entry.depth == 0 {
+            self.root = entry.path.clone();
+        }
+        self.open.push(0);
+        self.inner.begin_folder(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        if let Some(size) = self.open.last_mut() {
+            *size += entry.size;
+        }
+        self.inner.file(entry)
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        let size = self.open.pop().unwrap_or(0);
+        if let Some(parent) = self.open.last_mut() {
+            *parent += size;
+        }
+        let path = entry
+            .path
+            .strip_prefix(&self.root)
+            .unwrap_or(&entry.path)
+            .to_path_buf();
+        self.sizes.insert(path, size);
+        self.inner.end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.comment(text)
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.end_scan(info)
+    }
+}
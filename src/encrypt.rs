@@ -0,0 +1,103 @@
+//! Encryption of scan dumps with [age](https://age-encryption.org), used by `--encrypt`.
+//!
+//! Encrypted dumps can be read with `age --decrypt` and the matching identity or password.
+
+// Standard library imports
+use std::env;
+use std::fmt;
+use std::io::{self, Write};
+
+use age::secrecy::SecretString;
+use age::{scrypt, x25519, Encryptor};
+
+/// Environment variable that holds the password for `--encrypt password`.
+pub const PASSWORD_VARIABLE: &str = "GPSCAN_PASSWORD";
+
+/// The recipients a dump is encrypted to.
+pub struct Encryption {
+    recipients: Vec<Box<dyn age::Recipient>>,
+}
+
+impl Encryption {
+    /// Encrypts to age public keys (`age1...`), any of whose identities can decrypt.
+    pub fn for_keys(keys: &[x25519::Recipient]) -> Self {
+        Encryption {
+            recipients: keys
+                .iter()
+                .map(|key| Box::new(key.clone()) as Box<dyn age::Recipient>)
+                .collect(),
+        }
+    }
+
+    /// Encrypts with a password.
+    pub fn with_password(password: SecretString) -> Self {
+        Encryption {
+            recipients: vec![Box::new(scrypt::Recipient::new(password))],
+        }
+    }
+
+    /// Parses the `--encrypt` values: `age:<public key>` for each recipient, or `password`
+    /// alone to read the password from the `GPSCAN_PASSWORD` environment variable.
+    pub fn parse<'a>(values: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut keys = Vec::new();
+        let mut password = false;
+        for value in values {
+            if value == "password" {
+                password = true;
+            } else if let Some(key) = value.strip_prefix("age:") {
+                let key = key
+                    .parse::<x25519::Recipient>()
+                    .map_err(|e| format!("Invalid age recipient '{}': {}", key, e))?;
+                keys.push(key);
+            } else {
+                return Err(format!(
+                    "Invalid encryption '{}': expected age:<recipient> or password",
+                    value
+                ));
+            }
+        }
+
+        match (password, keys.is_empty()) {
+            (true, true) => {
+                let password = env::var(PASSWORD_VARIABLE)
+                    .ok()
+                    .filter(|password| !password.is_empty())
+                    .ok_or_else(|| {
+                        format!(
+                            "--encrypt password needs the password in {}",
+                            PASSWORD_VARIABLE
+                        )
+                    })?;
+                Ok(Self::with_password(SecretString::from(password)))
+            }
+            (true, false) => Err("A password cannot be combined with age recipients".to_string()),
+            (false, _) => Ok(Self::for_keys(&keys)),
+        }
+    }
+
+    /// Encrypts everything `write` writes to `output`.
+    pub fn encrypt<T>(
+        &self,
+        output: &mut dyn Write,
+        write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let encryptor = Encryptor::with_recipients(
+            self.recipients
+                .iter()
+                .map(|recipient| recipient.as_ref() as &dyn age::Recipient),
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let mut stream = encryptor.wrap_output(output)?;
+        let value = write(&mut stream)?;
+        stream.finish()?;
+        Ok(value)
+    }
+}
+
+impl fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encryption")
+            .field("recipients", &self.recipients.len())
+            .finish()
+    }
+}
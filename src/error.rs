@@ -0,0 +1,81 @@
+// Standard library imports
+use std::io;
+use std::path::PathBuf;
+
+use crate::exit_code;
+
+/// Errors that abort a scan, the reading of a dump, or a command, grouped by failure class.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The options cannot be combined, or have a value out of range.
+    #[error("{0}")]
+    InvalidOptions(String),
+    /// The scan root does not exist.
+    #[error("The specified path does not exist: {}", .0.display())]
+    RootNotFound(PathBuf),
+    /// The scan root is neither a directory nor a file, or cannot be scanned as asked.
+    #[error("{0}")]
+    InvalidRoot(String),
+    /// The output could not be created or written.
+    #[error("Failed to write output: {0}")]
+    Output(#[source] io::Error),
+    /// A scan dump is not well-formed XML.
+    #[error("Malformed XML at byte {position}: {source}")]
+    Xml {
+        /// Offset of the malformed element in the uncompressed dump.
+        position: u64,
+        source: quick_xml::Error,
+    },
+    /// A scan dump could not be read, or does not describe a complete scan.
+    #[error("{0}")]
+    InvalidDump(String),
+    /// Another process is scanning the same root (with `--lock`).
+    #[error("{0}")]
+    Locked(String),
+    /// Any other I/O error, including those of output formatters, scan visitors, and sources.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Result type of the library entry points.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Returns the process exit code for this failure class.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidOptions(_) => exit_code::USAGE_ERROR,
+            Error::RootNotFound(_) | Error::InvalidRoot(_) => exit_code::INVALID_ROOT,
+            Error::Output(e) if is_storage_full(e) => exit_code::OUTPUT_FULL,
+            Error::Output(_) | Error::Io(_) => exit_code::OUTPUT_ERROR,
+            Error::Xml { .. } | Error::InvalidDump(_) => exit_code::INVALID_DUMP,
+            Error::Locked(_) => exit_code::LOCKED,
+        }
+    }
+}
+
+/// Returns true if `e` means that the disk, or the quota of the user on it, is full.
+pub(crate) fn is_storage_full(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded
+    )
+}
+
+/// Lets errors of the entry points pass through callbacks that return `io::Result`.
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Output(e) | Error::Io(e) => e,
+            Error::RootNotFound(_) => io::Error::new(io::ErrorKind::NotFound, e),
+            Error::InvalidOptions(_) | Error::InvalidRoot(_) => {
+                io::Error::new(io::ErrorKind::InvalidInput, e)
+            }
+            Error::Xml { .. } | Error::InvalidDump(_) => {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            }
+            Error::Locked(_) => io::Error::other(e),
+        }
+    }
+}
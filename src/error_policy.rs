@@ -0,0 +1,75 @@
+// External crates
+use log::warn;
+
+// Standard library imports
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// What to do about an I/O error encountered during a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Skip the entry and continue the scan. Skipped entries are counted in the statistics.
+    Skip,
+    /// Run the failed operation again.
+    Retry,
+    /// Stop the scan and return the error.
+    Abort,
+}
+
+/// Decides how I/O errors are handled during a scan.
+pub trait ErrorPolicy: Send + Sync {
+    /// Called for every failed filesystem operation on `path`.
+    ///
+    /// `attempt` is the number of retries already made for this operation. Any
+    /// delay before a retry should be applied by the policy itself.
+    fn on_error(&self, path: &Path, error: &io::Error, attempt: u32) -> ErrorAction;
+}
+
+/// Default policy: retries transient errors a limited number of times and skips the rest.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl ErrorPolicy for RetryPolicy {
+    fn on_error(&self, path: &Path, error: &io::Error, attempt: u32) -> ErrorAction {
+        if attempt >= self.retries || !is_transient_error(error) {
+            return ErrorAction::Skip;
+        }
+        warn!(
+            "Transient error for '{}': {} (retry {}/{})",
+            path.display(),
+            error,
+            attempt + 1,
+            self.retries
+        );
+        thread::sleep(self.delay);
+        ErrorAction::Retry
+    }
+}
+
+/// Returns true if the error is likely to go away when the operation is retried.
+pub fn is_transient_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::StaleNetworkFileHandle
+    )
+}
+
+/// A user-supplied error policy.
+#[derive(Clone)]
+pub(crate) struct CustomErrorPolicy(pub(crate) Arc<dyn ErrorPolicy>);
+
+impl fmt::Debug for CustomErrorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomErrorPolicy")
+    }
+}
@@ -0,0 +1,21 @@
+// Exit codes of the command-line tool
+
+/// The scan completed without skipping any entries due to errors.
+pub const SUCCESS: i32 = 0;
+/// The command line could not be parsed, or its options cannot be combined.
+pub const USAGE_ERROR: i32 = 1;
+/// The scan root does not exist or is neither a directory nor a file.
+pub const INVALID_ROOT: i32 = 2;
+/// The output could not be created or written.
+pub const OUTPUT_ERROR: i32 = 3;
+/// The scan completed, but entries were skipped due to errors (with `--strict`).
+pub const INCOMPLETE: i32 = 4;
+/// An input dump (checked by `validate` or read by `merge`) is malformed or incomplete.
+pub const INVALID_DUMP: i32 = 5;
+/// Another gpscan process holds the `--lock` of the same scan root.
+pub const LOCKED: i32 = 6;
+/// The scan was stopped after `--max-errors` errors; the dump holds the entries read until then.
+pub const TOO_MANY_ERRORS: i32 = 7;
+/// The disk of the output filled up (and the `--fallback-output` as well, if given); the
+/// incomplete output file was removed.
+pub const OUTPUT_FULL: i32 = 8;
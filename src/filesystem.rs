@@ -1,416 +1,1146 @@
 // External crates
 use chrono::{DateTime, Utc};
 use clap::ArgMatches;
-use log::{error, info, warn};
-use quick_xml::escape::escape;
-use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
-use quick_xml::writer::Writer;
+use flate2::{write::GzEncoder, Compression};
+#[cfg(feature = "watch")]
+use log::info;
+use log::warn;
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use sysinfo::Disks;
 
 // Standard library imports
-use std::cmp::Reverse;
-use std::collections::HashSet;
-use std::fs::{self, Metadata};
-use std::io::{self, Write};
-use std::path::Path;
-use std::time::SystemTime;
-
-use crate::platform::MetadataExtOps; // Ensure this trait is implemented for Metadata
-
-// Constants for XML output
-const GRANDPERSPECTIVE_APP_VERSION: &str = "4";
-const GRANDPERSPECTIVE_FORMAT_VERSION: &str = "7";
-const XML_VERSION: &str = "1.0";
-const XML_ENCODING: &str = "UTF-8";
-const DEFAULT_DATETIME: &str = "1970-01-01T00:00:00Z";
-const TAG_SCAN_INFO: &str = "ScanInfo";
-const TAG_GRANDPERSPECTIVE_SCAN_DUMP: &str = "GrandPerspectiveScanDump";
-const TAG_FOLDER: &str = "Folder";
-const TAG_FILE: &str = "File";
-
-pub struct Options {
-    apparent_size: bool,
-    cross_mount_points: bool,
-    include_zero_files: bool,
-    include_empty_folders: bool,
-}
-
-impl Options {
-    pub fn from_matches(matches: &ArgMatches) -> Self {
-        Options {
-            apparent_size: matches.get_flag("apparent-size"),
-            cross_mount_points: matches.get_flag("mounts"),
-            include_zero_files: matches.get_flag("include-zero-files"),
-            include_empty_folders: matches.get_flag("include-empty-folders"),
-        }
-    }
-}
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use crate::daemon::{expand_output_name, run_daemon};
+use crate::delta::{folder_sizes, largest_growth, FolderSizeCollector, FolderSizes};
+#[cfg(feature = "encrypt")]
+use crate::encrypt::Encryption;
+use crate::error::{is_storage_full, Error};
+use crate::import::{import_du, import_ncdu, ImportFormat};
+use crate::lock::ScanLock;
+use crate::merge::merge;
+use crate::metrics::{MetricsCollector, ScanMetrics};
+use crate::model::ScanResult;
+use crate::notify::Notifier;
+use crate::options::{DirCache, Options, RootName};
+use crate::output::{write_result, OutputFormatter};
+use crate::output_file::OutputFile;
+use crate::platform::{is_stdout, is_stream, long_path, open_fd, windows_name_problem};
+use crate::progress::format_bytes;
+use crate::progress::{JsonProgress, ProgressReporter, ProgressSink, TerminalProgress};
+use crate::reader::read_scan;
+use crate::report::{Report, ReportCollector, ReportKind};
+#[cfg(feature = "s3")]
+use crate::s3::{scan_s3, S3Config, S3Location};
+use crate::scan::{scan_to_tree, scan_with_formatter, DirTime, ScanStats};
+use crate::snapshot::{Snapshot, SnapshotInfo};
+use crate::split::SplitFormatter;
+use crate::top::{top_entries, LargestFilesCollector};
+use crate::totals::TotalsCheck;
+#[cfg(feature = "http")]
+use crate::upload::upload;
+use crate::validate::{open_dump, validate_file};
+use crate::volume::{get_volume_info, network_file_system};
+use crate::volumes::{run_all_drives, run_all_volumes};
+#[cfg(feature = "watch")]
+use crate::watch::TreeWatcher;
+use crate::xml_output::{XmlFormatter, DATETIME_FORMAT};
+
+/// Interval between updates of the `--progress` line and `--progress-json` records.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
 
 /// Runs the main logic of the program.
-pub fn run(matches: ArgMatches) -> io::Result<()> {
+///
+/// Returns the statistics of the completed scan so that the caller can decide
+/// whether entries skipped due to errors should be treated as a failure.
+pub fn run(matches: ArgMatches) -> Result<ScanStats, Error> {
+    if matches.get_flag("all-volumes") {
+        return run_all_volumes(&matches);
+    }
+    if matches.get_flag("all-drives") {
+        return run_all_drives(&matches);
+    }
+
     // Get the directory path from arguments
     let directory = matches
         .get_one::<String>("directory")
         .expect("Directory path is required")
         .as_str();
 
+    // Object storage is listed through its API instead of the filesystem
+    if directory.starts_with("s3://") {
+        let outcome = lock_root(directory, &matches).and_then(|_lock| run_s3(directory, &matches));
+        return finish(&matches, directory, outcome);
+    }
+
     let root_path = Path::new(directory);
+    // The lock is held until the scan, watch, or daemon loop ends
+    let follow_symlink = matches
+        .get_one::<bool>("follow-root-symlink")
+        .copied()
+        .unwrap_or(true);
+    let _lock = match check_root(root_path, follow_symlink, matches.get_flag("local-only"))
+        .and_then(|()| lock_root(directory, &matches))
+    {
+        Ok(lock) => lock,
+        Err(e) => return finish(&matches, directory, Err(e)),
+    };
+
+    // Get option values
+    let mut option = match scan_options(&matches) {
+        Ok(option) => option,
+        Err(e) => return finish(&matches, directory, Err(e)),
+    };
 
+    if matches.get_flag("watch") {
+        return run_watch(root_path, &option, &matches);
+    }
+    if matches.get_flag("daemon") {
+        return run_daemon(root_path, &option, &matches);
+    }
+
+    let mut report_kinds: Vec<ReportKind> = matches
+        .get_many::<String>("report")
+        .into_iter()
+        .flatten()
+        .map(|kind| kind.parse().expect("Invalid report"))
+        .collect();
+    if matches.contains_id("classify") && !report_kinds.contains(&ReportKind::Kinds) {
+        report_kinds.push(ReportKind::Kinds);
+    }
+    // Read the baseline before scanning, so that an invalid file fails early
+    let baseline = match matches.get_one::<String>("baseline") {
+        Some(file) => match read_scan(Path::new(file)) {
+            Ok(baseline) => Some(baseline),
+            Err(e) => {
+                let e = Error::InvalidDump(format!("{}: {}", file, e));
+                return finish(&matches, directory, Err(e));
+            }
+        },
+        None => None,
+    };
+    if let Some(file) = matches.get_one::<String>("dir-cache") {
+        let cutoff = *matches
+            .get_one::<SystemTime>("trust-dir-mtime")
+            .expect("--dir-cache requires --trust-dir-mtime");
+        match read_scan(Path::new(file)) {
+            Ok(cache) => option.dir_cache = Some(DirCache::new(cache, cutoff)),
+            Err(e) => {
+                let e = Error::InvalidDump(format!("{}: {}", file, e));
+                return finish(&matches, directory, Err(e));
+            }
+        }
+    }
+
+    // A snapshot is scanned at its mount point, but named after the live directory
+    let snapshot = match matches.get_one::<String>("snapshot") {
+        Some(name) => match Snapshot::mount(root_path, name) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => return finish(&matches, directory, Err(e)),
+        },
+        None => None,
+    };
+    let root_path = match &snapshot {
+        Some(snapshot) => {
+            option.root_name = RootName::Custom(option.root_folder_name(root_path));
+            snapshot.root.as_path()
+        }
+        None => root_path,
+    };
+
+    // Without --print-largest-files, the largest files are recorded by the dump formatter
+    let largest_count = match matches.get_flag("print-largest-files") {
+        true => *matches.get_one::<usize>("largest-files").unwrap_or(&0),
+        false => 0,
+    };
+    let scan = |formatter: &mut dyn OutputFormatter| {
+        let formatter = SnapshotInfo::new(formatter, snapshot.as_ref());
+        let formatter = LargestFilesCollector::new(formatter, largest_count);
+        let mut reporter = ReportCollector::new(formatter, &report_kinds);
+        let (stats, metrics, sizes) = if baseline.is_some() {
+            let mut collector = FolderSizeCollector::new(&mut reporter);
+            let (stats, metrics) = scan_with_metrics(root_path, &option, &mut collector)?;
+            (stats, metrics, Some(collector.into_sizes()))
+        } else {
+            let (stats, metrics) = scan_with_metrics(root_path, &option, &mut reporter)?;
+            (stats, metrics, None)
+        };
+        let reports = reporter.reports();
+        let largest = reporter.into_inner().largest().to_vec();
+        Ok::<_, io::Error>((stats, metrics, reports, sizes, largest))
+    };
+    let outcome = match matches.get_one::<u64>("split-size") {
+        Some(&limit) => {
+            let output = output_name(&matches).expect("--split-size requires --output");
+            scan(&mut SplitFormatter::new(
+                &output,
+                limit,
+                matches.get_flag("compress"),
+                &option,
+            ))
+            .map_err(output_error)
+        }
+        None => write_dump(&matches, |handle| {
+            scan(&mut dump_formatter(&matches, handle))
+        }),
+    }
+    .and_then(|(stats, metrics, reports, sizes, largest)| {
+        write_metrics(&matches, &metrics)?;
+        write_reports(&matches, &reports)?;
+        write_largest_files(&matches, &largest)?;
+        if let (Some(baseline), Some(sizes)) = (&baseline, &sizes) {
+            write_growth(&matches, baseline, sizes, &metrics.root)?;
+        }
+        write_dir_times(&matches, &stats.slowest_dirs)?;
+        if matches.get_flag("verify-totals") {
+            let volume = get_volume_info(root_path, &Disks::new_with_refreshed_list());
+            let check = TotalsCheck::new(root_path, &volume, metrics.bytes, &stats, &option);
+            write_totals_check(&matches, &check)?;
+        }
+        Ok((stats, metrics))
+    });
+    finish(&matches, directory, outcome)
+}
+
+/// Checks that the scan root exists and is a directory, that it is not a symbolic link
+/// with `--follow-root-symlink=false`, and with `--local-only`, that it is not on a
+/// network file system.
+fn check_root(root_path: &Path, follow_symlink: bool, local_only: bool) -> Result<(), Error> {
     // Check if the provided path exists
     if !root_path.exists() {
-        error!("The specified path does not exist: {}", root_path.display());
-        std::process::exit(1); // Exit code 1 for non-existent path
+        return Err(Error::RootNotFound(root_path.to_path_buf()));
     }
 
-    // Check if the provided path is a directory
-    if !root_path.is_dir() {
-        error!(
-            "The specified path is not a directory: {}",
+    if !follow_symlink && root_path.is_symlink() {
+        return Err(Error::InvalidRoot(format!(
+            "The specified path is a symbolic link: {}",
             root_path.display()
-        );
-        std::process::exit(1); // Exit code 1 for invalid directory
+        )));
     }
 
-    // Get option values
-    let option = Options::from_matches(&matches);
+    // Check if the provided path is a directory or a regular file
+    if !root_path.is_dir() && !root_path.is_file() {
+        return Err(Error::InvalidRoot(format!(
+            "The specified path is neither a directory nor a file: {}",
+            root_path.display()
+        )));
+    }
 
-    // Get the device ID of the root directory
-    let root_metadata = fs::metadata(root_path)?;
-    let root_dev = root_metadata.device_id();
+    if local_only {
+        if let Some(file_system) = network_file_system(root_path) {
+            return Err(Error::InvalidRoot(format!(
+                "The specified path is on a network file system ({}): {}",
+                file_system,
+                root_path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Takes the `--lock` of the scan root, waiting for it with `--lock-wait`.
+pub(crate) fn lock_root(directory: &str, matches: &ArgMatches) -> Result<Option<ScanLock>, Error> {
+    let wait = matches.get_flag("lock-wait");
+    if !matches.get_flag("lock") && !wait {
+        return Ok(None);
+    }
+    ScanLock::acquire(directory, wait).map(Some)
+}
 
-    // Create Disks instance and refresh disk list
-    let disks = Disks::new_with_refreshed_list();
+/// Sends the `--notify-url` and `--notify-cmd` notifications for a finished scan.
+fn finish(
+    matches: &ArgMatches,
+    directory: &str,
+    outcome: Result<(ScanStats, ScanMetrics), Error>,
+) -> Result<ScanStats, Error> {
+    let output = matches
+        .get_one::<String>("output-url")
+        .cloned()
+        .or_else(|| output_name(matches));
+    Notifier::from_matches(matches).notify(
+        directory,
+        output.as_deref(),
+        outcome.as_ref().map(|(_, metrics)| metrics),
+    );
+    outcome.map(|(stats, _)| stats)
+}
 
-    // Get volume information
-    let (volume_path, volume_size, free_space) = get_volume_info(root_path, &disks);
+/// Scans `root_path` with `formatter` and returns the metrics of the scan.
+pub(crate) fn scan_with_metrics<F: OutputFormatter>(
+    root_path: &Path,
+    options: &Options,
+    formatter: F,
+) -> io::Result<(ScanStats, ScanMetrics)> {
+    let mut collector = MetricsCollector::new(formatter);
+    let stats = scan_with_formatter(root_path, options, &mut collector)?;
+    let (mut metrics, _) = collector.into_parts();
+    metrics.errors = stats.errors;
+    metrics.hard_link_bytes = stats.hard_link_bytes;
+    Ok((stats, metrics))
+}
 
-    // Determine output destination
-    let output = matches.get_one::<String>("output");
+/// Prints the `--report` tables, to stderr if the dump is written to stdout.
+fn write_reports(matches: &ArgMatches, reports: &[Report]) -> io::Result<()> {
+    if reports.is_empty() {
+        return Ok(());
+    }
+    let mut out = report_output(matches);
 
-    // Create a write handle
-    let handle: Box<dyn Write> = match output {
-        Some(file) => {
-            let file = fs::File::create(file)?;
-            Box::new(file)
+    if matches
+        .get_one::<String>("report-format")
+        .map(String::as_str)
+        == Some("json")
+    {
+        let json: serde_json::Map<String, serde_json::Value> = reports
+            .iter()
+            .map(|report| {
+                let rows = report
+                    .rows
+                    .iter()
+                    .map(|row| json!({"key": row.key, "size": row.size, "files": row.files}))
+                    .collect();
+                (
+                    report.kind.name().to_string(),
+                    serde_json::Value::Array(rows),
+                )
+            })
+            .collect();
+        return writeln!(out, "{}", serde_json::Value::Object(json));
+    }
+
+    for report in reports {
+        writeln!(out, "{}:", capitalize(report.kind.name()))?;
+        for row in &report.rows {
+            writeln!(
+                out,
+                "{:>10}  {:>8}  {}",
+                format_bytes(row.size),
+                row.files,
+                row.key
+            )?;
         }
-        None => Box::new(io::stdout()),
-    };
+    }
+    Ok(())
+}
 
-    let mut writer = Writer::new_with_indent(handle, b' ', 0);
-
-    // Output the XML header and start tag
-    output_xml_header(&mut writer)?;
-
-    // Output the scan information
-    let scan_time = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
-    let mut scan_info = BytesStart::new(TAG_SCAN_INFO);
-    scan_info.push_attribute(("volumePath", escape(&volume_path).as_ref()));
-    scan_info.push_attribute(("volumeSize", volume_size.to_string().as_str()));
-    scan_info.push_attribute(("freeSpace", free_space.to_string().as_str()));
-    scan_info.push_attribute(("scanTime", scan_time.to_string().as_str()));
-    scan_info.push_attribute(("fileSizeMeasure", "physical"));
-    writer
-        .write_event(Event::Start(scan_info))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-    // Create a set to store visited inodes
-    let mut visited_inodes = HashSet::new();
-
-    // Start traversing the directory with new options
-    traverse_directory_to_xml(
-        root_path,
-        true,
-        root_dev,
-        &option,
-        &mut visited_inodes,
-        &mut writer,
-    )?;
+/// Prints the `--profile-dirs` table of the directories that took the longest to scan.
+fn write_dir_times(matches: &ArgMatches, dirs: &[DirTime]) -> io::Result<()> {
+    if !matches.contains_id("profile-dirs") {
+        return Ok(());
+    }
+    let mut out = report_output(matches);
 
-    // </ScanInfo> tag
-    writer
-        .write_event(Event::End(BytesEnd::new(TAG_SCAN_INFO)))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    // </GrandPerspectiveScanDump> tag
-    writer
-        .write_event(Event::End(BytesEnd::new(TAG_GRANDPERSPECTIVE_SCAN_DUMP)))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    if matches
+        .get_one::<String>("report-format")
+        .map(String::as_str)
+        == Some("json")
+    {
+        let dirs: Vec<serde_json::Value> = dirs
+            .iter()
+            .map(|dir| {
+                json!({
+                    "path": dir.path.to_string_lossy(),
+                    "total_seconds": dir.total.as_secs_f64(),
+                    "own_seconds": dir.own.as_secs_f64(),
+                })
+            })
+            .collect();
+        return writeln!(out, "{}", json!({ "slowest_dirs": dirs }));
+    }
 
+    writeln!(out, "Slowest directories (total / own time):")?;
+    for dir in dirs {
+        writeln!(
+            out,
+            "{:>10}  {:>10}  {}",
+            format!("{:.2?}", dir.total),
+            format!("{:.2?}", dir.own),
+            dir.path.display()
+        )?;
+    }
     Ok(())
 }
 
-/// Reads the contents of a directory and returns a vector of directory entries.
-fn read_directory(path: &Path) -> io::Result<Vec<fs::DirEntry>> {
-    match fs::read_dir(path) {
-        Ok(read_dir) => read_dir.collect::<Result<Vec<_>, io::Error>>(),
-        Err(e) => {
-            error!("Failed to read directory '{}': {}", path.display(), e);
-            Err(e)
+fn write_totals_check(matches: &ArgMatches, check: &TotalsCheck) -> io::Result<()> {
+    let mut out = report_output(matches);
+
+    if matches
+        .get_one::<String>("report-format")
+        .map(String::as_str)
+        == Some("json")
+    {
+        let json = json!({
+            "totals": {
+                "scanned": check.scanned,
+                "used": check.used,
+                "difference": check.difference(),
+                "volume_path": check.volume_path,
+                "causes": check.causes,
+            }
+        });
+        return writeln!(out, "{}", json);
+    }
+
+    writeln!(out, "Totals:")?;
+    writeln!(out, "{:>10}  scanned", format_bytes(check.scanned))?;
+    if check.used == 0 {
+        writeln!(out, "The used space of {} is not known", check.volume_path)?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{:>10}  used on {}",
+        format_bytes(check.used),
+        check.volume_path
+    )?;
+    let difference = format_bytes(check.difference().unsigned_abs());
+    match check.difference() {
+        0 => writeln!(out, "The totals match")?,
+        d if d < 0 => writeln!(out, "{:>10}  less than used", difference)?,
+        _ => writeln!(out, "{:>10}  more than used", difference)?,
+    }
+    if !check.causes.is_empty() && check.difference() != 0 {
+        writeln!(out, "Likely causes:")?;
+        for cause in &check.causes {
+            writeln!(out, "  - {}", cause)?;
         }
     }
+    Ok(())
+}
+
+fn write_largest_files(matches: &ArgMatches, largest: &[(PathBuf, u64)]) -> io::Result<()> {
+    if !matches.get_flag("print-largest-files") {
+        return Ok(());
+    }
+    let mut out = report_output(matches);
+
+    if matches
+        .get_one::<String>("report-format")
+        .map(String::as_str)
+        == Some("json")
+    {
+        let files: Vec<serde_json::Value> = largest
+            .iter()
+            .map(|(path, size)| json!({"path": path.to_string_lossy(), "size": size}))
+            .collect();
+        return writeln!(out, "{}", json!({ "largest_files": files }));
+    }
+
+    writeln!(out, "Largest files:")?;
+    for (path, size) in largest {
+        writeln!(out, "{:>10}  {}", format_bytes(*size), path.display())?;
+    }
+    Ok(())
 }
 
-fn get_metadata(path: &Path) -> io::Result<Metadata> {
-    match fs::metadata(path) {
-        Ok(metadata) => Ok(metadata),
-        Err(e) => {
-            error!("Failed to access metadata for '{}': {}", path.display(), e);
-            Err(e)
+/// Prints the folders that grew the most since the `--baseline` scan.
+fn write_growth(
+    matches: &ArgMatches,
+    baseline: &ScanResult,
+    sizes: &FolderSizes,
+    root_name: &str,
+) -> io::Result<()> {
+    let count = *matches.get_one::<usize>("baseline-count").unwrap_or(&20);
+    let growth = largest_growth(&folder_sizes(&baseline.root), sizes, count);
+    let baseline_time = baseline.info.scan_time.format(DATETIME_FORMAT).to_string();
+    let display_path = |path: &Path| {
+        if path.as_os_str().is_empty() {
+            root_name.to_string()
+        } else {
+            Path::new(root_name).join(path).display().to_string()
         }
+    };
+    let mut out = report_output(matches);
+
+    if matches
+        .get_one::<String>("report-format")
+        .map(String::as_str)
+        == Some("json")
+    {
+        let folders: Vec<serde_json::Value> = growth
+            .iter()
+            .map(|delta| {
+                json!({
+                    "path": display_path(&delta.path),
+                    "before": delta.before,
+                    "after": delta.after,
+                })
+            })
+            .collect();
+        let json = json!({"baseline_scan_time": baseline_time, "growth": folders});
+        return writeln!(out, "{}", json);
+    }
+
+    writeln!(out, "Largest growth since {}:", baseline_time)?;
+    for delta in &growth {
+        writeln!(
+            out,
+            "{:>11}  {:>10}  {}",
+            format!("+{}", format_bytes(delta.after - delta.before)),
+            format_bytes(delta.after),
+            display_path(&delta.path)
+        )?;
     }
+    Ok(())
 }
 
-/// Retrieves volume information for the given path.
-fn get_volume_info(root_path: &Path, disks: &Disks) -> (String, u64, u64) {
-    // Convert root_path to absolute path
-    #[cfg(windows)]
-    let mut abs_root_path = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+/// Returns where reports are printed: stdout, or stderr if the dump is written to stdout.
+fn report_output(matches: &ArgMatches) -> Box<dyn Write> {
+    if writes_to_stdout(matches) {
+        Box::new(io::stderr().lock())
+    } else {
+        Box::new(io::stdout().lock())
+    }
+}
 
-    #[cfg(not(windows))]
-    let abs_root_path = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+/// Returns true if the dump is written to stdout: by default, with `-o -` or `-o /dev/stdout`,
+/// or with `--output-fd 1`.
+fn writes_to_stdout(matches: &ArgMatches) -> bool {
+    if matches.contains_id("output-url") {
+        return false;
+    }
+    if let Some(&fd) = matches.get_one::<i32>("output-fd") {
+        return fd == 1;
+    }
+    output_name(matches).is_none_or(|output| is_stdout(Path::new(&output)))
+}
 
-    // Remove the "\\?\" prefix on Windows
-    #[cfg(windows)]
-    {
-        abs_root_path =
-            std::path::PathBuf::from(abs_root_path.to_string_lossy().replacen(r"\\?\", "", 1));
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Writes the metrics to `--metrics-textfile`, if given.
+pub(crate) fn write_metrics(matches: &ArgMatches, metrics: &ScanMetrics) -> Result<(), Error> {
+    match matches.get_one::<String>("metrics-textfile") {
+        Some(path) => write_atomically(Path::new(path), |handle| {
+            handle.write_all(metrics.to_prometheus().as_bytes())
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Keeps the tree up to date and rewrites the output file whenever it changed.
+///
+/// Runs until it is interrupted or an error occurs.
+#[cfg(feature = "watch")]
+fn run_watch(
+    root_path: &Path,
+    options: &Options,
+    matches: &ArgMatches,
+) -> Result<ScanStats, Error> {
+    if !root_path.is_dir() {
+        return Err(Error::InvalidRoot(format!(
+            "Cannot watch {}: --watch needs a directory",
+            root_path.display()
+        )));
     }
+    let output = output_name(matches).expect("--watch requires --output");
+    let interval = Duration::from_secs(*matches.get_one::<u64>("watch-interval").unwrap_or(&60));
 
-    // Collect and sort disks by the depth of their mount points (in descending order)
-    let mut disks: Vec<_> = disks.iter().collect();
-    disks.sort_by_key(|disk| Reverse(disk.mount_point().components().count()));
+    let write_snapshot = |watcher: &TreeWatcher| {
+        let metrics = write_dump_file(matches, Path::new(&output), |handle| {
+            let mut collector = MetricsCollector::new(dump_formatter(matches, handle));
+            write_result(&watcher.result(), &mut collector)?;
+            Ok(collector.into_parts().0)
+        })?;
+        info!("Wrote snapshot to {}", output);
+        write_metrics(matches, &metrics)?;
+        Ok::<_, Error>(())
+    };
 
-    // Find the first matching disk
-    for disk in disks {
-        let mount_point = disk.mount_point();
+    let mut watcher = TreeWatcher::new(root_path, options)?;
+    write_snapshot(&watcher)?;
+    info!("Watching {} for changes", root_path.display());
 
-        if abs_root_path.starts_with(mount_point) {
-            let volume_path = mount_point.to_string_lossy().to_string();
-            let volume_size = disk.total_space();
-            let free_space = disk.available_space();
-            return (volume_path, volume_size, free_space);
+    loop {
+        if watcher.update(interval)? {
+            write_snapshot(&watcher)?;
         }
     }
+}
 
-    // If no matching disk is found, return defaults
-    (
-        "/".to_string(),
-        0, // volume_size
-        0, // free_space
-    )
-}
-
-fn output_xml_header<W: Write>(writer: &mut Writer<W>) -> io::Result<()> {
-    writer
-        .write_event(Event::Decl(BytesDecl::new(
-            XML_VERSION,
-            Some(XML_ENCODING),
-            None,
-        )))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    let mut root = BytesStart::new(TAG_GRANDPERSPECTIVE_SCAN_DUMP);
-    root.push_attribute(("appVersion", GRANDPERSPECTIVE_APP_VERSION));
-    root.push_attribute(("formatVersion", GRANDPERSPECTIVE_FORMAT_VERSION));
-    writer
-        .write_event(Event::Start(root))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    Ok(())
+#[cfg(not(feature = "watch"))]
+fn run_watch(
+    root_path: &Path,
+    _options: &Options,
+    _matches: &ArgMatches,
+) -> Result<ScanStats, Error> {
+    Err(Error::InvalidRoot(format!(
+        "Cannot watch {}: gpscan was built without the watch feature",
+        root_path.display()
+    )))
+}
+
+/// Returns the formatter of a scan dump, which records the `--largest-files` in the dump.
+pub(crate) fn dump_formatter<W: Write>(matches: &ArgMatches, output: W) -> XmlFormatter<W> {
+    let mut formatter = XmlFormatter::new(output).strict_names(matches.get_flag("strict-names"));
+    if let Some(times) = matches.get_one::<String>("times") {
+        formatter = formatter.timestamps(times.parse().expect("Invalid timestamps"));
+    }
+    match matches.get_one::<usize>("largest-files") {
+        Some(&n) if !matches.get_flag("print-largest-files") => formatter.largest_files(n),
+        _ => formatter,
+    }
 }
 
-/// Recursively traverses the directory and outputs XML.
-fn traverse_directory_to_xml<W: Write>(
+/// Checks that the output file `path` can be created, and returns it in the form to open.
+///
+/// On Windows, a name such as `CON` opens a device instead, and a name ending with a
+/// period loses it, so these are rejected naming the component. Paths longer than
+/// `MAX_PATH` are given the extended-length prefix.
+fn output_path(path: &Path) -> Result<PathBuf, Error> {
+    if !cfg!(target_os = "windows") {
+        return Ok(path.to_path_buf());
+    }
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if let Some(problem) = windows_name_problem(&name) {
+            return Err(Error::Output(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid output file '{}': '{}' {}",
+                    path.display(),
+                    name,
+                    problem
+                ),
+            )));
+        }
+    }
+    Ok(long_path(path))
+}
+
+/// Writes a dump to a temporary file and moves it to `path` once it is complete,
+/// so that readers never see a partial file.
+pub(crate) fn write_atomically<T>(
     path: &Path,
-    is_root: bool,
-    root_dev: u64,
-    options: &Options,
-    visited_inodes: &mut HashSet<u64>,
-    writer: &mut Writer<W>,
-) -> io::Result<()> {
-    // Get metadata of the current directory
-    let metadata = match get_metadata(path) {
-        Ok(metadata) => metadata,
-        Err(_) => return Ok(()),
-    };
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> Result<T, Error> {
+    let path = &output_path(path)?;
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+
+    let file = fs::File::create(&temp_path).map_err(|e| {
+        Error::Output(io::Error::new(
+            e.kind(),
+            format!("Failed to create output file '{}': {}", path.display(), e),
+        ))
+    })?;
+    let mut temp = TempFile::new(PathBuf::from(temp_path));
+    let mut writer = io::BufWriter::new(file);
+    let value = write(&mut writer)
+        .and_then(|value| writer.flush().map(|()| value))
+        .map_err(output_error)?;
+    // Closed before it is moved, which Windows requires
+    drop(writer);
+    temp.persist(path).map_err(|e| {
+        Error::Output(io::Error::new(
+            e.kind(),
+            format!("Failed to move the output to '{}': {}", path.display(), e),
+        ))
+    })?;
+    Ok(value)
+}
 
-    // Check if the current directory is on a different filesystem
-    if !options.cross_mount_points {
-        let current_dev = metadata.device_id();
-
-        if current_dev != root_dev {
-            info!(
-                "Skipping directory on different filesystem: {} (root: {}, current: {})",
-                path.display(),
-                root_dev,
-                current_dev
-            );
-            return Ok(());
+/// A temporary output file, removed when it is dropped unless it was moved into place,
+/// so that no incomplete file is left behind on any error.
+pub(crate) struct TempFile(Option<PathBuf>);
+
+impl TempFile {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        TempFile(Some(path))
+    }
+
+    /// Moves the file to `path`, where it is kept.
+    pub(crate) fn persist(&mut self, path: &Path) -> io::Result<()> {
+        if let Some(temp_path) = &self.0 {
+            fs::rename(temp_path, path)?;
+            self.0 = None;
         }
+        Ok(())
     }
+}
 
-    // Get file times
-    let (created, modified, accessed) = get_file_times(&metadata);
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
 
-    // Get directory name
-    let name = if is_root {
-        path.display().to_string()
+/// Builds the scan options, including the `--progress` line or `--progress-json` records.
+pub(crate) fn scan_options(matches: &ArgMatches) -> Result<Options, Error> {
+    let mut option = Options::from_matches(matches);
+    let sink: Option<Arc<dyn ProgressSink>> = match matches.get_one::<String>("progress-json") {
+        Some(path) if path == "-" => Some(Arc::new(JsonProgress::new(io::stderr()))),
+        Some(path) => {
+            let file = fs::File::create(path).map_err(|e| {
+                Error::Output(io::Error::new(
+                    e.kind(),
+                    format!("Failed to open progress output '{}': {}", path, e),
+                ))
+            })?;
+            Some(Arc::new(JsonProgress::new(file)))
+        }
+        None if matches.get_flag("progress") => Some(Arc::new(TerminalProgress)),
+        None => None,
+    };
+    option.progress = sink.map(|sink| ProgressReporter {
+        sink,
+        interval: PROGRESS_INTERVAL,
+    });
+    Ok(option)
+}
+
+/// Scans the objects below an `s3://bucket/prefix` URL.
+#[cfg(feature = "s3")]
+fn run_s3(url: &str, matches: &ArgMatches) -> Result<(ScanStats, ScanMetrics), Error> {
+    let location: S3Location = url.parse().map_err(Error::InvalidRoot)?;
+    let result = scan_s3(&location, &S3Config::from_env(), &scan_options(matches)?)
+        .map_err(|e| Error::InvalidRoot(format!("Failed to list {}: {}", location, e)))?;
+
+    let metrics = write_dump(matches, |handle| {
+        let mut collector = MetricsCollector::new(dump_formatter(matches, handle));
+        write_result(&result, &mut collector)?;
+        Ok(collector.into_parts().0)
+    })?;
+    write_metrics(matches, &metrics)?;
+    Ok((ScanStats::default(), metrics))
+}
+
+#[cfg(not(feature = "s3"))]
+fn run_s3(url: &str, _matches: &ArgMatches) -> Result<(ScanStats, ScanMetrics), Error> {
+    Err(Error::InvalidRoot(format!(
+        "Cannot scan {}: gpscan was built without the s3 feature",
+        url
+    )))
+}
+
+/// Writes the scan dump to `--output-url`, to `--output`, or to stdout.
+///
+/// For `--output-url`, the dump is compressed into a temporary file, which is
+/// uploaded once it is complete.
+pub(crate) fn write_dump<T>(
+    matches: &ArgMatches,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> Result<T, Error> {
+    if let Some(url) = matches.get_one::<String>("output-url") {
+        return upload_dump(matches, url, write);
+    }
+
+    let (value, digest, output) = match output_file(matches)? {
+        Some(mut file) => {
+            let (value, digest) = digest_output(matches, &mut file, |handle| {
+                encode_output(matches, handle, write)
+            })
+            .map_err(output_error)?;
+            (value, digest, Some(file.path().to_path_buf()))
+        }
+        None => {
+            let mut handle = create_output(matches)?;
+            let (value, digest) = digest_output(matches, &mut handle, |handle| {
+                encode_output(matches, handle, write)
+            })
+            .map_err(output_error)?;
+            (value, digest, output_name(matches).map(PathBuf::from))
+        }
+    };
+    if let Some(digest) = digest {
+        write_checksum(matches, output.as_deref(), &digest)?;
+    }
+    Ok(value)
+}
+
+/// Returns the error of writing the output, which is an output error if its disk is full.
+///
+/// Other errors, such as those of the scan, keep their class.
+fn output_error(e: io::Error) -> Error {
+    if is_storage_full(&e) {
+        Error::Output(e)
     } else {
-        path.file_name()
-            .unwrap_or_else(|| path.as_os_str())
-            .to_string_lossy()
-            .to_string()
+        Error::Io(e)
+    }
+}
+
+/// Creates the output file, unless the output goes to a stream such as stdout, a pipe,
+/// or an `--output-fd` descriptor.
+fn output_file(matches: &ArgMatches) -> Result<Option<OutputFile>, Error> {
+    if matches.contains_id("output-fd") {
+        return Ok(None);
+    }
+    let Some(output) = output_name(matches).filter(|output| !is_stream(Path::new(output))) else {
+        return Ok(None);
     };
+    let fallback = match matches.get_one::<String>("fallback-output") {
+        Some(fallback) => Some(output_path(Path::new(fallback))?),
+        None => None,
+    };
+    OutputFile::create(&output_path(Path::new(&output))?, fallback.as_deref()).map(Some)
+}
+
+/// Writes a dump file atomically, encoded as given by `--compress` and `--encrypt`,
+/// followed by its `--checksum` file.
+pub(crate) fn write_dump_file<T>(
+    matches: &ArgMatches,
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> Result<T, Error> {
+    let (value, digest) = write_atomically(path, |handle| {
+        digest_output(matches, handle, |handle| {
+            encode_output(matches, handle, write)
+        })
+    })?;
+    if let Some(digest) = digest {
+        write_checksum(matches, Some(path), &digest)?;
+    }
+    Ok(value)
+}
 
-    // Read directory entries
-    let mut entries: Vec<_> = match read_directory(path) {
-        Ok(entries) => entries,
-        Err(_) => return Ok(()),
+/// Computes the SHA-256 digest of the dump as written if `--checksum` is given.
+fn digest_output<T>(
+    matches: &ArgMatches,
+    handle: &mut dyn Write,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> io::Result<(T, Option<String>)> {
+    if !matches.contains_id("checksum") {
+        return Ok((write(handle)?, None));
+    }
+    let mut writer = DigestWriter {
+        inner: handle,
+        hasher: Sha256::new(),
     };
+    let value = write(&mut writer)?;
+    Ok((value, Some(format!("{:x}", writer.hasher.finalize()))))
+}
 
-    // Check if the folder is empty and should be skipped
-    if entries.is_empty() && !options.include_empty_folders {
-        info!("Skipping empty folder: {}", path.display());
-        return Ok(());
+/// Passes the output through while hashing it, for `--checksum`.
+struct DigestWriter<'a> {
+    inner: &'a mut dyn Write,
+    hasher: Sha256,
+}
+
+impl Write for DigestWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
     }
 
-    // Sort entries by file name
-    entries.sort_by(|a, b| {
-        a.file_name()
-            .to_string_lossy()
-            .cmp(&b.file_name().to_string_lossy())
-    });
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-    // Output Folder tag
-    let mut folder_tag = BytesStart::new(TAG_FOLDER);
-    folder_tag.push_attribute(("name", escape(&name).as_ref()));
-    folder_tag.push_attribute(("created", created.as_str()));
-    folder_tag.push_attribute(("modified", modified.as_str()));
-    folder_tag.push_attribute(("accessed", accessed.as_str()));
-    writer
-        .write_event(Event::Start(folder_tag))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-    // Iterate over directory entries
-    for entry in entries {
-        let entry_path = entry.path();
-
-        // Get metadata of the entry
-        let entry_metadata = match fs::symlink_metadata(&entry_path) {
-            Ok(m) => m,
-            Err(e) => {
-                error!(
-                    "Failed to access metadata for '{}': {}",
-                    entry_path.display(),
-                    e
-                );
-                continue;
-            }
-        };
+/// Prints the digest of the dump in the format of `sha256sum`, and writes it to
+/// `<output>.sha256` next to an output file, so that `sha256sum -c` can check the copy.
+fn write_checksum(matches: &ArgMatches, output: Option<&Path>, digest: &str) -> Result<(), Error> {
+    let name = output
+        .and_then(Path::file_name)
+        .map_or("-".into(), |name| name.to_string_lossy());
+    let line = format!("{}  {}\n", digest, name);
+    report_output(matches).write_all(line.as_bytes())?;
 
-        let file_type = entry_metadata.file_type();
+    match output {
+        Some(path) if !is_stream(path) => {
+            let mut checksum_path = path.as_os_str().to_owned();
+            checksum_path.push(".sha256");
+            write_atomically(Path::new(&checksum_path), |handle| {
+                handle.write_all(line.as_bytes())
+            })
+        }
+        _ => Ok(()),
+    }
+}
 
-        if file_type.is_symlink() {
-            // Skip symbolic links
-            info!("Skipping symbolic link: {}", entry_path.display());
-            continue;
-        } else if file_type.is_dir() {
-            // Recursively traverse directories
-            traverse_directory_to_xml(
-                &entry_path,
-                false,
-                root_dev,
-                options,
-                visited_inodes,
-                writer,
-            )?;
-        } else if file_type.is_file() {
-            // Process file entries
-            process_file_entry(
-                &entry_path,
-                &entry_metadata,
-                options,
-                visited_inodes,
-                writer,
-            )?;
+/// Compresses the dump with gzip if `--compress` is given, then encrypts it with `--encrypt`.
+fn encode_output<T>(
+    matches: &ArgMatches,
+    handle: &mut dyn Write,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> io::Result<T> {
+    encrypt_output(matches, handle, |handle| {
+        compress_output(matches, handle, write)
+    })
+}
+
+/// Compresses the dump with gzip if `--compress` is given.
+fn compress_output<T>(
+    matches: &ArgMatches,
+    handle: &mut dyn Write,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> io::Result<T> {
+    if !matches.get_flag("compress") {
+        return write(handle);
+    }
+    let mut encoder = GzEncoder::new(handle, Compression::default());
+    let value = write(&mut encoder)?;
+    encoder.finish()?;
+    Ok(value)
+}
+
+/// Encrypts the dump with `--encrypt`, whose recipients were checked when the arguments were parsed.
+#[cfg(feature = "encrypt")]
+fn encrypt_output<T>(
+    matches: &ArgMatches,
+    handle: &mut dyn Write,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> io::Result<T> {
+    let Some(values) = matches.get_many::<String>("encrypt") else {
+        return write(handle);
+    };
+    let encryption = Encryption::parse(values.map(String::as_str))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    encryption.encrypt(handle, write)
+}
+
+#[cfg(not(feature = "encrypt"))]
+fn encrypt_output<T>(
+    _matches: &ArgMatches,
+    handle: &mut dyn Write,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> io::Result<T> {
+    write(handle)
+}
+
+#[cfg(feature = "http")]
+fn upload_dump<T>(
+    matches: &ArgMatches,
+    url: &str,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> Result<T, Error> {
+    // A full temporary directory is not a full output, so it is not reported as one
+    let temp_error = |e: io::Error| {
+        Error::Io(io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to write the temporary file of the upload in {}: {}",
+                std::env::temp_dir().display(),
+                e
+            ),
+        ))
+    };
+    let mut file = tempfile::tempfile().map_err(temp_error)?;
+    let value = encrypt_output(matches, &mut file, |handle| {
+        let mut encoder = GzEncoder::new(handle, Compression::default());
+        let value = write(&mut encoder)?;
+        encoder.finish()?;
+        Ok(value)
+    })
+    .map_err(|e| {
+        if is_storage_full(&e) {
+            temp_error(e)
         } else {
-            // Handle other file types
-            warn!("Unknown file type: {}", entry_path.display());
+            Error::Io(e)
         }
-    }
+    })?;
 
-    // Close Folder tag
-    writer
-        .write_event(Event::End(BytesEnd::new(TAG_FOLDER)))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    Ok(())
+    upload(url, &mut file).map_err(|e| {
+        Error::Output(io::Error::new(
+            e.kind(),
+            format!("Failed to upload output to '{}': {}", url, e),
+        ))
+    })?;
+    Ok(value)
 }
 
-/// Processes a file entry and outputs XML.
-fn process_file_entry<W: Write>(
-    path: &Path,
-    metadata: &Metadata,
-    options: &Options,
-    visited_inodes: &mut HashSet<u64>,
-    writer: &mut Writer<W>,
-) -> io::Result<()> {
-    // Get inode number
-    let inode = metadata.inode_number();
+#[cfg(not(feature = "http"))]
+fn upload_dump<T>(
+    _matches: &ArgMatches,
+    url: &str,
+    _write: impl FnOnce(&mut dyn Write) -> io::Result<T>,
+) -> Result<T, Error> {
+    Err(Error::Output(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "Cannot upload output to '{}': gpscan was built without the http feature",
+            url
+        ),
+    )))
+}
 
-    // Skip if the file is a hard link
-    if visited_inodes.contains(&inode) {
-        info!("Skipping hard link file: {}", path.display());
-        return Ok(());
-    }
+/// Returns the output file name, with its placeholders expanded for the time this run started.
+///
+/// Returns `None` for stdout, either by default or with `-o -`.
+pub(crate) fn output_name(matches: &ArgMatches) -> Option<String> {
+    static STARTED: OnceLock<DateTime<Utc>> = OnceLock::new();
+    let started = *STARTED.get_or_init(Utc::now);
+    matches
+        .get_one::<String>("output")
+        .filter(|output| *output != "-")
+        .map(|output| expand_output_name(output, started))
+}
 
-    // Add inode number to the set of visited inodes
-    visited_inodes.insert(inode);
+/// Adds `placeholder` before the extension of the output name unless it is already there,
+/// so that `scan.gpscan.gz` becomes `scan-{volume}.gpscan.gz` for `{volume}`.
+pub fn placeholder_template(output: &str, placeholder: &str) -> String {
+    if output.contains(placeholder) {
+        return output.to_string();
+    }
 
-    // Get file name
+    let path = Path::new(output);
     let name = path
         .file_name()
-        .unwrap_or_else(|| path.as_os_str())
-        .to_string_lossy()
-        .to_string();
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Keep compound extensions such as `.gpscan.gz` together
+    let dot = name
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '.')
+        .map(|(index, _)| index);
+    let name = match dot {
+        Some(dot) => {
+            let (stem, extension) = name.split_at(dot);
+            format!("{}-{}{}", stem, placeholder, extension)
+        }
+        None => format!("{}-{}", name, placeholder),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
 
-    // Get physical file size
-    let size = metadata.file_size(options.apparent_size);
+/// Opens the `--output-fd` descriptor or the output file, or stdout if neither was given.
+fn create_output(matches: &ArgMatches) -> Result<Box<dyn Write>, Error> {
+    match matches.get_one::<i32>("output-fd") {
+        // Standard streams stay open for the log and the reports
+        Some(1) => return Ok(Box::new(io::stdout())),
+        Some(2) => return Ok(Box::new(io::stderr())),
+        Some(&fd) => {
+            let file = open_fd(fd).map_err(|e| {
+                Error::Output(io::Error::new(
+                    e.kind(),
+                    format!("Failed to open output file descriptor {}: {}", fd, e),
+                ))
+            })?;
+            return Ok(Box::new(file));
+        }
+        None => {}
+    }
+    match output_name(matches) {
+        Some(file) => {
+            let file = fs::File::create(output_path(Path::new(&file))?).map_err(|e| {
+                Error::Output(io::Error::new(
+                    e.kind(),
+                    format!("Failed to create output file '{}': {}", file, e),
+                ))
+            })?;
+            Ok(Box::new(file))
+        }
+        None => Ok(Box::new(io::stdout())),
+    }
+}
 
-    // Skip zero-byte files if the `include_zero_files` option is not set
-    if size == 0 && !options.include_zero_files {
-        info!("Skipping zero-byte file: {}", path.display());
-        return Ok(());
+/// Runs the `merge` subcommand.
+pub fn run_merge(matches: &ArgMatches) -> Result<(), Error> {
+    let mut results = Vec::new();
+    for file in matches.get_many::<String>("files").into_iter().flatten() {
+        let result = read_scan(Path::new(file))
+            .map_err(|e| Error::InvalidDump(format!("{}: {}", file, e)))?;
+        results.push(result);
     }
 
-    // Get file times
-    let (created, modified, accessed) = get_file_times(metadata);
+    let root_name = matches
+        .get_one::<String>("root-name")
+        .expect("Root name has a default value");
+    let merged = merge(results, root_name);
 
-    // Output File tag
-    let mut file_tag = BytesStart::new(TAG_FILE);
-    file_tag.push_attribute(("name", escape(&name).as_ref()));
-    file_tag.push_attribute(("size", size.to_string().as_str()));
-    file_tag.push_attribute(("created", created.as_str()));
-    file_tag.push_attribute(("modified", modified.as_str()));
-    file_tag.push_attribute(("accessed", accessed.as_str()));
-    writer
-        .write_event(Event::Empty(file_tag))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let handle = create_output(matches)?;
+    Ok(write_result(&merged, XmlFormatter::new(handle))?)
+}
 
-    Ok(())
+/// Runs the `import` subcommand.
+pub fn run_import(matches: &ArgMatches) -> Result<(), Error> {
+    let file = matches
+        .get_one::<String>("file")
+        .expect("Input file is required");
+    let format: ImportFormat = matches
+        .get_one::<String>("from")
+        .expect("Input format is required")
+        .parse()
+        .expect("Invalid import format");
+
+    let input: Box<dyn BufRead> = if file == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        open_dump(Path::new(file)).map_err(|e| Error::InvalidDump(format!("{}: {}", file, e)))?
+    };
+
+    let result = match format {
+        ImportFormat::Ncdu => import_ncdu(input, matches.get_flag("apparent-size")),
+        ImportFormat::Du => import_du(
+            input,
+            *matches.get_one::<u64>("block-size").unwrap_or(&1024),
+        ),
+    }
+    .map_err(|e| Error::InvalidDump(format!("{}: {}", file, e)))?;
+
+    let handle = create_output(matches)?;
+    Ok(write_result(&result, XmlFormatter::new(handle))?)
 }
 
-fn format_system_time(sys_time: Result<SystemTime, io::Error>) -> String {
-    match sys_time {
-        Ok(t) => {
-            let datetime: DateTime<Utc> = t.into();
-            datetime.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+/// Runs the `top` subcommand.
+pub fn run_top(matches: &ArgMatches) -> Result<(), Error> {
+    let input = matches
+        .get_one::<String>("input")
+        .expect("Input path is required");
+    let count = *matches.get_one::<usize>("count").unwrap_or(&20);
+    let path = Path::new(input);
+
+    let root = if path.is_dir() {
+        let options = Options::builder()
+            .apparent_size(matches.get_flag("apparent-size"))
+            .cross_mount_points(matches.get_flag("mounts"))
+            .build();
+        scan_to_tree(path, &options)?
+    } else if path.exists() {
+        read_scan(path)
+            .map_err(|e| Error::InvalidDump(format!("{}: {}", input, e)))?
+            .root
+    } else {
+        return Err(Error::InvalidRoot(format!(
+            "The specified path does not exist: {}",
+            path.display()
+        )));
+    };
+
+    let top = top_entries(&root, count);
+    let mut out = io::stdout().lock();
+    for (title, list) in [
+        ("Largest files", &top.files),
+        ("Largest directories", &top.folders),
+    ] {
+        writeln!(out, "{}:", title)?;
+        for (path, size) in list {
+            writeln!(out, "{:>10}  {}", format_bytes(*size), path.display())?;
         }
-        Err(_) => DEFAULT_DATETIME.to_string(),
     }
+    Ok(())
 }
 
-/// Retrieves creation, modification, and access times from metadata.
-fn get_file_times(metadata: &Metadata) -> (String, String, String) {
-    let created = format_system_time(metadata.created());
-    let modified = format_system_time(metadata.modified());
-    let accessed = format_system_time(metadata.accessed());
+/// Runs the `validate` subcommand.
+pub fn run_validate(matches: &ArgMatches) -> Result<(), Error> {
+    let file = matches
+        .get_one::<String>("file")
+        .expect("File path is required");
 
-    (created, modified, accessed)
+    let report = validate_file(Path::new(file))
+        .map_err(|e| Error::InvalidDump(format!("{}: {}", file, e)))?;
+    println!(
+        "{}: OK ({} folders, {} files, {} bytes)",
+        file, report.folders, report.files, report.total_size
+    );
+    Ok(())
 }
@@ -0,0 +1,226 @@
+// External crates
+use log::{debug, warn};
+
+// Standard library imports
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::platform;
+
+/// External command deciding which entries are skipped, with `--filter-cmd`.
+///
+/// The command is started with the system shell when the first directory is read, and
+/// runs until the options are dropped. For each directory, it reads the paths of the
+/// entries on its standard input, one per line, followed by an empty line, and writes
+/// `keep` or `skip` on a line for each path, in the same order. Paths that contain a
+/// line break cannot be sent, and are skipped.
+///
+/// The decisions are cached per directory with its modification time, so that later
+/// scans with the same options, as in watch mode, only ask about changed directories.
+#[derive(Clone)]
+pub(crate) struct FilterCommand {
+    command: Arc<str>,
+    state: Arc<Mutex<FilterState>>,
+}
+
+impl fmt::Debug for FilterCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FilterCommand").field(&self.command).finish()
+    }
+}
+
+#[derive(Default)]
+struct FilterState {
+    process: Option<FilterProcess>,
+    cache: HashMap<PathBuf, CachedDecisions>,
+}
+
+/// Names of the skipped entries of a directory, as it was when it was modified last.
+struct CachedDecisions {
+    modified: SystemTime,
+    skipped: HashSet<OsString>,
+}
+
+impl FilterCommand {
+    pub(crate) fn new(command: impl Into<String>) -> Self {
+        FilterCommand {
+            command: command.into().into(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Returns whether each of `paths`, entries of the directory at `dir`, is skipped.
+    ///
+    /// `modified` is the modification time of the directory, if known; without one, the
+    /// decisions are not cached.
+    pub(crate) fn skipped(
+        &self,
+        dir: &Path,
+        modified: Option<SystemTime>,
+        paths: &[PathBuf],
+    ) -> io::Result<Vec<bool>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = state
+            .cache
+            .get(dir)
+            .filter(|cached| Some(cached.modified) == modified)
+        {
+            debug!("Using the cached filter decisions for {}", dir.display());
+            return Ok(paths
+                .iter()
+                .map(|path| {
+                    path.file_name()
+                        .is_some_and(|name| cached.skipped.contains(name))
+                })
+                .collect());
+        }
+
+        let (sendable, unsendable): (Vec<_>, Vec<_>) = paths
+            .iter()
+            .partition(|path| !path.as_os_str().as_encoded_bytes().contains(&b'\n'));
+        for path in &unsendable {
+            warn!(
+                "Skipping {:?}: paths with line breaks cannot be passed to the filter command",
+                path
+            );
+        }
+        let answers = match self.ask(&mut state, &sendable) {
+            Ok(answers) => answers,
+            Err(e) => {
+                // Started again by the next scan
+                state.process = None;
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("Filter command '{}' failed: {}", self.command, e),
+                ));
+            }
+        };
+        let skipped: HashSet<&Path> = sendable
+            .into_iter()
+            .zip(answers)
+            .filter(|&(_, skip)| skip)
+            .map(|(path, _)| path.as_path())
+            .chain(unsendable.into_iter().map(PathBuf::as_path))
+            .collect();
+
+        if let Some(modified) = modified {
+            let names = skipped.iter().filter_map(|path| path.file_name());
+            state.cache.insert(
+                dir.to_path_buf(),
+                CachedDecisions {
+                    modified,
+                    skipped: names.map(OsString::from).collect(),
+                },
+            );
+        }
+        Ok(paths
+            .iter()
+            .map(|path| skipped.contains(path.as_path()))
+            .collect())
+    }
+
+    /// Sends `paths` to the command, starting it if needed, and reads its answers.
+    fn ask(&self, state: &mut FilterState, paths: &[&PathBuf]) -> io::Result<Vec<bool>> {
+        if paths.is_empty() {
+            return Ok(Vec::new());
+        }
+        let process = match &mut state.process {
+            Some(process) => process,
+            process => process.insert(FilterProcess::start(&self.command)?),
+        };
+        process.ask(paths)
+    }
+}
+
+/// The running filter command.
+struct FilterProcess {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl FilterProcess {
+    fn start(command: &str) -> io::Result<Self> {
+        debug!("Starting the filter command: {}", command);
+        let mut child = platform::shell_command(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(FilterProcess {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    fn ask(&mut self, paths: &[&PathBuf]) -> io::Result<Vec<bool>> {
+        let mut batch = Vec::new();
+        for path in paths {
+            batch.extend_from_slice(path.as_os_str().as_encoded_bytes());
+            batch.push(b'\n');
+        }
+        batch.push(b'\n');
+
+        let stdin = self.stdin.as_mut().expect("stdin is open until dropped");
+        let (child, stdout) = (&mut self.child, &mut self.stdout);
+        // Written by another thread, so that a command answering while it reads cannot
+        // block on a full pipe while a large folder is still being sent
+        thread::scope(|scope| {
+            let writer = scope.spawn(|| stdin.write_all(&batch).and_then(|()| stdin.flush()));
+            let answers = read_answers(stdout, paths);
+            if answers.is_err() {
+                // Unblocks the writer if the command stopped reading
+                let _ = child.kill();
+            }
+            match writer.join().expect("the writer does not panic") {
+                Ok(()) => answers,
+                // The answers tell more, such as that the command exited
+                Err(e) => answers.and(Err(e)),
+            }
+        })
+    }
+}
+
+impl Drop for FilterProcess {
+    fn drop(&mut self) {
+        // Closing the input tells the command to exit
+        self.stdin.take();
+        if let Err(e) = self.child.wait() {
+            warn!("Failed to wait for the filter command: {}", e);
+        }
+    }
+}
+
+/// Reads an answer for each of `paths`.
+fn read_answers(stdout: &mut impl BufRead, paths: &[&PathBuf]) -> io::Result<Vec<bool>> {
+    let mut line = String::new();
+    paths
+        .iter()
+        .map(|path| {
+            line.clear();
+            if stdout.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the command exited before answering",
+                ));
+            }
+            match line.trim_end() {
+                "keep" => Ok(false),
+                "skip" => Ok(true),
+                answer => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid answer '{}' for {}", answer, path.display()),
+                )),
+            }
+        })
+        .collect()
+}
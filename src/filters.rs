@@ -0,0 +1,221 @@
+//! Rules that decide which entries are left out of a scan.
+//!
+//! The rules are evaluated in this order, and the first one that matches decides:
+//!
+//! 1. [`SkipReason::Excluded`]: the name matches an exclude pattern (before the metadata is read).
+//! 2. [`SkipReason::FilterCommand`]: the filter command answers `skip` for the path (before
+//!    the metadata is read).
+//! 3. [`SkipReason::Symlink`], [`SkipReason::Junction`], [`SkipReason::OtherType`]: only
+//!    files and directories are scanned. Junctions can be recorded as empty folders instead
+//!    ([`OptionsBuilder::record_junctions`](crate::OptionsBuilder::record_junctions)).
+//!
+//! Then, for directories:
+//!
+//! 4. [`SkipReason::OtherFileSystem`]: the directory is on another file system than the
+//!    scan root (btrfs subvolumes excepted), unless mount points are crossed.
+//! 5. [`SkipReason::NetworkFileSystem`]: the directory is on a network file system (local only).
+//! 6. [`SkipReason::VisitedDirectory`]: the directory was already entered (bind mounts and loops).
+//! 7. [`SkipReason::EmptyFolder`]: the directory is empty, unless empty folders are included.
+//! 8. [`SkipReason::LargeFolder`]: the directory has more entries than allowed; it is kept,
+//!    but its entries are left out.
+//!
+//! And for files:
+//!
+//! 4. [`SkipReason::HardLink`]: the file is a hard link to a file that was already counted.
+//! 5. [`SkipReason::Owner`]: the file belongs to an excluded user, or not to one of the
+//!    users it is limited to.
+//! 6. [`SkipReason::ZeroSize`]: the file has a size of zero, unless zero-byte files are included.
+//!
+//! The scan root is never skipped by rules 2 and 4 to 7. The rules that depend on the
+//! entries seen so far, and the filter command, are applied by the traversal; the others
+//! are available as functions here.
+
+// External crates
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Standard library imports
+use std::fmt;
+
+use crate::options::Options;
+use crate::source::EntryKind;
+
+/// Why an entry was left out of a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    Excluded,
+    /// Entries that the command given with `--filter-cmd` decided to skip.
+    FilterCommand,
+    Symlink,
+    Junction,
+    /// Sockets, FIFOs, devices, and other special files.
+    OtherType,
+    OtherFileSystem,
+    NetworkFileSystem,
+    VisitedDirectory,
+    EmptyFolder,
+    LargeFolder,
+    HardLink,
+    Owner,
+    ZeroSize,
+}
+
+impl SkipReason {
+    /// All reasons, in evaluation order.
+    pub const ALL: [SkipReason; 13] = [
+        SkipReason::Excluded,
+        SkipReason::FilterCommand,
+        SkipReason::Symlink,
+        SkipReason::Junction,
+        SkipReason::OtherType,
+        SkipReason::OtherFileSystem,
+        SkipReason::NetworkFileSystem,
+        SkipReason::VisitedDirectory,
+        SkipReason::EmptyFolder,
+        SkipReason::LargeFolder,
+        SkipReason::HardLink,
+        SkipReason::Owner,
+        SkipReason::ZeroSize,
+    ];
+
+    /// Returns the name of the reason, as used in the scan summary.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SkipReason::Excluded => "excluded",
+            SkipReason::FilterCommand => "filter-command",
+            SkipReason::Symlink => "symlink",
+            SkipReason::Junction => "junction",
+            SkipReason::OtherType => "other-type",
+            SkipReason::OtherFileSystem => "other-file-system",
+            SkipReason::NetworkFileSystem => "network-file-system",
+            SkipReason::VisitedDirectory => "visited-directory",
+            SkipReason::EmptyFolder => "empty-folder",
+            SkipReason::LargeFolder => "large-folder",
+            SkipReason::HardLink => "hard-link",
+            SkipReason::Owner => "owner",
+            SkipReason::ZeroSize => "zero-size",
+        }
+    }
+
+    /// Describes a skipped entry, as in "Skipping symbolic link: /path".
+    pub(crate) fn description(&self) -> &'static str {
+        match self {
+            SkipReason::Excluded => "excluded entry",
+            SkipReason::FilterCommand => "entry rejected by the filter command",
+            SkipReason::Symlink => "symbolic link",
+            SkipReason::Junction => "junction",
+            SkipReason::OtherType => "entry of unknown file type",
+            SkipReason::OtherFileSystem => "directory on different filesystem",
+            SkipReason::NetworkFileSystem => "directory on network file system",
+            SkipReason::VisitedDirectory => "already visited directory (bind mount or loop)",
+            SkipReason::EmptyFolder => "empty folder",
+            SkipReason::LargeFolder => "entries of large folder",
+            SkipReason::HardLink => "hard link file",
+            SkipReason::Owner => "file of another user",
+            SkipReason::ZeroSize => "zero-byte file",
+        }
+    }
+
+    /// Returns true if the entry is skipped although the options do not ask for it.
+    pub(crate) fn is_unexpected(&self) -> bool {
+        matches!(self, SkipReason::OtherType | SkipReason::VisitedDirectory)
+    }
+}
+
+/// Number of entries skipped for each [`SkipReason`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SkipCounts {
+    counts: [u64; SkipReason::ALL.len()],
+}
+
+impl SkipCounts {
+    /// Returns the number of entries skipped for `reason`.
+    pub fn get(&self, reason: SkipReason) -> u64 {
+        self.counts[reason as usize]
+    }
+
+    /// Returns the number of skipped entries.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the reasons that entries were skipped for, with their counts, in evaluation order.
+    pub fn iter(&self) -> impl Iterator<Item = (SkipReason, u64)> + '_ {
+        SkipReason::ALL
+            .iter()
+            .map(|&reason| (reason, self.get(reason)))
+            .filter(|&(_, count)| count > 0)
+    }
+
+    pub(crate) fn add(&mut self, reason: SkipReason) {
+        self.counts[reason as usize] += 1;
+    }
+
+    pub(crate) fn set(&mut self, reason: SkipReason, count: u64) {
+        self.counts[reason as usize] = count;
+    }
+
+    /// Adds the counts of another scan.
+    pub fn add_all(&mut self, other: &SkipCounts) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+    }
+}
+
+/// Formats the counts as `symlink=3 zero-size=12`.
+impl fmt::Display for SkipCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (reason, count)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", reason.name(), count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the reason to skip an entry named `name`, before its metadata is read.
+pub fn skip_by_name(name: &str, options: &Options) -> Option<SkipReason> {
+    options.is_excluded(name).then_some(SkipReason::Excluded)
+}
+
+/// Returns the reason to skip an entry of this kind: only files and directories are scanned.
+pub fn skip_by_kind(kind: EntryKind) -> Option<SkipReason> {
+    match kind {
+        EntryKind::File | EntryKind::Dir => None,
+        EntryKind::Symlink => Some(SkipReason::Symlink),
+        EntryKind::Junction => Some(SkipReason::Junction),
+        EntryKind::Other => Some(SkipReason::OtherType),
+    }
+}
+
+/// Returns the reason to skip a folder with `entries` entries, below the scan root.
+pub fn skip_folder(entries: usize, options: &Options) -> Option<SkipReason> {
+    (entries == 0 && !options.include_empty_folders).then_some(SkipReason::EmptyFolder)
+}
+
+/// Returns the reason to leave out the entries of a folder with `entries` entries.
+pub fn skip_folder_entries(entries: usize, options: &Options) -> Option<SkipReason> {
+    options
+        .skip_dir_entries
+        .is_some_and(|max| entries as u64 > max)
+        .then_some(SkipReason::LargeFolder)
+}
+
+/// Returns the reason to skip a file owned by the user ID `owner`, if known.
+///
+/// Files whose owner is not known are only skipped if the scan is limited to some users.
+pub fn skip_by_owner(owner: Option<u32>, options: &Options) -> Option<SkipReason> {
+    let excluded = owner.is_some_and(|uid| options.exclude_users.contains(&uid));
+    let other = !options.only_users.is_empty()
+        && !owner.is_some_and(|uid| options.only_users.contains(&uid));
+    (excluded || other).then_some(SkipReason::Owner)
+}
+
+/// Returns the reason to skip a file of `size` bytes, as it would be written.
+pub fn skip_file(size: u64, options: &Options) -> Option<SkipReason> {
+    (size == 0 && !options.include_zero_files).then_some(SkipReason::ZeroSize)
+}
@@ -0,0 +1,251 @@
+// External crates
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+// Standard library imports
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, BufRead, Read};
+use std::str::FromStr;
+
+use crate::model::{FileNode, ScanInfo, ScanResult};
+
+/// Name of the file that holds the size of the files of a directory in `du`
+/// output without `-a`, where only directory totals are listed.
+const DU_FILES_NAME: &str = "(files)";
+
+/// Formats that can be converted into a scan dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// JSON export of ncdu (`ncdu -o`).
+    Ncdu,
+    /// Output of `du` (`SIZE<TAB>PATH` lines).
+    Du,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ncdu" => Ok(ImportFormat::Ncdu),
+            "du" => Ok(ImportFormat::Du),
+            _ => Err(format!("invalid import format: {}", s)),
+        }
+    }
+}
+
+/// Converts an ncdu JSON export.
+///
+/// Uses the disk usage (`dsize`) of the entries, or the apparent size (`asize`)
+/// if `apparent_size` is set. Hard links are counted once.
+pub fn import_ncdu<R: Read>(input: R, apparent_size: bool) -> io::Result<ScanResult> {
+    let export: Value = serde_json::from_reader(input).map_err(invalid_data)?;
+    let parts = export
+        .as_array()
+        .filter(|parts| parts.len() >= 4)
+        .ok_or_else(|| invalid_data("Not an ncdu export: expected [major, minor, meta, root]"))?;
+
+    let scan_time = parts[2]
+        .get("timestamp")
+        .and_then(Value::as_i64)
+        .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
+        .unwrap_or_else(Utc::now);
+
+    let mut hard_links = HashSet::new();
+    let root = ncdu_node(&parts[3], apparent_size, &mut hard_links)?
+        .ok_or_else(|| invalid_data("The root of the ncdu export is not a directory"))?;
+
+    Ok(import_result(root, scan_time, apparent_size))
+}
+
+/// Converts an ncdu entry: an object for files, an array `[info, children...]` for directories.
+fn ncdu_node(
+    value: &Value,
+    apparent_size: bool,
+    hard_links: &mut HashSet<(u64, u64)>,
+) -> io::Result<Option<FileNode>> {
+    match value {
+        Value::Array(items) => {
+            let info = items
+                .first()
+                .ok_or_else(|| invalid_data("Empty directory entry in ncdu export"))?;
+            let mut folder = FileNode::folder(ncdu_name(info)?);
+            ncdu_attributes(info, &mut folder);
+            for child in &items[1..] {
+                if let Some(node) = ncdu_node(child, apparent_size, hard_links)? {
+                    folder.children.push(node);
+                }
+            }
+            Ok(Some(folder))
+        }
+        Value::Object(_) => {
+            // Skip further links to a file that was already counted
+            if value.get("hlnkc").and_then(Value::as_bool) == Some(true) {
+                let dev = value.get("dev").and_then(Value::as_u64).unwrap_or(0);
+                if let Some(ino) = value.get("ino").and_then(Value::as_u64) {
+                    if !hard_links.insert((dev, ino)) {
+                        return Ok(None);
+                    }
+                }
+            }
+
+            let key = if apparent_size { "asize" } else { "dsize" };
+            let size = value.get(key).and_then(Value::as_u64).unwrap_or(0);
+            let mut file = FileNode::file(ncdu_name(value)?, size);
+            ncdu_attributes(value, &mut file);
+            Ok(Some(file))
+        }
+        _ => Err(invalid_data("Unexpected value in ncdu export")),
+    }
+}
+
+fn ncdu_name(info: &Value) -> io::Result<String> {
+    info.get("name")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| invalid_data("Entry without a name in ncdu export"))
+}
+
+fn ncdu_attributes(info: &Value, node: &mut FileNode) {
+    node.modified = info
+        .get("mtime")
+        .and_then(Value::as_i64)
+        .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0));
+    if info.get("read_error").and_then(Value::as_bool) == Some(true) {
+        node.attributes
+            .push(("unreadable".to_string(), "true".to_string()));
+    }
+}
+
+/// Converts the output of `du`, with sizes in units of `block_size` bytes.
+///
+/// Each line holds a size and a path separated by whitespace, as printed by `du`
+/// and `du -a`. Directory sizes are cumulative; entries without listed children
+/// are treated as files. Without `-a`, the size of the files directly in a
+/// directory is shown as a file named `(files)`.
+pub fn import_du<R: BufRead>(input: R, block_size: u64) -> io::Result<ScanResult> {
+    // Cumulative sizes by path, in path order so that parents come first
+    let mut sizes: BTreeMap<Vec<String>, u64> = BTreeMap::new();
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (size, path) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| invalid_data(format!("Invalid du output at line {}", number + 1)))?;
+        let size = size.parse::<u64>().map_err(|_| {
+            invalid_data(format!(
+                "Invalid size '{}' at line {}: expected an integer",
+                size,
+                number + 1
+            ))
+        })?;
+        let components = path
+            .trim_start()
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let components = if path.trim_start().starts_with('/') {
+            std::iter::once("/".to_string()).chain(components).collect()
+        } else {
+            components
+        };
+        sizes.insert(components, size.saturating_mul(block_size));
+    }
+
+    // du prints the scanned root last; it is the shortest path
+    let root_path = sizes
+        .keys()
+        .min_by_key(|path| path.len())
+        .cloned()
+        .ok_or_else(|| invalid_data("Empty du output"))?;
+    let root_name = if root_path.is_empty() {
+        ".".to_string()
+    } else {
+        join_components(&root_path)
+    };
+
+    let root = match du_node(&root_path, root_name, &sizes) {
+        // A root without listed children still has to be a folder
+        node if !node.is_folder() => {
+            let mut folder = FileNode::folder(node.name);
+            folder
+                .children
+                .push(FileNode::file(DU_FILES_NAME, node.size));
+            folder
+        }
+        node => node,
+    };
+    Ok(import_result(root, Utc::now(), false))
+}
+
+/// Builds the node for `path` from the listed sizes of it and its descendants.
+fn du_node(path: &[String], name: String, sizes: &BTreeMap<Vec<String>, u64>) -> FileNode {
+    let total = sizes.get(path).copied().unwrap_or(0);
+    let children: Vec<&Vec<String>> = sizes
+        .range(path.to_vec()..)
+        .skip(1)
+        .take_while(|(p, _)| p.starts_with(path))
+        .filter(|(p, _)| p.len() == path.len() + 1)
+        .map(|(p, _)| p)
+        .collect();
+
+    if children.is_empty() {
+        return FileNode::file(name, total);
+    }
+
+    let mut folder = FileNode::folder(name);
+    for child in children {
+        let child_name = child.last().cloned().unwrap_or_default();
+        folder.children.push(du_node(child, child_name, sizes));
+    }
+
+    // Sizes not accounted for by the listed children belong to unlisted files
+    let listed = folder.total_size();
+    if total > listed {
+        folder
+            .children
+            .push(FileNode::file(DU_FILES_NAME, total - listed));
+    }
+    folder
+}
+
+fn join_components(path: &[String]) -> String {
+    match path.split_first() {
+        Some((first, rest)) if first == "/" => format!("/{}", rest.join("/")),
+        _ => path.join("/"),
+    }
+}
+
+/// Wraps an imported tree. The volume is unknown, so its size is the size of the tree.
+fn import_result(root: FileNode, scan_time: DateTime<Utc>, apparent_size: bool) -> ScanResult {
+    let total = root.total_size();
+    ScanResult {
+        info: ScanInfo {
+            volume_path: root.name.clone(),
+            volume_size: total,
+            free_space: 0,
+            free_space_after: None,
+            scan_time,
+            file_size_measure: if apparent_size { "logical" } else { "physical" }.to_string(),
+            scan_duration: None,
+            volume_name: None,
+            file_system: None,
+            volume_id: None,
+            snapshot: None,
+            comment: None,
+            errors: None,
+            skipped: None,
+            consistency: None,
+            sample_rate: None,
+        },
+        root,
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,94 @@
+/// How files already counted are remembered, to skip hard links to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InodeFilter {
+    /// Every (device, inode) pair is kept in a hash set, which grows by about 40 bytes
+    /// per file.
+    #[default]
+    Exact,
+    /// A [`BloomFilter`] of the given size in bytes, which never grows but may mistake
+    /// a file for one already counted.
+    Bloom(u64),
+}
+
+/// Bloom filter of (device, inode) pairs, with a fixed size.
+///
+/// A pair that was inserted is always found again, but a pair that was not may be found
+/// as well. With `m` bits and `n` pairs inserted, this happens with a probability of about
+/// `(1 - e^(-k n / m))^k` for the `k` = [`HASHES`](Self::HASHES) bit positions of each
+/// pair: about one in a thousand at 16 bits (2 bytes) per pair, one in 100,000 at 32 bits,
+/// and one in ten million at 64 bits.
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    /// Pairs inserted that were not found before.
+    len: u64,
+}
+
+impl BloomFilter {
+    /// Number of bits set for each pair.
+    pub const HASHES: u32 = 7;
+
+    /// Creates an empty filter using about `bytes` bytes, and at least 8.
+    pub fn new(bytes: u64) -> Self {
+        let words = (bytes / 8).max(1);
+        BloomFilter {
+            bits: vec![0; words as usize],
+            len: 0,
+        }
+    }
+
+    /// Inserts `id`, and returns false if it was found already, possibly by mistake.
+    pub fn insert(&mut self, id: (u64, u64)) -> bool {
+        let mut new = false;
+        for bit in self.positions(id) {
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            new |= self.bits[word] & mask == 0;
+            self.bits[word] |= mask;
+        }
+        if new {
+            self.len += 1;
+        }
+        new
+    }
+
+    /// Returns whether `id` was inserted, or is mistaken for a pair that was.
+    pub fn contains(&self, id: (u64, u64)) -> bool {
+        self.positions(id)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Returns the number of pairs inserted.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether no pair was inserted.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the estimated probability that a pair not inserted is found anyway.
+    pub fn false_positive_rate(&self) -> f64 {
+        let bits = (self.bits.len() * 64) as f64;
+        let k = f64::from(Self::HASHES);
+        (1.0 - (-k * self.len as f64 / bits).exp()).powf(k)
+    }
+
+    /// Returns the bit positions of `id`, by double hashing.
+    fn positions(&self, (device, inode): (u64, u64)) -> impl Iterator<Item = u64> {
+        let bits = self.bits.len() as u64 * 64;
+        let first = mix(inode ^ mix(device));
+        // Odd, so that the positions do not repeat early
+        let step = mix(first) | 1;
+        (0..u64::from(Self::HASHES)).map(move |i| first.wrapping_add(i.wrapping_mul(step)) % bits)
+    }
+}
+
+/// The SplitMix64 finalizer, which spreads the similar inode numbers of a directory
+/// over the whole filter.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,119 @@
+// Standard library imports
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+
+use crate::error::{Error, Result};
+use crate::options::Options;
+use crate::scan::{scan_with_visitor, ScanEntry, ScanVisitor};
+
+/// Number of entries buffered ahead of the consumer.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Iterator over the entries of a directory tree, in depth-first order.
+///
+/// Folders are yielded before their children; use [`ScanEntry::depth`] to
+/// reconstruct the hierarchy. Entries skipped because they could not be read are
+/// yielded as errors, and the iteration continues after them. The traversal runs
+/// on a background thread and stops when the iterator is dropped.
+///
+/// ```no_run
+/// use gpscan::{Options, ScanIter};
+///
+/// for entry in ScanIter::new("/srv", &Options::default()) {
+///     match entry {
+///         Ok(entry) => println!("{} {}", entry.path.display(), entry.size),
+///         Err(e) => eprintln!("{}", e),
+///     }
+/// }
+/// ```
+pub struct ScanIter {
+    receiver: Receiver<Result<ScanEntry>>,
+}
+
+impl ScanIter {
+    /// Starts scanning the directory at `root_path`.
+    pub fn new(root_path: impl AsRef<Path>, options: &Options) -> Self {
+        let root_path = root_path.as_ref().to_path_buf();
+        let options = options.clone();
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+
+        thread::spawn(move || {
+            let mut visitor = ChannelVisitor {
+                sender: sender.clone(),
+            };
+            match scan_with_visitor(&root_path, &options, &mut visitor) {
+                // Nothing to report if the scan was stopped by dropping the iterator
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                }
+                Ok(_) => {}
+            }
+        });
+
+        ScanIter { receiver }
+    }
+}
+
+impl Iterator for ScanIter {
+    type Item = Result<ScanEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Forwards entries to the iterator over a bounded channel.
+struct ChannelVisitor {
+    sender: SyncSender<Result<ScanEntry>>,
+}
+
+impl ChannelVisitor {
+    fn send(&self, item: Result<ScanEntry>) -> io::Result<()> {
+        self.sender
+            .send(item)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Scan iterator was dropped"))
+    }
+}
+
+impl ScanVisitor for ChannelVisitor {
+    fn enter_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.send(Ok(entry.clone()))
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.send(Ok(entry.clone()))
+    }
+
+    fn error(&mut self, path: &Path, error: &io::Error) {
+        let error = io::Error::new(error.kind(), SkippedEntry::new(path, error));
+        // A dropped iterator is detected by the next entry callback
+        let _ = self.send(Err(Error::Io(error)));
+    }
+}
+
+/// Error yielded for an entry that could not be read.
+#[derive(Debug)]
+pub(crate) struct SkippedEntry {
+    path: PathBuf,
+    message: String,
+}
+
+impl SkippedEntry {
+    fn new(path: &Path, error: &io::Error) -> Self {
+        SkippedEntry {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SkippedEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for SkippedEntry {}
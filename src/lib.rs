@@ -1,8 +1,105 @@
-#![cfg_attr(windows, feature(windows_by_handle))] // volume_serial_number
-
+mod archive;
+#[cfg(feature = "cli")]
 pub mod args;
+#[cfg(feature = "async")]
+mod async_scan;
+pub mod cancel;
+#[cfg(feature = "gpscan-capi")]
+pub mod capi;
+pub mod classify;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "cli")]
+mod daemon;
+pub mod delta;
+#[cfg(feature = "encrypt")]
+pub mod encrypt;
+pub mod error;
+pub mod error_policy;
+pub mod exit_code;
+#[cfg(feature = "cli")]
 pub mod filesystem;
+mod filter_cmd;
+pub mod filters;
+pub mod import;
+pub mod inode_filter;
+pub mod iter;
+#[cfg(feature = "cli")]
+mod lock;
+pub mod merge;
+pub mod metrics;
+pub mod model;
+#[cfg(feature = "cli")]
+mod notify;
+pub mod options;
+pub mod output;
+#[cfg(feature = "cli")]
+pub mod output_file;
 pub mod platform;
+pub mod progress;
+#[cfg(feature = "python")]
+mod python;
+pub mod reader;
+pub mod report;
+#[cfg(feature = "s3")]
+pub mod s3;
+mod sampling;
+pub mod scan;
+#[cfg(feature = "cli")]
+mod snapshot;
+pub mod source;
+#[cfg(feature = "cli")]
+mod split;
+pub mod top;
+pub mod totals;
+#[cfg(feature = "http")]
+pub mod upload;
+pub mod validate;
+pub mod volume;
+#[cfg(feature = "cli")]
+mod volumes;
+#[cfg(feature = "watch")]
+pub mod watch;
+pub mod xml_output;
 
+#[cfg(feature = "cli")]
 pub use args::parse_args;
-pub use filesystem::run;
+#[cfg(feature = "async")]
+pub use async_scan::scan_async;
+pub use cancel::CancellationToken;
+pub use classify::{ClassifyMode, FileKind};
+pub use delta::{folder_sizes, largest_growth, FolderDelta, FolderSizeCollector, FolderSizes};
+#[cfg(feature = "encrypt")]
+pub use encrypt::Encryption;
+pub use error::{Error, Result};
+pub use error_policy::{ErrorAction, ErrorPolicy, RetryPolicy};
+#[cfg(feature = "cli")]
+pub use filesystem::{run, run_import, run_merge, run_top, run_validate};
+pub use filters::{SkipCounts, SkipReason};
+pub use import::{import_du, import_ncdu, ImportFormat};
+pub use inode_filter::{BloomFilter, InodeFilter};
+pub use iter::ScanIter;
+pub use merge::merge;
+pub use metrics::{MetricsCollector, ScanMetrics};
+pub use model::{Consistency, FileNode, NodeKind, ScanInfo, ScanResult};
+pub use options::{Options, OptionsBuilder, PlaceholderMode, RootName, SortOrder, Timestamps};
+pub use output::{write_result, FolderTotals, OutputFormatter, SizeOrder};
+#[cfg(feature = "cli")]
+pub use progress::{JsonProgress, TerminalProgress};
+pub use progress::{Progress, ProgressSink};
+pub use reader::{read_scan, read_scan_from};
+pub use report::{Report, ReportCollector, ReportKind, ReportRow};
+#[cfg(feature = "s3")]
+pub use s3::{scan_s3, S3Config, S3Location};
+pub use scan::{
+    scan, scan_to_result, scan_to_tree, scan_with_formatter, scan_with_visitor, DirTime, ScanEntry,
+    ScanStats, ScanVisitor, TreeShape,
+};
+pub use source::{EntryKind, EntryMetadata, LocalSource, MemorySource, ScanSource};
+pub use top::{top_entries, LargestFiles, LargestFilesCollector, TopEntries};
+pub use totals::TotalsCheck;
+#[cfg(feature = "http")]
+pub use upload::upload;
+#[cfg(feature = "watch")]
+pub use watch::TreeWatcher;
+pub use xml_output::XmlFormatter;
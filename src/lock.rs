@@ -0,0 +1,88 @@
+// External crates
+use log::info;
+
+// Standard library imports
+use std::env;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// Longest lock file name derived from a scan root, to stay within file name limits.
+const MAX_NAME_LENGTH: usize = 200;
+
+/// An advisory lock that keeps concurrent gpscan runs from scanning the same root.
+///
+/// The lock is released when this value is dropped or the process exits.
+/// The lock file itself is left in place, since removing it could let a third
+/// process lock a new file while the second still waits on the old one.
+pub(crate) struct ScanLock {
+    _file: File,
+}
+
+impl ScanLock {
+    /// Locks `root` (a directory or an `s3://` URL), or fails with [`Error::Locked`]
+    /// if another process holds the lock and `wait` is false.
+    pub(crate) fn acquire(root: &str, wait: bool) -> Result<Self, Error> {
+        let path = lock_path(root);
+        let file = open_lock_file(&path).map_err(|e| {
+            Error::Output(io::Error::new(
+                e.kind(),
+                format!("Failed to create lock file '{}': {}", path.display(), e),
+            ))
+        })?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) if wait => {
+                info!(
+                    "Waiting for another gpscan process to finish scanning {}",
+                    root
+                );
+                file.lock()?;
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(Error::Locked(format!(
+                    "Another gpscan process is scanning {} (lock file '{}')",
+                    root,
+                    path.display()
+                )))
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        Ok(ScanLock { _file: file })
+    }
+}
+
+/// Returns the lock file for `root` in the temporary directory, named after
+/// the absolute path of the root.
+fn lock_path(root: &str) -> PathBuf {
+    let root = if root.starts_with("s3://") {
+        root.to_string()
+    } else {
+        fs::canonicalize(root)
+            .unwrap_or_else(|_| PathBuf::from(root))
+            .to_string_lossy()
+            .into_owned()
+    };
+    let name: String = root
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let start = name.len().saturating_sub(MAX_NAME_LENGTH);
+    env::temp_dir().join(format!("gpscan-{}.lock", &name[start..]))
+}
+
+fn open_lock_file(path: &Path) -> io::Result<File> {
+    match OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+    {
+        // A lock file created by another user can still be locked for reading
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => File::open(path),
+        result => result,
+    }
+}
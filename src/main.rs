@@ -1,16 +1,17 @@
 // External library imports
 use env_logger::Builder;
-use log::LevelFilter;
+use log::{error, LevelFilter};
 use std::env;
 
 // Standard library imports
-use std::io;
 use std::io::Write;
+use std::process;
 use std::time::Instant; // For execution time measurement
 
 // Import functions
+use gpscan::exit_code;
 use gpscan::parse_args;
-use gpscan::run;
+use gpscan::{run, run_import, run_merge, run_top, run_validate};
 
 fn init_logger(quiet_mode: bool) {
     let log_level = if quiet_mode {
@@ -26,27 +27,73 @@ fn init_logger(quiet_mode: bool) {
 }
 
 /// Entry point of the program.
-fn main() -> io::Result<()> {
+fn main() {
     // Start measuring execution time
     let start_time = Instant::now();
 
     // Parse arguments
     let matches = parse_args();
     let quiet_mode = matches.get_flag("quiet");
+    let strict_mode = matches.get_flag("strict");
 
     // Initialize logger with quiet mode support
     init_logger(quiet_mode);
 
-    // Parse arguments and run the program
-    let matches = parse_args();
+    // Subcommands working on existing dumps or other inputs
+    let subcommand = match matches.subcommand() {
+        Some(("validate", sub_matches)) => Some(run_validate(sub_matches)),
+        Some(("merge", sub_matches)) => Some(run_merge(sub_matches)),
+        Some(("import", sub_matches)) => Some(run_import(sub_matches)),
+        Some(("top", sub_matches)) => Some(run_top(sub_matches)),
+        _ => None,
+    };
+    if let Some(result) = subcommand {
+        let code = match result {
+            Ok(()) => exit_code::SUCCESS,
+            Err(e) => {
+                error!("{}", e);
+                e.exit_code()
+            }
+        };
+        process::exit(code);
+    }
+
+    // Run the program
     let result = run(matches);
 
-    // Print execution time
-    // This will be printed even if quiet mode is enabled
+    // Print execution time and the hard link savings
+    // These will be printed even if quiet mode is enabled
     eprintln!(
         "[gpscan] [INFO] Execution time: {:.2?}",
         start_time.elapsed()
     );
+    // Files with the same content are not detected, only hard links
+    if let Ok(stats) = &result {
+        if stats.hard_links > 0 {
+            eprintln!(
+                "[gpscan] [INFO] Hard links: {} files counted once, {} bytes saved",
+                stats.hard_links, stats.hard_link_bytes
+            );
+        }
+    }
+
+    let code = match result {
+        // The dump is complete up to the entry where the scan was stopped
+        Ok(stats) if stats.too_many_errors => exit_code::TOO_MANY_ERRORS,
+        // In strict mode, a partial dump is reported with a distinct exit code
+        Ok(stats) if strict_mode && stats.errors > 0 => {
+            error!(
+                "Strict mode: {} entries were skipped due to errors",
+                stats.errors
+            );
+            exit_code::INCOMPLETE
+        }
+        Ok(_) => exit_code::SUCCESS,
+        Err(e) => {
+            error!("{}", e);
+            e.exit_code()
+        }
+    };
 
-    result
+    process::exit(code);
 }
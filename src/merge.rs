@@ -0,0 +1,105 @@
+// External crates
+use chrono::{DateTime, Utc};
+use log::warn;
+
+use crate::model::{Consistency, FileNode, ScanInfo, ScanResult};
+
+/// Combines several scans into one tree under a synthetic root folder.
+///
+/// Each scanned root becomes a child of the new root, keeping its name. The
+/// volume size and free space are summed over the distinct volumes, and the
+/// scan time is the time of the most recent scan.
+pub fn merge(results: Vec<ScanResult>, root_name: &str) -> ScanResult {
+    let mut root = FileNode::folder(root_name);
+    let mut info = ScanInfo {
+        volume_path: String::new(),
+        volume_size: 0,
+        free_space: 0,
+        free_space_after: None,
+        scan_time: DateTime::<Utc>::UNIX_EPOCH,
+        file_size_measure: "physical".to_string(),
+        scan_duration: None,
+        volume_name: None,
+        file_system: None,
+        volume_id: None,
+        snapshot: None,
+        comment: None,
+        errors: None,
+        skipped: None,
+        consistency: None,
+        sample_rate: None,
+    };
+    let mut volumes: Vec<(String, u64)> = Vec::new();
+
+    for (i, result) in results.into_iter().enumerate() {
+        if i == 0 {
+            info.volume_path = result.info.volume_path.clone();
+            info.volume_name = result.info.volume_name.clone();
+            info.file_system = result.info.file_system.clone();
+            info.volume_id = result.info.volume_id.clone();
+            info.snapshot = result.info.snapshot.clone();
+            info.comment = result.info.comment.clone();
+            info.file_size_measure = result.info.file_size_measure.clone();
+            info.errors = result.info.errors;
+            info.skipped = result.info.skipped.clone();
+            info.consistency = result.info.consistency;
+            info.sample_rate = result.info.sample_rate;
+        } else if result.info.file_size_measure != info.file_size_measure {
+            warn!(
+                "Merging scans with different size measures ({} and {})",
+                info.file_size_measure, result.info.file_size_measure
+            );
+        }
+
+        // Count every volume only once
+        let volume = (result.info.volume_path.clone(), result.info.volume_size);
+        if !volumes.contains(&volume) {
+            info.volume_size += result.info.volume_size;
+            info.free_space += result.info.free_space;
+            // Only known if it is known for every volume
+            info.free_space_after = match i {
+                0 => result.info.free_space_after,
+                _ => info
+                    .free_space_after
+                    .zip(result.info.free_space_after)
+                    .map(|(a, b)| a + b),
+            };
+            volumes.push(volume);
+        }
+        if volumes.len() > 1 {
+            info.volume_path = "/".to_string();
+            info.volume_name = None;
+            info.file_system = None;
+            info.volume_id = None;
+            info.snapshot = None;
+        }
+        info.scan_time = info.scan_time.max(result.info.scan_time);
+        if i > 0 {
+            // The counts are only known if they are known for every scan
+            info.errors = info.errors.zip(result.info.errors).map(|(a, b)| a + b);
+            info.skipped = match (info.skipped.take(), &result.info.skipped) {
+                (Some(mut skipped), Some(other)) => {
+                    skipped.add_all(other);
+                    Some(skipped)
+                }
+                _ => None,
+            };
+            info.consistency =
+                info.consistency
+                    .zip(result.info.consistency)
+                    .map(|(a, b)| Consistency {
+                        checked: a.checked + b.checked,
+                        changed: a.changed + b.changed,
+                    });
+            // A merge with a sample is an estimate, as rough as its smallest sample
+            info.sample_rate = match (info.sample_rate, result.info.sample_rate) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+
+        root.children.push(result.root);
+    }
+
+    ScanResult { info, root }
+}
@@ -0,0 +1,192 @@
+// External crates
+use chrono::{DateTime, Utc};
+
+// Standard library imports
+use std::fmt::Write as _;
+use std::io;
+use std::time::Duration;
+
+use crate::model::ScanInfo;
+use crate::output::OutputFormatter;
+use crate::scan::ScanEntry;
+
+/// Totals of a scan, for monitoring systems.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanMetrics {
+    /// Name of the scan root.
+    pub root: String,
+    pub files: u64,
+    pub folders: u64,
+    /// Total size of the files in bytes.
+    pub bytes: u64,
+    /// Cumulative size of each folder directly below the root.
+    pub top_level: Vec<(String, u64)>,
+    /// Number of entries skipped due to errors.
+    pub errors: u64,
+    /// Size of the hard links that were not counted again, in bytes.
+    pub hard_link_bytes: u64,
+    pub scan_time: Option<DateTime<Utc>>,
+    pub duration: Option<Duration>,
+}
+
+impl ScanMetrics {
+    /// Formats the metrics in the Prometheus text exposition format, as read by
+    /// the node_exporter textfile collector.
+    pub fn to_prometheus(&self) -> String {
+        let root = escape_label(&self.root);
+        let mut text = String::new();
+
+        let mut gauge = |name: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} gauge", name);
+            for (labels, value) in samples {
+                let _ = writeln!(text, "{}{{{}}} {}", name, labels, value);
+            }
+        };
+        let root_label = format!("root=\"{}\"", root);
+        let sample = |value: String| vec![(root_label.clone(), value)];
+
+        gauge(
+            "gpscan_size_bytes",
+            "Total size of the scanned files in bytes.",
+            &sample(self.bytes.to_string()),
+        );
+        gauge(
+            "gpscan_files",
+            "Number of scanned files.",
+            &sample(self.files.to_string()),
+        );
+        gauge(
+            "gpscan_folders",
+            "Number of scanned folders.",
+            &sample(self.folders.to_string()),
+        );
+        gauge(
+            "gpscan_errors",
+            "Number of entries skipped due to errors.",
+            &sample(self.errors.to_string()),
+        );
+        gauge(
+            "gpscan_hard_link_saved_bytes",
+            "Size of the hard links to already counted files in bytes.",
+            &sample(self.hard_link_bytes.to_string()),
+        );
+        if let Some(duration) = self.duration {
+            gauge(
+                "gpscan_scan_duration_seconds",
+                "Time the scan took in seconds.",
+                &sample(format!("{:.3}", duration.as_secs_f64())),
+            );
+        }
+        if let Some(scan_time) = self.scan_time {
+            gauge(
+                "gpscan_scan_timestamp_seconds",
+                "Time the scan started, in seconds since the epoch.",
+                &sample(scan_time.timestamp().to_string()),
+            );
+        }
+        let directories: Vec<(String, String)> = self
+            .top_level
+            .iter()
+            .map(|(name, size)| {
+                (
+                    format!("{},directory=\"{}\"", root_label, escape_label(name)),
+                    size.to_string(),
+                )
+            })
+            .collect();
+        gauge(
+            "gpscan_directory_size_bytes",
+            "Cumulative size of each folder directly below the scan root in bytes.",
+            &directories,
+        );
+        text
+    }
+}
+
+/// Collects [`ScanMetrics`] while passing everything on to another formatter.
+///
+/// ```
+/// use gpscan::{scan_with_formatter, MetricsCollector, Options, XmlFormatter};
+///
+/// let mut collector = MetricsCollector::new(XmlFormatter::new(std::io::sink()));
+/// scan_with_formatter(std::path::Path::new("src"), &Options::default(), &mut collector)?;
+/// println!("{}", collector.metrics().to_prometheus());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct MetricsCollector<F> {
+    inner: F,
+    metrics: ScanMetrics,
+}
+
+impl<F: OutputFormatter> MetricsCollector<F> {
+    pub fn new(inner: F) -> Self {
+        MetricsCollector {
+            inner,
+            metrics: ScanMetrics::default(),
+        }
+    }
+
+    /// Returns the metrics collected so far.
+    pub fn metrics(&self) -> &ScanMetrics {
+        &self.metrics
+    }
+
+    /// Returns the metrics and the wrapped formatter.
+    pub fn into_parts(self) -> (ScanMetrics, F) {
+        (self.metrics, self.inner)
+    }
+}
+
+impl<F: OutputFormatter> OutputFormatter for MetricsCollector<F> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.metrics = ScanMetrics {
+            scan_time: Some(info.scan_time),
+            ..ScanMetrics::default()
+        };
+        self.inner.begin_scan(info)
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        match entry.depth {
+            0 => self.metrics.root = entry.name.clone(),
+            1 => self.metrics.top_level.push((entry.name.clone(), 0)),
+            _ => {}
+        }
+        self.metrics.folders += 1;
+        self.inner.begin_folder(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.metrics.files += 1;
+        self.metrics.bytes += entry.size;
+        // Files below depth 1 are in the most recently entered top-level folder
+        if entry.depth > 1 {
+            if let Some((_, size)) = self.metrics.top_level.last_mut() {
+                *size += entry.size;
+            }
+        }
+        self.inner.file(entry)
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.comment(text)
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.metrics.duration = info.scan_duration;
+        self.inner.end_scan(info)
+    }
+}
+
+/// Escapes a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
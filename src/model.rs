@@ -0,0 +1,185 @@
+// External crates
+use chrono::{DateTime, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Standard library imports
+use std::time::{Duration, SystemTime};
+
+use crate::filters::SkipCounts;
+use crate::scan::ScanEntry;
+
+/// Information about a scan, written to the `ScanInfo` element.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanInfo {
+    /// Mount point of the volume containing the scan root.
+    pub volume_path: String,
+    pub volume_size: u64,
+    /// Free space of the volume in bytes when the scan started.
+    pub free_space: u64,
+    /// Free space of the volume in bytes when the scan finished, if it was measured again.
+    pub free_space_after: Option<u64>,
+    pub scan_time: DateTime<Utc>,
+    /// `physical` for disk usage, `logical` for apparent sizes.
+    pub file_size_measure: String,
+    /// Time the scan took, if known.
+    pub scan_duration: Option<Duration>,
+    /// Label or device name of the volume, if known.
+    pub volume_name: Option<String>,
+    /// File system type of the volume, such as `ext4`, `NTFS`, or `apfs`.
+    pub file_system: Option<String>,
+    /// Identifier of the volume (a file system UUID or volume GUID path), if known.
+    pub volume_id: Option<String>,
+    /// Name of the file system snapshot that was scanned instead of the live volume.
+    pub snapshot: Option<String>,
+    /// Free-form description of the scan, such as why it was taken.
+    pub comment: Option<String>,
+    /// Number of entries that could not be read, if known.
+    pub errors: Option<u64>,
+    /// Number of entries left out for each reason, if known.
+    pub skipped: Option<SkipCounts>,
+    /// How much the tree changed while it was scanned, if it was verified.
+    pub consistency: Option<Consistency>,
+    /// Fraction of the files that were scanned if only a sample was, in which case the
+    /// sizes are estimates.
+    pub sample_rate: Option<f64>,
+}
+
+/// Result of checking a random sample of the scanned entries again after the scan
+/// (see [`OptionsBuilder::verify`](crate::OptionsBuilder::verify)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Consistency {
+    /// Number of entries checked again.
+    pub checked: u64,
+    /// Number of those whose size or modification time changed, or that were removed.
+    pub changed: u64,
+}
+
+impl Consistency {
+    /// Returns `stable` if no entry changed, `shifting` if up to 1% changed, and
+    /// `unstable` otherwise.
+    pub fn level(&self) -> &'static str {
+        if self.changed == 0 {
+            "stable"
+        } else if self.changed * 100 <= self.checked {
+            "shifting"
+        } else {
+            "unstable"
+        }
+    }
+}
+
+/// A complete scan: the scan information and the root folder.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanResult {
+    pub info: ScanInfo,
+    pub root: FileNode,
+}
+
+/// Kind of an entry in the scanned tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum NodeKind {
+    Folder,
+    File,
+}
+
+/// A file or folder in an in-memory scan result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FileNode {
+    pub name: String,
+    pub kind: NodeKind,
+    /// Size in bytes. For folders this is 0; use [`FileNode::total_size`] instead.
+    pub size: u64,
+    pub created: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
+    pub accessed: Option<DateTime<Utc>>,
+    /// Additional attributes, such as `unreadable` or `placeholder`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub attributes: Vec<(String, String)>,
+    /// Children in output order. Always empty for files.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub children: Vec<FileNode>,
+}
+
+impl FileNode {
+    /// Creates an empty folder without times.
+    pub fn folder(name: impl Into<String>) -> Self {
+        FileNode {
+            name: name.into(),
+            kind: NodeKind::Folder,
+            size: 0,
+            created: None,
+            modified: None,
+            accessed: None,
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Creates a file of the given size without times.
+    pub fn file(name: impl Into<String>, size: u64) -> Self {
+        FileNode {
+            kind: NodeKind::File,
+            size,
+            ..FileNode::folder(name)
+        }
+    }
+
+    pub(crate) fn from_entry(entry: &ScanEntry, is_file: bool) -> Self {
+        fn time(t: Option<SystemTime>) -> Option<DateTime<Utc>> {
+            t.map(DateTime::<Utc>::from)
+        }
+
+        FileNode {
+            name: entry.name.clone(),
+            kind: if is_file {
+                NodeKind::File
+            } else {
+                NodeKind::Folder
+            },
+            size: entry.size,
+            created: time(entry.metadata.created),
+            modified: time(entry.metadata.modified),
+            accessed: time(entry.metadata.accessed),
+            attributes: entry.attributes.clone(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn is_folder(&self) -> bool {
+        self.kind == NodeKind::Folder
+    }
+
+    /// Returns the value of an additional attribute.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the size of a file, or the sum of all file sizes below a folder.
+    pub fn total_size(&self) -> u64 {
+        self.size + self.children.iter().map(FileNode::total_size).sum::<u64>()
+    }
+
+    /// Returns the number of files in this subtree.
+    pub fn file_count(&self) -> u64 {
+        match self.kind {
+            NodeKind::File => 1,
+            NodeKind::Folder => self.children.iter().map(FileNode::file_count).sum(),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// External crates
+use clap::ArgMatches;
+use log::warn;
+use serde_json::json;
+
+// Standard library imports
+use std::io;
+
+use crate::error::Error;
+use crate::metrics::ScanMetrics;
+use crate::platform;
+
+/// Reports finished and failed scans to `--notify-url` and `--notify-cmd`.
+///
+/// Notifications that cannot be delivered are logged, but do not change the
+/// outcome of the scan.
+pub(crate) struct Notifier {
+    url: Option<String>,
+    command: Option<String>,
+}
+
+impl Notifier {
+    pub(crate) fn from_matches(matches: &ArgMatches) -> Self {
+        Notifier {
+            url: matches.get_one::<String>("notify-url").cloned(),
+            command: matches.get_one::<String>("notify-cmd").cloned(),
+        }
+    }
+
+    /// Sends the outcome of a scan of `root` written to `output`.
+    pub(crate) fn notify(
+        &self,
+        root: &str,
+        output: Option<&str>,
+        outcome: Result<&ScanMetrics, &Error>,
+    ) {
+        if self.url.is_none() && self.command.is_none() {
+            return;
+        }
+        let summary = Summary::new(root, output, outcome);
+
+        if let Some(url) = &self.url {
+            if let Err(e) = post(url, &summary.to_json()) {
+                warn!("Failed to send notification to '{}': {}", url, e);
+            }
+        }
+        if let Some(command) = &self.command {
+            if let Err(e) = run_command(command, &summary.env()) {
+                warn!("Notification command '{}' failed: {}", command, e);
+            }
+        }
+    }
+}
+
+/// The values reported about a scan.
+struct Summary {
+    status: &'static str,
+    root: String,
+    output: Option<String>,
+    metrics: ScanMetrics,
+    error: Option<String>,
+}
+
+impl Summary {
+    fn new(root: &str, output: Option<&str>, outcome: Result<&ScanMetrics, &Error>) -> Self {
+        let (status, metrics, error) = match outcome {
+            Ok(metrics) => ("ok", metrics.clone(), None),
+            Err(e) => ("failed", ScanMetrics::default(), Some(e.to_string())),
+        };
+        Summary {
+            status,
+            root: root.to_string(),
+            output: output.map(str::to_string),
+            metrics,
+            error,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let metrics = &self.metrics;
+        json!({
+            "status": self.status,
+            "root": self.root,
+            "output": self.output,
+            "files": metrics.files,
+            "folders": metrics.folders,
+            "bytes": metrics.bytes,
+            "errors": metrics.errors,
+            "scan_time": metrics.scan_time.map(|time| time.to_rfc3339()),
+            "duration_seconds": metrics.duration.map(|duration| duration.as_secs_f64()),
+            "error": self.error,
+        })
+        .to_string()
+    }
+
+    /// Environment variables for `--notify-cmd`. Unknown values are left unset.
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let metrics = &self.metrics;
+        let mut env = vec![
+            ("GPSCAN_STATUS", self.status.to_string()),
+            ("GPSCAN_ROOT", self.root.clone()),
+            ("GPSCAN_FILES", metrics.files.to_string()),
+            ("GPSCAN_FOLDERS", metrics.folders.to_string()),
+            ("GPSCAN_BYTES", metrics.bytes.to_string()),
+            ("GPSCAN_ERRORS", metrics.errors.to_string()),
+        ];
+        if let Some(output) = &self.output {
+            env.push(("GPSCAN_OUTPUT", output.clone()));
+        }
+        if let Some(duration) = metrics.duration {
+            env.push(("GPSCAN_DURATION", format!("{:.3}", duration.as_secs_f64())));
+        }
+        if let Some(error) = &self.error {
+            env.push(("GPSCAN_ERROR", error.clone()));
+        }
+        env
+    }
+}
+
+#[cfg(feature = "http")]
+fn post(url: &str, body: &str) -> io::Result<()> {
+    let response = ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body);
+    match response {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) => Err(io::Error::other(format!(
+            "notification failed with HTTP status {}",
+            status
+        ))),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn post(_url: &str, _body: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "gpscan was built without the http feature",
+    ))
+}
+
+/// Runs `command` with the system shell and waits for it to finish.
+fn run_command(command: &str, env: &[(&str, String)]) -> io::Result<()> {
+    let status = platform::shell_command(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("exited with {}", status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,886 @@
+// External crates
+#[cfg(feature = "cli")]
+use clap::ArgMatches;
+
+// Standard library imports
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::cancel::CancellationToken;
+use crate::classify::ClassifyMode;
+use crate::error_policy::{CustomErrorPolicy, ErrorAction, ErrorPolicy, RetryPolicy};
+use crate::filter_cmd::FilterCommand;
+use crate::inode_filter::InodeFilter;
+use crate::model::{FileNode, ScanResult};
+use crate::progress::{ProgressReporter, ProgressSink};
+use crate::source::{CustomSource, EntryMetadata, LocalSource, ScanSource};
+
+/// Order in which the entries of a folder are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Raw byte order of the (UTF-8) file names. Identical on every platform.
+    #[default]
+    Bytes,
+    /// Lexicographic order of the lossily converted file names.
+    Name,
+    /// Order of file managers such as Finder and Explorer: numbers in names by their
+    /// value (`file2` before `file10`), and letters regardless of case.
+    Natural,
+    /// Largest entries first, with folders by the total size of their files.
+    ///
+    /// The whole tree is held in memory to order it (see [`SizeOrder`](crate::SizeOrder)).
+    Size,
+    /// Most recently modified entries first.
+    Mtime,
+    /// Order returned by the operating system.
+    None,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(SortOrder::Bytes),
+            "name" => Ok(SortOrder::Name),
+            "natural" => Ok(SortOrder::Natural),
+            "size" => Ok(SortOrder::Size),
+            "mtime" => Ok(SortOrder::Mtime),
+            "none" => Ok(SortOrder::None),
+            _ => Err(format!("invalid sort order: {}", s)),
+        }
+    }
+}
+
+/// Which times are written for each entry.
+///
+/// Times that are not known, such as the creation time on file systems without birth
+/// times, are left out instead of being written as the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamps {
+    /// No times at all.
+    None,
+    /// Only the modification time.
+    Modified,
+    /// Creation, modification, and access times.
+    All,
+}
+
+impl FromStr for Timestamps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Timestamps::None),
+            "mtime" => Ok(Timestamps::Modified),
+            "all" => Ok(Timestamps::All),
+            _ => Err(format!("invalid timestamps: {}", s)),
+        }
+    }
+}
+
+/// How online-only cloud placeholder files are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaceholderMode {
+    /// Report the size returned by the filesystem.
+    #[default]
+    Keep,
+    /// Report a physical size of zero.
+    Zero,
+    /// Keep the size and add a `placeholder="true"` attribute.
+    Mark,
+}
+
+impl FromStr for PlaceholderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(PlaceholderMode::Keep),
+            "zero" => Ok(PlaceholderMode::Zero),
+            "mark" => Ok(PlaceholderMode::Mark),
+            _ => Err(format!("invalid placeholder mode: {}", s)),
+        }
+    }
+}
+
+/// How the root folder of a scan is named in the output.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum RootName {
+    /// The path of the scan root as given.
+    #[default]
+    Path,
+    /// The absolute path of the scan root, without resolving symbolic links.
+    Absolute,
+    /// Only the name of the scan root directory, such as `data` for `/mnt/data`.
+    Basename,
+    /// A fixed name.
+    Custom(String),
+}
+
+impl From<&str> for RootName {
+    fn from(name: &str) -> Self {
+        RootName::Custom(name.to_string())
+    }
+}
+
+impl From<String> for RootName {
+    fn from(name: String) -> Self {
+        RootName::Custom(name)
+    }
+}
+
+impl FromStr for RootName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(RootName::Path),
+            "absolute" => Ok(RootName::Absolute),
+            "basename" => Ok(RootName::Basename),
+            _ => match s.strip_prefix("custom:") {
+                Some(name) if !name.is_empty() => Ok(RootName::Custom(name.to_string())),
+                _ => Err(format!(
+                    "invalid root name (use path, absolute, basename, or custom:NAME): {}",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// Function returning extra attributes for an entry, from its path and metadata.
+pub(crate) type AttributeFn = dyn Fn(&Path, &EntryMetadata) -> Vec<(String, String)> + Send + Sync;
+
+/// A user-supplied attribute function.
+#[derive(Clone)]
+pub(crate) struct CustomAttributes(pub(crate) Arc<AttributeFn>);
+
+impl fmt::Debug for CustomAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomAttributes")
+    }
+}
+
+/// A previous scan whose folders are copied when their directory was not modified since
+/// `cutoff`, set with [`OptionsBuilder::trust_dir_mtime`].
+#[derive(Debug, Clone)]
+pub(crate) struct DirCache {
+    pub(crate) root: Arc<FileNode>,
+    pub(crate) cutoff: SystemTime,
+}
+
+impl DirCache {
+    pub(crate) fn new(cache: ScanResult, cutoff: SystemTime) -> Self {
+        DirCache {
+            cutoff: cutoff.min(cache.info.scan_time.into()),
+            root: Arc::new(cache.root),
+        }
+    }
+}
+
+/// Options controlling a scan.
+///
+/// Use [`Options::builder`] to configure a scan without going through the command line.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub(crate) apparent_size: bool,
+    pub(crate) both_sizes: bool,
+    pub(crate) allocation_unit: bool,
+    pub(crate) dir_entry_size: bool,
+    pub(crate) cross_mount_points: bool,
+    pub(crate) follow_root_symlink: bool,
+    pub(crate) local_only: bool,
+    pub(crate) mark_subvolumes: bool,
+    pub(crate) record_junctions: bool,
+    pub(crate) include_zero_files: bool,
+    pub(crate) include_empty_folders: bool,
+    pub(crate) retries: u32,
+    pub(crate) retry_delay: Duration,
+    pub(crate) sort_order: SortOrder,
+    pub(crate) placeholders: PlaceholderMode,
+    pub(crate) excludes: Vec<String>,
+    pub(crate) exclude_users: Vec<u32>,
+    pub(crate) only_users: Vec<u32>,
+    pub(crate) filter_command: Option<FilterCommand>,
+    pub(crate) scan_archives: bool,
+    pub(crate) folder_totals: bool,
+    pub(crate) hide_access_times: bool,
+    pub(crate) timestamps: Option<Timestamps>,
+    pub(crate) strict_names: bool,
+    pub(crate) embed_warnings: bool,
+    pub(crate) log_each_skip: bool,
+    pub(crate) max_memory: Option<u64>,
+    pub(crate) inode_filter: InodeFilter,
+    pub(crate) dir_cache: Option<DirCache>,
+    pub(crate) max_errors: Option<u64>,
+    pub(crate) warn_dir_entries: Option<u64>,
+    pub(crate) skip_dir_entries: Option<u64>,
+    pub(crate) profile_dirs: Option<usize>,
+    pub(crate) verify: Option<usize>,
+    pub(crate) sample: Option<f64>,
+    pub(crate) classify: Option<ClassifyMode>,
+    pub(crate) uniform_types: bool,
+    pub(crate) absolute_paths: bool,
+    pub(crate) writer_thread: bool,
+    pub(crate) root_name: RootName,
+    pub(crate) comment: Option<String>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) progress: Option<ProgressReporter>,
+    pub(crate) error_policy: Option<CustomErrorPolicy>,
+    pub(crate) source: Option<CustomSource>,
+    pub(crate) attributes: Option<CustomAttributes>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            apparent_size: false,
+            both_sizes: false,
+            allocation_unit: false,
+            dir_entry_size: false,
+            cross_mount_points: false,
+            follow_root_symlink: true,
+            local_only: false,
+            mark_subvolumes: false,
+            record_junctions: false,
+            include_zero_files: false,
+            include_empty_folders: false,
+            retries: 0,
+            retry_delay: Duration::from_millis(100),
+            sort_order: SortOrder::default(),
+            placeholders: PlaceholderMode::default(),
+            excludes: Vec::new(),
+            exclude_users: Vec::new(),
+            only_users: Vec::new(),
+            filter_command: None,
+            scan_archives: false,
+            folder_totals: false,
+            hide_access_times: false,
+            timestamps: None,
+            strict_names: false,
+            embed_warnings: false,
+            log_each_skip: false,
+            max_memory: None,
+            inode_filter: InodeFilter::Exact,
+            dir_cache: None,
+            max_errors: None,
+            warn_dir_entries: None,
+            skip_dir_entries: None,
+            profile_dirs: None,
+            verify: None,
+            sample: None,
+            classify: None,
+            uniform_types: false,
+            absolute_paths: false,
+            writer_thread: false,
+            root_name: RootName::default(),
+            comment: None,
+            cancellation: None,
+            progress: None,
+            error_policy: None,
+            source: None,
+            attributes: None,
+        }
+    }
+}
+
+impl Options {
+    /// Returns a builder initialized with the default options.
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::default()
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        Options {
+            apparent_size: matches.get_flag("apparent-size"),
+            both_sizes: matches.get_flag("both-sizes"),
+            allocation_unit: matches.get_flag("allocation-unit"),
+            dir_entry_size: matches.get_flag("dir-entry-size"),
+            cross_mount_points: matches.get_flag("mounts"),
+            follow_root_symlink: matches
+                .get_one::<bool>("follow-root-symlink")
+                .copied()
+                .unwrap_or(true),
+            local_only: matches.get_flag("local-only"),
+            mark_subvolumes: matches.get_flag("mark-subvolumes"),
+            record_junctions: matches.get_flag("record-junctions"),
+            include_zero_files: matches.get_flag("include-zero-files"),
+            include_empty_folders: matches.get_flag("include-empty-folders"),
+            retries: *matches.get_one::<u32>("retries").unwrap_or(&0),
+            retry_delay: Duration::from_millis(
+                *matches.get_one::<u64>("retry-delay").unwrap_or(&100),
+            ),
+            sort_order: matches
+                .get_one::<String>("sort")
+                .map(|s| s.parse().expect("Invalid sort order"))
+                .unwrap_or_default(),
+            placeholders: matches
+                .get_one::<String>("placeholders")
+                .map(|s| s.parse().expect("Invalid placeholder mode"))
+                .unwrap_or_default(),
+            excludes: matches
+                .get_many::<String>("exclude")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default(),
+            exclude_users: matches
+                .get_many::<u32>("exclude-user")
+                .map(|values| values.copied().collect())
+                .unwrap_or_default(),
+            only_users: matches
+                .get_many::<u32>("only-user")
+                .map(|values| values.copied().collect())
+                .unwrap_or_default(),
+            filter_command: matches
+                .get_one::<String>("filter-cmd")
+                .map(FilterCommand::new),
+            scan_archives: matches.get_flag("scan-archives"),
+            folder_totals: matches.get_flag("folder-totals"),
+            hide_access_times: matches.get_flag("no-atime"),
+            timestamps: matches
+                .get_one::<String>("times")
+                .map(|s| s.parse().expect("Invalid timestamps")),
+            strict_names: matches.get_flag("strict-names"),
+            embed_warnings: matches.get_flag("embed-warnings"),
+            log_each_skip: matches.get_flag("log-each-skip"),
+            max_memory: matches.get_one::<u64>("max-memory").copied(),
+            inode_filter: matches
+                .get_one::<InodeFilter>("inode-filter")
+                .copied()
+                .unwrap_or_default(),
+            // Read by the caller, which reports an invalid dump
+            dir_cache: None,
+            max_errors: matches.get_one::<u64>("max-errors").copied(),
+            warn_dir_entries: matches.get_one::<u64>("warn-dir-entries").copied(),
+            skip_dir_entries: matches.get_one::<u64>("skip-dir-entries").copied(),
+            profile_dirs: matches.get_one::<usize>("profile-dirs").copied(),
+            verify: matches.get_one::<usize>("verify").copied(),
+            sample: matches.get_one::<f64>("sample").copied(),
+            classify: matches
+                .get_one::<String>("classify")
+                .map(|s| s.parse().expect("Invalid classification mode")),
+            uniform_types: matches.get_flag("uniform-types"),
+            absolute_paths: matches.get_flag("absolute-paths"),
+            writer_thread: matches.get_flag("writer-thread"),
+            root_name: match matches.get_one::<String>("relative-root") {
+                Some(label) => RootName::Custom(label.clone()),
+                None => matches
+                    .get_one::<RootName>("root-name")
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+            comment: matches.get_one::<String>("comment").cloned(),
+            cancellation: None,
+            progress: None,
+            error_policy: None,
+            source: None,
+            attributes: None,
+        }
+    }
+
+    /// Decides how an I/O error is handled, using the custom error policy if one
+    /// was set and retrying transient errors otherwise.
+    pub(crate) fn on_error(&self, path: &Path, error: &io::Error, attempt: u32) -> ErrorAction {
+        match &self.error_policy {
+            Some(CustomErrorPolicy(policy)) => policy.on_error(path, error, attempt),
+            None => RetryPolicy {
+                retries: self.retries,
+                delay: self.retry_delay,
+            }
+            .on_error(path, error, attempt),
+        }
+    }
+
+    /// Returns the source the traversal reads from.
+    pub(crate) fn source(&self) -> &dyn ScanSource {
+        match &self.source {
+            Some(CustomSource(source)) => source.as_ref(),
+            None => &LocalSource,
+        }
+    }
+
+    /// Returns true if the scan was cancelled through the cancellation token.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Returns the attribute with the size measure that is not selected, if both sizes are recorded.
+    pub(crate) fn other_size(&self, metadata: &EntryMetadata) -> Option<(String, String)> {
+        if !self.both_sizes {
+            return None;
+        }
+        Some(if self.apparent_size {
+            ("physicalSize".to_string(), metadata.size.to_string())
+        } else {
+            (
+                "logicalSize".to_string(),
+                metadata.apparent_size.to_string(),
+            )
+        })
+    }
+
+    /// Returns `metadata` with the Unix epoch as its access time if access times are hidden.
+    ///
+    /// The epoch is a known time, so that it is written with `--times all` as well.
+    pub(crate) fn visible_metadata(&self, mut metadata: EntryMetadata) -> EntryMetadata {
+        if self.hide_access_times {
+            metadata.accessed = Some(SystemTime::UNIX_EPOCH);
+        }
+        metadata
+    }
+
+    /// Returns the name of the root folder of a scan of `root_path`.
+    pub(crate) fn root_folder_name(&self, root_path: &Path) -> String {
+        match &self.root_name {
+            RootName::Path => root_path.display().to_string(),
+            // Paths of other sources are not relative to the working directory
+            RootName::Absolute if self.source.is_some() => root_path.display().to_string(),
+            RootName::Absolute => std::path::absolute(root_path)
+                .unwrap_or_else(|_| root_path.to_path_buf())
+                .display()
+                .to_string(),
+            RootName::Basename => {
+                let path = match self.source {
+                    Some(_) => root_path.to_path_buf(),
+                    None => {
+                        std::path::absolute(root_path).unwrap_or_else(|_| root_path.to_path_buf())
+                    }
+                };
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| root_path.display().to_string())
+            }
+            RootName::Custom(name) => name.clone(),
+        }
+    }
+
+    /// Returns true if an entry with the given name matches an exclude pattern.
+    pub(crate) fn is_excluded(&self, name: &str) -> bool {
+        self.excludes
+            .iter()
+            .any(|pattern| matches_pattern(pattern, name))
+    }
+}
+
+/// Builder for [`Options`].
+///
+/// ```
+/// let options = gpscan::Options::builder()
+///     .apparent_size(true)
+///     .exclude("node_modules")
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+impl OptionsBuilder {
+    /// Use apparent size instead of disk usage.
+    pub fn apparent_size(mut self, value: bool) -> Self {
+        self.options.apparent_size = value;
+        self
+    }
+
+    /// Cross filesystem boundaries during the scan.
+    pub fn cross_mount_points(mut self, value: bool) -> Self {
+        self.options.cross_mount_points = value;
+        self
+    }
+
+    /// Scan the target of a root path that is a symbolic link, on the device of the
+    /// target, while the root folder is still named after the path as given (the default).
+    /// Otherwise such a root is rejected with [`Error::InvalidRoot`](crate::Error::InvalidRoot).
+    pub fn follow_root_symlink(mut self, value: bool) -> Self {
+        self.options.follow_root_symlink = value;
+        self
+    }
+
+    /// Skip directories on network file systems (NFS, SMB, FUSE, ...) instead of
+    /// warning that they are slow to scan.
+    pub fn local_only(mut self, value: bool) -> Self {
+        self.options.local_only = value;
+        self
+    }
+
+    /// Adds `subvolume="true"` to the folders that are btrfs subvolumes.
+    ///
+    /// Subvolumes are scanned as part of their file system either way.
+    pub fn mark_subvolumes(mut self, value: bool) -> Self {
+        self.options.mark_subvolumes = value;
+        self
+    }
+
+    /// Writes Windows junctions and mount points as empty folders with `junction="true"`,
+    /// instead of skipping them. Their targets are not entered either way.
+    pub fn record_junctions(mut self, value: bool) -> Self {
+        self.options.record_junctions = value;
+        self
+    }
+
+    /// Include zero-byte files.
+    pub fn include_zero_files(mut self, value: bool) -> Self {
+        self.options.include_zero_files = value;
+        self
+    }
+
+    /// Include empty folders.
+    pub fn include_empty_folders(mut self, value: bool) -> Self {
+        self.options.include_empty_folders = value;
+        self
+    }
+
+    /// Number of times transient I/O errors are retried.
+    pub fn retries(mut self, value: u32) -> Self {
+        self.options.retries = value;
+        self
+    }
+
+    /// Delay between retries.
+    pub fn retry_delay(mut self, value: Duration) -> Self {
+        self.options.retry_delay = value;
+        self
+    }
+
+    /// Order of entries within a folder.
+    ///
+    /// [`SortOrder::Size`] holds the scanned tree in memory until the scan is complete.
+    pub fn sort_order(mut self, value: SortOrder) -> Self {
+        self.options.sort_order = value;
+        self
+    }
+
+    /// How online-only cloud files are reported.
+    pub fn placeholders(mut self, value: PlaceholderMode) -> Self {
+        self.options.placeholders = value;
+        self
+    }
+
+    /// Records the [`FileKind`](crate::FileKind) of each file in a `kind` attribute.
+    pub fn classify(mut self, mode: ClassifyMode) -> Self {
+        self.options.classify = Some(mode);
+        self
+    }
+
+    /// Selects the times written to the dump for each entry, leaving out unknown times
+    /// (see [`XmlFormatter::timestamps`](crate::XmlFormatter::timestamps)).
+    ///
+    /// By default, all three times are written, with the Unix epoch for unknown times.
+    pub fn timestamps(mut self, value: Timestamps) -> Self {
+        self.options.timestamps = Some(value);
+        self
+    }
+
+    /// Fails the scan on names with characters that XML 1.0 does not allow, instead of
+    /// replacing them (see [`sanitize_xml_text`](crate::xml_output::sanitize_xml_text)).
+    pub fn strict_names(mut self, value: bool) -> Self {
+        self.options.strict_names = value;
+        self
+    }
+
+    /// Records the uniform type identifier of each file with a known extension in a
+    /// `type` attribute, such as `type="public.jpeg"`.
+    pub fn uniform_types(mut self, value: bool) -> Self {
+        self.options.uniform_types = value;
+        self
+    }
+
+    /// Records the full path of each file and folder in a `path` attribute, next to its
+    /// name, so that the output can be searched without following the nesting. A relative
+    /// root path is made absolute first.
+    pub fn absolute_paths(mut self, value: bool) -> Self {
+        self.options.absolute_paths = value;
+        self
+    }
+
+    /// Skip files and folders whose name matches the pattern.
+    ///
+    /// `*` matches any sequence of characters and `?` matches a single character.
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.options.excludes.push(pattern.into());
+        self
+    }
+
+    /// Skip files owned by the user ID `uid`. Folders are still traversed.
+    pub fn exclude_user(mut self, uid: u32) -> Self {
+        self.options.exclude_users.push(uid);
+        self
+    }
+
+    /// Skip files that are not owned by the user ID `uid`, or by another user passed
+    /// to this method. Folders are still traversed, and files whose owner is not known
+    /// are skipped.
+    pub fn only_user(mut self, uid: u32) -> Self {
+        self.options.only_users.push(uid);
+        self
+    }
+
+    /// Ask the shell command `command` which entries to skip, before their metadata is read.
+    ///
+    /// The command is started once, and reads the paths of the entries of each folder
+    /// on its standard input, one per line, followed by an empty line. It writes `keep` or
+    /// `skip` on a line for each path, in the same order. Its decisions are cached per
+    /// folder until the folder is modified, for later scans with the same options.
+    pub fn filter_command(mut self, command: impl Into<String>) -> Self {
+        self.options.filter_command = Some(FilterCommand::new(command));
+        self
+    }
+
+    /// List the contents of `.tar`, `.tar.gz`, and `.zip` files as folders.
+    ///
+    /// Member sizes are the uncompressed sizes recorded in the archive. Only
+    /// archives on the local filesystem are opened.
+    pub fn scan_archives(mut self, value: bool) -> Self {
+        self.options.scan_archives = value;
+        self
+    }
+
+    /// Adds the cumulative logical and physical size and the number of files and
+    /// folders below each folder to its attributes (see [`FolderTotals`](crate::output::FolderTotals)).
+    ///
+    /// The scanned tree is held in memory until the scan is complete.
+    pub fn folder_totals(mut self, value: bool) -> Self {
+        self.options.folder_totals = value;
+        self
+    }
+
+    /// Records the other size measure of each file as well, as `logicalSize` (or
+    /// `physicalSize` with [`apparent_size`](Self::apparent_size)), besides `size`.
+    pub fn both_sizes(mut self, value: bool) -> Self {
+        self.options.both_sizes = value;
+        self
+    }
+
+    /// Rounds the disk usage of each file up to the allocation unit of its file system:
+    /// the fragment size from `statvfs`, or the cluster size on Windows, where the disk
+    /// usage is otherwise the apparent size. This makes sizes from file systems with
+    /// different block or cluster sizes comparable. Not applied to custom sources.
+    pub fn allocation_unit(mut self, value: bool) -> Self {
+        self.options.allocation_unit = value;
+        self
+    }
+
+    /// Records the space taken by each directory itself as a file named `.` in the
+    /// directory, so that folder totals match `du`.
+    pub fn dir_entry_size(mut self, value: bool) -> Self {
+        self.options.dir_entry_size = value;
+        self
+    }
+
+    /// Writes a fixed access time (the Unix epoch) for every entry, so that shared
+    /// dumps do not reveal when files were last read.
+    pub fn hide_access_times(mut self, value: bool) -> Self {
+        self.options.hide_access_times = value;
+        self
+    }
+
+    /// Writes skipped entries, read errors, and cancellation notices into the output as
+    /// comments, at the position of the entries they concern.
+    pub fn embed_warnings(mut self, value: bool) -> Self {
+        self.options.embed_warnings = value;
+        self
+    }
+
+    /// Logs every skipped entry. By default, only the first few entries skipped for each
+    /// reason are logged one by one, and the rest are counted in periodic summaries, so
+    /// that trees with millions of empty files or hard links do not flood the log.
+    /// Unexpected skips, such as special files, are always logged.
+    pub fn log_each_skip(mut self, value: bool) -> Self {
+        self.options.log_each_skip = value;
+        self
+    }
+
+    /// Bounds the memory used to detect hard links and directory loops, in bytes.
+    ///
+    /// Only these sets are bounded: the entries of each directory are still read as a
+    /// whole, and the folder tree is written out as the scan goes.
+    ///
+    /// Close to the limit, only files with several links are remembered, and loops are
+    /// detected among the ancestors of a directory. At the limit, hard links are no longer
+    /// detected, so files linked again later in the scan are counted more than once.
+    ///
+    /// The tree itself is not bounded, so [`folder_totals`](Self::folder_totals) and
+    /// [`SortOrder::Size`], which hold it in memory, make the scan fail with
+    /// [`io::ErrorKind::InvalidInput`].
+    pub fn max_memory(mut self, bytes: u64) -> Self {
+        self.options.max_memory = Some(bytes);
+        self
+    }
+
+    /// Sets how files already counted are remembered, to skip hard links to them.
+    ///
+    /// With [`InodeFilter::Bloom`], only files with several links are remembered, in a
+    /// Bloom filter of fixed size instead of a hash set that grows with every file. The
+    /// filter may mistake a file for one already counted and skip it as a hard link, with a
+    /// small probability given by the filter size (see [`BloomFilter`](crate::BloomFilter)).
+    pub fn inode_filter(mut self, filter: InodeFilter) -> Self {
+        self.options.inode_filter = filter;
+        self
+    }
+
+    /// Copies the folders of directories last modified before `cutoff` from `cache`, a
+    /// previous scan of the same root, instead of reading them.
+    ///
+    /// The modification time of a directory only changes when entries are added, removed,
+    /// or renamed in it, not when files are changed or entries deeper in the tree, so this
+    /// is a heuristic for trees that only grow by new directories, such as append-only
+    /// archives. The cutoff is never later than the scan time of `cache`. Folders that are
+    /// not in `cache` are read as usual, and copied folders keep the sizes and filters of
+    /// the previous scan.
+    pub fn trust_dir_mtime(mut self, cache: ScanResult, cutoff: SystemTime) -> Self {
+        self.options.dir_cache = Some(DirCache::new(cache, cutoff));
+        self
+    }
+
+    /// Stops the scan once `count` entries could not be read. The open folders are
+    /// closed, so the output is complete up to that point.
+    pub fn max_errors(mut self, count: u64) -> Self {
+        self.options.max_errors = Some(count);
+        self
+    }
+
+    /// Logs a warning for each directory with more than `count` entries.
+    pub fn warn_dir_entries(mut self, count: u64) -> Self {
+        self.options.warn_dir_entries = Some(count);
+        self
+    }
+
+    /// Leaves out the contents of directories with more than `count` entries, such as
+    /// mail spools, without reading the metadata of their entries. The folder itself is
+    /// kept, with the number of entries in its `skippedEntries` attribute.
+    pub fn skip_dir_entries(mut self, count: u64) -> Self {
+        self.options.skip_dir_entries = Some(count);
+        self
+    }
+
+    /// Measures the time taken by each directory subtree, and keeps the `count` slowest
+    /// in [`ScanStats::slowest_dirs`](crate::ScanStats::slowest_dirs).
+    pub fn profile_dirs(mut self, count: usize) -> Self {
+        self.options.profile_dirs = Some(count);
+        self
+    }
+
+    /// Scans only about `rate` (more than 0, up to 1) of the files, chosen by a hash of their
+    /// paths below the scan root, and scales their sizes up by `1 / rate`, for a quick
+    /// estimate of a tree too large to scan in full. Every folder is still read, but files
+    /// left out are not, where the listing tells files apart. The rate is recorded in the
+    /// scan information, which marks the sizes as estimates.
+    ///
+    /// Other rates, including NaN, make the scan fail with
+    /// [`Error::InvalidOptions`](crate::Error::InvalidOptions).
+    pub fn sample(mut self, rate: f64) -> Self {
+        self.options.sample = Some(rate);
+        self
+    }
+
+    /// Checks a random sample of `count` scanned files and folders again after the scan, and
+    /// records how many changed their size or modification time, or were removed, in
+    /// [`ScanStats::consistency`](crate::ScanStats::consistency) and the scan information.
+    pub fn verify(mut self, count: usize) -> Self {
+        self.options.verify = Some(count);
+        self
+    }
+
+    /// Traverses the tree on a background thread, so that reading metadata overlaps
+    /// with formatting and writing the output. The formatter stays on the calling thread.
+    pub fn writer_thread(mut self, value: bool) -> Self {
+        self.options.writer_thread = value;
+        self
+    }
+
+    /// Name of the root folder in the output: a fixed name, or a [`RootName`] derived
+    /// from the scan root. By default, the path of the scan root is used as given.
+    pub fn root_name(mut self, name: impl Into<RootName>) -> Self {
+        self.options.root_name = name.into();
+        self
+    }
+
+    /// Description of the scan, recorded as the `comment` attribute of `ScanInfo`.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Token that stops the scan when cancelled.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.options.cancellation = Some(token);
+        self
+    }
+
+    /// Report progress to `sink` at most once per `interval`.
+    pub fn progress(mut self, sink: Arc<dyn ProgressSink>, interval: Duration) -> Self {
+        self.options.progress = Some(ProgressReporter { sink, interval });
+        self
+    }
+
+    /// Decide per error whether to skip, retry, or abort.
+    ///
+    /// Replaces the default policy configured by `retries` and `retry_delay`.
+    pub fn error_policy(mut self, policy: Arc<dyn ErrorPolicy>) -> Self {
+        self.options.error_policy = Some(CustomErrorPolicy(policy));
+        self
+    }
+
+    /// Read directories and metadata from `source` instead of the local filesystem.
+    pub fn source(mut self, source: Arc<dyn ScanSource>) -> Self {
+        self.options.source = Some(CustomSource(source));
+        self
+    }
+
+    /// Appends the attributes returned by `attributes` to every file and folder.
+    ///
+    /// The function is called with the path and metadata of each entry, including archive
+    /// members, and its attributes are written after the built-in ones. Keys that are not
+    /// XML names or that are already used by the entry are left out with a warning, once
+    /// per key. Values are escaped like file names.
+    pub fn attributes<F>(mut self, attributes: F) -> Self
+    where
+        F: Fn(&Path, &EntryMetadata) -> Vec<(String, String)> + Send + Sync + 'static,
+    {
+        self.options.attributes = Some(CustomAttributes(Arc::new(attributes)));
+        self
+    }
+
+    pub fn build(self) -> Options {
+        self.options
+    }
+}
+
+/// Matches a name against a pattern with `*` and `?` wildcards.
+///
+/// Both are compared by characters, so that `?` matches one character of a non-ASCII name.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,364 @@
+// Standard library imports
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::model::{FileNode, NodeKind, ScanInfo, ScanResult};
+use crate::scan::ScanEntry;
+use crate::source::{EntryKind, EntryMetadata};
+
+/// Writes scan results in an output format.
+///
+/// The methods are called in document order: `begin_scan`, then the folder tree
+/// depth-first, then `end_scan`. Values only known after the traversal, such as
+/// the scan duration, are passed to `end_scan`.
+pub trait OutputFormatter {
+    /// Called before the traversal starts.
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()>;
+
+    /// Called when a folder is entered, before any of its children.
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()>;
+
+    /// Called for every file in the current folder.
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()>;
+
+    /// Called after all children of the most recently entered folder.
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()>;
+
+    /// Called with a diagnostic about the current position in the tree, such as a
+    /// skipped entry. Formats without comments ignore it.
+    fn comment(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called after the traversal with the final scan information.
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()>;
+}
+
+impl<F: OutputFormatter + ?Sized> OutputFormatter for &mut F {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        (**self).begin_scan(info)
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        (**self).begin_folder(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        (**self).file(entry)
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        (**self).end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        (**self).comment(text)
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        (**self).end_scan(info)
+    }
+}
+
+/// Adds cumulative totals to the attributes of every folder before passing it on:
+/// `totalLogicalSize`, `totalPhysicalSize`, `totalFiles`, and `totalFolders`
+/// (the folders below it, not counting itself).
+///
+/// Start tags are written before the children, so entries are held in memory until
+/// their top folder is complete. For a scan, that is the whole tree.
+pub struct FolderTotals<F> {
+    inner: F,
+    /// True if entry sizes are logical (apparent) sizes.
+    logical: bool,
+    buffered: Vec<Buffered>,
+    /// Index of the start of each open folder in `buffered`, with its totals so far.
+    open: Vec<(usize, Totals)>,
+}
+
+enum Buffered {
+    Begin(ScanEntry),
+    File(ScanEntry),
+    End(ScanEntry),
+    Comment(String),
+}
+
+#[derive(Default)]
+struct Totals {
+    logical_size: u64,
+    physical_size: u64,
+    files: u64,
+    folders: u64,
+}
+
+impl<F: OutputFormatter> FolderTotals<F> {
+    pub fn new(inner: F) -> Self {
+        FolderTotals {
+            inner,
+            logical: false,
+            buffered: Vec::new(),
+            open: Vec::new(),
+        }
+    }
+
+    /// Returns the wrapped formatter.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: OutputFormatter> OutputFormatter for FolderTotals<F> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.logical = info.file_size_measure == "logical";
+        self.inner.begin_scan(info)
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.open.push((self.buffered.len(), Totals::default()));
+        self.buffered.push(Buffered::Begin(entry.clone()));
+        Ok(())
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        if let Some((_, totals)) = self.open.last_mut() {
+            // The size of the entry reflects the scan options, so it is used for its own measure
+            let (logical_size, physical_size) = if self.logical {
+                (entry.size, entry.metadata.size)
+            } else {
+                (entry.metadata.apparent_size, entry.size)
+            };
+            totals.logical_size += logical_size;
+            totals.physical_size += physical_size;
+            totals.files += 1;
+        }
+        self.buffered.push(Buffered::File(entry.clone()));
+        Ok(())
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        let (start, totals) = self
+            .open
+            .pop()
+            .ok_or_else(|| io::Error::other("end_folder was called without begin_folder"))?;
+        if let Buffered::Begin(folder) = &mut self.buffered[start] {
+            folder.attributes.extend([
+                (
+                    "totalLogicalSize".to_string(),
+                    totals.logical_size.to_string(),
+                ),
+                (
+                    "totalPhysicalSize".to_string(),
+                    totals.physical_size.to_string(),
+                ),
+                ("totalFiles".to_string(), totals.files.to_string()),
+                ("totalFolders".to_string(), totals.folders.to_string()),
+            ]);
+        }
+        self.buffered.push(Buffered::End(entry.clone()));
+
+        let Some((_, parent)) = self.open.last_mut() else {
+            // The top folder is complete
+            for buffered in self.buffered.drain(..) {
+                match buffered {
+                    Buffered::Begin(entry) => self.inner.begin_folder(&entry)?,
+                    Buffered::File(entry) => self.inner.file(&entry)?,
+                    Buffered::End(entry) => self.inner.end_folder(&entry)?,
+                    Buffered::Comment(text) => self.inner.comment(&text)?,
+                }
+            }
+            return Ok(());
+        };
+        parent.logical_size += totals.logical_size;
+        parent.physical_size += totals.physical_size;
+        parent.files += totals.files;
+        parent.folders += totals.folders + 1;
+        Ok(())
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        if self.open.is_empty() {
+            return self.inner.comment(text);
+        }
+        self.buffered.push(Buffered::Comment(text.to_string()));
+        Ok(())
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.end_scan(info)
+    }
+}
+
+/// Passes entries on with the children of every folder ordered by descending cumulative
+/// size, for [`SortOrder::Size`](crate::SortOrder::Size). Ties are broken by byte order
+/// of the names.
+///
+/// The order of the top folder's children is only known once all of them are complete,
+/// so for a scan, the whole tree is held in memory.
+pub struct SizeOrder<F> {
+    inner: F,
+    /// Folders entered but not yet left, outermost first.
+    open: Vec<OrderedFolder>,
+}
+
+struct OrderedFolder {
+    entry: ScanEntry,
+    /// Total size of the files below the folder.
+    size: u64,
+    children: Vec<Ordered>,
+}
+
+enum Ordered {
+    Folder(Box<OrderedFolder>, ScanEntry),
+    File(ScanEntry),
+    Comment(String),
+}
+
+impl Ordered {
+    fn size(&self) -> u64 {
+        match self {
+            Ordered::Folder(folder, _) => folder.size,
+            Ordered::File(entry) => entry.size,
+            Ordered::Comment(_) => 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Ordered::Folder(folder, _) => &folder.entry.name,
+            Ordered::File(entry) => &entry.name,
+            Ordered::Comment(_) => "",
+        }
+    }
+}
+
+impl<F: OutputFormatter> SizeOrder<F> {
+    pub fn new(inner: F) -> Self {
+        SizeOrder {
+            inner,
+            open: Vec::new(),
+        }
+    }
+
+    /// Returns the wrapped formatter.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    fn write(&mut self, item: Ordered) -> io::Result<()> {
+        match item {
+            Ordered::Folder(mut folder, end) => {
+                folder
+                    .children
+                    .sort_by(|a, b| b.size().cmp(&a.size()).then_with(|| a.name().cmp(b.name())));
+                self.inner.begin_folder(&folder.entry)?;
+                for child in folder.children {
+                    self.write(child)?;
+                }
+                self.inner.end_folder(&end)
+            }
+            Ordered::File(entry) => self.inner.file(&entry),
+            Ordered::Comment(text) => self.inner.comment(&text),
+        }
+    }
+}
+
+impl<F: OutputFormatter> OutputFormatter for SizeOrder<F> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.begin_scan(info)
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.open.push(OrderedFolder {
+            entry: entry.clone(),
+            size: 0,
+            children: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        let Some(parent) = self.open.last_mut() else {
+            return self.inner.file(entry);
+        };
+        parent.size += entry.size;
+        parent.children.push(Ordered::File(entry.clone()));
+        Ok(())
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        let folder = self
+            .open
+            .pop()
+            .ok_or_else(|| io::Error::other("end_folder was called without begin_folder"))?;
+        let Some(parent) = self.open.last_mut() else {
+            // The top folder is complete
+            return self.write(Ordered::Folder(Box::new(folder), entry.clone()));
+        };
+        parent.size += folder.size;
+        parent
+            .children
+            .push(Ordered::Folder(Box::new(folder), entry.clone()));
+        Ok(())
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(Ordered::Comment(text.to_string())),
+            None => return self.inner.comment(text),
+        }
+        Ok(())
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.end_scan(info)
+    }
+}
+
+/// Writes an in-memory scan result with `formatter`.
+///
+/// Used to re-emit dumps that were read, merged, or imported rather than scanned.
+pub fn write_result<F: OutputFormatter>(result: &ScanResult, mut formatter: F) -> io::Result<()> {
+    formatter.begin_scan(&result.info)?;
+    write_node(&result.root, &PathBuf::new(), 0, &mut formatter)?;
+    formatter.end_scan(&result.info)
+}
+
+fn write_node<F: OutputFormatter>(
+    node: &FileNode,
+    parent: &Path,
+    depth: usize,
+    formatter: &mut F,
+) -> io::Result<()> {
+    let entry = node_entry(node, parent, depth);
+    match node.kind {
+        NodeKind::File => formatter.file(&entry),
+        NodeKind::Folder => {
+            formatter.begin_folder(&entry)?;
+            for child in &node.children {
+                write_node(child, &entry.path, depth + 1, formatter)?;
+            }
+            formatter.end_folder(&entry)
+        }
+    }
+}
+
+/// Converts a tree node into the entry passed to formatters.
+pub(crate) fn node_entry(node: &FileNode, parent: &Path, depth: usize) -> ScanEntry {
+    let kind = match node.kind {
+        NodeKind::File => EntryKind::File,
+        NodeKind::Folder => EntryKind::Dir,
+    };
+    let mut metadata = EntryMetadata::new(kind, node.size);
+    metadata.created = node.created.map(SystemTime::from);
+    metadata.modified = node.modified.map(SystemTime::from);
+    metadata.accessed = node.accessed.map(SystemTime::from);
+
+    ScanEntry {
+        path: parent.join(&node.name),
+        name: node.name.clone(),
+        depth,
+        size: node.size,
+        metadata,
+        attributes: node.attributes.clone(),
+    }
+}
@@ -0,0 +1,139 @@
+// External crates
+use log::{info, warn};
+
+// Standard library imports
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{is_storage_full, Error};
+
+/// The output file of a scan, which handles its disk filling up (`ENOSPC`).
+///
+/// With a fallback path (`--fallback-output`), the output written so far is copied there
+/// and the rest follows, so that compressed and encrypted streams stay intact. Without one,
+/// or if the fallback fills up as well, the incomplete file is removed when it is dropped,
+/// instead of leaving a corrupt dump behind.
+pub struct OutputFile {
+    file: Option<Box<dyn Write + Send>>,
+    path: PathBuf,
+    /// Bytes written to the current file.
+    written: u64,
+    fallback: Option<PathBuf>,
+    /// Whether the disk filled up, so that the file is incomplete.
+    full: bool,
+}
+
+impl OutputFile {
+    /// Creates the file at `path`, to continue in `fallback` if its disk fills up.
+    pub fn create(path: &Path, fallback: Option<&Path>) -> Result<Self, Error> {
+        let file = create_file(path).map_err(Error::Output)?;
+        Ok(OutputFile::new(file, path, fallback))
+    }
+
+    /// Writes to `writer`, which writes the file at `path` without buffering, so that
+    /// the bytes written so far can be read back from `path` when its disk fills up.
+    pub fn new(writer: impl Write + Send + 'static, path: &Path, fallback: Option<&Path>) -> Self {
+        OutputFile {
+            file: Some(Box::new(writer)),
+            path: path.to_path_buf(),
+            written: 0,
+            fallback: fallback.map(Path::to_path_buf),
+            full: false,
+        }
+    }
+
+    /// Returns the path of the file the output is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves the output to the fallback path, after `e` was returned for the current file.
+    fn switch_to_fallback(&mut self, e: io::Error) -> io::Result<()> {
+        // The current file stays incomplete unless the fallback takes over
+        self.full = true;
+        let Some(fallback) = self.fallback.take() else {
+            return Err(e);
+        };
+        warn!(
+            "{}: {}; continuing the output in {}",
+            self.path.display(),
+            e,
+            fallback.display()
+        );
+        let mut file = create_file(&fallback)?;
+        let copied = File::open(&self.path)
+            .and_then(|written| io::copy(&mut written.take(self.written), &mut file));
+        if let Err(e) = copied {
+            drop(file);
+            if let Err(e) = fs::remove_file(&fallback) {
+                warn!("Failed to remove {}: {}", fallback.display(), e);
+            }
+            return Err(e);
+        }
+
+        // Closed before it is removed, which Windows requires
+        self.file = Some(Box::new(file));
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+        self.path = fallback;
+        self.full = false;
+        Ok(())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.full {
+            return Err(io::ErrorKind::StorageFull.into());
+        }
+        let file = self.file.as_mut().expect("the file is open until dropped");
+        let written = match file.write(buf) {
+            Err(e) if is_storage_full(&e) => {
+                self.switch_to_fallback(e)?;
+                self.file.as_mut().expect("the fallback is open").write(buf)
+            }
+            result => result,
+        };
+        match written {
+            Ok(written) => {
+                self.written += written as u64;
+                Ok(written)
+            }
+            Err(e) => {
+                self.full = is_storage_full(&e);
+                Err(e)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if !self.full {
+            return;
+        }
+        self.file.take();
+        match fs::remove_file(&self.path) {
+            Ok(()) => info!("Removed the incomplete output {}", self.path.display()),
+            Err(e) => warn!("Failed to remove {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+fn create_file(path: &Path) -> Result<File, io::Error> {
+    File::create(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to create output file '{}': {}", path.display(), e),
+        )
+    })
+}
@@ -1,66 +1,86 @@
-use std::fs::Metadata;
+use std::fs::{self, File, Metadata};
+use std::io;
 #[cfg(target_os = "linux")]
 use std::os::linux::fs::MetadataExt;
-#[cfg(any(target_os = "freebsd", target_os = "macos"))]
+#[cfg(all(unix, not(target_os = "linux")))]
 use std::os::unix::fs::MetadataExt;
 #[cfg(target_os = "windows")]
 use std::os::windows::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::source::EntryKind;
 
 pub trait MetadataExtOps {
-    fn device_id(&self) -> u64;
-    fn inode_number(&self) -> u64;
     fn file_size(&self, apparent: bool) -> u64;
+    /// Returns true for online-only cloud files whose content is not stored locally.
+    fn is_placeholder(&self) -> bool;
+    /// Returns the user ID of the owner, if the platform has one.
+    fn owner_id(&self) -> Option<u32>;
+    /// Returns the number of hard links to the entry, or 0 if unknown.
+    fn link_count(&self) -> u64;
 }
 
 #[cfg(target_os = "linux")]
 impl MetadataExtOps for Metadata {
-    fn device_id(&self) -> u64 {
-        self.st_dev()
-    }
-
-    fn inode_number(&self) -> u64 {
-        self.st_ino()
-    }
-
     fn file_size(&self, apparent: bool) -> u64 {
         if apparent {
-            self.st_size() as u64
+            self.st_size()
         } else {
-            self.st_blocks() as u64 * 512
+            self.st_blocks() * 512
         }
     }
-}
 
-#[cfg(any(target_os = "macos", target_os = "freebsd"))]
-impl MetadataExtOps for Metadata {
-    fn device_id(&self) -> u64 {
-        self.dev()
+    fn is_placeholder(&self) -> bool {
+        false
+    }
+
+    fn owner_id(&self) -> Option<u32> {
+        Some(self.st_uid())
     }
 
-    fn inode_number(&self) -> u64 {
-        self.ino()
+    fn link_count(&self) -> u64 {
+        self.st_nlink()
     }
+}
 
+/// macOS, the BSDs, illumos, and other Unix systems.
+#[cfg(all(unix, not(target_os = "linux")))]
+impl MetadataExtOps for Metadata {
     fn file_size(&self, apparent: bool) -> u64 {
         if apparent {
-            self.size() as u64
+            self.size()
         } else {
-            self.blocks() as u64 * 512
+            // `st_blocks` counts 512-byte units on every Unix
+            self.blocks() * 512
         }
     }
-}
 
-#[cfg(target_os = "windows")]
-impl MetadataExtOps for Metadata {
-    fn device_id(&self) -> u64 {
-        self.volume_serial_number().unwrap_or(0) as u64
+    #[cfg(target_os = "macos")]
+    fn is_placeholder(&self) -> bool {
+        use std::os::macos::fs::MetadataExt as MacMetadataExt;
+
+        // File content is not stored locally (iCloud Drive, File Provider extensions)
+        const SF_DATALESS: u32 = 0x4000_0000;
+        self.st_flags() & SF_DATALESS != 0
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn is_placeholder(&self) -> bool {
+        false
+    }
+
+    fn owner_id(&self) -> Option<u32> {
+        Some(self.uid())
     }
 
-    fn inode_number(&self) -> u64 {
-        // Windows does not have inode, so use file index
-        self.file_index().unwrap_or(0)
+    fn link_count(&self) -> u64 {
+        self.nlink()
     }
+}
 
+#[cfg(target_os = "windows")]
+impl MetadataExtOps for Metadata {
     fn file_size(&self, apparent: bool) -> u64 {
         if apparent {
             self.len()
@@ -69,4 +89,524 @@ impl MetadataExtOps for Metadata {
             self.len()
         }
     }
+
+    fn is_placeholder(&self) -> bool {
+        use windows_sys::Win32::Storage::FileSystem::{
+            FILE_ATTRIBUTE_OFFLINE, FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS,
+            FILE_ATTRIBUTE_RECALL_ON_OPEN,
+        };
+
+        // Content is fetched on demand (OneDrive, Dropbox, and other cloud files)
+        self.file_attributes()
+            & (FILE_ATTRIBUTE_OFFLINE
+                | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS
+                | FILE_ATTRIBUTE_RECALL_ON_OPEN)
+            != 0
+    }
+
+    fn owner_id(&self) -> Option<u32> {
+        // Windows files are owned by security identifiers, which are not read
+        None
+    }
+
+    fn link_count(&self) -> u64 {
+        // `number_of_links` is not stable yet
+        0
+    }
+}
+
+/// Returns the device ID and inode number identifying the entry at `path`.
+///
+/// `link` is the [`link_kind`] of the entry, if `metadata` describes a link itself.
+#[cfg(target_os = "linux")]
+pub fn file_id(_path: &Path, metadata: &Metadata, _link: Option<LinkKind>) -> (u64, u64) {
+    (metadata.st_dev(), metadata.st_ino())
+}
+
+/// Returns the device ID and inode number identifying the entry at `path`.
+///
+/// `link` is the [`link_kind`] of the entry, if `metadata` describes a link itself.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn file_id(_path: &Path, metadata: &Metadata, _link: Option<LinkKind>) -> (u64, u64) {
+    (metadata.dev(), metadata.ino())
+}
+
+/// Returns the volume serial number and file index identifying the entry at `path`,
+/// or zeros if the entry cannot be opened.
+///
+/// `link` is the [`link_kind`] of the entry, so that links are identified themselves
+/// when `metadata` from `symlink_metadata` describes them. Unlike on Unix, where the IDs
+/// come with the metadata, a handle is opened to each entry, which costs about as much
+/// as reading its metadata again; hard link and loop detection depend on it, as the link
+/// count of files is not known.
+#[cfg(target_os = "windows")]
+pub fn file_id(path: &Path, _metadata: &Metadata, link: Option<LinkKind>) -> (u64, u64) {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, FILE_SHARE_DELETE,
+        FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+
+    let wide_path: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    // Directories can only be opened with backup semantics
+    let mut flags = FILE_FLAG_BACKUP_SEMANTICS;
+    if link.is_some() {
+        flags |= FILE_FLAG_OPEN_REPARSE_POINT;
+    }
+
+    // SAFETY: `wide_path` is NUL-terminated, `info` is a valid, writable structure,
+    // and the handle is closed exactly once.
+    unsafe {
+        // No access rights are needed to query the file information
+        let handle = CreateFileW(
+            wide_path.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            flags,
+            std::ptr::null_mut(),
+        );
+        if handle == INVALID_HANDLE_VALUE {
+            return (0, 0);
+        }
+        let mut info: BY_HANDLE_FILE_INFORMATION = std::mem::zeroed();
+        let ok = GetFileInformationByHandle(handle, &mut info);
+        CloseHandle(handle);
+        if ok == 0 {
+            return (0, 0);
+        }
+        (
+            info.dwVolumeSerialNumber as u64,
+            ((info.nFileIndexHigh as u64) << 32) | info.nFileIndexLow as u64,
+        )
+    }
+}
+
+/// Returns the allocation unit of the file system holding `path`: the fragment size
+/// reported by `statvfs`, which disk usage is a multiple of.
+#[cfg(unix)]
+pub fn allocation_unit(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid, writable structure.
+    let stat = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(c_path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        stat
+    };
+    // Some file systems leave the fragment size unset
+    [stat.f_frsize as u64, stat.f_bsize as u64]
+        .into_iter()
+        .find(|&unit| unit > 0)
+}
+
+/// Returns the cluster size of the volume holding `path`.
+#[cfg(target_os = "windows")]
+pub fn allocation_unit(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{GetDiskFreeSpaceW, GetVolumePathNameW};
+
+    // Long enough for volume GUID paths and the usual mount point paths
+    const VOLUME_PATH_LEN: usize = 1024;
+
+    let wide_path: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut volume = [0u16; VOLUME_PATH_LEN];
+    let (mut sectors_per_cluster, mut bytes_per_sector) = (0u32, 0u32);
+    let (mut free_clusters, mut total_clusters) = (0u32, 0u32);
+
+    // SAFETY: `wide_path` is NUL-terminated, `volume` is writable for its whole length and
+    // NUL-terminated on success, and the counts are valid, writable integers.
+    unsafe {
+        if GetVolumePathNameW(wide_path.as_ptr(), volume.as_mut_ptr(), volume.len() as u32) == 0 {
+            return None;
+        }
+        if GetDiskFreeSpaceW(
+            volume.as_ptr(),
+            &mut sectors_per_cluster,
+            &mut bytes_per_sector,
+            &mut free_clusters,
+            &mut total_clusters,
+        ) == 0
+        {
+            return None;
+        }
+    }
+    Some(sectors_per_cluster as u64 * bytes_per_sector as u64).filter(|&unit| unit > 0)
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+pub fn allocation_unit(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Returns the user ID of the user `name`, if the platform has user IDs and the user exists.
+#[cfg(unix)]
+pub fn user_id(name: &str) -> Option<u32> {
+    uzers::get_user_by_name(name).map(|user| user.uid())
+}
+
+#[cfg(not(unix))]
+pub fn user_id(_name: &str) -> Option<u32> {
+    None
+}
+
+/// Kinds of entries that redirect to another location and are not traversed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A symbolic link.
+    Symlink,
+    /// A Windows directory junction, volume mount point, or other name-surrogate reparse point.
+    Junction,
+}
+
+/// Returns the kind of link the entry is, or `None` for regular entries.
+///
+/// `metadata` must be obtained with `symlink_metadata` so that links are not followed.
+#[cfg(not(target_os = "windows"))]
+pub fn link_kind(_path: &Path, metadata: &Metadata) -> Option<LinkKind> {
+    if metadata.file_type().is_symlink() {
+        Some(LinkKind::Symlink)
+    } else {
+        None
+    }
+}
+
+/// Returns the kind of link the entry is, or `None` for regular entries.
+///
+/// `metadata` must be obtained with `symlink_metadata` so that links are not followed.
+/// Junctions and mount points are not reported by `is_symlink()` on every toolchain,
+/// so the reparse tag is inspected directly. Reparse points that are not name
+/// surrogates (e.g. cloud files or deduplicated files) are regular entries.
+#[cfg(target_os = "windows")]
+pub fn link_kind(path: &Path, metadata: &Metadata) -> Option<LinkKind> {
+    use windows_sys::Win32::Storage::FileSystem::FILE_ATTRIBUTE_REPARSE_POINT;
+
+    // Reparse tags with this bit set redirect to another named entity
+    const NAME_SURROGATE_BIT: u32 = 0x2000_0000;
+    const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000_000C;
+
+    if metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT == 0 {
+        return None;
+    }
+    match reparse_tag(path) {
+        Some(IO_REPARSE_TAG_SYMLINK) => Some(LinkKind::Symlink),
+        Some(tag) if tag & NAME_SURROGATE_BIT != 0 => Some(LinkKind::Junction),
+        Some(_) => None,
+        // The tag could not be read; fall back to the standard library's view
+        None if metadata.file_type().is_symlink() => Some(LinkKind::Symlink),
+        None => None,
+    }
+}
+
+/// Reads the reparse tag of the entry at the given path.
+#[cfg(target_os = "windows")]
+pub fn reparse_tag(path: &Path) -> Option<u32> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+    use windows_sys::Win32::Storage::FileSystem::{FindClose, FindFirstFileW, WIN32_FIND_DATAW};
+
+    let wide_path: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    // SAFETY: `wide_path` is NUL-terminated and `data` is a valid, writable structure.
+    unsafe {
+        let mut data: WIN32_FIND_DATAW = std::mem::zeroed();
+        let handle = FindFirstFileW(wide_path.as_ptr(), &mut data);
+        if handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        FindClose(handle);
+        Some(data.dwReserved0)
+    }
+}
+
+/// Returns the paths of the entries in the directory at `path`, without updating
+/// the access time of the directory.
+///
+/// `O_NOATIME` is only permitted on files owned by the caller (or with `CAP_FOWNER`);
+/// other directories are listed normally.
+#[cfg(target_os = "linux")]
+pub fn read_dir_noatime(path: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(read_dir_kinds(path)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect())
+}
+
+/// Returns the paths of the entries in the directory at `path` like [`read_dir_noatime`],
+/// with their kinds if the file system reports them in the listing (`d_type`).
+#[cfg(target_os = "linux")]
+pub fn read_dir_kinds(path: &Path) -> io::Result<Vec<(PathBuf, Option<EntryKind>)>> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+
+    // SAFETY: `c_path` is NUL-terminated. The stream owns `fd` once `fdopendir`
+    // succeeds and is closed exactly once below; entries are copied before the
+    // next call to `readdir`.
+    unsafe {
+        let mut fd = libc::open(c_path.as_ptr(), flags | libc::O_NOATIME);
+        if fd < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) {
+            fd = libc::open(c_path.as_ptr(), flags);
+        }
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let dir = libc::fdopendir(fd);
+        if dir.is_null() {
+            let e = io::Error::last_os_error();
+            libc::close(fd);
+            return Err(e);
+        }
+
+        let mut paths = Vec::new();
+        let result = loop {
+            // The end of the directory and errors are told apart by errno
+            *libc::__errno_location() = 0;
+            let entry = libc::readdir(dir);
+            if entry.is_null() {
+                let e = io::Error::last_os_error();
+                break if e.raw_os_error() == Some(0) {
+                    Ok(())
+                } else {
+                    Err(e)
+                };
+            }
+            let name = CStr::from_ptr((*entry).d_name.as_ptr()).to_bytes();
+            let kind = match (*entry).d_type {
+                libc::DT_REG => Some(EntryKind::File),
+                libc::DT_DIR => Some(EntryKind::Dir),
+                libc::DT_LNK => Some(EntryKind::Symlink),
+                libc::DT_UNKNOWN => None,
+                _ => Some(EntryKind::Other),
+            };
+            if name != b"." && name != b".." {
+                paths.push((path.join(OsStr::from_bytes(name)), kind));
+            }
+        };
+        libc::closedir(dir);
+        result.map(|()| paths)
+    }
+}
+
+/// Returns the paths of the entries in the directory at `path`.
+///
+/// Access times are left to the file system (`relatime` or `noatime` mounts, or
+/// NTFS, which no longer updates them by default).
+#[cfg(not(target_os = "linux"))]
+pub fn read_dir_noatime(path: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect()
+}
+
+/// Returns the paths of the entries in the directory at `path`, with their kinds if the
+/// listing reports them without reading the metadata of each entry.
+#[cfg(not(target_os = "linux"))]
+pub fn read_dir_kinds(path: &Path) -> io::Result<Vec<(PathBuf, Option<EntryKind>)>> {
+    fs::read_dir(path)?
+        .map(|entry| {
+            let entry = entry?;
+            let kind = entry.file_type().ok().map(|file_type| {
+                if file_type.is_file() {
+                    EntryKind::File
+                } else if file_type.is_dir() {
+                    EntryKind::Dir
+                } else if file_type.is_symlink() {
+                    EntryKind::Symlink
+                } else {
+                    EntryKind::Other
+                }
+            });
+            Ok((entry.path(), kind))
+        })
+        .collect()
+}
+
+/// Opens a file for reading without updating its access time, where permitted.
+#[cfg(target_os = "linux")]
+pub fn open_noatime(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    match fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOATIME)
+        .open(path)
+    {
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => File::open(path),
+        result => result,
+    }
+}
+
+/// Opens a file for reading.
+#[cfg(not(target_os = "linux"))]
+pub fn open_noatime(path: &Path) -> io::Result<File> {
+    File::open(path)
+}
+
+/// Returns true if `path` is a FIFO, socket, or character device, or a file below `/dev`
+/// or `/proc` such as `/dev/stdout` or `/proc/self/fd/3`, which can be written to, but
+/// not replaced or renamed.
+#[cfg(unix)]
+pub fn is_stream(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    // `/dev/stdout` is a regular file when stdout is redirected to one
+    if path.starts_with("/dev") || path.starts_with("/proc") {
+        return true;
+    }
+    fs::metadata(path).is_ok_and(|metadata| {
+        let file_type = metadata.file_type();
+        file_type.is_fifo() || file_type.is_socket() || file_type.is_char_device()
+    })
+}
+
+/// Returns true if `path` is a FIFO, socket, or character device such as `/dev/stdout`.
+#[cfg(not(unix))]
+pub fn is_stream(_path: &Path) -> bool {
+    false
+}
+
+/// Returns true if `path` refers to the same file as stdout, as `/dev/stdout` does.
+#[cfg(unix)]
+pub fn is_stdout(path: &Path) -> bool {
+    match (fs::metadata(path), fs::metadata("/dev/fd/1")) {
+        (Ok(output), Ok(stdout)) => {
+            file_id(path, &output, None) == file_id(Path::new("/dev/fd/1"), &stdout, None)
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if `path` refers to the same file as stdout.
+#[cfg(not(unix))]
+pub fn is_stdout(_path: &Path) -> bool {
+    false
+}
+
+/// Takes over the open file descriptor `fd`, such as one set up with `3>file` by a shell.
+#[cfg(unix)]
+pub fn open_fd(fd: i32) -> io::Result<File> {
+    use std::os::unix::io::FromRawFd;
+
+    // SAFETY: F_GETFD only reads the flags of the descriptor
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor is open, and it was handed to this process for the output only
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// File descriptors are only available on Unix.
+#[cfg(not(unix))]
+pub fn open_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "file descriptors are only supported on Unix",
+    ))
+}
+
+/// Returns a command running `command` with the system shell: `cmd /C` on Windows, and
+/// `sh -c` elsewhere.
+pub fn shell_command(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+/// Names of devices that Windows reserves in every directory, with any extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Returns why `name` cannot be the name of a file or directory on Windows, or `None` if
+/// it can. `CON` and `con.xml` are reserved device names, for example.
+pub fn windows_name_problem(name: &str) -> Option<&'static str> {
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Some("is a reserved device name");
+    }
+    if name
+        .chars()
+        .any(|c| c.is_ascii_control() || "<>:\"|?*".contains(c))
+    {
+        return Some(
+            "contains a character that is not allowed (< > : \" | ? * or a control character)",
+        );
+    }
+    if name.ends_with([' ', '.']) {
+        return Some("ends with a space or a period, which Windows removes");
+    }
+    None
+}
+
+/// Returns the extended-length form of the absolute Windows path `path`, such as
+/// `\\?\C:\dir` for `C:\dir` and `\\?\UNC\server\share` for `\\server\share`, which is
+/// not limited to `MAX_PATH` characters.
+pub fn extended_length(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        path.to_string()
+    } else if let Some(share) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", share)
+    } else {
+        format!(r"\\?\{}", path)
+    }
+}
+
+/// Returns `path` in extended-length form if it is too long for `MAX_PATH`.
+#[cfg(target_os = "windows")]
+pub fn long_path(path: &Path) -> PathBuf {
+    // Below `MAX_PATH` (260), leaving room for the suffixes of temporary and checksum files
+    const LONG_PATH_LEN: usize = 240;
+
+    let Some(absolute) = std::path::absolute(path)
+        .ok()
+        .and_then(|absolute| absolute.to_str().map(str::to_string))
+    else {
+        return path.to_path_buf();
+    };
+    if absolute.encode_utf16().count() < LONG_PATH_LEN {
+        return path.to_path_buf();
+    }
+    // The prefix turns off the normalization of `/` and `..`, which `absolute` did already
+    PathBuf::from(extended_length(&absolute))
+}
+
+/// Returns `path`, which is not limited in length on this platform.
+#[cfg(not(target_os = "windows"))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
 }
@@ -0,0 +1,175 @@
+// Standard library imports
+use std::fmt;
+#[cfg(feature = "cli")]
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+#[cfg(feature = "cli")]
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "cli")]
+use std::time::Instant;
+
+#[cfg(feature = "cli")]
+use serde_json::json;
+
+/// Snapshot of the scan progress.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    /// Number of files reported so far.
+    pub files: u64,
+    /// Number of folders entered so far.
+    pub folders: u64,
+    /// Total size of the reported files in bytes.
+    pub bytes: u64,
+    /// Path of the entry being processed.
+    pub current_path: &'a Path,
+}
+
+/// Receives periodic progress updates during a scan.
+///
+/// Callbacks run on the scanning thread, so implementations should return quickly.
+pub trait ProgressSink: Send + Sync {
+    /// Called at most once per progress interval while the scan is running.
+    fn update(&self, progress: &Progress);
+
+    /// Called once when the traversal is complete.
+    fn finish(&self, _progress: &Progress) {}
+}
+
+/// A progress sink together with its update interval.
+#[derive(Clone)]
+pub(crate) struct ProgressReporter {
+    pub(crate) sink: Arc<dyn ProgressSink>,
+    pub(crate) interval: Duration,
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Progress line on stderr, used by the `--progress` option.
+#[cfg(feature = "cli")]
+#[derive(Debug, Default)]
+pub struct TerminalProgress;
+
+#[cfg(feature = "cli")]
+impl TerminalProgress {
+    fn render(progress: &Progress) -> String {
+        format!(
+            "[gpscan] {} folders, {} files, {}",
+            progress.folders,
+            progress.files,
+            format_bytes(progress.bytes)
+        )
+    }
+}
+
+#[cfg(feature = "cli")]
+impl ProgressSink for TerminalProgress {
+    fn update(&self, progress: &Progress) {
+        // Clear the line, then show as much of the path as fits in a typical terminal
+        let line = format!(
+            "{}: {}",
+            Self::render(progress),
+            progress.current_path.display()
+        );
+        let line: String = line.chars().take(79).collect();
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    }
+
+    fn finish(&self, progress: &Progress) {
+        let mut stderr = io::stderr().lock();
+        let _ = writeln!(stderr, "\r\x1b[2K{}", Self::render(progress));
+    }
+}
+
+/// Progress records as JSON lines, used by the `--progress-json` option.
+///
+/// Each update is written as one line with the fields `event`, `path`, `folders`, `files`,
+/// `bytes`, `elapsed` (seconds), `files_per_second`, and `bytes_per_second`. The event is
+/// `progress`, or `finish` on the last line. The rates are averages since the sink was created.
+#[cfg(feature = "cli")]
+pub struct JsonProgress {
+    output: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+}
+
+#[cfg(feature = "cli")]
+impl JsonProgress {
+    /// Creates a sink that writes to `output`.
+    pub fn new(output: impl Write + Send + 'static) -> Self {
+        JsonProgress {
+            output: Mutex::new(Box::new(output)),
+            started: Instant::now(),
+        }
+    }
+
+    fn write(&self, event: &str, progress: &Progress) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = |count: u64| {
+            if elapsed > 0.0 {
+                count as f64 / elapsed
+            } else {
+                0.0
+            }
+        };
+        let record = json!({
+            "event": event,
+            "path": progress.current_path.to_string_lossy(),
+            "folders": progress.folders,
+            "files": progress.files,
+            "bytes": progress.bytes,
+            "elapsed": elapsed,
+            "files_per_second": rate(progress.files),
+            "bytes_per_second": rate(progress.bytes),
+        });
+        // A reader that went away must not stop the scan
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(output, "{}", record);
+        let _ = output.flush();
+    }
+}
+
+#[cfg(feature = "cli")]
+impl fmt::Debug for JsonProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonProgress")
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "cli")]
+impl ProgressSink for JsonProgress {
+    fn update(&self, progress: &Progress) {
+        self.write("progress", progress);
+    }
+
+    fn finish(&self, progress: &Progress) {
+        self.write("finish", progress);
+    }
+}
+
+/// Formats a byte count with a binary unit.
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,216 @@
+//! Python module, built with `maturin build --features python`.
+//!
+//! ```python
+//! import gpscan
+//!
+//! gpscan.scan("/srv", "srv.gpscan", apparent_size=True)  # writes a GrandPerspective dump
+//! tree = gpscan.scan("/srv", exclude=["*.tmp"])           # nested dicts
+//! for entry in gpscan.scan("/srv", iterate=True):         # one dict per entry
+//!     print(entry["path"], entry["size"])
+//! ```
+
+// Standard library imports
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use pyo3::exceptions::{PyFileNotFoundError, PyOSError, PyRuntimeWarning, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3::IntoPyObjectExt;
+
+use crate::error::Error;
+use crate::iter::{ScanIter, SkippedEntry};
+use crate::model::FileNode;
+use crate::options::Options;
+use crate::scan::{scan as scan_to_writer, scan_to_tree, ScanEntry};
+use crate::source::EntryKind;
+
+/// Scans the directory at `path`.
+///
+/// Writes a GrandPerspective dump to `output` and returns the scan counters, returns an
+/// iterator of entry dicts in depth-first order with `iterate=True`, or else returns the
+/// tree as nested dicts. The scan runs without holding the GIL.
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    output = None,
+    *,
+    iterate = false,
+    apparent_size = false,
+    cross_mount_points = false,
+    include_zero_files = false,
+    include_empty_folders = false,
+    exclude = Vec::new(),
+))]
+#[allow(clippy::too_many_arguments)]
+fn scan(
+    py: Python<'_>,
+    path: PathBuf,
+    output: Option<PathBuf>,
+    iterate: bool,
+    apparent_size: bool,
+    cross_mount_points: bool,
+    include_zero_files: bool,
+    include_empty_folders: bool,
+    exclude: Vec<String>,
+) -> PyResult<PyObject> {
+    let options = exclude
+        .into_iter()
+        .fold(Options::builder(), |builder, pattern| {
+            builder.exclude(pattern)
+        })
+        .apparent_size(apparent_size)
+        .cross_mount_points(cross_mount_points)
+        .include_zero_files(include_zero_files)
+        .include_empty_folders(include_empty_folders)
+        .build();
+
+    if let Some(output) = output {
+        if iterate {
+            return Err(PyValueError::new_err(
+                "output cannot be combined with iterate",
+            ));
+        }
+        let stats = py.allow_threads(|| {
+            write_atomically(&output, |writer| scan_to_writer(&path, &options, writer))
+        })?;
+        let counters = PyDict::new(py);
+        counters.set_item("errors", stats.errors)?;
+        counters.set_item("vanished", stats.vanished)?;
+        counters.set_item("hard_links", stats.hard_links)?;
+        counters.set_item("hard_link_bytes", stats.hard_link_bytes)?;
+        return counters.into_py_any(py);
+    }
+
+    if iterate {
+        let entries = ScanEntries {
+            iter: Mutex::new(ScanIter::new(&path, &options)),
+        };
+        return entries.into_py_any(py);
+    }
+
+    let root = py.allow_threads(|| scan_to_tree(&path, &options))?;
+    node_to_dict(py, &root)?.into_py_any(py)
+}
+
+/// Writes to a temporary file next to `output` and moves it there once `write` succeeded,
+/// so that a failed scan leaves no partial dump behind, nor replaces an earlier one.
+fn write_atomically<T>(
+    output: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut temp_path = output.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut writer = BufWriter::new(File::create(&temp_path).map_err(Error::Output)?);
+    let result = write(&mut writer).and_then(|value| {
+        writer.flush().map_err(Error::Output)?;
+        Ok(value)
+    });
+    drop(writer);
+    match result {
+        Ok(value) => {
+            fs::rename(&temp_path, output).map_err(Error::Output)?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Iterator over the entries of a scan, returned by `scan(path, iterate=True)`.
+///
+/// Entries that cannot be read are reported as `RuntimeWarning`s and skipped.
+#[pyclass(module = "gpscan")]
+struct ScanEntries {
+    iter: Mutex<ScanIter>,
+}
+
+#[pymethods]
+impl ScanEntries {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        loop {
+            let item = py.allow_threads(|| self.iter.lock().unwrap().next());
+            match item {
+                None => return Ok(None),
+                Some(Ok(entry)) => return entry_to_dict(py, &entry).map(Some),
+                Some(Err(Error::Io(e))) if e.get_ref().is_some_and(|e| e.is::<SkippedEntry>()) => {
+                    let message = CString::new(e.to_string()).unwrap_or_default();
+                    let category = py.get_type::<PyRuntimeWarning>();
+                    PyErr::warn(py, &category, &message, 1)?;
+                }
+                Some(Err(e)) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::RootNotFound(_) => PyFileNotFoundError::new_err(e.to_string()),
+            Error::InvalidOptions(_) | Error::InvalidRoot(_) => {
+                PyValueError::new_err(e.to_string())
+            }
+            Error::Io(e) => e.into(),
+            e => PyOSError::new_err(e.to_string()),
+        }
+    }
+}
+
+fn node_to_dict<'py>(py: Python<'py>, node: &FileNode) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("name", &node.name)?;
+    dict.set_item("kind", if node.is_folder() { "folder" } else { "file" })?;
+    dict.set_item("size", node.total_size())?;
+    dict.set_item("modified", node.modified)?;
+    if node.is_folder() {
+        let children = PyList::empty(py);
+        for child in &node.children {
+            children.append(node_to_dict(py, child)?)?;
+        }
+        dict.set_item("children", children)?;
+    }
+    Ok(dict)
+}
+
+fn entry_to_dict<'py>(py: Python<'py>, entry: &ScanEntry) -> PyResult<Bound<'py, PyDict>> {
+    let kind = match entry.metadata.kind {
+        EntryKind::File => "file",
+        EntryKind::Dir => "folder",
+        EntryKind::Symlink => "symlink",
+        EntryKind::Junction => "junction",
+        EntryKind::Other => "other",
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("path", &entry.path)?;
+    dict.set_item("name", &entry.name)?;
+    dict.set_item("depth", entry.depth)?;
+    dict.set_item("kind", kind)?;
+    dict.set_item("size", entry.size)?;
+    dict.set_item("apparent_size", entry.metadata.apparent_size)?;
+    dict.set_item(
+        "modified",
+        entry.metadata.modified.map(DateTime::<Utc>::from),
+    )?;
+    Ok(dict)
+}
+
+#[pymodule]
+#[pyo3(name = "gpscan")]
+fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(scan, m)?)?;
+    m.add_class::<ScanEntries>()?;
+    Ok(())
+}
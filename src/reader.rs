@@ -0,0 +1,260 @@
+// External crates
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+// Standard library imports
+use std::io::BufRead;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::filters::SkipReason;
+use crate::model::{Consistency, FileNode, ScanInfo, ScanResult};
+use crate::validate::open_dump;
+use crate::xml_output::{skipped_attribute, DATETIME_FORMAT};
+
+const TAG_SCAN_INFO: &[u8] = b"ScanInfo";
+const TAG_SCAN_SUMMARY: &[u8] = b"ScanSummary";
+const TAG_FOLDER: &[u8] = b"Folder";
+const TAG_FILE: &[u8] = b"File";
+
+/// Reads a scan dump (plain or gzip-compressed) into memory.
+///
+/// Dumps written by GrandPerspective itself are supported as well. Unknown
+/// elements are ignored, and unknown attributes of `Folder` and `File` elements
+/// are kept in [`FileNode::attributes`].
+pub fn read_scan(path: &Path) -> Result<ScanResult> {
+    read_scan_from(open_dump(path)?)
+}
+
+/// Reads a scan dump from an uncompressed stream.
+pub fn read_scan_from<R: BufRead>(input: R) -> Result<ScanResult> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    let mut info = None;
+    let mut stack: Vec<FileNode> = Vec::new();
+    let mut root = None;
+
+    loop {
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|source| Error::Xml { position, source })?;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                match e.name().as_ref() {
+                    TAG_SCAN_INFO => info = Some(parse_scan_info(e, position)?),
+                    TAG_SCAN_SUMMARY => match &mut info {
+                        Some(info) => parse_scan_summary(info, e, position)?,
+                        None => {
+                            return Err(invalid_dump(format!(
+                                "ScanSummary before ScanInfo at byte {}",
+                                position
+                            )))
+                        }
+                    },
+                    TAG_FOLDER => {
+                        let folder = parse_node(e, false, position)?;
+                        if is_empty {
+                            attach(&mut stack, &mut root, folder, position)?;
+                        } else {
+                            stack.push(folder);
+                        }
+                    }
+                    TAG_FILE => {
+                        let file = parse_node(e, true, position)?;
+                        match stack.last_mut() {
+                            Some(parent) => parent.children.push(file),
+                            None => {
+                                return Err(invalid_dump(format!(
+                                    "File outside of a folder at byte {}",
+                                    position
+                                )))
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(ref e) if e.name().as_ref() == TAG_FOLDER => {
+                let folder = stack.pop().ok_or_else(|| {
+                    invalid_dump(format!("Unexpected </Folder> at byte {}", position))
+                })?;
+                attach(&mut stack, &mut root, folder, position)?;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if !stack.is_empty() {
+        return Err(invalid_dump(
+            "Unexpected end of file, the dump is probably truncated",
+        ));
+    }
+
+    Ok(ScanResult {
+        info: info.ok_or_else(|| invalid_dump("Missing ScanInfo element"))?,
+        root: root.ok_or_else(|| invalid_dump("Missing root Folder element"))?,
+    })
+}
+
+/// Adds a completed folder to its parent, or makes it the root.
+fn attach(
+    stack: &mut [FileNode],
+    root: &mut Option<FileNode>,
+    folder: FileNode,
+    position: u64,
+) -> Result<()> {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(folder),
+        None if root.is_none() => *root = Some(folder),
+        None => {
+            return Err(invalid_dump(format!(
+                "More than one root Folder at byte {}",
+                position
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn parse_scan_info(e: &BytesStart, position: u64) -> Result<ScanInfo> {
+    let mut info = ScanInfo {
+        volume_path: String::new(),
+        volume_size: 0,
+        free_space: 0,
+        free_space_after: None,
+        scan_time: DateTime::<Utc>::UNIX_EPOCH,
+        file_size_measure: "physical".to_string(),
+        scan_duration: None,
+        volume_name: None,
+        file_system: None,
+        volume_id: None,
+        snapshot: None,
+        comment: None,
+        errors: None,
+        skipped: None,
+        consistency: None,
+        sample_rate: None,
+    };
+    parse_scan_summary(&mut info, e, position)?;
+    Ok(info)
+}
+
+/// Reads the attributes of `ScanInfo` into `info`, or those of the `ScanSummary` element
+/// that follows the folder tree, with the values only known once the scan completed.
+/// Older dumps have these in `ScanInfo` as well.
+fn parse_scan_summary(info: &mut ScanInfo, e: &BytesStart, position: u64) -> Result<()> {
+    let (mut checked, mut changed) = (None, None);
+    for (key, value) in attributes(e, position)? {
+        match key.as_str() {
+            "volumePath" => info.volume_path = value,
+            "volumeSize" => info.volume_size = parse_u64(&key, &value, position)?,
+            "freeSpace" => info.free_space = parse_u64(&key, &value, position)?,
+            "freeSpaceAfter" => info.free_space_after = Some(parse_u64(&key, &value, position)?),
+            "scanTime" => info.scan_time = parse_time(&value).unwrap_or(info.scan_time),
+            "fileSizeMeasure" => info.file_size_measure = value,
+            "volumeName" => info.volume_name = Some(value),
+            "fileSystem" => info.file_system = Some(value),
+            "volumeId" => info.volume_id = Some(value),
+            "snapshot" => info.snapshot = Some(value),
+            "comment" => info.comment = Some(value),
+            "scanDuration" => {
+                info.scan_duration = value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            }
+            "errors" => info.errors = Some(parse_u64(&key, &value, position)?),
+            "verifiedEntries" => checked = Some(parse_u64(&key, &value, position)?),
+            "changedEntries" => changed = Some(parse_u64(&key, &value, position)?),
+            "sampleRate" => info.sample_rate = value.parse::<f64>().ok(),
+            _ => {
+                let reason = SkipReason::ALL
+                    .into_iter()
+                    .find(|&reason| skipped_attribute(reason) == key);
+                if let Some(reason) = reason {
+                    let count = parse_u64(&key, &value, position)?;
+                    info.skipped
+                        .get_or_insert_with(Default::default)
+                        .set(reason, count);
+                }
+            }
+        }
+    }
+    // The counts are written together, leaving out reasons without skipped entries
+    if info.errors.is_some() {
+        info.skipped.get_or_insert_with(Default::default);
+    }
+    if let Some(checked) = checked {
+        info.consistency = Some(Consistency {
+            checked,
+            changed: changed.unwrap_or(0),
+        });
+    }
+    Ok(())
+}
+
+fn parse_node(e: &BytesStart, is_file: bool, position: u64) -> Result<FileNode> {
+    let mut node = if is_file {
+        FileNode::file(String::new(), 0)
+    } else {
+        FileNode::folder(String::new())
+    };
+
+    for (key, value) in attributes(e, position)? {
+        match key.as_str() {
+            "name" => node.name = value,
+            "size" if is_file => node.size = parse_u64(&key, &value, position)?,
+            "created" => node.created = parse_time(&value),
+            "modified" => node.modified = parse_time(&value),
+            "accessed" => node.accessed = parse_time(&value),
+            _ => node.attributes.push((key, value)),
+        }
+    }
+    Ok(node)
+}
+
+/// Returns the unescaped attributes of an element.
+fn attributes(e: &BytesStart, position: u64) -> Result<Vec<(String, String)>> {
+    e.attributes()
+        .map(|attr| {
+            let attr = attr.map_err(|err| {
+                invalid_dump(format!("Malformed attribute at byte {}: {}", position, err))
+            })?;
+            let value = attr.unescape_value().map_err(|err| {
+                invalid_dump(format!("Malformed attribute at byte {}: {}", position, err))
+            })?;
+            Ok((
+                String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+                value.into_owned(),
+            ))
+        })
+        .collect()
+}
+
+/// Parses a timestamp, accepting RFC 3339 variants used by other writers.
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, DATETIME_FORMAT)
+        .map(|t| t.and_utc())
+        .or_else(|_| DateTime::parse_from_rfc3339(value).map(|t| t.with_timezone(&Utc)))
+        .ok()
+}
+
+fn parse_u64(key: &str, value: &str, position: u64) -> Result<u64> {
+    value.parse::<u64>().map_err(|_| {
+        invalid_dump(format!(
+            "Invalid {} '{}' at byte {}: expected a non-negative integer",
+            key, value, position
+        ))
+    })
+}
+
+fn invalid_dump(message: impl Into<String>) -> Error {
+    Error::InvalidDump(message.into())
+}
@@ -0,0 +1,187 @@
+// Standard library imports
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::classify::{FileKind, KIND_ATTRIBUTE};
+use crate::model::ScanInfo;
+use crate::output::OutputFormatter;
+use crate::scan::ScanEntry;
+
+/// Key of the files that have no extension in an extensions report.
+pub const NO_EXTENSION: &str = "(none)";
+/// Key of the files whose owner is not known in an owners report.
+pub const UNKNOWN_OWNER: &str = "(unknown)";
+
+/// What the files of a report are grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportKind {
+    /// The lowercase file extension, such as `mp4` (the last one for `.tar.gz`).
+    Extensions,
+    /// The user name of the owner, or the user ID if it has no name.
+    Owners,
+    /// The [`FileKind`], from the `kind` attribute or else the extension.
+    Kinds,
+}
+
+impl ReportKind {
+    /// Returns the name of the report, as used on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportKind::Extensions => "extensions",
+            ReportKind::Owners => "owners",
+            ReportKind::Kinds => "kinds",
+        }
+    }
+
+    /// Returns the group of the file `entry`, looking up user names in `users`.
+    fn key(&self, entry: &ScanEntry, users: &mut HashMap<u32, String>) -> String {
+        match self {
+            ReportKind::Extensions => Path::new(&entry.name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| NO_EXTENSION.to_string()),
+            ReportKind::Owners => match entry.metadata.owner {
+                Some(uid) => users.entry(uid).or_insert_with(|| user_name(uid)).clone(),
+                None => UNKNOWN_OWNER.to_string(),
+            },
+            ReportKind::Kinds => entry
+                .attributes
+                .iter()
+                .find(|(key, _)| key == KIND_ATTRIBUTE)
+                .map(|(_, kind)| kind.clone())
+                .unwrap_or_else(|| FileKind::from_name(&entry.name).name().to_string()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> String {
+    uzers::get_user_by_uid(uid)
+        .map(|user| user.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+#[cfg(not(unix))]
+fn user_name(uid: u32) -> String {
+    uid.to_string()
+}
+
+impl FromStr for ReportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "extensions" => Ok(ReportKind::Extensions),
+            "owners" => Ok(ReportKind::Owners),
+            "kinds" => Ok(ReportKind::Kinds),
+            _ => Err(format!("invalid report: {}", s)),
+        }
+    }
+}
+
+/// Total size and number of the files in one group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportRow {
+    pub key: String,
+    /// Total size in bytes.
+    pub size: u64,
+    pub files: u64,
+}
+
+/// Sizes of the scanned files, grouped by a [`ReportKind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub kind: ReportKind,
+    /// Groups with the largest total size first, then by key.
+    pub rows: Vec<ReportRow>,
+}
+
+/// Collects [`Report`]s while passing everything on to another formatter.
+///
+/// ```
+/// use gpscan::{scan_with_formatter, Options, ReportCollector, ReportKind, XmlFormatter};
+///
+/// let formatter = XmlFormatter::new(std::io::sink());
+/// let mut collector = ReportCollector::new(formatter, &[ReportKind::Extensions]);
+/// scan_with_formatter(std::path::Path::new("src"), &Options::default(), &mut collector)?;
+/// for row in &collector.reports()[0].rows {
+///     println!("{}: {} bytes in {} files", row.key, row.size, row.files);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ReportCollector<F> {
+    inner: F,
+    groups: Vec<(ReportKind, HashMap<String, ReportRow>)>,
+    // User names by user ID, looked up once per owner
+    users: HashMap<u32, String>,
+}
+
+impl<F: OutputFormatter> ReportCollector<F> {
+    pub fn new(inner: F, kinds: &[ReportKind]) -> Self {
+        ReportCollector {
+            inner,
+            groups: kinds.iter().map(|&kind| (kind, HashMap::new())).collect(),
+            users: HashMap::new(),
+        }
+    }
+
+    /// Returns the reports collected so far, in the order of the kinds given to [`new`](Self::new).
+    pub fn reports(&self) -> Vec<Report> {
+        self.groups
+            .iter()
+            .map(|(kind, groups)| {
+                let mut rows: Vec<ReportRow> = groups.values().cloned().collect();
+                rows.sort_by(|a, b| {
+                    Reverse(a.size)
+                        .cmp(&Reverse(b.size))
+                        .then_with(|| a.key.cmp(&b.key))
+                });
+                Report { kind: *kind, rows }
+            })
+            .collect()
+    }
+
+    /// Returns the wrapped formatter.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: OutputFormatter> OutputFormatter for ReportCollector<F> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.begin_scan(info)
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.begin_folder(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        for (kind, groups) in &mut self.groups {
+            let key = kind.key(entry, &mut self.users);
+            let row = groups.entry(key.clone()).or_insert(ReportRow {
+                key,
+                size: 0,
+                files: 0,
+            });
+            row.size += entry.size;
+            row.files += 1;
+        }
+        self.inner.file(entry)
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.comment(text)
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.end_scan(info)
+    }
+}
@@ -0,0 +1,537 @@
+// External crates
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use sha2::{Digest, Sha256};
+
+// Standard library imports
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::model::{ScanInfo, ScanResult};
+use crate::options::Options;
+use crate::scan::scan_to_tree;
+use crate::source::{CustomSource, MemorySource};
+
+/// SHA-256 of an empty request body.
+const EMPTY_PAYLOAD_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// A bucket and key prefix, written as `s3://bucket/prefix`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Location {
+    pub bucket: String,
+    /// Key prefix without a trailing slash, or an empty string for the whole bucket.
+    pub prefix: String,
+}
+
+impl FromStr for S3Location {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("s3://")
+            .ok_or_else(|| format!("invalid S3 URL (expected s3://bucket/prefix): {}", s))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(format!("missing bucket name in S3 URL: {}", s));
+        }
+        Ok(S3Location {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+}
+
+impl fmt::Display for S3Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.prefix.is_empty() {
+            write!(f, "s3://{}", self.bucket)
+        } else {
+            write!(f, "s3://{}/{}", self.bucket, self.prefix)
+        }
+    }
+}
+
+/// Access key used to sign requests.
+#[derive(Clone)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Connection settings for S3 or an S3-compatible object storage.
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub region: String,
+    /// Endpoint of an S3-compatible service, such as `http://localhost:9000`.
+    /// Buckets are then addressed in path style.
+    pub endpoint: Option<String>,
+    /// Requests are sent unsigned without credentials, which works for public buckets.
+    pub credentials: Option<S3Credentials>,
+}
+
+impl S3Config {
+    /// Reads the settings from the standard AWS environment variables
+    /// (`AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_SESSION_TOKEN`,
+    /// `AWS_REGION` or `AWS_DEFAULT_REGION`, and `AWS_ENDPOINT_URL`).
+    pub fn from_env() -> Self {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+        let credentials = match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key_id), Some(secret_access_key)) => Some(S3Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+            }),
+            _ => None,
+        };
+        S3Config {
+            region: var("AWS_REGION")
+                .or_else(|| var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| DEFAULT_REGION.to_string()),
+            endpoint: var("AWS_ENDPOINT_URL"),
+            credentials,
+        }
+    }
+}
+
+/// An object listed in a bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Object {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Lists all objects below `location` with the ListObjectsV2 API.
+pub fn list_objects(location: &S3Location, config: &S3Config) -> io::Result<Vec<S3Object>> {
+    let prefix = if location.prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", location.prefix)
+    };
+
+    let mut objects = Vec::new();
+    let mut continuation_token = None;
+    loop {
+        let mut query = vec![
+            ("list-type".to_string(), "2".to_string()),
+            ("prefix".to_string(), prefix.clone()),
+        ];
+        if let Some(token) = continuation_token.take() {
+            query.push(("continuation-token".to_string(), token));
+        }
+
+        let body = send("GET", &location.bucket, "", &query, None, config)?;
+        let page = parse_list_response(&body)?;
+        objects.extend(page.objects);
+        match page.next_continuation_token {
+            Some(token) if page.is_truncated => continuation_token = Some(token),
+            _ => break,
+        }
+    }
+    Ok(objects)
+}
+
+/// Builds a directory tree from object keys, with folders for the `/`-separated
+/// key prefixes below `location`.
+pub fn s3_source(location: &S3Location, objects: &[S3Object]) -> MemorySource {
+    let root = PathBuf::from(location.to_string());
+    let mut source = MemorySource::new(&root);
+
+    for object in objects {
+        let relative = object
+            .key
+            .strip_prefix(location.prefix.as_str())
+            .unwrap_or(&object.key);
+        let components: Vec<&str> = relative.split('/').filter(|c| !c.is_empty()).collect();
+        if components.is_empty() {
+            continue;
+        }
+        let path = components.iter().fold(root.clone(), |path, c| path.join(c));
+
+        // Zero-byte keys ending in a slash are folder markers created by consoles
+        let metadata = if object.key.ends_with('/') {
+            source.add_dir(path)
+        } else {
+            source.add_file(path, object.size)
+        };
+        metadata.modified = object.last_modified.map(SystemTime::from);
+    }
+    source
+}
+
+/// Lists the objects below `location` and scans them as a directory tree.
+///
+/// The volume is the bucket; its size is the total size of the listed objects.
+pub fn scan_s3(
+    location: &S3Location,
+    config: &S3Config,
+    options: &Options,
+) -> io::Result<ScanResult> {
+    let scan_time = Utc::now();
+    let objects = list_objects(location, config)?;
+    let source = s3_source(location, &objects);
+
+    let mut options = options.clone();
+    options.source = Some(CustomSource(Arc::new(source)));
+    let root = scan_to_tree(Path::new(&location.to_string()), &options)?;
+
+    Ok(ScanResult {
+        info: ScanInfo {
+            volume_path: format!("s3://{}", location.bucket),
+            volume_size: objects.iter().map(|object| object.size).sum(),
+            free_space: 0,
+            free_space_after: None,
+            scan_time,
+            file_size_measure: "logical".to_string(),
+            scan_duration: Some((Utc::now() - scan_time).to_std().unwrap_or_default()),
+            volume_name: Some(location.bucket.clone()),
+            file_system: Some("s3".to_string()),
+            volume_id: None,
+            snapshot: None,
+            comment: options.comment.clone(),
+            errors: None,
+            skipped: None,
+            consistency: None,
+            sample_rate: None,
+        },
+        root,
+    })
+}
+
+/// Uploads the contents of `file` as the object at `location` (the prefix is the key).
+///
+/// The object is written with a single PUT request, which S3 limits to 5 GiB.
+pub fn put_object(location: &S3Location, config: &S3Config, file: &mut File) -> io::Result<()> {
+    if location.prefix.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("missing object key in S3 URL: {}", location),
+        ));
+    }
+    send(
+        "PUT",
+        &location.bucket,
+        &location.prefix,
+        &[],
+        Some(file),
+        config,
+    )
+    .map(|_| ())
+}
+
+/// Sends a signed request for the bucket, or for the object `key` if it is not
+/// empty, and returns the response body.
+fn send(
+    method: &str,
+    bucket: &str,
+    key: &str,
+    query: &[(String, String)],
+    body: Option<&mut File>,
+    config: &S3Config,
+) -> io::Result<String> {
+    let object_path = if key.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", uri_encode(key, false))
+    };
+    // Bucket names with dots do not match the wildcard certificate of virtual hosts
+    let (base, host, uri) = match &config.endpoint {
+        Some(endpoint) => {
+            let endpoint = endpoint.trim_end_matches('/');
+            let host = endpoint
+                .split_once("://")
+                .map_or(endpoint, |(_, host)| host)
+                .to_string();
+            (
+                endpoint.to_string(),
+                host,
+                format!("/{}{}", uri_encode(bucket, false), object_path),
+            )
+        }
+        None if bucket.contains('.') => {
+            let host = format!("s3.{}.amazonaws.com", config.region);
+            (
+                format!("https://{}", host),
+                host,
+                format!("/{}{}", uri_encode(bucket, false), object_path),
+            )
+        }
+        None => {
+            let host = format!("{}.s3.{}.amazonaws.com", bucket, config.region);
+            let uri = if object_path.is_empty() {
+                "/".to_string()
+            } else {
+                object_path
+            };
+            (format!("https://{}", host), host, uri)
+        }
+    };
+
+    let mut query: Vec<(String, String)> = query
+        .iter()
+        .map(|(key, value)| (uri_encode(key, true), uri_encode(value, true)))
+        .collect();
+    query.sort();
+    let query = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let url = if query.is_empty() {
+        format!("{}{}", base, uri)
+    } else {
+        format!("{}{}?{}", base, uri, query)
+    };
+    let mut request = ureq::request(method, &url);
+
+    let payload_hash = match &body {
+        Some(file) => hash_file(file)?,
+        None => EMPTY_PAYLOAD_HASH.to_string(),
+    };
+    if let Some(credentials) = &config.credentials {
+        let headers = sign(
+            method,
+            &host,
+            &uri,
+            &query,
+            &payload_hash,
+            config,
+            credentials,
+            Utc::now(),
+        );
+        for (name, value) in headers {
+            request = request.set(name, &value);
+        }
+    }
+
+    let response = match body {
+        Some(file) => {
+            let length = file.metadata()?.len();
+            request
+                .set("Content-Length", &length.to_string())
+                .send(file)
+        }
+        None => request.call(),
+    };
+    match response {
+        Ok(response) => response.into_string(),
+        Err(ureq::Error::Status(status, response)) => {
+            let kind = match status {
+                403 => io::ErrorKind::PermissionDenied,
+                404 => io::ErrorKind::NotFound,
+                _ => io::ErrorKind::Other,
+            };
+            let body = response.into_string().unwrap_or_default();
+            let message = error_message(&body).unwrap_or_else(|| format!("HTTP status {}", status));
+            Err(io::Error::new(
+                kind,
+                format!("S3 request failed: {}", message),
+            ))
+        }
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+/// Returns the hex-encoded SHA-256 of the file contents, leaving the file at its start.
+fn hash_file(mut file: &File) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    file.seek(SeekFrom::Start(0))?;
+    io::copy(&mut file, &mut hasher)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Returns the headers of an AWS Signature Version 4 signed request.
+#[allow(clippy::too_many_arguments)]
+fn sign(
+    method: &str,
+    host: &str,
+    uri: &str,
+    query: &str,
+    payload_hash: &str,
+    config: &S3Config,
+    credentials: &S3Credentials,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let mut headers = vec![
+        ("host", host.to_string()),
+        ("x-amz-content-sha256", payload_hash.to_string()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method, uri, query, canonical_headers, signed_headers, payload_hash
+    );
+
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, config.region.as_bytes());
+    let key = hmac_sha256(&key, b"s3");
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    // The host header is set by the HTTP client
+    headers.retain(|(name, _)| *name != "host");
+    headers.push((
+        "authorization",
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    headers
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes everything except unreserved characters, and `/` unless `encode_slash` is set.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// One page of a ListObjectsV2 response.
+#[derive(Default)]
+struct ListPage {
+    objects: Vec<S3Object>,
+    is_truncated: bool,
+    next_continuation_token: Option<String>,
+}
+
+fn parse_list_response(body: &str) -> io::Result<ListPage> {
+    let mut reader = Reader::from_str(body);
+    let mut page = ListPage::default();
+    let mut object: Option<S3Object> = None;
+    let mut element = Vec::new();
+
+    loop {
+        match reader.read_event().map_err(invalid_data)? {
+            Event::Start(e) => {
+                element = e.name().as_ref().to_vec();
+                if element == b"Contents" {
+                    object = Some(S3Object {
+                        key: String::new(),
+                        size: 0,
+                        last_modified: None,
+                    });
+                }
+            }
+            Event::Text(text) => {
+                let text = text.unescape().map_err(invalid_data)?;
+                match (element.as_slice(), object.as_mut()) {
+                    (b"Key", Some(object)) => object.key = text.into_owned(),
+                    (b"Size", Some(object)) => {
+                        object.size = text.trim().parse().map_err(invalid_data)?
+                    }
+                    (b"LastModified", Some(object)) => {
+                        object.last_modified = DateTime::parse_from_rfc3339(text.trim())
+                            .ok()
+                            .map(|t| t.with_timezone(&Utc))
+                    }
+                    (b"IsTruncated", _) => page.is_truncated = text.trim() == "true",
+                    (b"NextContinuationToken", _) => {
+                        page.next_continuation_token = Some(text.into_owned())
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                if e.name().as_ref() == b"Contents" {
+                    page.objects.extend(object.take());
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(page)
+}
+
+/// Extracts the message of an S3 error response.
+fn error_message(body: &str) -> Option<String> {
+    let mut reader = Reader::from_str(body);
+    let (mut code, mut message) = (None, None);
+    let mut element = Vec::new();
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) => element = e.name().as_ref().to_vec(),
+            Event::Text(text) => {
+                let text = text.unescape().ok()?.into_owned();
+                match element.as_slice() {
+                    b"Code" => code = Some(text),
+                    b"Message" => message = Some(text),
+                    _ => {}
+                }
+            }
+            Event::End(_) => element.clear(),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    match (code, message) {
+        (Some(code), Some(message)) => Some(format!("{}: {}", code, message)),
+        (code, message) => code.or(message),
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
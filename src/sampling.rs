@@ -0,0 +1,67 @@
+// Standard library imports
+use std::path::{Component, Path, PathBuf};
+
+/// Content-defined sample of the files of a scan, with `--sample`.
+///
+/// A file is in the sample if a hash of its path below the scan root falls below the
+/// rate, so that every scan of the same tree samples the same files, wherever the tree is
+/// mounted. The sizes of the sampled files are scaled up by the inverse of the probability
+/// that they are sampled, which makes the folder totals unbiased estimates of the real ones.
+#[derive(Debug)]
+pub(crate) struct Sampler {
+    root: PathBuf,
+    rate: f64,
+    /// Hashes below this are in the sample.
+    threshold: u64,
+}
+
+impl Sampler {
+    /// Creates a sampler of the files below `root`, keeping about `rate` of them.
+    pub(crate) fn new(root: &Path, rate: f64) -> Self {
+        let rate = rate.clamp(f64::MIN_POSITIVE, 1.0);
+        Sampler {
+            root: root.to_path_buf(),
+            rate,
+            // Saturates at u64::MAX for a rate of 1
+            threshold: (rate * u64::MAX as f64) as u64,
+        }
+    }
+
+    /// Returns whether the file at `path` is in the sample.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        self.rate >= 1.0
+            || path_hash(path.strip_prefix(&self.root).unwrap_or(path)) < self.threshold
+    }
+
+    /// Returns the estimated total size of the files that a sampled file of `size` with
+    /// `links` hard links stands for.
+    ///
+    /// A file with several links is counted once if any of its links is sampled, which is
+    /// more likely than for a single link, so it is scaled up less.
+    pub(crate) fn scale(&self, size: u64, links: u64) -> u64 {
+        let links = i32::try_from(links.max(1)).unwrap_or(i32::MAX);
+        let probability = 1.0 - (1.0 - self.rate).powi(links);
+        (size as f64 / probability).round() as u64
+    }
+}
+
+/// Hashes a relative path with FNV-1a, joining its components with `/` on every platform.
+fn path_hash(path: &Path) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET;
+    for (i, component) in path.components().enumerate() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        let separator: &[u8] = if i > 0 { b"/" } else { b"" };
+        for &byte in separator.iter().chain(name.as_encoded_bytes()) {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(PRIME);
+        }
+    }
+    // FNV spreads the last bytes poorly, which would keep `a1` and `a2` together
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^ (hash >> 33)
+}
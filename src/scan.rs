@@ -0,0 +1,1912 @@
+// External crates
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use sysinfo::Disks;
+
+// Standard library imports
+use std::borrow::Cow;
+use std::cmp;
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::archive::{filter_members, read_archive, ArchiveFormat};
+use crate::classify::{classify, uniform_type, ClassifyMode, KIND_ATTRIBUTE};
+use crate::error::{Error, Result};
+use crate::error_policy::ErrorAction;
+use crate::filters::{self, SkipCounts, SkipReason};
+use crate::inode_filter::{BloomFilter, InodeFilter};
+use crate::model::{Consistency, FileNode, ScanInfo, ScanResult};
+use crate::options::{CustomAttributes, Options, PlaceholderMode, RootName, SortOrder};
+use crate::output::{node_entry, FolderTotals, OutputFormatter, SizeOrder};
+use crate::platform::allocation_unit;
+use crate::progress::Progress;
+use crate::sampling::Sampler;
+use crate::source::{EntryKind, EntryMetadata};
+use crate::top::TopN;
+use crate::volume::{
+    file_system_types, get_volume_info, is_btrfs_subvolume, is_network_file_system, mount_table,
+};
+use crate::xml_output::{
+    is_xml_name, XmlFormatter, ATTR_PATH, ATTR_UNIFORM_TYPE, ENTRY_ATTRIBUTES,
+};
+
+/// Counters collected while traversing the directory tree.
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    /// Number of entries skipped because they could not be read.
+    pub errors: u64,
+    /// Number of entries removed between listing their directory and reading them, which
+    /// changed during the scan and are left out without counting as errors.
+    pub vanished: u64,
+    /// True if the scan was stopped early through a cancellation token.
+    pub cancelled: bool,
+    /// True if the scan was stopped early because the error limit was reached
+    /// (see [`OptionsBuilder::max_errors`](crate::OptionsBuilder::max_errors)).
+    pub too_many_errors: bool,
+    /// Number of files skipped because they are hard links to a file that was already counted.
+    pub hard_links: u64,
+    /// Number of files left out of the sample
+    /// (see [`OptionsBuilder::sample`](crate::OptionsBuilder::sample)).
+    pub unsampled: u64,
+    /// Number of folders copied from the previous scan instead of being read
+    /// (see [`OptionsBuilder::trust_dir_mtime`](crate::OptionsBuilder::trust_dir_mtime)).
+    pub cached_folders: u64,
+    /// Size of the skipped hard links, which would have been counted again without
+    /// the hard link check (according to the selected size measure).
+    pub hard_link_bytes: u64,
+    /// Number of entries left out of the scan, for each rule of [`crate::filters`].
+    pub skipped: SkipCounts,
+    /// Depth and breadth of the scanned tree.
+    pub shape: TreeShape,
+    /// The directories whose subtrees took the longest to scan, slowest first
+    /// (see [`OptionsBuilder::profile_dirs`](crate::OptionsBuilder::profile_dirs)).
+    pub slowest_dirs: Vec<DirTime>,
+    /// How many of the entries checked again after the scan had changed
+    /// (see [`OptionsBuilder::verify`](crate::OptionsBuilder::verify)).
+    pub consistency: Option<Consistency>,
+}
+
+/// Time spent scanning a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirTime {
+    pub path: PathBuf,
+    /// Wall time of the whole subtree, from reading the directory until its last entry.
+    pub total: Duration,
+    /// Wall time not spent in subdirectories: reading the directory and the metadata of its files.
+    pub own: Duration,
+}
+
+/// Structural statistics of a scanned tree, to diagnose trees that are slow to scan.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TreeShape {
+    /// Number of folders and files reported.
+    pub entries: u64,
+    /// Sum of the depths of the reported entries, for [`average_depth`](Self::average_depth).
+    pub depth_total: u64,
+    /// Depth of the deepest entry; the root has depth 0.
+    pub max_depth: usize,
+    /// Directory with the most entries, and their number, including skipped entries.
+    pub widest_dir: Option<(PathBuf, u64)>,
+    /// Longest path of a reported entry, measured in bytes.
+    pub longest_path: Option<PathBuf>,
+}
+
+impl TreeShape {
+    /// Returns the mean depth of the reported entries.
+    pub fn average_depth(&self) -> f64 {
+        if self.entries == 0 {
+            return 0.0;
+        }
+        self.depth_total as f64 / self.entries as f64
+    }
+
+    fn record_entry(&mut self, entry: &ScanEntry) {
+        self.entries += 1;
+        self.depth_total += entry.depth as u64;
+        self.max_depth = self.max_depth.max(entry.depth);
+        let longer = self
+            .longest_path
+            .as_ref()
+            .is_none_or(|longest| entry.path.as_os_str().len() > longest.as_os_str().len());
+        if longer {
+            self.longest_path = Some(entry.path.clone());
+        }
+    }
+
+    fn record_dir_entries(&mut self, path: &Path, count: u64) {
+        if self
+            .widest_dir
+            .as_ref()
+            .is_none_or(|(_, widest)| count > *widest)
+        {
+            self.widest_dir = Some((path.to_path_buf(), count));
+        }
+    }
+}
+
+impl fmt::Display for TreeShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "max depth {}, average depth {:.1}",
+            self.max_depth,
+            self.average_depth()
+        )?;
+        if let Some((path, count)) = &self.widest_dir {
+            write!(f, ", widest folder {} ({} entries)", path.display(), count)?;
+        }
+        if let Some(path) = &self.longest_path {
+            write!(
+                f,
+                ", longest path {} ({} bytes)",
+                path.display(),
+                path.as_os_str().len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A file or folder found during traversal.
+#[derive(Debug, Clone)]
+pub struct ScanEntry {
+    /// Full path of the entry.
+    pub path: PathBuf,
+    /// Name written to the output. The root folder is named after the path given by the user.
+    pub name: String,
+    /// Depth below the scan root, which has depth 0.
+    pub depth: usize,
+    /// Size of a file in bytes, according to the selected size measure. Always 0 for folders.
+    pub size: u64,
+    /// Metadata of the entry (not following symbolic links).
+    pub metadata: EntryMetadata,
+    /// Additional attributes to record, such as `unreadable="true"`.
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Receives the entries found during traversal, in depth-first order.
+///
+/// Entries that are skipped by the options (excluded names, zero-byte files,
+/// hard links, other filesystems, ...) are not reported. Returning an error from
+/// any callback aborts the scan.
+pub trait ScanVisitor {
+    /// Called when a folder is entered, before any of its children.
+    fn enter_dir(&mut self, _entry: &ScanEntry) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called for every file that is not skipped.
+    fn file(&mut self, _entry: &ScanEntry) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called after all children of the most recently entered folder.
+    fn leave_dir(&mut self, _entry: &ScanEntry) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called when an entry is skipped because it could not be read.
+    fn error(&mut self, _path: &Path, _error: &io::Error) {}
+
+    /// Called with a diagnostic about the current position in the tree, such as a
+    /// skipped entry, if [`embed_warnings`](crate::OptionsBuilder::embed_warnings) is set.
+    fn comment(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<V: ScanVisitor + ?Sized> ScanVisitor for &mut V {
+    fn enter_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        (**self).enter_dir(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        (**self).file(entry)
+    }
+
+    fn leave_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        (**self).leave_dir(entry)
+    }
+
+    fn error(&mut self, path: &Path, error: &io::Error) {
+        (**self).error(path, error)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        (**self).comment(text)
+    }
+}
+
+/// Mutable state shared across the traversal.
+#[derive(Default)]
+struct ScanState {
+    /// Files already written, used to skip hard links.
+    visited_inodes: HashSet<(u64, u64)>,
+    /// Files with several links already written, with `InodeFilter::Bloom`.
+    inode_bloom: Option<BloomFilter>,
+    /// The files to scan, with `sample`.
+    sampler: Option<Sampler>,
+    /// Directories already entered, used to detect cycles.
+    visited_dirs: HashSet<(u64, u64)>,
+    /// The directory being traversed and its ancestors, outermost first.
+    open_dirs: Vec<(u64, u64)>,
+    memory_mode: MemoryMode,
+    stats: ScanStats,
+    /// File system types by device ID, looked up when the first directory is entered.
+    file_systems: Option<HashMap<u64, String>>,
+    /// Mount points and their file system types, read when the first directory on
+    /// another device is entered.
+    mount_table: Option<Vec<(PathBuf, String)>>,
+    /// Devices of the btrfs subvolumes entered so far, which belong to the
+    /// file system of the root despite their own device ID.
+    subvolume_devices: HashSet<u64>,
+    /// Devices on network file systems that were already warned about.
+    network_devices: HashSet<u64>,
+    /// Keys of user-supplied attributes that were left out and warned about.
+    rejected_attributes: HashSet<String>,
+    /// Allocation units by device ID, looked up with `allocation_unit`.
+    allocation_units: HashMap<u64, Option<u64>>,
+    /// Folders and files reported so far, for progress updates.
+    folders: u64,
+    files: u64,
+    bytes: u64,
+    last_progress: Option<Instant>,
+    /// When the skipped entries that are no longer logged one by one were last summarized.
+    last_skip_summary: Option<Instant>,
+    /// Entries to check again after the scan, with `verify`.
+    verify_sample: VerifySample,
+    /// Slowest directories with `profile_dirs`, by subtree time, then path.
+    dir_times: Option<TopN<(Duration, Reverse<PathBuf>, Duration)>>,
+    /// Time spent in the subdirectories of each directory being traversed.
+    child_times: Vec<Duration>,
+}
+
+/// Approximate memory used by each entry of the visited sets, including the spare
+/// capacity of the hash tables.
+const VISITED_ENTRY_BYTES: u64 = 40;
+
+/// Number of entries skipped for each reason that are logged one by one, unless
+/// [`log_each_skip`](crate::OptionsBuilder::log_each_skip) is set.
+const LOGGED_SKIPS: u64 = 10;
+
+/// Interval between the summaries of the skipped entries that are not logged one by one.
+const SKIP_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A uniform random sample of the scanned entries, kept by reservoir sampling.
+#[derive(Default)]
+struct VerifySample {
+    /// Path, apparent size, and modification time of the sampled entries.
+    entries: Vec<(PathBuf, u64, Option<SystemTime>)>,
+    /// Entries offered so far.
+    offered: u64,
+    rng: u64,
+}
+
+impl VerifySample {
+    /// Offers an entry, which is kept in a sample of `capacity` entries with the same
+    /// probability as every other entry offered.
+    fn offer(&mut self, capacity: usize, path: &Path, metadata: &EntryMetadata) {
+        self.offered += 1;
+        let entry = (
+            path.to_path_buf(),
+            metadata.apparent_size,
+            metadata.modified,
+        );
+        if self.entries.len() < capacity {
+            self.entries.push(entry);
+            return;
+        }
+        let index = self.next_random() % self.offered;
+        if index < capacity as u64 {
+            self.entries[index as usize] = entry;
+        }
+    }
+
+    /// Returns the next number of a xorshift generator, seeded by the standard library.
+    fn next_random(&mut self) -> u64 {
+        if self.rng == 0 {
+            self.rng = RandomState::new().build_hasher().finish() | 1;
+        }
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+/// How hard links and loops are detected, from the most to the least memory used
+/// (see [`OptionsBuilder::max_memory`](crate::OptionsBuilder::max_memory)).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MemoryMode {
+    /// Every file and directory is remembered.
+    #[default]
+    Full,
+    /// Only files with several links are remembered, and loops are detected among
+    /// the ancestors of a directory.
+    Reduced,
+    /// Hard links are no longer detected.
+    Minimal,
+}
+
+impl ScanState {
+    fn visited_bytes(&self) -> u64 {
+        (self.visited_inodes.len() + self.visited_dirs.len()) as u64 * VISITED_ENTRY_BYTES
+    }
+}
+
+/// Returns the (device, inode) pair identifying the entry on this machine.
+///
+/// An inode of 0 means the platform could not provide one, so a unique key is
+/// generated to keep such entries from being treated as duplicates.
+fn file_id(metadata: &EntryMetadata) -> (u64, u64) {
+    static UNKNOWN: AtomicU64 = AtomicU64::new(0);
+
+    match metadata.inode {
+        0 => (u64::MAX, UNKNOWN.fetch_add(1, Ordering::Relaxed)),
+        inode => (metadata.device, inode),
+    }
+}
+
+/// Scans the directory at `root_path` and writes a GrandPerspective scan dump to `writer`.
+///
+/// Entries that cannot be read are logged and skipped; their number is returned in
+/// the statistics. Errors are only returned if the root does not exist
+/// ([`Error::RootNotFound`]), is not a readable directory or file ([`Error::InvalidRoot`],
+/// [`Error::Io`]), or the output cannot be written ([`Error::Io`]).
+pub fn scan<W: Write>(root_path: &Path, options: &Options, writer: W) -> Result<ScanStats> {
+    let mut formatter = XmlFormatter::new(writer).strict_names(options.strict_names);
+    if let Some(timestamps) = options.timestamps {
+        formatter = formatter.timestamps(timestamps);
+    }
+    scan_with_formatter(root_path, options, formatter)
+}
+
+/// Scans the directory at `root_path` and writes the result with `formatter`.
+///
+/// Fails with [`Error::InvalidOptions`] if the options cannot be combined.
+pub fn scan_with_formatter<F: OutputFormatter>(
+    root_path: &Path,
+    options: &Options,
+    formatter: F,
+) -> Result<ScanStats> {
+    let by_size = options.sort_order == SortOrder::Size;
+    if options.max_memory.is_some() && (options.folder_totals || by_size) {
+        return Err(Error::InvalidOptions(
+            "The memory limit cannot be combined with folder totals or sorting by size, which hold the tree in memory".to_string(),
+        ));
+    }
+    match (options.folder_totals, by_size) {
+        (true, true) => scan_into(
+            root_path,
+            options,
+            SizeOrder::new(FolderTotals::new(formatter)),
+        ),
+        (true, false) => scan_into(root_path, options, FolderTotals::new(formatter)),
+        (false, true) => scan_into(root_path, options, SizeOrder::new(formatter)),
+        (false, false) => scan_into(root_path, options, formatter),
+    }
+}
+
+fn scan_into<F: OutputFormatter>(
+    root_path: &Path,
+    options: &Options,
+    mut formatter: F,
+) -> Result<ScanStats> {
+    // Get the device ID of the root directory
+    let root = scan_root(root_path, options)?;
+    let (root_path, options, root_dev) = (root.path.as_ref(), root.options.as_ref(), root.device);
+
+    // Create Disks instance and refresh disk list
+    let mut disks = Disks::new_with_refreshed_list();
+
+    let mut info = scan_info(root_path, options, &disks);
+    formatter.begin_scan(&info)?;
+
+    // Start traversing the directory with new options
+    let scan_start = Instant::now();
+    let stats = if options.writer_thread {
+        walk_in_background(root_path, root_dev, options, &mut formatter)?
+    } else {
+        walk(
+            root_path,
+            root_dev,
+            options,
+            &mut FormatterVisitor(&mut formatter),
+        )?
+    };
+    info.scan_duration = Some(scan_start.elapsed());
+    info.errors = Some(stats.errors);
+    info.skipped = Some(stats.skipped.clone());
+    info.consistency = stats.consistency;
+
+    // Measure the free space again, which may have changed during a long scan
+    disks.refresh();
+    info.free_space_after = Some(get_volume_info(root_path, &disks).free_space);
+
+    formatter.end_scan(&info)?;
+
+    Ok(stats)
+}
+
+/// Describes the volume of `root_path` and the size measure, as of now.
+pub(crate) fn scan_info(root_path: &Path, options: &Options, disks: &Disks) -> ScanInfo {
+    let volume = get_volume_info(root_path, disks);
+
+    ScanInfo {
+        volume_path: volume.path,
+        volume_size: volume.size,
+        free_space: volume.free_space,
+        free_space_after: None,
+        scan_time: Utc::now(),
+        file_size_measure: if options.apparent_size {
+            "logical"
+        } else {
+            "physical"
+        }
+        .to_string(),
+        scan_duration: None,
+        volume_name: volume.name,
+        file_system: volume.file_system,
+        volume_id: volume.id,
+        snapshot: None,
+        comment: options.comment.clone(),
+        errors: None,
+        skipped: None,
+        consistency: None,
+        sample_rate: options.sample,
+    }
+}
+
+/// Scans the directory at `root_path` and returns the folder tree in memory.
+///
+/// The same entries are included as in the XML output of [`scan`]. If the root
+/// folder itself is skipped (e.g. because it cannot be read), an empty folder is returned.
+pub fn scan_to_tree(root_path: &Path, options: &Options) -> Result<FileNode> {
+    let root = scan_root(root_path, options)?;
+    let (root_path, options, root_dev) = (root.path.as_ref(), root.options.as_ref(), root.device);
+    let mut builder = TreeBuilder::default();
+
+    walk(root_path, root_dev, options, &mut builder)?;
+
+    Ok(builder.into_root(root_path, options))
+}
+
+/// Scans the directory at `root_path` into a complete [`ScanResult`] in memory,
+/// with the same scan information as the XML output of [`scan`].
+pub fn scan_to_result(root_path: &Path, options: &Options) -> Result<(ScanResult, ScanStats)> {
+    let root = scan_root(root_path, options)?;
+    let (root_path, options, root_dev) = (root.path.as_ref(), root.options.as_ref(), root.device);
+    let mut builder = TreeBuilder::default();
+
+    let mut info = scan_info(root_path, options, &Disks::new_with_refreshed_list());
+    let scan_start = Instant::now();
+    let stats = walk(root_path, root_dev, options, &mut builder)?;
+    info.scan_duration = Some(scan_start.elapsed());
+    info.errors = Some(stats.errors);
+    info.skipped = Some(stats.skipped.clone());
+    info.consistency = stats.consistency;
+
+    let root = builder.into_root(root_path, options);
+    Ok((ScanResult { info, root }, stats))
+}
+
+/// Scans the directory at `root_path`, passing every entry to `visitor`.
+///
+/// This provides the traversal of [`scan`] without committing to an output format.
+pub fn scan_with_visitor<V: ScanVisitor>(
+    root_path: &Path,
+    options: &Options,
+    mut visitor: V,
+) -> Result<ScanStats> {
+    let root = scan_root(root_path, options)?;
+    let (root_path, options, root_dev) = (root.path.as_ref(), root.options.as_ref(), root.device);
+    Ok(walk(root_path, root_dev, options, &mut visitor)?)
+}
+
+/// The directory or file that is scanned, with the options to scan it.
+struct ScanRoot<'a> {
+    path: Cow<'a, Path>,
+    options: Cow<'a, Options>,
+    device: u64,
+}
+
+/// Resolves a root path that is a symbolic link to its target with `follow_root_symlink`,
+/// or rejects it otherwise, and makes a relative root path absolute with `absolute_paths`.
+///
+/// The target is scanned, so that the mount check and the volume information use its
+/// device on every platform, while the root folder is still named after the path as given.
+fn scan_root<'a>(root_path: &'a Path, options: &'a Options) -> Result<ScanRoot<'a>> {
+    // The builder does not check the rate, unlike the command line
+    if let Some(rate) = options.sample.filter(|rate| !(*rate > 0.0 && *rate <= 1.0)) {
+        return Err(Error::InvalidOptions(format!(
+            "The sample rate must be more than 0 and at most 1: {}",
+            rate
+        )));
+    }
+
+    // Custom sources resolve their own links, and their paths are not relative to the
+    // working directory
+    if options.source.is_some() {
+        return Ok(ScanRoot {
+            path: Cow::Borrowed(root_path),
+            options: Cow::Borrowed(options),
+            device: root_device_id(root_path, options)?,
+        });
+    }
+
+    let path = if root_path.is_symlink() {
+        if !options.follow_root_symlink {
+            return Err(Error::InvalidRoot(format!(
+                "The specified path is a symbolic link: {}",
+                root_path.display()
+            )));
+        }
+        let target = match fs::canonicalize(root_path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::RootNotFound(root_path.to_path_buf()))
+            }
+            result => result?,
+        };
+        debug!(
+            "{} is a symbolic link to {}",
+            root_path.display(),
+            target.display()
+        );
+        Cow::Owned(target)
+    } else if options.absolute_paths && root_path.is_relative() {
+        Cow::Owned(std::path::absolute(root_path)?)
+    } else {
+        Cow::Borrowed(root_path)
+    };
+    let options = match path {
+        Cow::Borrowed(_) => Cow::Borrowed(options),
+        Cow::Owned(_) => {
+            let mut options = options.clone();
+            options.root_name = RootName::Custom(options.root_folder_name(root_path));
+            Cow::Owned(options)
+        }
+    };
+    let device = root_device_id(&path, &options)?;
+    // Network folders below the root are skipped, but a network root leaves nothing to scan
+    if options.local_only {
+        if let Some(file_system) = file_system_types()
+            .remove(&device)
+            .filter(|file_system| is_network_file_system(file_system))
+        {
+            return Err(Error::InvalidRoot(format!(
+                "The specified path is on a network file system ({}): {}",
+                file_system,
+                path.display()
+            )));
+        }
+    }
+    Ok(ScanRoot {
+        device,
+        path,
+        options,
+    })
+}
+
+/// Returns the device ID of the scan root, checking that it is a directory or a file.
+fn root_device_id(root_path: &Path, options: &Options) -> Result<u64> {
+    let root_metadata = match options.source().metadata(root_path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::RootNotFound(root_path.to_path_buf()))
+        }
+        result => result?,
+    };
+    if !root_metadata.is_dir() && root_metadata.kind != EntryKind::File {
+        return Err(Error::InvalidRoot(format!(
+            "The specified path is neither a directory nor a file: {}",
+            root_path.display()
+        )));
+    }
+    Ok(root_metadata.device)
+}
+
+/// Traverses the tree below `root_path`, passing every entry to `visitor`.
+fn walk<S: ScanVisitor>(
+    root_path: &Path,
+    root_dev: u64,
+    options: &Options,
+    visitor: &mut S,
+) -> io::Result<ScanStats> {
+    let mut walker = Walker {
+        options,
+        root_dev,
+        state: ScanState::default(),
+        visitor,
+        cached_dirs: Vec::new(),
+    };
+    if let InodeFilter::Bloom(bytes) = options.inode_filter {
+        walker.state.inode_bloom = Some(BloomFilter::new(bytes));
+    }
+    // A single file is scanned as the only entry of its parent folder
+    if options.source().metadata(root_path)?.kind == EntryKind::File {
+        let parent = root_path.parent().unwrap_or(Path::new(""));
+        walker.state.sampler = options.sample.map(|rate| Sampler::new(parent, rate));
+        walker.traverse_file(root_path)?;
+    } else {
+        walker.state.sampler = options.sample.map(|rate| Sampler::new(root_path, rate));
+        walker.traverse_directory(root_path, 0)?;
+    }
+    if options.verify.is_some() {
+        walker.state.stats.consistency = Some(walker.verify_sample());
+    }
+
+    if let Some(reporter) = &options.progress {
+        reporter.sink.finish(&walker.state.progress(root_path));
+    }
+
+    let mut stats = walker.state.stats;
+    if let Some(dir_times) = walker.state.dir_times {
+        stats.slowest_dirs = dir_times
+            .to_sorted_vec()
+            .into_iter()
+            .map(|(total, Reverse(path), own)| DirTime { path, total, own })
+            .collect();
+    }
+    if stats.shape.entries > 0 {
+        info!("Tree shape: {}", stats.shape);
+    }
+    if stats.skipped.total() > 0 {
+        info!("Skipped entries: {}", stats.skipped);
+    }
+    if let Some(rate) = options.sample {
+        info!(
+            "{} files were left out of the {}% sample, the sizes are estimates",
+            stats.unsampled,
+            rate * 100.0
+        );
+    }
+    if stats.cached_folders > 0 {
+        info!(
+            "{} folders not modified since the cutoff were copied from the dir cache",
+            stats.cached_folders
+        );
+    }
+    if stats.hard_links > 0 {
+        // Summarized by the command line tool after the scan
+        debug!(
+            "{} hard links to already counted files were skipped ({} bytes saved)",
+            stats.hard_links, stats.hard_link_bytes
+        );
+    }
+    if let Some(bloom) = walker
+        .state
+        .inode_bloom
+        .as_ref()
+        .filter(|bloom| !bloom.is_empty())
+    {
+        info!(
+            "Inode filter: {} files with several links, estimated false skip probability {:.1e}",
+            bloom.len(),
+            bloom.false_positive_rate()
+        );
+    }
+    if stats.vanished > 0 {
+        info!(
+            "{} entries were removed during the scan and left out",
+            stats.vanished
+        );
+    }
+    if stats.errors > 0 {
+        warn!("{} entries were skipped due to errors", stats.errors);
+    }
+    if let Some(consistency) = stats.consistency {
+        let message = format!(
+            "{} of {} entries checked again had changed during the scan ({})",
+            consistency.changed,
+            consistency.checked,
+            consistency.level()
+        );
+        match consistency.changed {
+            0 => info!("{}", message),
+            _ => warn!("{}", message),
+        }
+    }
+    if stats.cancelled {
+        warn!("Scan was cancelled, the result is incomplete");
+    }
+    if stats.too_many_errors {
+        warn!(
+            "Scan was stopped after {} errors, the result is incomplete",
+            stats.errors
+        );
+    }
+    Ok(stats)
+}
+
+/// Runs a filesystem operation, handling errors according to the error policy.
+///
+/// The outer result is an error if the policy aborts the scan; the inner result
+/// is an error if the entry should be skipped.
+fn with_error_policy<T, F>(path: &Path, options: &Options, mut op: F) -> io::Result<io::Result<T>>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(Ok(value)),
+            // Removed entries do not come back, and the policy is for errors
+            Err(e) if is_vanished(&e) => return Ok(Err(e)),
+            Err(e) => match options.on_error(path, &e, attempt) {
+                ErrorAction::Skip => return Ok(Err(e)),
+                ErrorAction::Retry => attempt += 1,
+                ErrorAction::Abort => {
+                    error!("Aborting scan at '{}': {}", path.display(), e);
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("Scan aborted at '{}': {}", path.display(), e),
+                    ));
+                }
+            },
+        }
+    }
+}
+
+/// Returns true if an entry was removed since its directory was listed, which is a change
+/// during the scan rather than an error.
+fn is_vanished(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::NotFound
+}
+
+/// Entries of a directory, with their kinds if the listing tells them.
+type Listing = Vec<(PathBuf, Option<EntryKind>)>;
+
+/// Reads the contents of a directory and returns its entries.
+fn read_directory(path: &Path, options: &Options) -> io::Result<io::Result<Listing>> {
+    let result = with_error_policy(path, options, || options.source().read_dir_kinds(path))?;
+    if let Some(e) = result.as_ref().err().filter(|e| !is_vanished(e)) {
+        error!("Failed to read directory '{}': {}", path.display(), e);
+    }
+    Ok(result)
+}
+
+fn get_metadata(path: &Path, options: &Options) -> io::Result<io::Result<EntryMetadata>> {
+    let result = with_error_policy(path, options, || options.source().metadata(path))?
+        .map(|metadata| options.visible_metadata(metadata));
+    if let Some(e) = result.as_ref().err().filter(|e| !is_vanished(e)) {
+        error!("Failed to access metadata for '{}': {}", path.display(), e);
+    }
+    Ok(result)
+}
+
+/// Sorts directory entries in place according to `options.sort_order`.
+///
+/// Ties are broken by byte order, so the output is deterministic for every order
+/// except `SortOrder::None`.
+fn sort_entries(entries: &mut [(PathBuf, EntryMetadata)], options: &Options) {
+    fn name_bytes(path: &Path) -> &[u8] {
+        path.file_name()
+            .unwrap_or(path.as_os_str())
+            .as_encoded_bytes()
+    }
+
+    match options.sort_order {
+        SortOrder::Bytes => entries.sort_by(|a, b| name_bytes(&a.0).cmp(name_bytes(&b.0))),
+        SortOrder::Name => entries.sort_by(|a, b| {
+            let a_name = a.0.file_name().unwrap_or(a.0.as_os_str());
+            let b_name = b.0.file_name().unwrap_or(b.0.as_os_str());
+            a_name
+                .to_string_lossy()
+                .cmp(&b_name.to_string_lossy())
+                .then_with(|| name_bytes(&a.0).cmp(name_bytes(&b.0)))
+        }),
+        SortOrder::Natural => entries.sort_by(|a, b| {
+            let a_name = a.0.file_name().unwrap_or(a.0.as_os_str());
+            let b_name = b.0.file_name().unwrap_or(b.0.as_os_str());
+            natural_cmp(&a_name.to_string_lossy(), &b_name.to_string_lossy())
+                .then_with(|| name_bytes(&a.0).cmp(name_bytes(&b.0)))
+        }),
+        SortOrder::Size => entries.sort_by(|a, b| {
+            let a_size = a.1.file_size(options.apparent_size);
+            let b_size = b.1.file_size(options.apparent_size);
+            b_size
+                .cmp(&a_size)
+                .then_with(|| name_bytes(&a.0).cmp(name_bytes(&b.0)))
+        }),
+        SortOrder::Mtime => entries.sort_by(|a, b| {
+            let a_time = a.1.modified;
+            let b_time = b.1.modified;
+            b_time
+                .cmp(&a_time)
+                .then_with(|| name_bytes(&a.0).cmp(name_bytes(&b.0)))
+        }),
+        SortOrder::None => {}
+    }
+}
+
+/// Sorts tree nodes in place according to `order`, like [`sort_entries`].
+pub(crate) fn sort_nodes(nodes: &mut [FileNode], order: SortOrder) {
+    match order {
+        // Node names are already valid UTF-8, so both orders are the same
+        SortOrder::Bytes | SortOrder::Name => nodes.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortOrder::Natural => {
+            nodes.sort_by(|a, b| natural_cmp(&a.name, &b.name).then_with(|| a.name.cmp(&b.name)))
+        }
+        SortOrder::Size => nodes.sort_by(|a, b| {
+            b.total_size()
+                .cmp(&a.total_size())
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        SortOrder::Mtime => nodes.sort_by(|a, b| {
+            b.modified
+                .cmp(&a.modified)
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        SortOrder::None => {}
+    }
+}
+
+/// Compares names as file managers do: runs of digits by their numeric value, and
+/// other characters case-insensitively, so that `file2` comes before `File3` and `file10`.
+fn natural_cmp(a: &str, b: &str) -> cmp::Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return cmp::Ordering::Equal,
+            (None, Some(_)) => return cmp::Ordering::Less,
+            (Some(_), None) => return cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_number = take_digits(&mut a_chars);
+                let b_number = take_digits(&mut b_chars);
+                // Without leading zeros, a longer number is a larger one
+                let a_number = a_number.trim_start_matches('0');
+                let b_number = b_number.trim_start_matches('0');
+                a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(b_number))
+            }
+            (Some(&x), Some(&y)) => {
+                a_chars.next();
+                b_chars.next();
+                x.to_lowercase().cmp(y.to_lowercase())
+            }
+        };
+        if ordering != cmp::Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+    digits
+}
+
+/// Depth-first traversal of a directory tree.
+struct Walker<'a, S> {
+    options: &'a Options,
+    root_dev: u64,
+    state: ScanState,
+    visitor: &'a mut S,
+    /// Folders of the dir cache by name, for the directory being traversed and its
+    /// ancestors; empty for directories that are not in the cache.
+    cached_dirs: Vec<HashMap<&'a str, &'a FileNode>>,
+}
+
+impl<'a, S: ScanVisitor> Walker<'a, S> {
+    /// Counts an entry skipped due to an error and reports it to the visitor.
+    fn record_error(&mut self, path: &Path, e: &io::Error) -> io::Result<()> {
+        self.state.stats.errors += 1;
+        self.visitor.error(path, e);
+        self.comment(&format!("Failed to read {}: {}", path.display(), e))?;
+
+        let stats = &mut self.state.stats;
+        let limit_reached = self
+            .options
+            .max_errors
+            .is_some_and(|max| stats.errors >= max);
+        if limit_reached && !stats.too_many_errors {
+            stats.too_many_errors = true;
+            error!("Stopping the scan after {} errors", stats.errors);
+            self.comment(
+                "Scan was stopped after too many errors, the remaining entries are missing",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Leaves out an entry that was removed since its directory was listed, without
+    /// counting it as an error.
+    fn record_vanished(&mut self, path: &Path) -> io::Result<()> {
+        self.state.stats.vanished += 1;
+        debug!("{} was removed during the scan", path.display());
+        self.comment(&format!("Removed during the scan: {}", path.display()))
+    }
+
+    /// Leaves an entry out of the scan, and counts it in the scan summary.
+    fn skip(&mut self, path: &Path, reason: SkipReason) -> io::Result<()> {
+        let message = format!("Skipping {}: {}", reason.description(), path.display());
+        self.state.stats.skipped.add(reason);
+        let count = self.state.stats.skipped.get(reason);
+        if reason.is_unexpected() {
+            warn!("{}", message);
+        } else if self.options.log_each_skip || count <= LOGGED_SKIPS {
+            info!("{}", message);
+            if count == LOGGED_SKIPS && !self.options.log_each_skip {
+                info!(
+                    "Further entries skipped as {} are only counted",
+                    reason.name()
+                );
+            }
+        } else {
+            self.summarize_skips();
+        }
+        self.comment(&message)
+    }
+
+    /// Logs the number of entries skipped so far, at most once per summary interval.
+    fn summarize_skips(&mut self) {
+        let now = Instant::now();
+        match self.state.last_skip_summary {
+            Some(last) if now.duration_since(last) < SKIP_SUMMARY_INTERVAL => {}
+            // The interval starts with the first entry that is not logged
+            None => self.state.last_skip_summary = Some(now),
+            Some(_) => {
+                info!("Skipped entries so far: {}", self.state.stats.skipped);
+                self.state.last_skip_summary = Some(now);
+            }
+        }
+    }
+
+    /// Passes a diagnostic to the visitor if warnings are embedded in the output.
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        if !self.options.embed_warnings {
+            return Ok(());
+        }
+        self.visitor.comment(text)
+    }
+
+    /// Remembers a directory at `depth`, and returns false if it was already entered.
+    fn visit_dir(&mut self, id: (u64, u64), depth: usize) -> bool {
+        self.state.open_dirs.truncate(depth);
+        let new = match self.state.memory_mode {
+            MemoryMode::Full => self.state.visited_dirs.insert(id),
+            // A bind mount elsewhere in the tree is scanned again, but loops are still detected
+            _ => !self.state.visited_dirs.contains(&id) && !self.state.open_dirs.contains(&id),
+        };
+        if new {
+            self.state.open_dirs.push(id);
+            self.check_memory();
+        }
+        new
+    }
+
+    /// Remembers a file, and returns false if it is a hard link to a file already counted.
+    fn visit_file(&mut self, metadata: &EntryMetadata) -> bool {
+        // The filter has a fixed size, so it is kept regardless of the memory mode
+        if let Some(bloom) = &mut self.state.inode_bloom {
+            return metadata.links == 1 || bloom.insert(file_id(metadata));
+        }
+        let remember = match self.state.memory_mode {
+            MemoryMode::Full => true,
+            MemoryMode::Reduced => metadata.links != 1,
+            MemoryMode::Minimal => false,
+        };
+        if !remember {
+            return true;
+        }
+        let new = self.state.visited_inodes.insert(file_id(metadata));
+        self.check_memory();
+        new
+    }
+
+    /// Moves to a leaner way of detecting hard links and loops when the visited
+    /// entries come close to the memory limit.
+    fn check_memory(&mut self) {
+        let Some(limit) = self.options.max_memory else {
+            return;
+        };
+        let used = self.state.visited_bytes();
+        let mode = if used >= limit {
+            MemoryMode::Minimal
+        } else if used >= limit / 4 * 3 {
+            MemoryMode::Reduced
+        } else {
+            return;
+        };
+        if mode <= self.state.memory_mode {
+            return;
+        }
+        self.state.memory_mode = mode;
+        match mode {
+            MemoryMode::Reduced => info!(
+                "Close to the memory limit, only files with several links are checked for hard links"
+            ),
+            _ => {
+                warn!("Memory limit reached, hard links are no longer detected and may be counted more than once");
+                self.state.visited_inodes = HashSet::new();
+            }
+        }
+    }
+
+    /// Warns once per device that a directory is on a network file system, or with
+    /// `local_only`, returns true to skip it. A network scan root is rejected before.
+    fn skip_network_file_system(
+        &mut self,
+        path: &Path,
+        device: u64,
+        depth: usize,
+    ) -> io::Result<bool> {
+        // Devices of custom sources are not those of the mounted volumes
+        if self.options.source.is_some() || (depth > 0 && device == self.root_dev) {
+            return Ok(false);
+        }
+        let file_systems = self
+            .state
+            .file_systems
+            .get_or_insert_with(file_system_types);
+        let Some(file_system) = file_systems
+            .get(&device)
+            .filter(|file_system| is_network_file_system(file_system))
+        else {
+            return Ok(false);
+        };
+
+        if self.options.local_only {
+            debug!("{} is on {}", path.display(), file_system);
+            self.skip(path, SkipReason::NetworkFileSystem)?;
+            return Ok(true);
+        }
+        if self.state.network_devices.insert(device) {
+            warn!(
+                "{} is on a network file system ({}), scanning it may be slow",
+                path.display(),
+                file_system
+            );
+        }
+        Ok(false)
+    }
+
+    /// Recursively traverses the directory, measuring the time taken with `profile_dirs`.
+    fn traverse_directory(&mut self, path: &Path, depth: usize) -> io::Result<()> {
+        let Some(count) = self.options.profile_dirs else {
+            return self.traverse_directory_entries(path, depth);
+        };
+
+        let started = Instant::now();
+        self.state.child_times.push(Duration::ZERO);
+        let result = self.traverse_directory_entries(path, depth);
+        let children = self.state.child_times.pop().unwrap_or_default();
+        let total = started.elapsed();
+        if let Some(parent) = self.state.child_times.last_mut() {
+            *parent += total;
+        }
+        self.state
+            .dir_times
+            .get_or_insert_with(|| TopN::new(count))
+            .push((
+                total,
+                Reverse(path.to_path_buf()),
+                total.saturating_sub(children),
+            ));
+        result
+    }
+
+    /// Traverses the directory and passes its entries to the visitor.
+    fn traverse_directory_entries(&mut self, path: &Path, depth: usize) -> io::Result<()> {
+        let options = self.options;
+
+        // Get metadata of the current directory
+        let metadata = match get_metadata(path, options)? {
+            Ok(metadata) => metadata,
+            Err(e) if depth > 0 && is_vanished(&e) => return self.record_vanished(path),
+            Err(e) => {
+                self.record_error(path, &e)?;
+                return Ok(());
+            }
+        };
+
+        // Check if the current directory is on a different filesystem
+        let current_dev = metadata.device;
+        let mut subvolume = false;
+        if depth > 0
+            && current_dev != self.root_dev
+            && !self.state.subvolume_devices.contains(&current_dev)
+        {
+            if options.source.is_none()
+                && is_btrfs_subvolume(
+                    path,
+                    metadata.inode,
+                    self.state.mount_table.get_or_insert_with(mount_table),
+                )
+            {
+                info!("Entering btrfs subvolume: {}", path.display());
+                self.state.subvolume_devices.insert(current_dev);
+                subvolume = true;
+            } else if !options.cross_mount_points {
+                debug!(
+                    "{} is on device {} (root: {})",
+                    path.display(),
+                    current_dev,
+                    self.root_dev
+                );
+                self.skip(path, SkipReason::OtherFileSystem)?;
+                return Ok(());
+            }
+        }
+
+        if self.skip_network_file_system(path, metadata.device, depth)? {
+            return Ok(());
+        }
+
+        // Skip directories that were already entered (bind mounts and loops)
+        if !self.visit_dir(file_id(&metadata), depth) {
+            self.skip(path, SkipReason::VisitedDirectory)?;
+            return Ok(());
+        }
+
+        // Get directory name
+        let name = if depth == 0 {
+            options.root_folder_name(path)
+        } else {
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .to_string()
+        };
+
+        let mut folder = ScanEntry {
+            path: path.to_path_buf(),
+            name,
+            depth,
+            size: 0,
+            metadata,
+            attributes: Vec::new(),
+        };
+        if subvolume && options.mark_subvolumes {
+            folder
+                .attributes
+                .push(("subvolume".to_string(), "true".to_string()));
+        }
+        if let Some(cached) = self.cached_dir(path, &folder.metadata, depth) {
+            return self.copy_cached_dir(&mut folder, cached);
+        }
+
+        // Read directory entries
+        let entries = match read_directory(path, options)? {
+            Ok(entries) => entries,
+            Err(e) if depth > 0 && is_vanished(&e) => return self.record_vanished(path),
+            Err(e) => {
+                self.record_error(path, &e)?;
+                // Keep the folder in the tree, but mark it as unreadable
+                folder
+                    .attributes
+                    .push(("unreadable".to_string(), "true".to_string()));
+                self.enter_dir(&mut folder)?;
+                self.dir_entry(&folder)?;
+                return self.visitor.leave_dir(&folder);
+            }
+        };
+
+        self.state
+            .stats
+            .shape
+            .record_dir_entries(path, entries.len() as u64);
+
+        // The root folder is always written, so that every dump has one
+        if let Some(reason) = filters::skip_folder(entries.len(), options).filter(|_| depth > 0) {
+            self.skip(path, reason)?;
+            return Ok(());
+        }
+
+        // Large folders are kept, but their entries are not read
+        if let Some(reason) = filters::skip_folder_entries(entries.len(), options) {
+            self.skip(path, reason)?;
+            folder
+                .attributes
+                .push(("skippedEntries".to_string(), entries.len().to_string()));
+            self.enter_dir(&mut folder)?;
+            self.dir_entry(&folder)?;
+            return self.visitor.leave_dir(&folder);
+        }
+        if options
+            .warn_dir_entries
+            .is_some_and(|max| entries.len() as u64 > max)
+        {
+            warn!("{} has {} entries", path.display(), entries.len());
+        }
+
+        self.enter_dir(&mut folder)?;
+        self.dir_entry(&folder)?;
+        self.offer_to_verify(path, &folder.metadata);
+
+        // Leave out entries by name, before their metadata is read
+        let mut candidates = Vec::with_capacity(entries.len());
+        for (entry_path, kind) in entries {
+            let file_name = entry_path
+                .file_name()
+                .unwrap_or(entry_path.as_os_str())
+                .to_string_lossy();
+
+            if let Some(reason) = filters::skip_by_name(&file_name, options) {
+                self.skip(&entry_path, reason)?;
+                continue;
+            }
+            // Files left out of the sample are not even read
+            if kind == Some(EntryKind::File) && !self.in_sample(&entry_path) {
+                continue;
+            }
+            candidates.push(entry_path);
+        }
+        let filtered = match &options.filter_command {
+            Some(command) => command.skipped(path, folder.metadata.modified, &candidates)?,
+            None => vec![false; candidates.len()],
+        };
+
+        // Get metadata of the entries
+        let mut children = Vec::with_capacity(candidates.len());
+        for (entry_path, filtered) in candidates.into_iter().zip(filtered) {
+            if self.state.stats.too_many_errors {
+                break;
+            }
+            if filtered {
+                self.skip(&entry_path, SkipReason::FilterCommand)?;
+                continue;
+            }
+
+            match with_error_policy(&entry_path, options, || {
+                options.source().symlink_metadata(&entry_path)
+            })? {
+                Ok(m) => children.push((entry_path, options.visible_metadata(m))),
+                Err(e) if is_vanished(&e) => self.record_vanished(&entry_path)?,
+                Err(e) => {
+                    error!(
+                        "Failed to access metadata for '{}': {}",
+                        entry_path.display(),
+                        e
+                    );
+                    self.record_error(&entry_path, &e)?;
+                }
+            }
+        }
+
+        // Sort entries in the requested order
+        sort_entries(&mut children, options);
+
+        // Iterate over directory entries
+        for (entry_path, entry_metadata) in children {
+            // Stop reading, but still close the open folders
+            if options.is_cancelled() {
+                self.state.stats.cancelled = true;
+                self.comment("Scan was cancelled, the remaining entries are missing")?;
+                break;
+            }
+            if self.state.stats.too_many_errors {
+                break;
+            }
+
+            if entry_metadata.kind == EntryKind::Junction && options.record_junctions {
+                self.record_junction(entry_path, entry_metadata, depth + 1)?;
+            } else if let Some(reason) = filters::skip_by_kind(entry_metadata.kind) {
+                self.skip(&entry_path, reason)?;
+            } else if entry_metadata.is_dir() {
+                self.traverse_directory(&entry_path, depth + 1)?;
+            } else {
+                self.process_file_entry(entry_path, entry_metadata, depth + 1)?;
+            }
+        }
+
+        self.visitor.leave_dir(&folder)
+    }
+
+    /// Writes the junction at `path` as an empty folder, without entering its target.
+    fn record_junction(
+        &mut self,
+        path: PathBuf,
+        metadata: EntryMetadata,
+        depth: usize,
+    ) -> io::Result<()> {
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .to_string();
+        let mut folder = ScanEntry {
+            path,
+            name,
+            depth,
+            size: 0,
+            metadata,
+            attributes: vec![("junction".to_string(), "true".to_string())],
+        };
+        self.enter_dir(&mut folder)?;
+        self.visitor.leave_dir(&folder)
+    }
+
+    /// Returns the folder of the dir cache for the directory at `path`, if the directory
+    /// was not modified since the cutoff. Otherwise, remembers the cached subfolders
+    /// of the directory, which is read.
+    fn cached_dir(
+        &mut self,
+        path: &Path,
+        metadata: &EntryMetadata,
+        depth: usize,
+    ) -> Option<&'a FileNode> {
+        let cache = self.options.dir_cache.as_ref()?;
+        self.cached_dirs.truncate(depth);
+        let cached = match depth {
+            0 => Some(cache.root.as_ref()),
+            _ => {
+                let name = path.file_name()?.to_string_lossy();
+                self.cached_dirs
+                    .get(depth - 1)
+                    .and_then(|folders| folders.get(name.as_ref()).copied())
+            }
+        };
+        if cached.is_some() && metadata.modified.is_some_and(|time| time < cache.cutoff) {
+            return cached;
+        }
+        let folders = cached
+            .into_iter()
+            .flat_map(|folder| &folder.children)
+            .filter(|child| child.is_folder())
+            .map(|child| (child.name.as_str(), child))
+            .collect();
+        self.cached_dirs.push(folders);
+        None
+    }
+
+    /// Passes `folder` to the visitor with the contents of its previous scan, `cached`.
+    fn copy_cached_dir(&mut self, folder: &mut ScanEntry, cached: &FileNode) -> io::Result<()> {
+        debug!("Copying {} from the dir cache", folder.path.display());
+        self.state.stats.cached_folders += 1;
+        self.enter_dir(folder)?;
+        for child in &cached.children {
+            self.process_node(child, &folder.path, folder.depth + 1)?;
+        }
+        self.visitor.leave_dir(folder)
+    }
+
+    /// Passes the file at `path` to the visitor, inside a folder for its parent directory.
+    fn traverse_file(&mut self, path: &Path) -> io::Result<()> {
+        let options = self.options;
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let (folder_metadata, metadata) =
+            match (get_metadata(parent, options)?, get_metadata(path, options)?) {
+                (Ok(folder_metadata), Ok(metadata)) => (folder_metadata, metadata),
+                (Err(e), _) | (_, Err(e)) => {
+                    self.record_error(path, &e)?;
+                    return Ok(());
+                }
+            };
+
+        let mut folder = ScanEntry {
+            path: parent.to_path_buf(),
+            name: options.root_folder_name(parent),
+            depth: 0,
+            size: 0,
+            metadata: folder_metadata,
+            attributes: Vec::new(),
+        };
+        self.enter_dir(&mut folder)?;
+        self.process_file_entry(path.to_path_buf(), metadata, 1)?;
+        self.visitor.leave_dir(&folder)
+    }
+
+    /// Processes a file entry and passes it to the visitor.
+    fn process_file_entry(
+        &mut self,
+        path: PathBuf,
+        mut metadata: EntryMetadata,
+        depth: usize,
+    ) -> io::Result<()> {
+        let options = self.options;
+        if !self.in_sample(&path) {
+            return Ok(());
+        }
+        self.round_to_allocation_unit(&path, &mut metadata);
+
+        // Skip if the file is a hard link to a file that was already written
+        if !self.visit_file(&metadata) {
+            self.skip(&path, SkipReason::HardLink)?;
+            self.state.stats.hard_links += 1;
+            self.state.stats.hard_link_bytes += metadata.file_size(options.apparent_size);
+            return Ok(());
+        }
+        if let Some(reason) = filters::skip_by_owner(metadata.owner, options) {
+            self.skip(&path, reason)?;
+            return Ok(());
+        }
+
+        // Get file name
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .to_string();
+
+        // List the contents of archives as folders
+        if options.scan_archives {
+            if let Some(format) = ArchiveFormat::from_name(&name) {
+                if self.process_archive(&path, &name, &metadata, format, depth)? {
+                    return Ok(());
+                }
+            }
+        }
+
+        // Get physical file size
+        let mut size = metadata.file_size(options.apparent_size);
+        let mut attributes: Vec<(String, String)> =
+            options.other_size(&metadata).into_iter().collect();
+
+        // Online-only cloud files occupy (almost) no local storage
+        if metadata.placeholder {
+            info!("Cloud placeholder file: {}", path.display());
+            match options.placeholders {
+                PlaceholderMode::Zero if !options.apparent_size => size = 0,
+                PlaceholderMode::Mark => {
+                    attributes.push(("placeholder".to_string(), "true".to_string()))
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(reason) = filters::skip_file(size, options) {
+            self.skip(&path, reason)?;
+            return Ok(());
+        }
+        if let Some(sampler) = &self.state.sampler {
+            size = sampler.scale(size, metadata.links);
+        }
+
+        if options.uniform_types {
+            if let Some(uti) = uniform_type(&name) {
+                attributes.push((ATTR_UNIFORM_TYPE.to_string(), uti.to_string()));
+            }
+        }
+
+        self.offer_to_verify(&path, &metadata);
+        let entry = ScanEntry {
+            path,
+            name,
+            depth,
+            size,
+            metadata,
+            attributes,
+        };
+        self.file(entry)
+    }
+
+    /// Passes the members of an archive to the visitor as a folder.
+    ///
+    /// Returns false if the archive should be recorded as a plain file instead.
+    fn process_archive(
+        &mut self,
+        path: &Path,
+        name: &str,
+        metadata: &EntryMetadata,
+        format: ArchiveFormat,
+        depth: usize,
+    ) -> io::Result<bool> {
+        // Archives are opened directly, which is only possible on the local filesystem
+        if self.options.source.is_some() {
+            return Ok(false);
+        }
+
+        let mut root = match read_archive(path, name.to_string(), format) {
+            Ok(root) => root,
+            Err(e) => {
+                let message = format!("Failed to list archive '{}': {}", path.display(), e);
+                warn!("{}", message);
+                self.comment(&message)?;
+                return Ok(false);
+            }
+        };
+        filter_members(&mut root, self.options);
+        if root.children.is_empty() {
+            return Ok(false);
+        }
+        root.created = metadata.created.map(DateTime::from);
+        root.modified = metadata.modified.map(DateTime::from);
+        root.accessed = metadata.accessed.map(DateTime::from);
+
+        let parent = path.parent().unwrap_or(Path::new(""));
+        self.process_node(&root, parent, depth)?;
+        Ok(true)
+    }
+
+    /// Passes a file or folder of an in-memory tree, such as the members of an archive,
+    /// to the visitor.
+    fn process_node(&mut self, node: &FileNode, parent: &Path, depth: usize) -> io::Result<()> {
+        let mut entry = node_entry(node, parent, depth);
+        if node.is_folder() {
+            self.enter_dir(&mut entry)?;
+            for child in &node.children {
+                self.process_node(child, &entry.path, depth + 1)?;
+            }
+            self.visitor.leave_dir(&entry)
+        } else {
+            self.file(entry)
+        }
+    }
+
+    fn enter_dir(&mut self, folder: &mut ScanEntry) -> io::Result<()> {
+        self.add_path_attribute(folder);
+        self.add_custom_attributes(folder);
+        self.visitor.enter_dir(folder)?;
+
+        self.state.folders += 1;
+        self.state.stats.shape.record_entry(folder);
+        self.report_progress(&folder.path);
+        Ok(())
+    }
+
+    /// Returns whether the file at `path` is in the sample, counting it if it is not.
+    fn in_sample(&mut self, path: &Path) -> bool {
+        let Some(sampler) = &self.state.sampler else {
+            return true;
+        };
+        let sampled = sampler.contains(path);
+        if !sampled {
+            self.state.stats.unsampled += 1;
+        }
+        sampled
+    }
+
+    /// Offers an entry read from the source to the sample checked again after the scan.
+    fn offer_to_verify(&mut self, path: &Path, metadata: &EntryMetadata) {
+        if let Some(count) = self.options.verify {
+            self.state.verify_sample.offer(count, path, metadata);
+        }
+    }
+
+    /// Reads the metadata of the sampled entries again, and counts those that changed
+    /// since they were scanned or were removed. Entries that cannot be read for another
+    /// reason are not counted.
+    fn verify_sample(&mut self) -> Consistency {
+        let entries = std::mem::take(&mut self.state.verify_sample.entries);
+        let mut consistency = Consistency {
+            checked: 0,
+            changed: 0,
+        };
+        for (path, size, modified) in entries {
+            let change = match self.options.source().symlink_metadata(&path) {
+                Ok(metadata) if metadata.apparent_size != size => Some(format!(
+                    "size changed from {} to {} bytes",
+                    size, metadata.apparent_size
+                )),
+                Ok(metadata) if metadata.modified != modified => {
+                    Some("modification time changed".to_string())
+                }
+                Ok(_) => None,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Some("removed".to_string()),
+                Err(e) => {
+                    debug!("Failed to check {} again: {}", path.display(), e);
+                    continue;
+                }
+            };
+            consistency.checked += 1;
+            if let Some(change) = change {
+                consistency.changed += 1;
+                let message = format!("Changed during the scan: {} ({})", path.display(), change);
+                match consistency.changed <= LOGGED_SKIPS {
+                    true => info!("{}", message),
+                    false => debug!("{}", message),
+                }
+            }
+        }
+        consistency
+    }
+
+    /// Records the full path of `entry` in a `path` attribute, if
+    /// [`absolute_paths`](crate::OptionsBuilder::absolute_paths) is set.
+    fn add_path_attribute(&self, entry: &mut ScanEntry) {
+        if !self.options.absolute_paths {
+            return;
+        }
+        // Entries copied from a dump, as from the dir cache, carry the path they had then
+        entry.attributes.retain(|(name, _)| name != ATTR_PATH);
+        entry
+            .attributes
+            .push((ATTR_PATH.to_string(), entry.path.display().to_string()));
+    }
+
+    /// Appends the attributes returned by the user-supplied
+    /// [`attributes`](crate::OptionsBuilder::attributes) function to `entry`.
+    fn add_custom_attributes(&mut self, entry: &mut ScanEntry) {
+        let Some(CustomAttributes(attributes)) = &self.options.attributes else {
+            return;
+        };
+        for (key, value) in attributes(&entry.path, &entry.metadata) {
+            let taken = ENTRY_ATTRIBUTES.contains(&key.as_str())
+                || entry.attributes.iter().any(|(name, _)| *name == key);
+            if taken || !is_xml_name(&key) {
+                if self.state.rejected_attributes.insert(key.clone()) {
+                    let problem = if taken {
+                        "is already used"
+                    } else {
+                        "is not an XML name"
+                    };
+                    warn!("Leaving out the attribute {:?}, which {}", key, problem);
+                }
+                continue;
+            }
+            entry.attributes.push((key, value));
+        }
+    }
+
+    /// Rounds the disk usage in `metadata` up to the allocation unit of the file system
+    /// holding `path`, if [`allocation_unit`](crate::OptionsBuilder::allocation_unit) is set.
+    fn round_to_allocation_unit(&mut self, path: &Path, metadata: &mut EntryMetadata) {
+        // Devices of custom sources are not those of the mounted volumes
+        if !self.options.allocation_unit || self.options.source.is_some() {
+            return;
+        }
+        let unit = *self
+            .state
+            .allocation_units
+            .entry(metadata.device)
+            .or_insert_with(|| {
+                let unit = allocation_unit(path);
+                debug!("Allocation unit of {}: {:?}", path.display(), unit);
+                unit
+            });
+        if let Some(unit) = unit {
+            metadata.size = metadata.size.div_ceil(unit) * unit;
+        }
+    }
+
+    /// Passes the space taken by the directory itself as a file named `.`, which cannot
+    /// clash with a real entry.
+    fn dir_entry(&mut self, folder: &ScanEntry) -> io::Result<()> {
+        if !self.options.dir_entry_size {
+            return Ok(());
+        }
+        let mut metadata = EntryMetadata {
+            kind: EntryKind::File,
+            ..folder.metadata.clone()
+        };
+        self.round_to_allocation_unit(&folder.path, &mut metadata);
+        let size = metadata.file_size(self.options.apparent_size);
+        if size == 0 {
+            return Ok(());
+        }
+        let entry = ScanEntry {
+            path: folder.path.join("."),
+            name: ".".to_string(),
+            depth: folder.depth + 1,
+            size,
+            metadata,
+            attributes: vec![("directoryEntry".to_string(), "true".to_string())],
+        };
+        self.file(entry)
+    }
+
+    fn file(&mut self, mut entry: ScanEntry) -> io::Result<()> {
+        if let Some(mode) = self.options.classify {
+            // Paths of other sources cannot be opened to read magic bytes
+            let mode = match self.options.source {
+                Some(_) => ClassifyMode::Extension,
+                None => mode,
+            };
+            let kind = classify(&entry.path, &entry.name, mode);
+            entry
+                .attributes
+                .push((KIND_ATTRIBUTE.to_string(), kind.name().to_string()));
+        }
+        self.add_path_attribute(&mut entry);
+        self.add_custom_attributes(&mut entry);
+        self.visitor.file(&entry)?;
+
+        self.state.files += 1;
+        self.state.bytes += entry.size;
+        self.state.stats.shape.record_entry(&entry);
+        self.report_progress(&entry.path);
+        Ok(())
+    }
+
+    /// Sends a progress update if the progress interval has elapsed.
+    fn report_progress(&mut self, path: &Path) {
+        let Some(reporter) = &self.options.progress else {
+            return;
+        };
+        let now = Instant::now();
+        if self
+            .state
+            .last_progress
+            .is_some_and(|last| now.duration_since(last) < reporter.interval)
+        {
+            return;
+        }
+        self.state.last_progress = Some(now);
+        reporter.sink.update(&self.state.progress(path));
+    }
+}
+
+impl ScanState {
+    fn progress<'a>(&self, current_path: &'a Path) -> Progress<'a> {
+        Progress {
+            files: self.files,
+            folders: self.folders,
+            bytes: self.bytes,
+            current_path,
+        }
+    }
+}
+
+/// Passes entries to an output formatter.
+struct FormatterVisitor<'a, F>(&'a mut F);
+
+impl<F: OutputFormatter> ScanVisitor for FormatterVisitor<'_, F> {
+    fn enter_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.0.begin_folder(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.0.file(entry)
+    }
+
+    fn leave_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.0.end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.0.comment(text)
+    }
+}
+
+/// Number of entries passed from the traversal to the formatter at a time.
+const WRITER_BATCH_SIZE: usize = 256;
+/// Number of batches queued between the traversal and the formatter.
+const WRITER_QUEUE_CAPACITY: usize = 4;
+
+/// An entry passed from the traversal thread to the formatter.
+enum WalkEvent {
+    Enter(ScanEntry),
+    File(ScanEntry),
+    Leave(ScanEntry),
+    Comment(String),
+}
+
+/// Traverses on a background thread and passes the entries to `formatter` on this one,
+/// over a bounded queue, so that reading metadata overlaps with writing the output.
+fn walk_in_background<F: OutputFormatter>(
+    root_path: &Path,
+    root_dev: u64,
+    options: &Options,
+    formatter: &mut F,
+) -> io::Result<ScanStats> {
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel(WRITER_QUEUE_CAPACITY);
+        let walker = scope.spawn(move || {
+            let mut sender = EventSender {
+                sender,
+                batch: Vec::with_capacity(WRITER_BATCH_SIZE),
+            };
+            let stats = walk(root_path, root_dev, options, &mut sender)?;
+            sender.flush()?;
+            Ok(stats)
+        });
+
+        let written = receiver.iter().flatten().try_for_each(|event| match event {
+            WalkEvent::Enter(entry) => formatter.begin_folder(&entry),
+            WalkEvent::File(entry) => formatter.file(&entry),
+            WalkEvent::Leave(entry) => formatter.end_folder(&entry),
+            WalkEvent::Comment(text) => formatter.comment(&text),
+        });
+        // If the output failed, the traversal stops at its next batch
+        drop(receiver);
+        let walked = walker
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        written?;
+        walked
+    })
+}
+
+/// Sends entries to [`walk_in_background`] in batches.
+struct EventSender {
+    sender: SyncSender<Vec<WalkEvent>>,
+    batch: Vec<WalkEvent>,
+}
+
+impl EventSender {
+    fn send(&mut self, event: WalkEvent) -> io::Result<()> {
+        self.batch.push(event);
+        if self.batch.len() < WRITER_BATCH_SIZE {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(WRITER_BATCH_SIZE));
+        self.sender
+            .send(batch)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Output was closed"))
+    }
+}
+
+impl ScanVisitor for EventSender {
+    fn enter_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.send(WalkEvent::Enter(entry.clone()))
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.send(WalkEvent::File(entry.clone()))
+    }
+
+    fn leave_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.send(WalkEvent::Leave(entry.clone()))
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.send(WalkEvent::Comment(text.to_string()))
+    }
+}
+
+/// Collects entries into a [`FileNode`] tree.
+#[derive(Default)]
+struct TreeBuilder {
+    stack: Vec<FileNode>,
+    root: Option<FileNode>,
+}
+
+impl TreeBuilder {
+    /// Returns the collected tree, with folders ordered by their total size for
+    /// [`SortOrder::Size`], which the traversal cannot know in advance.
+    fn into_root(self, root_path: &Path, options: &Options) -> FileNode {
+        let mut root = self
+            .root
+            .unwrap_or_else(|| FileNode::folder(options.root_folder_name(root_path)));
+        if options.sort_order == SortOrder::Size {
+            sort_by_total_size(&mut root);
+        }
+        root
+    }
+}
+
+fn sort_by_total_size(folder: &mut FileNode) {
+    for child in &mut folder.children {
+        sort_by_total_size(child);
+    }
+    sort_nodes(&mut folder.children, SortOrder::Size);
+}
+
+impl ScanVisitor for TreeBuilder {
+    fn enter_dir(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.stack.push(FileNode::from_entry(entry, false));
+        Ok(())
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children.push(FileNode::from_entry(entry, true));
+        }
+        Ok(())
+    }
+
+    fn leave_dir(&mut self, _entry: &ScanEntry) -> io::Result<()> {
+        if let Some(folder) = self.stack.pop() {
+            match self.stack.last_mut() {
+                Some(parent) => parent.children.push(folder),
+                None => self.root = Some(folder),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+// External crates
+use log::{info, warn};
+use sysinfo::Disks;
+use tempfile::TempDir;
+
+// Standard library imports
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::Error;
+use crate::model::ScanInfo;
+use crate::output::OutputFormatter;
+use crate::scan::ScanEntry;
+use crate::volume::{get_volume_info, VolumeInfo};
+
+/// A read-only mount of a named APFS snapshot (`--snapshot`), unmounted when dropped.
+pub(crate) struct Snapshot {
+    name: String,
+    /// The live volume the snapshot was taken of.
+    volume: VolumeInfo,
+    mount_dir: TempDir,
+    /// The scan root inside the mounted snapshot.
+    pub(crate) root: PathBuf,
+}
+
+impl Snapshot {
+    /// Mounts the snapshot `name` of the volume containing `root_path`.
+    pub(crate) fn mount(root_path: &Path, name: &str) -> Result<Self, Error> {
+        let root = fs::canonicalize(root_path)?;
+        let volume = get_volume_info(&root, &Disks::new_with_refreshed_list());
+        let relative = root
+            .strip_prefix(&volume.path)
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        let mount_dir = tempfile::Builder::new()
+            .prefix("gpscan-snapshot-")
+            .tempdir()?;
+
+        mount_apfs(name, &volume.path, mount_dir.path())?;
+        info!(
+            "Mounted snapshot {} of {} at {}",
+            name,
+            volume.path,
+            mount_dir.path().display()
+        );
+        Ok(Snapshot {
+            name: name.to_string(),
+            root: mount_dir.path().join(relative),
+            volume,
+            mount_dir,
+        })
+    }
+
+    /// Describes the live volume and the snapshot instead of the snapshot mount.
+    fn scan_info(&self, info: &ScanInfo) -> ScanInfo {
+        ScanInfo {
+            volume_path: self.volume.path.clone(),
+            volume_name: self.volume.name.clone(),
+            file_system: self.volume.file_system.clone(),
+            volume_id: self.volume.id.clone(),
+            snapshot: Some(self.name.clone()),
+            ..info.clone()
+        }
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let path = self.mount_dir.path();
+        match Command::new("umount").arg(path).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!("Failed to unmount {}: umount {}", path.display(), status),
+            Err(e) => warn!("Failed to unmount {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn mount_apfs(name: &str, volume: &str, directory: &Path) -> Result<(), Error> {
+    let status = Command::new("mount_apfs")
+        .args(["-o", "rdonly,nobrowse", "-s", name, volume])
+        .arg(directory)
+        .status()
+        .map_err(|e| Error::InvalidRoot(format!("Failed to run mount_apfs: {}", e)))?;
+    if !status.success() {
+        return Err(Error::InvalidRoot(format!(
+            "Failed to mount snapshot '{}' of {}: mount_apfs {}",
+            name, volume, status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn mount_apfs(name: &str, _volume: &str, _directory: &Path) -> Result<(), Error> {
+    Err(Error::InvalidRoot(format!(
+        "Cannot scan snapshot '{}': APFS snapshots are only available on macOS",
+        name
+    )))
+}
+
+/// Passes everything on to another formatter, with the [`ScanInfo`] of the
+/// snapshot if one is scanned.
+pub(crate) struct SnapshotInfo<'a, F> {
+    inner: F,
+    snapshot: Option<&'a Snapshot>,
+}
+
+impl<'a, F: OutputFormatter> SnapshotInfo<'a, F> {
+    pub(crate) fn new(inner: F, snapshot: Option<&'a Snapshot>) -> Self {
+        SnapshotInfo { inner, snapshot }
+    }
+}
+
+impl<F: OutputFormatter> OutputFormatter for SnapshotInfo<'_, F> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        match self.snapshot {
+            Some(snapshot) => self.inner.begin_scan(&snapshot.scan_info(info)),
+            None => self.inner.begin_scan(info),
+        }
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.begin_folder(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.file(entry)
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.comment(text)
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        match self.snapshot {
+            Some(snapshot) => self.inner.end_scan(&snapshot.scan_info(info)),
+            None => self.inner.end_scan(info),
+        }
+    }
+}
@@ -0,0 +1,284 @@
+// Standard library imports
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::platform::{
+    file_id, link_kind, read_dir_kinds, read_dir_noatime, LinkKind, MetadataExtOps,
+};
+
+/// Type of an entry, as seen without following links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// A Windows directory junction or another name surrogate reparse point.
+    Junction,
+    /// Sockets, devices, FIFOs, ...
+    Other,
+}
+
+/// Metadata of an entry, independent of where it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub kind: EntryKind,
+    /// Disk usage in bytes.
+    pub size: u64,
+    /// Apparent size in bytes.
+    pub apparent_size: u64,
+    /// Device (volume) identifier.
+    pub device: u64,
+    /// Inode (file index) on the device, or 0 if unknown.
+    pub inode: u64,
+    pub created: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    /// True for online-only cloud files.
+    pub placeholder: bool,
+    /// User ID of the owner, if known.
+    pub owner: Option<u32>,
+    /// Number of hard links to the entry, or 0 if unknown.
+    pub links: u64,
+}
+
+impl EntryMetadata {
+    /// Creates metadata for an entry without device, inode, or times.
+    pub fn new(kind: EntryKind, size: u64) -> Self {
+        EntryMetadata {
+            kind,
+            size,
+            apparent_size: size,
+            device: 0,
+            inode: 0,
+            created: None,
+            modified: None,
+            accessed: None,
+            placeholder: false,
+            owner: None,
+            links: 0,
+        }
+    }
+
+    /// Converts metadata read from the local filesystem.
+    pub fn from_std(path: &Path, metadata: &Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let link = link_kind(path, metadata);
+        let kind = match link {
+            Some(LinkKind::Symlink) => EntryKind::Symlink,
+            Some(LinkKind::Junction) => EntryKind::Junction,
+            None if file_type.is_dir() => EntryKind::Dir,
+            None if file_type.is_file() => EntryKind::File,
+            None => EntryKind::Other,
+        };
+
+        let (device, inode) = file_id(path, metadata, link);
+        EntryMetadata {
+            kind,
+            size: metadata.file_size(false),
+            apparent_size: metadata.file_size(true),
+            device,
+            inode,
+            created: metadata.created().ok(),
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            placeholder: metadata.is_placeholder(),
+            owner: metadata.owner_id(),
+            links: metadata.link_count(),
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    /// Returns the apparent size or the disk usage.
+    pub fn file_size(&self, apparent: bool) -> u64 {
+        if apparent {
+            self.apparent_size
+        } else {
+            self.size
+        }
+    }
+}
+
+/// Where the traversal reads directories and metadata from.
+///
+/// The default is the local filesystem. Other implementations can feed the scan
+/// from mock trees in tests, archives, or remote listings.
+pub trait ScanSource: Send + Sync {
+    /// Returns the metadata of `path`, following symbolic links.
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata>;
+
+    /// Returns the metadata of `path` itself, without following symbolic links.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata>;
+
+    /// Returns the paths of the entries in the directory at `path`.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Returns the paths of the entries in the directory at `path`, with their kinds where
+    /// the listing tells them without reading the metadata of each entry.
+    ///
+    /// The kinds let the scan leave out files without reading their metadata, as with
+    /// [`sample`](crate::OptionsBuilder::sample). By default, no kind is known.
+    fn read_dir_kinds(&self, path: &Path) -> io::Result<Vec<(PathBuf, Option<EntryKind>)>> {
+        Ok(self
+            .read_dir(path)?
+            .into_iter()
+            .map(|path| (path, None))
+            .collect())
+    }
+}
+
+/// The local filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalSource;
+
+impl ScanSource for LocalSource {
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        Ok(EntryMetadata::from_std(path, &fs::metadata(path)?))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        Ok(EntryMetadata::from_std(path, &fs::symlink_metadata(path)?))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        read_dir_noatime(path)
+    }
+
+    fn read_dir_kinds(&self, path: &Path) -> io::Result<Vec<(PathBuf, Option<EntryKind>)>> {
+        read_dir_kinds(path)
+    }
+}
+
+/// An in-memory directory tree.
+///
+/// ```
+/// use gpscan::source::MemorySource;
+///
+/// let mut source = MemorySource::new("/data");
+/// source.add_file("/data/logs/app.log", 2048);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemorySource {
+    nodes: HashMap<PathBuf, MemoryNode>,
+    next_inode: u64,
+}
+
+#[derive(Debug, Clone)]
+struct MemoryNode {
+    metadata: EntryMetadata,
+    children: Vec<PathBuf>,
+}
+
+impl MemorySource {
+    /// Creates a tree containing only the root directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let mut source = MemorySource {
+            nodes: HashMap::new(),
+            next_inode: 1,
+        };
+        source.insert(root.into(), EntryMetadata::new(EntryKind::Dir, 0));
+        source
+    }
+
+    /// Adds a directory, creating missing parent directories.
+    pub fn add_dir(&mut self, path: impl Into<PathBuf>) -> &mut EntryMetadata {
+        self.add(path.into(), EntryMetadata::new(EntryKind::Dir, 0))
+    }
+
+    /// Adds a file of the given size, creating missing parent directories.
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, size: u64) -> &mut EntryMetadata {
+        self.add(path.into(), EntryMetadata::new(EntryKind::File, size))
+    }
+
+    /// Adds an entry with the given metadata, creating missing parent directories.
+    ///
+    /// An inode number of 0 is replaced with a unique value.
+    pub fn add(&mut self, path: PathBuf, metadata: EntryMetadata) -> &mut EntryMetadata {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !self.nodes.contains_key(parent) {
+                self.add_dir(parent);
+            }
+            if let Some(node) = self.nodes.get_mut(parent) {
+                if !node.children.contains(&path) {
+                    node.children.push(path.clone());
+                }
+            }
+        }
+        self.insert(path, metadata)
+    }
+
+    fn insert(&mut self, path: PathBuf, mut metadata: EntryMetadata) -> &mut EntryMetadata {
+        if metadata.inode == 0 {
+            metadata.inode = self.next_inode;
+            self.next_inode += 1;
+        }
+        let node = self.nodes.entry(path).or_insert(MemoryNode {
+            metadata: metadata.clone(),
+            children: Vec::new(),
+        });
+        node.metadata = metadata;
+        &mut node.metadata
+    }
+
+    fn node(&self, path: &Path) -> io::Result<&MemoryNode> {
+        self.nodes.get(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such file or directory: {}", path.display()),
+            )
+        })
+    }
+}
+
+impl ScanSource for MemorySource {
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.symlink_metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        Ok(self.node(path)?.metadata.clone())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let node = self.node(path)?;
+        if !node.metadata.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("Not a directory: {}", path.display()),
+            ));
+        }
+        Ok(node.children.clone())
+    }
+
+    fn read_dir_kinds(&self, path: &Path) -> io::Result<Vec<(PathBuf, Option<EntryKind>)>> {
+        Ok(self
+            .read_dir(path)?
+            .into_iter()
+            .map(|child| {
+                let kind = self.nodes.get(&child).map(|node| node.metadata.kind);
+                (child, kind)
+            })
+            .collect())
+    }
+}
+
+/// A user-supplied scan source.
+#[derive(Clone)]
+pub(crate) struct CustomSource(pub(crate) Arc<dyn ScanSource>);
+
+impl fmt::Debug for CustomSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomSource")
+    }
+}
@@ -0,0 +1,275 @@
+// External crates
+use flate2::{write::GzEncoder, Compression};
+use log::info;
+
+// Standard library imports
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::TempFile;
+use crate::model::ScanInfo;
+use crate::options::{Options, Timestamps};
+use crate::output::OutputFormatter;
+use crate::scan::ScanEntry;
+use crate::xml_output::XmlFormatter;
+
+/// Writes a dump in numbered parts (`--split-size`), `scan.gpscan.001`, `scan.gpscan.002`, ...
+///
+/// A new part is started once the bytes written to the current one, after compression,
+/// exceed the size limit. Every part is a complete dump with the same `ScanInfo`: the
+/// folders that are open when a part ends are closed in it and opened again in the next
+/// one. A part is completed as soon as it ends, so only the last one has the end-of-scan
+/// values in `ScanSummary`.
+pub(crate) struct SplitFormatter {
+    output: String,
+    limit: u64,
+    compress: bool,
+    timestamps: Option<Timestamps>,
+    strict_names: bool,
+    info: Option<ScanInfo>,
+    /// Folders entered but not yet left, outermost first.
+    open: Vec<ScanEntry>,
+    part: Option<Part>,
+    /// Number of parts started so far.
+    parts: usize,
+    /// Whether the current part has entries besides the reopened folders.
+    started: bool,
+}
+
+struct Part {
+    path: PathBuf,
+    formatter: XmlFormatter<PartWriter>,
+    // Declared last, so that the file is closed before it is removed
+    temp: TempFile,
+}
+
+impl Part {
+    /// Completes the part and moves it to its final name.
+    fn finish(self, info: &ScanInfo) -> io::Result<()> {
+        let Part {
+            path,
+            mut formatter,
+            mut temp,
+        } = self;
+        formatter.end_scan(info)?;
+        formatter.into_inner().finish()?;
+        temp.persist(&path)?;
+        info!("Wrote {}", path.display());
+        Ok(())
+    }
+}
+
+impl SplitFormatter {
+    pub(crate) fn new(output: &str, limit: u64, compress: bool, options: &Options) -> Self {
+        SplitFormatter {
+            output: output.to_string(),
+            limit,
+            compress,
+            timestamps: options.timestamps,
+            strict_names: options.strict_names,
+            info: None,
+            open: Vec::new(),
+            part: None,
+            parts: 0,
+            started: false,
+        }
+    }
+
+    fn current(&mut self) -> io::Result<&mut XmlFormatter<PartWriter>> {
+        self.part
+            .as_mut()
+            .map(|part| &mut part.formatter)
+            .ok_or_else(|| io::Error::other("begin_scan was not called"))
+    }
+
+    fn info(&self) -> io::Result<&ScanInfo> {
+        self.info
+            .as_ref()
+            .ok_or_else(|| io::Error::other("begin_scan was not called"))
+    }
+
+    /// Starts a new part, with the currently open folders.
+    fn start_part(&mut self) -> io::Result<()> {
+        let path = PathBuf::from(format!("{}.{:03}", self.output, self.parts + 1));
+        let temp_path = temp_path(&path);
+        let writer = PartWriter::create(&path, &temp_path, self.compress)?;
+        let temp = TempFile::new(temp_path);
+        let mut formatter = XmlFormatter::new(writer).strict_names(self.strict_names);
+        if let Some(timestamps) = self.timestamps {
+            formatter = formatter.timestamps(timestamps);
+        }
+        formatter.begin_scan(self.info()?)?;
+        for folder in &self.open {
+            formatter.begin_folder(folder)?;
+        }
+        self.part = Some(Part {
+            path,
+            formatter,
+            temp,
+        });
+        self.parts += 1;
+        self.started = false;
+        Ok(())
+    }
+
+    /// Moves on to the next part if the current one is full.
+    fn check_limit(&mut self) -> io::Result<()> {
+        if !self.started || self.current()?.get_ref().written() < self.limit {
+            self.started = true;
+            return Ok(());
+        }
+        if let Some(mut part) = self.part.take() {
+            for folder in self.open.iter().rev() {
+                part.formatter.end_folder(folder)?;
+            }
+            part.finish(self.info()?)?;
+        }
+        self.start_part()?;
+        self.started = true;
+        Ok(())
+    }
+}
+
+impl OutputFormatter for SplitFormatter {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.info = Some(info.clone());
+        self.start_part()
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.check_limit()?;
+        self.current()?.begin_folder(entry)?;
+        self.open.push(entry.clone());
+        Ok(())
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.check_limit()?;
+        self.current()?.file(entry)
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.open.pop();
+        self.current()?.end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.current()?.comment(text)
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        match self.part.take() {
+            Some(part) => part.finish(info),
+            None => Err(io::Error::other("begin_scan was not called")),
+        }
+    }
+}
+
+/// Output of one part, written to a temporary file that is renamed once it is complete.
+enum PartWriter {
+    Plain(CountingFile),
+    Gzip(GzEncoder<CountingFile>),
+}
+
+impl PartWriter {
+    fn create(path: &Path, temp_path: &Path, compress: bool) -> io::Result<Self> {
+        let file = File::create(temp_path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Failed to create output file '{}': {}", path.display(), e),
+            )
+        })?;
+        let file = CountingFile {
+            inner: BufWriter::new(file),
+            written: 0,
+        };
+        Ok(if compress {
+            PartWriter::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            PartWriter::Plain(file)
+        })
+    }
+
+    /// Returns the number of bytes written to the file so far, after compression.
+    fn written(&self) -> u64 {
+        match self {
+            PartWriter::Plain(file) => file.written,
+            PartWriter::Gzip(encoder) => encoder.get_ref().written,
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            PartWriter::Plain(file) => file,
+            PartWriter::Gzip(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for PartWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            PartWriter::Plain(file) => file.write(buf),
+            PartWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            PartWriter::Plain(file) => file.flush(),
+            PartWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Counts the bytes written to the file of a part.
+struct CountingFile {
+    inner: BufWriter<File>,
+    written: u64,
+}
+
+impl Write for CountingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    PathBuf::from(temp_path)
+}
+
+/// Parses a size such as `500M` or `1G` (powers of 1024), or a number of bytes.
+pub(crate) fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size: {}", value))?;
+    let exponent = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return Err(format!("invalid size unit (use K, M, G, or T): {}", value)),
+    };
+    if number == 0 {
+        return Err("size must be greater than zero".to_string());
+    }
+    number
+        .checked_mul(1024u64.pow(exponent))
+        .ok_or_else(|| format!("size is too large: {}", value))
+}
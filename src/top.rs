@@ -0,0 +1,196 @@
+// Standard library imports
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::model::{FileNode, NodeKind, ScanInfo};
+use crate::output::OutputFormatter;
+use crate::scan::ScanEntry;
+
+/// The largest files and folders of a tree.
+#[derive(Debug, Clone, Default)]
+pub struct TopEntries {
+    /// Largest files with their size, largest first.
+    pub files: Vec<(PathBuf, u64)>,
+    /// Largest folders with their cumulative size, largest first.
+    pub folders: Vec<(PathBuf, u64)>,
+}
+
+/// Returns the `n` largest files and the `n` largest folders by cumulative size.
+///
+/// Paths are built from the node names, starting with the name of `root`.
+/// Entries of equal size are ordered by path.
+pub fn top_entries(root: &FileNode, n: usize) -> TopEntries {
+    let mut top = TopEntries::default();
+    collect(root, Path::new(""), &mut top);
+
+    for list in [&mut top.files, &mut top.folders] {
+        list.sort_by(|a, b| Reverse(a.1).cmp(&Reverse(b.1)).then_with(|| a.0.cmp(&b.0)));
+        list.truncate(n);
+    }
+    top
+}
+
+/// Collects all files and folders below `node`, returning its cumulative size.
+fn collect(node: &FileNode, parent: &Path, top: &mut TopEntries) -> u64 {
+    let path = parent.join(&node.name);
+    match node.kind {
+        NodeKind::File => {
+            top.files.push((path, node.size));
+            node.size
+        }
+        NodeKind::Folder => {
+            let size = node
+                .children
+                .iter()
+                .map(|child| collect(child, &path, top))
+                .sum();
+            top.folders.push((path, size));
+            size
+        }
+    }
+}
+
+/// The `capacity` greatest items added so far, kept in a bounded min-heap.
+///
+/// Adding an item takes `O(log capacity)` time, and memory stays bounded by
+/// `capacity` however many items are added.
+#[derive(Debug, Clone)]
+pub(crate) struct TopN<T: Ord> {
+    capacity: usize,
+    // The least of the kept items is at the top, to be replaced first
+    heap: BinaryHeap<Reverse<T>>,
+}
+
+impl<T: Ord + Clone> TopN<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        TopN {
+            capacity,
+            heap: BinaryHeap::with_capacity(capacity + 1),
+        }
+    }
+
+    /// Adds an item, dropping the least one if more than `capacity` are kept.
+    pub(crate) fn push(&mut self, item: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.heap.len() == self.capacity {
+            match self.heap.peek() {
+                Some(Reverse(least)) if item > *least => {
+                    self.heap.pop();
+                }
+                _ => return,
+            }
+        }
+        self.heap.push(Reverse(item));
+    }
+
+    /// Returns the kept items, greatest first.
+    pub(crate) fn to_sorted_vec(&self) -> Vec<T> {
+        let mut items: Vec<T> = self.heap.iter().map(|Reverse(item)| item.clone()).collect();
+        items.sort_by(|a, b| b.cmp(a));
+        items
+    }
+}
+
+/// The `n` largest files seen so far, kept in a bounded min-heap.
+///
+/// Adding a file takes `O(log n)` time, and memory stays bounded by `n` however
+/// many files are added.
+#[derive(Debug, Clone)]
+pub struct LargestFiles {
+    // Of files of equal size, those with the smaller path are kept
+    top: TopN<(u64, Reverse<PathBuf>)>,
+}
+
+impl LargestFiles {
+    pub fn new(capacity: usize) -> Self {
+        LargestFiles {
+            top: TopN::new(capacity),
+        }
+    }
+
+    /// Adds a file, dropping the smallest one if more than `capacity` are kept.
+    ///
+    /// Of files of equal size, those with the smaller path are kept.
+    pub fn push(&mut self, path: &Path, size: u64) {
+        if self.top.capacity > 0 {
+            self.top.push((size, Reverse(path.to_path_buf())));
+        }
+    }
+
+    /// Returns the kept files with their size, largest first, then by path.
+    pub fn to_vec(&self) -> Vec<(PathBuf, u64)> {
+        self.top
+            .to_sorted_vec()
+            .into_iter()
+            .map(|(size, Reverse(path))| (path, size))
+            .collect()
+    }
+}
+
+/// Tracks the largest files while passing everything on to another formatter.
+///
+/// ```
+/// use gpscan::{scan_with_formatter, LargestFilesCollector, Options, XmlFormatter};
+///
+/// let formatter = XmlFormatter::new(std::io::sink());
+/// let mut collector = LargestFilesCollector::new(formatter, 10);
+/// scan_with_formatter(std::path::Path::new("src"), &Options::default(), &mut collector)?;
+/// for (path, size) in collector.largest().to_vec() {
+///     println!("{} {}", size, path.display());
+/// }
+/// # Ok::<(), gpscan::Error>(())
+/// ```
+pub struct LargestFilesCollector<F> {
+    inner: F,
+    largest: LargestFiles,
+}
+
+impl<F: OutputFormatter> LargestFilesCollector<F> {
+    pub fn new(inner: F, n: usize) -> Self {
+        LargestFilesCollector {
+            inner,
+            largest: LargestFiles::new(n),
+        }
+    }
+
+    /// Returns the largest files seen so far.
+    pub fn largest(&self) -> &LargestFiles {
+        &self.largest
+    }
+
+    /// Returns the wrapped formatter.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: OutputFormatter> OutputFormatter for LargestFilesCollector<F> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.begin_scan(info)
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.begin_folder(entry)
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.largest.push(&entry.path, entry.size);
+        self.inner.file(entry)
+    }
+
+    fn end_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.inner.end_folder(entry)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.comment(text)
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.inner.end_scan(info)
+    }
+}
@@ -0,0 +1,137 @@
+// Standard library imports
+use std::fs;
+use std::path::Path;
+
+use crate::filters::SkipReason;
+use crate::options::Options;
+use crate::progress::format_bytes;
+use crate::scan::ScanStats;
+use crate::volume::VolumeInfo;
+
+/// Comparison of the scanned total with the used space of the volume, as reported by `df`.
+///
+/// The two rarely match exactly; [`causes`](Self::causes) lists the likely reasons for
+/// this scan, so that the numbers can be explained instead of just differing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TotalsCheck {
+    /// Total size of the scanned files in bytes, according to the selected size measure.
+    pub scanned: u64,
+    /// Used space of the volume in bytes: its size minus the space available to the user,
+    /// or 0 if the size of the volume is not known.
+    pub used: u64,
+    /// Mount point of the volume.
+    pub volume_path: String,
+    /// Likely reasons why the totals differ, most specific first.
+    pub causes: Vec<String>,
+}
+
+impl TotalsCheck {
+    /// Compares the `scanned` total of a scan of `root_path` with the used space of `volume`.
+    pub fn new(
+        root_path: &Path,
+        volume: &VolumeInfo,
+        scanned: u64,
+        stats: &ScanStats,
+        options: &Options,
+    ) -> Self {
+        let used = volume.size.saturating_sub(volume.free_space);
+        let mut causes = Vec::new();
+        let mut cause = |condition: bool, text: String| {
+            if condition {
+                causes.push(text);
+            }
+        };
+
+        let root = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+        cause(
+            root != Path::new(&volume.path),
+            format!(
+                "Only {} was scanned, not the whole volume mounted at {}",
+                root.display(),
+                volume.path
+            ),
+        );
+        if let Some(rate) = options.sample {
+            cause(
+                true,
+                format!(
+                    "Only a {}% sample of the files was scanned, so the total is an estimate",
+                    rate * 100.0
+                ),
+            );
+        }
+        cause(
+            options.apparent_size,
+            "Apparent sizes were counted, while the volume counts allocated blocks".to_string(),
+        );
+        cause(
+            options.cross_mount_points,
+            "Mount points were crossed, so files on other volumes are included".to_string(),
+        );
+        cause(
+            stats.errors > 0,
+            format!(
+                "{} entries could not be read, so their contents are missing",
+                stats.errors
+            ),
+        );
+        let skipped = |reason| stats.skipped.get(reason);
+        cause(
+            skipped(SkipReason::Excluded) > 0,
+            format!(
+                "{} entries were excluded by patterns",
+                skipped(SkipReason::Excluded)
+            ),
+        );
+        cause(
+            skipped(SkipReason::FilterCommand) > 0,
+            format!(
+                "{} entries were skipped by the filter command",
+                skipped(SkipReason::FilterCommand)
+            ),
+        );
+        cause(
+            skipped(SkipReason::LargeFolder) > 0,
+            format!(
+                "The entries of {} large folders were left out",
+                skipped(SkipReason::LargeFolder)
+            ),
+        );
+        cause(
+            skipped(SkipReason::OtherFileSystem) > 0,
+            format!(
+                "{} mount points were not crossed; files hidden below a mount point still use space",
+                skipped(SkipReason::OtherFileSystem)
+            ),
+        );
+        cause(
+            stats.hard_links > 0,
+            format!(
+                "{} hard links ({}) were counted once, like the volume stores them",
+                stats.hard_links,
+                format_bytes(stats.hard_link_bytes)
+            ),
+        );
+        cause(
+            !options.dir_entry_size && scanned < used,
+            "The space taken by directories themselves was not counted (see --dir-entry-size)"
+                .to_string(),
+        );
+        cause(
+            scanned < used,
+            "Space is also used by file system metadata, reserved blocks, snapshots, and deleted files that are still open".to_string(),
+        );
+
+        TotalsCheck {
+            scanned,
+            used,
+            volume_path: volume.path.clone(),
+            causes,
+        }
+    }
+
+    /// Returns the scanned total minus the used space, negative if less was found.
+    pub fn difference(&self) -> i64 {
+        self.scanned as i64 - self.used as i64
+    }
+}
@@ -0,0 +1,61 @@
+// Standard library imports
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom};
+
+#[cfg(feature = "s3")]
+use crate::s3::{put_object, S3Config, S3Location};
+
+/// Uploads the contents of `file` to `url`.
+///
+/// `http://` and `https://` URLs receive an HTTP PUT request. `s3://bucket/key`
+/// URLs are uploaded as an object with the credentials from the environment
+/// (requires the `s3` feature).
+pub fn upload(url: &str, file: &mut File) -> io::Result<()> {
+    file.seek(SeekFrom::Start(0))?;
+
+    if url.starts_with("s3://") {
+        return upload_s3(url, file);
+    }
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unsupported output URL (expected http, https, or s3): {}",
+                url
+            ),
+        ));
+    }
+
+    let length = file.metadata()?.len();
+    let response = ureq::put(url)
+        .set("Content-Type", "application/gzip")
+        .set("Content-Length", &length.to_string())
+        .send(file);
+    match response {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) => Err(io::Error::other(format!(
+            "upload failed with HTTP status {}",
+            status
+        ))),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+#[cfg(feature = "s3")]
+fn upload_s3(url: &str, file: &mut File) -> io::Result<()> {
+    let location: S3Location = url
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    put_object(&location, &S3Config::from_env(), file)
+}
+
+#[cfg(not(feature = "s3"))]
+fn upload_s3(url: &str, _file: &mut File) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "cannot upload to {}: gpscan was built without the s3 feature",
+            url
+        ),
+    ))
+}
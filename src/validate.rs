@@ -0,0 +1,250 @@
+// External crates
+use chrono::NaiveDateTime;
+use flate2::read::MultiGzDecoder;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+// Standard library imports
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+const TAG_GRANDPERSPECTIVE_SCAN_DUMP: &[u8] = b"GrandPerspectiveScanDump";
+const TAG_SCAN_INFO: &[u8] = b"ScanInfo";
+const TAG_FOLDER: &[u8] = b"Folder";
+const TAG_FILE: &[u8] = b"File";
+
+/// Summary of a successfully validated scan dump.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub folders: u64,
+    pub files: u64,
+    pub total_size: u64,
+}
+
+/// Opens a scan dump, transparently decompressing gzip streams.
+pub fn open_dump(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let is_gzip = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if is_gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Validates the scan dump at the given path.
+pub fn validate_file(path: &Path) -> io::Result<ValidationReport> {
+    validate(open_dump(path)?)
+}
+
+/// Validates a GrandPerspective scan dump.
+///
+/// Checks that the document is well-formed and complete, that the elements are
+/// nested as GrandPerspective expects, and that numeric and date attributes are
+/// in range. Unknown elements are ignored.
+pub fn validate<R: BufRead>(input: R) -> io::Result<ValidationReport> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    let mut stack: Vec<Vec<u8>> = Vec::new();
+    let mut report = ValidationReport::default();
+    let mut root_seen = false;
+    let mut scan_info_seen = false;
+    let mut root_folder_seen = false;
+
+    loop {
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| invalid_data(format!("Malformed XML at byte {}: {}", position, e)))?;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let name = e.name().as_ref().to_vec();
+                let parent = stack.last().map(|p| p.as_slice());
+
+                match name.as_slice() {
+                    TAG_GRANDPERSPECTIVE_SCAN_DUMP => {
+                        if parent.is_some() || root_seen {
+                            return Err(unexpected(&name, position));
+                        }
+                        root_seen = true;
+                        require_attribute(e, "formatVersion", position)?;
+                    }
+                    TAG_SCAN_INFO => {
+                        if parent != Some(TAG_GRANDPERSPECTIVE_SCAN_DUMP) || scan_info_seen {
+                            return Err(unexpected(&name, position));
+                        }
+                        scan_info_seen = true;
+                        check_scan_info(e, position)?;
+                    }
+                    TAG_FOLDER => {
+                        match parent {
+                            Some(TAG_SCAN_INFO) if !root_folder_seen => root_folder_seen = true,
+                            Some(TAG_FOLDER) => {}
+                            _ => return Err(unexpected(&name, position)),
+                        }
+                        require_attribute(e, "name", position)?;
+                        check_times(e, position)?;
+                        report.folders += 1;
+                    }
+                    TAG_FILE => {
+                        if parent != Some(TAG_FOLDER) || !is_empty {
+                            return Err(unexpected(&name, position));
+                        }
+                        require_attribute(e, "name", position)?;
+                        let size = require_attribute(e, "size", position)?;
+                        report.total_size += parse_u64("size", &size, position)?;
+                        check_times(e, position)?;
+                        report.files += 1;
+                    }
+                    _ if !root_seen => return Err(unexpected(&name, position)),
+                    _ => {}
+                }
+
+                if !is_empty {
+                    stack.push(name);
+                }
+            }
+            Event::End(_) => {
+                stack.pop();
+            }
+            Event::Text(ref t) => {
+                let text = t.unescape().map_err(|e| {
+                    invalid_data(format!("Malformed XML at byte {}: {}", position, e))
+                })?;
+                if !text.trim().is_empty() {
+                    return Err(invalid_data(format!(
+                        "Unexpected text content at byte {}",
+                        position
+                    )));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if !stack.is_empty() {
+        return Err(invalid_data(format!(
+            "Unexpected end of file: {} unclosed element(s), the dump is probably truncated",
+            stack.len()
+        )));
+    }
+    if !root_seen {
+        return Err(invalid_data("Missing GrandPerspectiveScanDump element"));
+    }
+    if !scan_info_seen {
+        return Err(invalid_data("Missing ScanInfo element"));
+    }
+    if !root_folder_seen {
+        return Err(invalid_data("Missing root Folder element"));
+    }
+
+    Ok(report)
+}
+
+fn check_scan_info(e: &BytesStart, position: u64) -> io::Result<()> {
+    require_attribute(e, "volumePath", position)?;
+    let volume_size = parse_u64(
+        "volumeSize",
+        &require_attribute(e, "volumeSize", position)?,
+        position,
+    )?;
+    let free_space = parse_u64(
+        "freeSpace",
+        &require_attribute(e, "freeSpace", position)?,
+        position,
+    )?;
+    if free_space > volume_size && volume_size > 0 {
+        return Err(invalid_data(format!(
+            "freeSpace ({}) exceeds volumeSize ({}) at byte {}",
+            free_space, volume_size, position
+        )));
+    }
+    parse_datetime(
+        "scanTime",
+        &require_attribute(e, "scanTime", position)?,
+        position,
+    )?;
+    if let Some(measure) = get_attribute(e, "fileSizeMeasure", position)? {
+        if measure != "physical" && measure != "logical" {
+            return Err(invalid_data(format!(
+                "Invalid fileSizeMeasure '{}' at byte {}",
+                measure, position
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_times(e: &BytesStart, position: u64) -> io::Result<()> {
+    for key in ["created", "modified", "accessed"] {
+        if let Some(value) = get_attribute(e, key, position)? {
+            parse_datetime(key, &value, position)?;
+        }
+    }
+    Ok(())
+}
+
+fn get_attribute(e: &BytesStart, key: &str, position: u64) -> io::Result<Option<String>> {
+    for attr in e.attributes() {
+        let attr = attr.map_err(|err| {
+            invalid_data(format!("Malformed attribute at byte {}: {}", position, err))
+        })?;
+        if attr.key.as_ref() == key.as_bytes() {
+            let value = attr.unescape_value().map_err(|err| {
+                invalid_data(format!("Malformed attribute at byte {}: {}", position, err))
+            })?;
+            return Ok(Some(value.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+fn require_attribute(e: &BytesStart, key: &str, position: u64) -> io::Result<String> {
+    get_attribute(e, key, position)?.ok_or_else(|| {
+        invalid_data(format!(
+            "Missing '{}' attribute on <{}> at byte {}",
+            key,
+            String::from_utf8_lossy(e.name().as_ref()),
+            position
+        ))
+    })
+}
+
+fn parse_u64(key: &str, value: &str, position: u64) -> io::Result<u64> {
+    value.parse::<u64>().map_err(|_| {
+        invalid_data(format!(
+            "Invalid {} '{}' at byte {}: expected a non-negative integer",
+            key, value, position
+        ))
+    })
+}
+
+fn parse_datetime(key: &str, value: &str, position: u64) -> io::Result<()> {
+    NaiveDateTime::parse_from_str(value, DATETIME_FORMAT)
+        .map(|_| ())
+        .map_err(|_| {
+            invalid_data(format!(
+                "Invalid {} '{}' at byte {}: expected YYYY-MM-DDThh:mm:ssZ",
+                key, value, position
+            ))
+        })
+}
+
+fn unexpected(name: &[u8], position: u64) -> io::Error {
+    invalid_data(format!(
+        "Unexpected <{}> element at byte {}",
+        String::from_utf8_lossy(name),
+        position
+    ))
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,599 @@
+// External crates
+use sysinfo::{Disk, Disks};
+
+// Standard library imports
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::platform::file_id;
+
+/// File system types that are accessed over the network. Generic FUSE mounts are
+/// included, since they mostly serve remote storage (sshfs, rclone, s3fs, ...).
+const NETWORK_FILE_SYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb",
+    "smb2",
+    "smb3",
+    "smbfs",
+    "afpfs",
+    "webdav",
+    "davfs",
+    "9p",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "afs",
+    "fuse",
+    "macfuse",
+    "osxfuse",
+];
+
+/// Information about the volume that contains the scan root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeInfo {
+    /// Mount point of the volume.
+    pub path: String,
+    /// Total size of the volume in bytes.
+    pub size: u64,
+    /// Space available to the current user in bytes.
+    pub free_space: u64,
+    /// Label of the volume, or the name of its device if it has no label.
+    pub name: Option<String>,
+    /// File system type, such as `ext4`, `NTFS`, or `apfs`.
+    pub file_system: Option<String>,
+    /// Identifier of the volume: the file system UUID on Linux, the volume GUID path on Windows.
+    pub id: Option<String>,
+}
+
+/// Retrieves volume information for the given path.
+pub fn get_volume_info(root_path: &Path, disks: &Disks) -> VolumeInfo {
+    // Convert root_path to absolute path
+    #[cfg(windows)]
+    let mut abs_root_path = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+
+    #[cfg(not(windows))]
+    let abs_root_path = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+
+    // Remove the "\\?\" prefix on Windows, turning "\\?\UNC\server\share" into "\\server\share"
+    #[cfg(windows)]
+    {
+        let path = abs_root_path.to_string_lossy().into_owned();
+        abs_root_path = match path.strip_prefix(r"\\?\UNC\") {
+            Some(unc) => PathBuf::from(format!(r"\\{}", unc)),
+            None => PathBuf::from(path.replacen(r"\\?\", "", 1)),
+        };
+    }
+
+    // Find the disk by the device of the scan root, or by its mount point
+    let disks: Vec<&Disk> = disks.iter().collect();
+    let mounts = mount_devices(&disks, &abs_root_path);
+    if let Some(index) = find_mount(&abs_root_path, device_id(&abs_root_path), &mounts) {
+        let disk = disks[index];
+        return VolumeInfo {
+            path: disk.mount_point().to_string_lossy().to_string(),
+            size: disk.total_space(),
+            free_space: disk.available_space(),
+            name: volume_name(disk),
+            file_system: non_empty(disk.file_system().to_string_lossy().into_owned()),
+            id: volume_id(disk),
+        };
+    }
+
+    // Network shares and mapped drives are not listed as disks
+    #[cfg(windows)]
+    if let Some(volume) = unlisted_volume_info(&abs_root_path) {
+        return volume;
+    }
+
+    // If no matching disk is found, return defaults
+    VolumeInfo {
+        path: "/".to_string(),
+        size: 0,
+        free_space: 0,
+        name: None,
+        file_system: None,
+        id: None,
+    }
+}
+
+/// Returns the index of the mount in `mounts`, given as mount points and the device IDs of
+/// their root directories, that holds `path` on the device `device`.
+///
+/// Mounts of the same device are preferred, those whose mount point contains `path` and
+/// then the deepest first, so that bind mounts and volumes mounted over others are told
+/// apart. Without one, such as in btrfs subvolumes, the deepest mount point containing
+/// `path` is used. Of mounts on the same mount point, the last one, mounted on top, wins.
+pub fn find_mount(
+    path: &Path,
+    device: Option<u64>,
+    mounts: &[(PathBuf, Option<u64>)],
+) -> Option<usize> {
+    let contains = |&index: &usize| path.starts_with(&mounts[index].0);
+    let depth = |&index: &usize| mounts[index].0.components().count();
+    (0..mounts.len())
+        .filter(|&index| device.is_some() && mounts[index].1 == device)
+        .max_by_key(|index| (contains(index), depth(index)))
+        .or_else(|| (0..mounts.len()).filter(contains).max_by_key(depth))
+}
+
+/// Returns the mount points of `disks` with the device IDs of their root directories.
+///
+/// Only the mount points containing `root` are looked up with `stat`, so that a hung
+/// network mount elsewhere cannot block the scan. On Linux, the devices of the others are
+/// read from the mount table; elsewhere they are not known.
+fn mount_devices(disks: &[&Disk], root: &Path) -> Vec<(PathBuf, Option<u64>)> {
+    #[cfg(target_os = "linux")]
+    let table = read_mountinfo().unwrap_or_default();
+    #[cfg(target_os = "linux")]
+    let listed_device = |mount_point: &Path| {
+        // The last mount on a mount point is on top
+        table
+            .iter()
+            .rev()
+            .find(|mount| mount.path == mount_point)
+            .map(|mount| mount.device)
+    };
+    #[cfg(not(target_os = "linux"))]
+    let listed_device = |_: &Path| None;
+
+    disks
+        .iter()
+        .map(|disk| {
+            let mount_point = disk.mount_point();
+            let device = if root.starts_with(mount_point) {
+                device_id(mount_point)
+            } else {
+                listed_device(mount_point)
+            };
+            (mount_point.to_path_buf(), device)
+        })
+        .collect()
+}
+
+/// Returns the device ID of the entry at `path`.
+fn device_id(path: &Path) -> Option<u64> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(file_id(path, &metadata, None).0)
+}
+
+/// Returns the root of the network share in a UNC path, such as `\\server\share\` for
+/// `\\server\share\dir` or `\\?\UNC\server\share\dir`, or `None` for other paths.
+pub fn unc_share_root(path: &str) -> Option<String> {
+    let rest = path.strip_prefix(r"\\?\UNC\").or_else(|| {
+        // "\\?\C:\" and "\\.\device" are not shares
+        path.strip_prefix(r"\\")
+            .filter(|rest| !rest.starts_with(['?', '.']))
+    })?;
+    let mut components = rest.split('\\');
+    let server = components.next().filter(|server| !server.is_empty())?;
+    let share = components.next().filter(|share| !share.is_empty())?;
+    Some(format!(r"\\{}\{}\", server, share))
+}
+
+/// Describes the volume of `path` directly, for network shares (`\\server\share`) and mapped
+/// drives, which sysinfo does not list.
+#[cfg(windows)]
+fn unlisted_volume_info(path: &Path) -> Option<VolumeInfo> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetVolumeInformationW, GetVolumePathNameW,
+    };
+
+    // Long enough for the usual share and mount point paths
+    const VOLUME_PATH_LEN: usize = 1024;
+    // The maximum length of volume labels and file system names, with the terminating null
+    const NAME_LEN: usize = 261;
+
+    let wide_path: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut buffer = [0u16; VOLUME_PATH_LEN];
+    // SAFETY: `wide_path` is NUL-terminated and `buffer` is writable for its whole length.
+    let ok =
+        unsafe { GetVolumePathNameW(wide_path.as_ptr(), buffer.as_mut_ptr(), buffer.len() as u32) };
+    let root = if ok != 0 {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        String::from_utf16_lossy(&buffer[..len])
+    } else {
+        unc_share_root(&path.to_string_lossy())?
+    };
+    let wide_root: Vec<u16> = root.encode_utf16().chain(std::iter::once(0)).collect();
+
+    let (mut available, mut total, mut free) = (0u64, 0u64, 0u64);
+    // SAFETY: `wide_root` is NUL-terminated and the sizes are valid, writable integers.
+    if unsafe { GetDiskFreeSpaceExW(wide_root.as_ptr(), &mut available, &mut total, &mut free) }
+        == 0
+    {
+        return None;
+    }
+
+    let mut label = [0u16; NAME_LEN];
+    let mut file_system = [0u16; NAME_LEN];
+    // SAFETY: `wide_root` is NUL-terminated, the buffers are writable for their whole
+    // length, and the serial number, component length, and flags are not requested.
+    let ok = unsafe {
+        GetVolumeInformationW(
+            wide_root.as_ptr(),
+            label.as_mut_ptr(),
+            label.len() as u32,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            file_system.as_mut_ptr(),
+            file_system.len() as u32,
+        )
+    };
+    let text = |buffer: &[u16]| {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        non_empty(String::from_utf16_lossy(&buffer[..len]))
+    };
+    let (label, file_system) = if ok != 0 {
+        (text(&label), text(&file_system))
+    } else {
+        (None, None)
+    };
+
+    Some(VolumeInfo {
+        name: label.or_else(|| unc_share_root(&root)),
+        path: root,
+        size: total,
+        free_space: available,
+        file_system,
+        id: None,
+    })
+}
+
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn volume_name(disk: &Disk) -> Option<String> {
+    device_link("/dev/disk/by-label", disk)
+        .or_else(|| non_empty(disk.name().to_string_lossy().into_owned()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn volume_name(disk: &Disk) -> Option<String> {
+    non_empty(disk.name().to_string_lossy().into_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn volume_id(disk: &Disk) -> Option<String> {
+    device_link("/dev/disk/by-uuid", disk)
+}
+
+#[cfg(windows)]
+fn volume_id(disk: &Disk) -> Option<String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetVolumeNameForVolumeMountPointW;
+
+    let mut mount_point: Vec<u16> = disk.mount_point().as_os_str().encode_wide().collect();
+    if mount_point.last() != Some(&(b'\\' as u16)) {
+        mount_point.push(b'\\' as u16);
+    }
+    mount_point.push(0);
+    // A volume GUID path has 49 characters and a terminating null
+    let mut buffer = [0u16; 50];
+    let ok = unsafe {
+        GetVolumeNameForVolumeMountPointW(
+            mount_point.as_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    non_empty(String::from_utf16_lossy(&buffer[..len]))
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn volume_id(_disk: &Disk) -> Option<String> {
+    None
+}
+
+/// Returns the name of the link in `directory` that points to the device of `disk`.
+///
+/// udev escapes characters such as spaces in these names as `\xNN`.
+#[cfg(target_os = "linux")]
+fn device_link(directory: &str, disk: &Disk) -> Option<String> {
+    let device = fs::canonicalize(disk.name()).ok()?;
+    fs::read_dir(directory)
+        .ok()?
+        .flatten()
+        .find(|entry| fs::canonicalize(entry.path()).is_ok_and(|target| target == device))
+        .map(|entry| unescape_udev(&entry.file_name().to_string_lossy()))
+}
+
+#[cfg(target_os = "linux")]
+fn unescape_udev(name: &str) -> String {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .strip_prefix(b"x")
+            .and_then(|hex| hex.get(..2))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(value) if byte == b'\\' => {
+                bytes.push(value);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Returns the mount points of the mounted volumes, sorted and without duplicates.
+///
+/// Pseudo file systems such as `proc` and `sysfs` are not listed.
+pub fn mount_points(disks: &Disks) -> Vec<PathBuf> {
+    let mut mount_points: Vec<PathBuf> = disks
+        .iter()
+        .map(|disk| disk.mount_point().to_path_buf())
+        .filter(|mount_point| mount_point.is_dir())
+        .collect();
+    mount_points.sort();
+    mount_points.dedup();
+    mount_points
+}
+
+/// Returns the name of a volume for output file names: its mount point with
+/// separators replaced by `_`, `C` for `C:\`, or `root` for `/`.
+pub fn mount_point_name(mount_point: &Path) -> String {
+    let name: String = mount_point
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let name = name.trim_matches('_');
+    if name.is_empty() {
+        "root".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns the name of the physical disk that holds the volume mounted at `mount_point`,
+/// such as `sda` for `/dev/sda2`, or `None` if it is not known.
+///
+/// Volumes on different disks can be scanned at the same time without competing for the
+/// same device. The disk is looked up in `/sys/class/block` on Linux, following device
+/// mapper and RAID devices to the disk of their first member; elsewhere it is not known.
+#[cfg(target_os = "linux")]
+pub fn physical_disk(mount_point: &Path, disks: &Disks) -> Option<String> {
+    let disk = disks
+        .iter()
+        .find(|disk| disk.mount_point() == mount_point)?;
+    let device = fs::canonicalize(disk.name()).ok()?;
+    block_device_disk(device.file_name()?.to_str()?, 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn physical_disk(_mount_point: &Path, _disks: &Disks) -> Option<String> {
+    None
+}
+
+/// Returns the disk of the block device `name`: the parent of a partition, or the disk of
+/// the first member of a device mapper or RAID device.
+#[cfg(target_os = "linux")]
+fn block_device_disk(name: &str, depth: usize) -> Option<String> {
+    // Stacked devices are rarely more than a few levels deep
+    const MAX_DEPTH: usize = 8;
+
+    let sys = Path::new("/sys/class/block").join(name);
+    if sys.join("partition").exists() {
+        let device = fs::canonicalize(&sys).ok()?;
+        return Some(device.parent()?.file_name()?.to_str()?.to_string());
+    }
+    let member = fs::read_dir(sys.join("slaves")).ok().and_then(|entries| {
+        let mut members: Vec<String> = entries
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        members.sort();
+        members.into_iter().next()
+    });
+    match member {
+        Some(member) if depth < MAX_DEPTH => block_device_disk(&member, depth + 1),
+        _ if sys.exists() => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns the root directories of the lettered drives (`C:\`, `D:\`, ...).
+///
+/// Fixed drives are always listed; removable and network drives only if asked for.
+#[cfg(windows)]
+pub fn drives(include_removable: bool, include_network: bool) -> Vec<PathBuf> {
+    use windows_sys::Win32::Storage::FileSystem::{GetDriveTypeW, GetLogicalDrives};
+    use windows_sys::Win32::System::WindowsProgramming::{
+        DRIVE_FIXED, DRIVE_REMOTE, DRIVE_REMOVABLE,
+    };
+
+    // Bit 0 is drive A, bit 1 is drive B, and so on
+    let mask = unsafe { GetLogicalDrives() };
+    (0..26u8)
+        .filter(|index| mask & (1 << index) != 0)
+        .map(|index| format!("{}:\\", (b'A' + index) as char))
+        .filter(|root| {
+            let wide: Vec<u16> = root.encode_utf16().chain(Some(0)).collect();
+            match unsafe { GetDriveTypeW(wide.as_ptr()) } {
+                DRIVE_FIXED => true,
+                DRIVE_REMOVABLE => include_removable,
+                DRIVE_REMOTE => include_network,
+                // Optical drives, RAM disks, and unknown types
+                _ => false,
+            }
+        })
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Returns true if `file_system` (as reported by the OS, such as `nfs4` or `fuse.sshfs`)
+/// is a network file system.
+pub fn is_network_file_system(file_system: &str) -> bool {
+    let file_system = file_system.to_ascii_lowercase();
+    NETWORK_FILE_SYSTEMS.contains(&file_system.as_str()) || file_system.starts_with("fuse.")
+}
+
+/// Returns the file system type of `path` if it is on a network file system.
+pub fn network_file_system(path: &Path) -> Option<String> {
+    let device = device_id(path)?;
+    file_system_types()
+        .remove(&device)
+        .filter(|file_system| is_network_file_system(file_system))
+}
+
+/// Returns the file system type of every mounted volume, by device ID.
+pub fn file_system_types() -> HashMap<u64, String> {
+    // The mount table also lists the network file systems that sysinfo leaves out
+    #[cfg(target_os = "linux")]
+    if let Ok(mounts) = read_mountinfo() {
+        return mounts
+            .into_iter()
+            .map(|mount| (mount.device, mount.file_system))
+            .collect();
+    }
+
+    Disks::new_with_refreshed_list()
+        .iter()
+        .filter_map(|disk| {
+            let metadata = fs::metadata(disk.mount_point()).ok()?;
+            Some((
+                file_id(disk.mount_point(), &metadata, None).0,
+                disk.file_system().to_string_lossy().into_owned(),
+            ))
+        })
+        .collect()
+}
+
+/// Returns the mount points with their file system types, in the order they were mounted.
+///
+/// The table is read from `/proc/self/mountinfo` on Linux, and is empty elsewhere.
+#[cfg(target_os = "linux")]
+pub fn mount_table() -> Vec<(PathBuf, String)> {
+    fs::read_to_string("/proc/self/mountinfo")
+        .map(|mountinfo| parse_mount_table(&mountinfo))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn mount_table() -> Vec<(PathBuf, String)> {
+    Vec::new()
+}
+
+/// Parses the contents of `/proc/self/mountinfo` into the mount points and their file
+/// system types.
+#[cfg(target_os = "linux")]
+pub fn parse_mount_table(mountinfo: &str) -> Vec<(PathBuf, String)> {
+    parse_mountinfo(mountinfo)
+        .into_iter()
+        .map(|mount| (mount.path, mount.file_system))
+        .collect()
+}
+
+/// Returns true if `path` is a btrfs subvolume that is not mounted on its own, given the
+/// `mounts` of [`mount_table`].
+///
+/// Such a subvolume has a device ID of its own, but belongs to the file system
+/// it is in. The root directory of every subvolume has inode 256.
+pub fn is_btrfs_subvolume(path: &Path, inode: u64, mounts: &[(PathBuf, String)]) -> bool {
+    const SUBVOLUME_ROOT_INODE: u64 = 256;
+
+    if inode != SUBVOLUME_ROOT_INODE || mounts.is_empty() {
+        return false;
+    }
+    fs::canonicalize(path).is_ok_and(|path| is_in_btrfs_mount(&path, mounts))
+}
+
+/// Returns true if the deepest of `mounts` containing the absolute `path` is a btrfs
+/// file system mounted somewhere above it. Of mounts on the same mount point, the last
+/// one, mounted on top, wins.
+pub fn is_in_btrfs_mount(path: &Path, mounts: &[(PathBuf, String)]) -> bool {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .is_some_and(|(mount_point, file_system)| file_system == "btrfs" && mount_point != path)
+}
+
+/// An entry of the mount table.
+#[cfg(target_os = "linux")]
+struct Mount {
+    device: u64,
+    path: PathBuf,
+    file_system: String,
+}
+
+#[cfg(target_os = "linux")]
+fn read_mountinfo() -> std::io::Result<Vec<Mount>> {
+    Ok(parse_mountinfo(&fs::read_to_string(
+        "/proc/self/mountinfo",
+    )?))
+}
+
+/// Parses `/proc/self/mountinfo`, where a line looks like
+/// `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw`.
+#[cfg(target_os = "linux")]
+fn parse_mountinfo(mountinfo: &str) -> Vec<Mount> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (mount, file_system) = line.split_once(" - ")?;
+            let mut fields = mount.split_whitespace();
+            let (major, minor) = fields.nth(2)?.split_once(':')?;
+            let (major, minor): (u64, u64) = (major.parse().ok()?, minor.parse().ok()?);
+            // The encoding of `makedev` in glibc
+            let device = ((major & 0xfffff000) << 32)
+                | ((major & 0xfff) << 8)
+                | ((minor & 0xffffff00) << 12)
+                | (minor & 0xff);
+            Some(Mount {
+                device,
+                path: PathBuf::from(unescape_octal(fields.nth(1)?)),
+                file_system: file_system.split_whitespace().next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Decodes the `\040`-style escapes of spaces and other characters in the mount table.
+#[cfg(target_os = "linux")]
+fn unescape_octal(field: &str) -> String {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match escaped {
+            Some(value) if byte == b'\\' => {
+                bytes.push(value);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -0,0 +1,264 @@
+// External crates
+use clap::ArgMatches;
+use log::{error, info, warn};
+use sysinfo::{Disks, System};
+
+// Standard library imports
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::error::Error;
+#[cfg(windows)]
+use crate::filesystem::placeholder_template;
+use crate::filesystem::{
+    dump_formatter, lock_root, output_name, scan_options, scan_with_metrics, write_dump,
+    write_dump_file,
+};
+use crate::merge::merge;
+use crate::metrics::MetricsCollector;
+use crate::notify::Notifier;
+use crate::options::Options;
+use crate::output::write_result;
+use crate::scan::{scan_to_result, ScanStats};
+use crate::volume::{mount_point_name, mount_points, network_file_system, physical_disk};
+
+/// Placeholder in the output name that is replaced with the name of each volume.
+const VOLUME_PLACEHOLDER: &str = "{volume}";
+
+/// Scans every mounted volume (`--all-volumes`).
+///
+/// If the output name contains `{volume}`, each volume is written to its own dump.
+/// Otherwise the volumes are combined into one dump, under a root folder named after the host.
+pub(crate) fn run_all_volumes(matches: &ArgMatches) -> Result<ScanStats, Error> {
+    let mut volumes = mount_points(&Disks::new_with_refreshed_list());
+    if matches.get_flag("local-only") {
+        volumes.retain(|volume| match network_file_system(volume) {
+            Some(file_system) => {
+                info!("Skipping {} ({})", volume.display(), file_system);
+                false
+            }
+            None => true,
+        });
+    }
+    if volumes.is_empty() {
+        return Err(Error::InvalidRoot("No mounted volumes found".to_string()));
+    }
+    info!("Scanning {} volumes", volumes.len());
+
+    // Every volume is scanned on its own, so nested volumes must not be entered twice
+    let mut options = scan_options(matches)?;
+    options.cross_mount_points = false;
+
+    match output_name(matches) {
+        Some(template) if template.contains(VOLUME_PLACEHOLDER) => {
+            scan_each(&volumes, &options, &template, matches)
+        }
+        _ => scan_combined(&volumes, &options, matches),
+    }
+}
+
+/// Scans every lettered drive (`--all-drives`) to one dump per drive.
+///
+/// The drive letter replaces `{volume}` in the output name, which is added before
+/// the extension if missing, so that `scan.gpscan` becomes `scan-C.gpscan`.
+#[cfg(windows)]
+pub(crate) fn run_all_drives(matches: &ArgMatches) -> Result<ScanStats, Error> {
+    let drives = crate::volume::drives(
+        matches.get_flag("removable-drives"),
+        matches.get_flag("network-drives"),
+    );
+    if drives.is_empty() {
+        return Err(Error::InvalidRoot("No drives found".to_string()));
+    }
+    info!("Scanning {} drives", drives.len());
+
+    let template = placeholder_template(
+        &output_name(matches).expect("--all-drives requires --output"),
+        VOLUME_PLACEHOLDER,
+    );
+    let mut options = scan_options(matches)?;
+    options.cross_mount_points = false;
+    scan_each(&drives, &options, &template, matches)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn run_all_drives(_matches: &ArgMatches) -> Result<ScanStats, Error> {
+    Err(Error::InvalidRoot(
+        "--all-drives is only available on Windows (use --all-volumes)".to_string(),
+    ))
+}
+
+/// Writes one dump per volume. A failed volume is logged, and the last failure is
+/// returned once all volumes were tried.
+fn scan_each(
+    volumes: &[PathBuf],
+    options: &Options,
+    template: &str,
+    matches: &ArgMatches,
+) -> Result<ScanStats, Error> {
+    let notifier = Notifier::from_matches(matches);
+    let mut total = ScanStats::default();
+    let mut failure = None;
+
+    let outcomes = for_each_volume(volumes, matches, |volume| {
+        let root = volume.to_string_lossy();
+        let path = template.replace(VOLUME_PLACEHOLDER, &mount_point_name(volume));
+        info!("Scanning {} to {}", root, path);
+
+        let outcome = lock_root(&root, matches).and_then(|_lock| {
+            write_dump_file(matches, Path::new(&path), |handle| {
+                scan_with_metrics(volume, options, dump_formatter(matches, handle))
+            })
+        });
+        notifier.notify(
+            &root,
+            Some(&path),
+            outcome.as_ref().map(|(_, metrics)| metrics),
+        );
+        outcome
+    });
+
+    for (volume, outcome) in volumes.iter().zip(outcomes) {
+        match outcome {
+            Ok((stats, _)) => add_stats(&mut total, &stats),
+            Err(e) => {
+                error!("Failed to scan {}: {}", volume.display(), e);
+                failure = Some(e);
+            }
+        }
+    }
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(total),
+    }
+}
+
+/// Writes all volumes to a single dump. Volumes that cannot be scanned are left out.
+fn scan_combined(
+    volumes: &[PathBuf],
+    options: &Options,
+    matches: &ArgMatches,
+) -> Result<ScanStats, Error> {
+    let root_name = System::host_name().unwrap_or_else(|| "volumes".to_string());
+    let mut total = ScanStats::default();
+    let mut results = Vec::new();
+
+    let scanned = for_each_volume(volumes, matches, |volume| {
+        let root = volume.to_string_lossy();
+        info!("Scanning {}", root);
+        lock_root(&root, matches).and_then(|_lock| scan_to_result(volume, options))
+    });
+    for (volume, scanned) in volumes.iter().zip(scanned) {
+        match scanned {
+            Ok((result, stats)) => {
+                add_stats(&mut total, &stats);
+                results.push(result);
+            }
+            Err(e) => warn!("Skipping volume {}: {}", volume.display(), e),
+        }
+    }
+    if results.is_empty() {
+        return Err(Error::InvalidRoot(
+            "None of the mounted volumes could be scanned".to_string(),
+        ));
+    }
+
+    let merged = merge(results, &root_name);
+    let outcome = write_dump(matches, |handle| {
+        let mut collector = MetricsCollector::new(dump_formatter(matches, handle));
+        write_result(&merged, &mut collector)?;
+        Ok(collector.into_parts().0)
+    })
+    .map(|mut metrics| {
+        metrics.errors = total.errors;
+        metrics.hard_link_bytes = total.hard_link_bytes;
+        metrics
+    });
+    let output = matches
+        .get_one::<String>("output-url")
+        .cloned()
+        .or_else(|| output_name(matches));
+    Notifier::from_matches(matches).notify(&root_name, output.as_deref(), outcome.as_ref());
+    outcome.map(|_| total)
+}
+
+/// Calls `scan` for each volume and returns the results in the order of `volumes`.
+///
+/// With `--parallel-volumes`, the volumes on each physical disk are scanned on a thread
+/// of their own, one after another, so that the disks are read at the same time without
+/// competing with themselves. Volumes on an unknown disk share one thread.
+fn for_each_volume<T, F>(volumes: &[PathBuf], matches: &ArgMatches, scan: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    if !matches.get_flag("parallel-volumes") {
+        return volumes.iter().map(|volume| scan(volume)).collect();
+    }
+
+    let groups = disk_groups(volumes, &Disks::new_with_refreshed_list());
+    info!(
+        "Scanning {} volumes on {} disks in parallel",
+        volumes.len(),
+        groups.len()
+    );
+    let mut results: Vec<Option<T>> = volumes.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let workers: Vec<_> = groups
+            .iter()
+            .map(|group| {
+                let scan = &scan;
+                scope.spawn(move || {
+                    group
+                        .iter()
+                        .map(|&index| (index, scan(&volumes[index])))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for worker in workers {
+            let scanned = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (index, result) in scanned {
+                results[index] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("every volume is scanned"))
+        .collect()
+}
+
+/// Groups the indices of `volumes` by the physical disk that holds them, in the order of
+/// their first volume.
+fn disk_groups(volumes: &[PathBuf], disks: &Disks) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of: HashMap<Option<String>, usize> = HashMap::new();
+    for (index, volume) in volumes.iter().enumerate() {
+        let disk = physical_disk(volume, disks);
+        info!(
+            "{} is on disk {}",
+            volume.display(),
+            disk.as_deref().unwrap_or("unknown")
+        );
+        let group = *group_of.entry(disk).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+    groups
+}
+
+fn add_stats(total: &mut ScanStats, stats: &ScanStats) {
+    total.errors += stats.errors;
+    total.vanished += stats.vanished;
+    total.cancelled |= stats.cancelled;
+    total.too_many_errors |= stats.too_many_errors;
+    total.hard_links += stats.hard_links;
+    total.hard_link_bytes += stats.hard_link_bytes;
+    total.skipped.add_all(&stats.skipped);
+}
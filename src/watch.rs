@@ -0,0 +1,225 @@
+// External crates
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use sysinfo::Disks;
+
+// Standard library imports
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::filters;
+use crate::model::{FileNode, ScanInfo, ScanResult};
+use crate::options::Options;
+use crate::scan::{scan_info, scan_to_tree, sort_nodes};
+use crate::source::EntryKind;
+use crate::volume::get_volume_info;
+
+/// A scanned tree that is kept up to date from filesystem change notifications
+/// (inotify, FSEvents, or ReadDirectoryChangesW).
+///
+/// Only the changed entries are read again (a changed folder is rescanned with
+/// its contents), and the whole tree when notifications were lost. Hard links
+/// added after the initial scan are not detected.
+pub struct TreeWatcher {
+    root: PathBuf,
+    options: Options,
+    root_dev: u64,
+    info: ScanInfo,
+    tree: FileNode,
+    events: Receiver<notify::Result<Event>>,
+    // Notifications stop when the watcher is dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl TreeWatcher {
+    /// Starts watching the directory at `root` and scans it.
+    pub fn new(root: &Path, options: &Options) -> io::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(notify_error)?;
+        // Watch before scanning, so that changes during the scan are not lost
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(notify_error)?;
+
+        let root_dev = options.source().metadata(root)?.device;
+        let info = scan_info(root, options, &Disks::new_with_refreshed_list());
+        let tree = scan_to_tree(root, options)?;
+
+        Ok(TreeWatcher {
+            root: root.to_path_buf(),
+            options: options.clone(),
+            root_dev,
+            info,
+            tree,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns the current tree.
+    pub fn tree(&self) -> &FileNode {
+        &self.tree
+    }
+
+    /// Returns a snapshot of the current tree, with the free space and time of now.
+    pub fn result(&self) -> ScanResult {
+        let disks = Disks::new_with_refreshed_list();
+        let mut info = self.info.clone();
+        info.free_space = get_volume_info(&self.root, &disks).free_space;
+        info.scan_time = Utc::now();
+
+        ScanResult {
+            info,
+            root: self.tree.clone(),
+        }
+    }
+
+    /// Collects change notifications for `timeout`, then applies them to the tree.
+    ///
+    /// Returns true if any change was applied.
+    pub fn update(&mut self, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        let mut paths = BTreeSet::new();
+        let mut rescan = false;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(remaining) {
+                Ok(Ok(event)) => {
+                    rescan |= event.need_rescan();
+                    paths.extend(event.paths);
+                }
+                Ok(Err(e)) => {
+                    warn!("Watch error, rescanning: {}", e);
+                    rescan = true;
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("File watcher stopped"))
+                }
+            }
+        }
+
+        if rescan {
+            info!("Rescanning {}", self.root.display());
+            self.tree = scan_to_tree(&self.root, &self.options)?;
+            return Ok(true);
+        }
+
+        // Paths are sorted, so the contents of a folder directly follow it
+        let mut changed = false;
+        let mut last: Option<PathBuf> = None;
+        for path in paths {
+            if last.as_ref().is_some_and(|folder| path.starts_with(folder)) {
+                continue;
+            }
+            self.refresh(&path)?;
+            last = Some(path);
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    /// Reads `path` again and replaces its node in the tree.
+    fn refresh(&mut self, path: &Path) -> io::Result<()> {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return Ok(());
+        };
+        let names: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let Some((name, parents)) = names.split_last() else {
+            self.tree = scan_to_tree(&self.root, &self.options)?;
+            return Ok(());
+        };
+
+        let node = self.read_node(path, name);
+        let Some(parent) = find_folder(&mut self.tree, parents) else {
+            // The parent is not in the tree yet (e.g. it was empty), so add it instead
+            return match path.parent() {
+                Some(parent) => self.refresh(parent),
+                None => Ok(()),
+            };
+        };
+
+        parent.children.retain(|child| child.name != *name);
+        if let Some(node) = node {
+            parent.children.push(node);
+            sort_nodes(&mut parent.children, self.options.sort_order);
+        }
+        Ok(())
+    }
+
+    /// Reads the entry at `path`, applying the same filters as a scan.
+    fn read_node(&self, path: &Path, name: &str) -> Option<FileNode> {
+        let options = &self.options;
+        if filters::skip_by_name(name, options).is_some() {
+            return None;
+        }
+
+        let metadata = match options.source().symlink_metadata(path) {
+            Ok(metadata) => options.visible_metadata(metadata),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Failed to access metadata for '{}': {}", path.display(), e);
+                return None;
+            }
+        };
+
+        match metadata.kind {
+            EntryKind::Dir if options.cross_mount_points || metadata.device == self.root_dev => {
+                match scan_to_tree(path, options) {
+                    Ok(mut folder) => {
+                        folder.name = name.to_string();
+                        filters::skip_folder(folder.children.len(), options)
+                            .is_none()
+                            .then_some(folder)
+                    }
+                    Err(e) => {
+                        warn!("Failed to scan '{}': {}", path.display(), e);
+                        None
+                    }
+                }
+            }
+            EntryKind::File => {
+                let size = metadata.file_size(options.apparent_size);
+                if filters::skip_file(size, options).is_some() {
+                    return None;
+                }
+                let mut file = FileNode::file(name, size);
+                file.created = metadata.created.map(DateTime::from);
+                file.modified = metadata.modified.map(DateTime::from);
+                file.accessed = metadata.accessed.map(DateTime::from);
+                file.attributes.extend(options.other_size(&metadata));
+                Some(file)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Finds the folder at the path given by `names` below `node`.
+fn find_folder<'a>(node: &'a mut FileNode, names: &[String]) -> Option<&'a mut FileNode> {
+    match names.split_first() {
+        None => Some(node),
+        Some((name, rest)) => {
+            let child = node
+                .children
+                .iter_mut()
+                .find(|child| child.is_folder() && child.name == *name)?;
+            find_folder(child, rest)
+        }
+    }
+}
+
+fn notify_error(e: notify::Error) -> io::Error {
+    match e.kind {
+        notify::ErrorKind::Io(e) => e,
+        _ => io::Error::other(e),
+    }
+}
@@ -0,0 +1,445 @@
+// External crates
+use chrono::{DateTime, Utc};
+use log::warn;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::writer::Writer;
+
+// Standard library imports
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+use crate::filters::SkipReason;
+use crate::model::ScanInfo;
+use crate::options::Timestamps;
+use crate::output::OutputFormatter;
+use crate::scan::ScanEntry;
+use crate::source::EntryMetadata;
+use crate::top::LargestFiles;
+
+// Constants for XML output
+const GRANDPERSPECTIVE_APP_VERSION: &str = "4";
+const GRANDPERSPECTIVE_FORMAT_VERSION: &str = "7";
+const XML_VERSION: &str = "1.0";
+const XML_ENCODING: &str = "UTF-8";
+pub(crate) const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+const DEFAULT_DATETIME: &str = "1970-01-01T00:00:00Z";
+const TAG_SCAN_INFO: &str = "ScanInfo";
+const TAG_SCAN_SUMMARY: &str = "ScanSummary";
+const TAG_GRANDPERSPECTIVE_SCAN_DUMP: &str = "GrandPerspectiveScanDump";
+const TAG_FOLDER: &str = "Folder";
+const TAG_FILE: &str = "File";
+const TAG_LARGEST_FILES: &str = "LargestFiles";
+const TAG_LARGEST_FILE: &str = "LargestFile";
+/// Uniform type identifier of a file, such as `public.jpeg`.
+pub(crate) const ATTR_UNIFORM_TYPE: &str = "type";
+/// Full path of a file or folder, written with `--absolute-paths`.
+pub(crate) const ATTR_PATH: &str = "path";
+
+/// Writes a GrandPerspective XML scan dump.
+///
+/// The dump is written as the scan goes. Values only known once it completes, such as
+/// its duration and what it left out, follow the folder tree in a `ScanSummary` element.
+pub struct XmlFormatter<W: Write> {
+    writer: Writer<CountingWriter<W>>,
+    /// Bytes written before the folder tree, once `begin_scan` was called.
+    tree_start: Option<u64>,
+    /// Start tag of the most recently entered folder, written once its first child
+    /// is known, so that folders without children become empty elements.
+    pending: Option<BytesStart<'static>>,
+    largest: Option<LargestFiles>,
+    timestamps: Option<Timestamps>,
+    strict_names: bool,
+}
+
+impl<W: Write> XmlFormatter<W> {
+    pub fn new(output: W) -> Self {
+        XmlFormatter {
+            writer: Writer::new_with_indent(
+                CountingWriter {
+                    inner: output,
+                    count: 0,
+                },
+                b' ',
+                0,
+            ),
+            tree_start: None,
+            pending: None,
+            largest: None,
+            timestamps: None,
+            strict_names: false,
+        }
+    }
+
+    /// Fails with [`io::ErrorKind::InvalidData`] on names with characters that XML 1.0
+    /// does not allow, instead of replacing them (see [`sanitize_xml_text`]).
+    pub fn strict_names(mut self, value: bool) -> Self {
+        self.strict_names = value;
+        self
+    }
+
+    /// Writes only the selected times of each entry, and leaves out unknown times
+    /// instead of writing the Unix epoch.
+    pub fn timestamps(mut self, timestamps: Timestamps) -> Self {
+        self.timestamps = Some(timestamps);
+        self
+    }
+
+    /// Records the `n` largest files in a `LargestFiles` element after `ScanInfo`,
+    /// which GrandPerspective ignores.
+    ///
+    /// The files are tracked while they are written, without another pass over the tree.
+    pub fn largest_files(mut self, n: usize) -> Self {
+        self.largest = Some(LargestFiles::new(n));
+        self
+    }
+
+    /// Returns the number of bytes of the folder tree written so far.
+    pub fn tree_len(&self) -> u64 {
+        self.tree_start
+            .map_or(0, |start| self.writer.get_ref().count - start)
+    }
+
+    /// Returns a reference to the output.
+    pub fn get_ref(&self) -> &W {
+        &self.writer.get_ref().inner
+    }
+
+    /// Returns the output, such as to finish a compressed stream after [`end_scan`](OutputFormatter::end_scan).
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().inner
+    }
+
+    fn tree(&mut self) -> io::Result<&mut Writer<CountingWriter<W>>> {
+        match self.tree_start {
+            Some(_) => Ok(&mut self.writer),
+            None => Err(io::Error::other("begin_scan was not called")),
+        }
+    }
+
+    /// Returns the name of `entry` as written to the dump.
+    fn entry_name<'a>(&self, entry: &'a ScanEntry) -> io::Result<Cow<'a, str>> {
+        let name = sanitize_xml_text(&entry.name);
+        if let Cow::Owned(_) = name {
+            if self.strict_names {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "The name of '{}' contains characters that are not allowed in XML",
+                        entry.path.display()
+                    ),
+                ));
+            }
+            warn!(
+                "Replaced characters that are not allowed in XML in the name of '{}'",
+                entry.path.display()
+            );
+        }
+        Ok(name)
+    }
+
+    fn flush_pending(&mut self) -> io::Result<()> {
+        if let Some(folder_tag) = self.pending.take() {
+            write_event(self.tree()?, Event::Start(folder_tag))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputFormatter for XmlFormatter<W> {
+    fn begin_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        // Output the XML header and start tag
+        output_xml_header(&mut self.writer)?;
+
+        // Output the scan information known before the scan
+        write_event(&mut self.writer, Event::Start(scan_info_tag(info)))?;
+        self.tree_start = Some(self.writer.get_ref().count);
+        Ok(())
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        self.flush_pending()?;
+        let name = self.entry_name(entry)?;
+        self.pending = Some(entry_tag(TAG_FOLDER, entry, &name, false, self.timestamps));
+        Ok(())
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> io::Result<()> {
+        if let Some(largest) = &mut self.largest {
+            largest.push(&entry.path, entry.size);
+        }
+        self.flush_pending()?;
+        let name = self.entry_name(entry)?;
+        let file_tag = entry_tag(TAG_FILE, entry, &name, true, self.timestamps);
+        write_event(self.tree()?, Event::Empty(file_tag))
+    }
+
+    fn end_folder(&mut self, _entry: &ScanEntry) -> io::Result<()> {
+        let event = match self.pending.take() {
+            Some(folder_tag) => Event::Empty(folder_tag),
+            None => Event::End(BytesEnd::new(TAG_FOLDER)),
+        };
+        write_event(self.tree()?, event)
+    }
+
+    fn comment(&mut self, text: &str) -> io::Result<()> {
+        self.flush_pending()?;
+        let text = comment_text(text);
+        write_event(self.tree()?, Event::Comment(BytesText::from_escaped(text)))
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> io::Result<()> {
+        self.tree()?;
+        let writer = &mut self.writer;
+
+        // </ScanInfo> tag
+        write_event(writer, Event::End(BytesEnd::new(TAG_SCAN_INFO)))?;
+        if let Some(summary) = scan_summary_tag(info) {
+            write_event(writer, Event::Empty(summary))?;
+        }
+        if let Some(largest) = &self.largest {
+            write_largest_files(writer, largest)?;
+        }
+        // </GrandPerspectiveScanDump> tag
+        write_event(
+            writer,
+            Event::End(BytesEnd::new(TAG_GRANDPERSPECTIVE_SCAN_DUMP)),
+        )?;
+        writer.get_mut().flush()
+    }
+}
+
+fn write_largest_files<W: Write>(writer: &mut Writer<W>, largest: &LargestFiles) -> io::Result<()> {
+    write_event(writer, Event::Start(BytesStart::new(TAG_LARGEST_FILES)))?;
+    for (path, size) in largest.to_vec() {
+        let mut file = BytesStart::new(TAG_LARGEST_FILE);
+        file.push_attribute(("path", sanitize_xml_text(&path.to_string_lossy()).as_ref()));
+        file.push_attribute(("size", size.to_string().as_str()));
+        write_event(writer, Event::Empty(file))?;
+    }
+    write_event(writer, Event::End(BytesEnd::new(TAG_LARGEST_FILES)))
+}
+
+/// Replaces the characters of `text` that XML 1.0 does not allow, or that do not survive
+/// in attribute values, so that every dump can be loaded:
+///
+/// - The control characters U+0000 to U+001F, including tab and line breaks, which parsers
+///   turn into spaces in attribute values, become the matching symbols of the Control
+///   Pictures block, U+2400 to U+241F (so a line feed becomes `␊`).
+/// - The noncharacters U+FFFE and U+FFFF become the replacement character U+FFFD.
+///
+/// Unpaired surrogates in names that are not valid Unicode are already replaced with
+/// U+FFFD when the names are converted to strings. Text without such characters is
+/// returned unchanged.
+pub fn sanitize_xml_text(text: &str) -> Cow<'_, str> {
+    let replace = |c: char| match c {
+        '\0'..='\x1f' => char::from_u32(0x2400 + c as u32),
+        '\u{fffe}' | '\u{ffff}' => Some(char::REPLACEMENT_CHARACTER),
+        _ => None,
+    };
+    if !text.chars().any(|c| replace(c).is_some()) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.chars().map(|c| replace(c).unwrap_or(c)).collect())
+}
+
+/// Makes `text` the content of an XML comment, which must not contain `--`.
+fn comment_text(text: &str) -> String {
+    let mut content = format!(" {} ", sanitize_xml_text(text));
+    while content.contains("--") {
+        content = content.replace("--", "- -");
+    }
+    content
+}
+
+/// Counts the bytes written to the output.
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn write_event<W: Write>(writer: &mut Writer<W>, event: Event) -> io::Result<()> {
+    writer.write_event(event).map_err(|e| match e {
+        // Keep the kind, such as a full disk
+        quick_xml::Error::Io(e) => io::Error::new(e.kind(), e),
+        e => io::Error::other(e),
+    })
+}
+
+fn output_xml_header<W: Write>(writer: &mut Writer<W>) -> io::Result<()> {
+    write_event(
+        writer,
+        Event::Decl(BytesDecl::new(XML_VERSION, Some(XML_ENCODING), None)),
+    )?;
+    let mut root = BytesStart::new(TAG_GRANDPERSPECTIVE_SCAN_DUMP);
+    root.push_attribute(("appVersion", GRANDPERSPECTIVE_APP_VERSION));
+    root.push_attribute(("formatVersion", GRANDPERSPECTIVE_FORMAT_VERSION));
+    write_event(writer, Event::Start(root))
+}
+
+fn scan_info_tag(info: &ScanInfo) -> BytesStart<'static> {
+    let mut scan_info = BytesStart::new(TAG_SCAN_INFO);
+    // Attribute values are escaped by quick-xml
+    scan_info.push_attribute(("volumePath", sanitize_xml_text(&info.volume_path).as_ref()));
+    scan_info.push_attribute(("volumeSize", info.volume_size.to_string().as_str()));
+    scan_info.push_attribute(("freeSpace", info.free_space.to_string().as_str()));
+    scan_info.push_attribute((
+        "scanTime",
+        info.scan_time.format(DATETIME_FORMAT).to_string().as_str(),
+    ));
+    scan_info.push_attribute(("fileSizeMeasure", info.file_size_measure.as_str()));
+    // Not part of the GrandPerspective format, which ignores unknown attributes
+    let optional_attributes = [
+        ("volumeName", &info.volume_name),
+        ("fileSystem", &info.file_system),
+        ("volumeId", &info.volume_id),
+        ("snapshot", &info.snapshot),
+        ("comment", &info.comment),
+    ];
+    for (key, value) in optional_attributes {
+        if let Some(value) = value {
+            scan_info.push_attribute((key, sanitize_xml_text(value).as_ref()));
+        }
+    }
+    // The estimate mark is derived from the rate, which is read back
+    if let Some(rate) = info.sample_rate {
+        scan_info.push_attribute(("estimate", "true"));
+        scan_info.push_attribute(("sampleRate", rate.to_string().as_str()));
+    }
+    scan_info
+}
+
+/// Builds the `ScanSummary` element with the values of `info` only known once the scan
+/// completed, or returns `None` if there are none, as for imported dumps.
+fn scan_summary_tag(info: &ScanInfo) -> Option<BytesStart<'static>> {
+    let mut summary = BytesStart::new(TAG_SCAN_SUMMARY);
+    if let Some(duration) = info.scan_duration {
+        summary.push_attribute((
+            "scanDuration",
+            format!("{:.3}", duration.as_secs_f64()).as_str(),
+        ));
+    }
+    if let Some(free_space) = info.free_space_after {
+        summary.push_attribute(("freeSpaceAfter", free_space.to_string().as_str()));
+    }
+    // The level is derived from the counts, which are read back
+    if let Some(consistency) = &info.consistency {
+        summary.push_attribute(("consistency", consistency.level()));
+        summary.push_attribute(("verifiedEntries", consistency.checked.to_string().as_str()));
+        summary.push_attribute(("changedEntries", consistency.changed.to_string().as_str()));
+    }
+    // What was left out, if known; reasons without skipped entries are not written
+    if let Some(errors) = info.errors {
+        summary.push_attribute(("errors", errors.to_string().as_str()));
+    }
+    for (reason, count) in info.skipped.iter().flat_map(|skipped| skipped.iter()) {
+        summary.push_attribute((
+            skipped_attribute(reason).as_str(),
+            count.to_string().as_str(),
+        ));
+    }
+    summary.attributes().next().is_some().then_some(summary)
+}
+
+/// Returns the name of the `ScanInfo` attribute with the number of entries skipped for
+/// `reason`, such as `skippedHardLink`.
+pub(crate) fn skipped_attribute(reason: SkipReason) -> String {
+    let mut name = "skipped".to_string();
+    for word in reason.name().split('-') {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
+
+/// Attributes that `Folder` and `File` elements may have regardless of their entry.
+pub(crate) const ENTRY_ATTRIBUTES: [&str; 5] = ["name", "size", "created", "modified", "accessed"];
+
+/// Returns whether `name` can be written as the name of an attribute: a letter or `_`
+/// followed by letters, digits, `_`, `-`, or `.`, without namespace prefixes.
+pub(crate) fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Builds the start tag shared by `Folder` and `File` elements.
+fn entry_tag(
+    tag: &'static str,
+    entry: &ScanEntry,
+    name: &str,
+    is_file: bool,
+    timestamps: Option<Timestamps>,
+) -> BytesStart<'static> {
+    let mut entry_tag = BytesStart::new(tag);
+    entry_tag.push_attribute(("name", name));
+    if is_file {
+        entry_tag.push_attribute(("size", entry.size.to_string().as_str()));
+    }
+    match timestamps {
+        None => {
+            // Get file times
+            let (created, modified, accessed) = get_file_times(&entry.metadata);
+            entry_tag.push_attribute(("created", created.as_str()));
+            entry_tag.push_attribute(("modified", modified.as_str()));
+            entry_tag.push_attribute(("accessed", accessed.as_str()));
+        }
+        Some(timestamps) => {
+            let metadata = &entry.metadata;
+            let all = timestamps == Timestamps::All;
+            let times = [
+                ("created", metadata.created.filter(|_| all)),
+                (
+                    "modified",
+                    metadata.modified.filter(|_| timestamps != Timestamps::None),
+                ),
+                ("accessed", metadata.accessed.filter(|_| all)),
+            ];
+            for (key, time) in times {
+                if time.is_some() {
+                    entry_tag.push_attribute((key, format_system_time(time).as_str()));
+                }
+            }
+        }
+    }
+    for (key, value) in &entry.attributes {
+        entry_tag.push_attribute((key.as_str(), sanitize_xml_text(value).as_ref()));
+    }
+    entry_tag
+}
+
+fn format_system_time(sys_time: Option<SystemTime>) -> String {
+    match sys_time {
+        Some(t) => {
+            let datetime: DateTime<Utc> = t.into();
+            datetime.format(DATETIME_FORMAT).to_string()
+        }
+        None => DEFAULT_DATETIME.to_string(),
+    }
+}
+
+/// Retrieves creation, modification, and access times from metadata.
+fn get_file_times(metadata: &EntryMetadata) -> (String, String, String) {
+    let created = format_system_time(metadata.created);
+    let modified = format_system_time(metadata.modified);
+    let accessed = format_system_time(metadata.accessed);
+
+    (created, modified, accessed)
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "cli")]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs::{self, File};
@@ -107,6 +109,11 @@ fn test_gpscan_output() {
         !predicate::str::contains(r#"<File name="hardlink_to_file2""#).eval(&xml_output),
         "XML output contains hardlink_to_file2"
     );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        predicate::str::contains("Hard links: 1 files counted once").eval(&stderr),
+        "The summary does not report the hard link"
+    );
 
     // Test for start and end XML tags
     assert!(
@@ -118,7 +125,7 @@ fn test_gpscan_output() {
         "XML output does not start with <GrandPerspectiveScanDump>"
     );
     assert!(
-        predicate::str::ends_with(r#"</GrandPerspectiveScanDump>"#).eval(&xml_output.trim_end()),
+        predicate::str::ends_with(r#"</GrandPerspectiveScanDump>"#).eval(xml_output.trim_end()),
         "XML output does not end with </GrandPerspectiveScanDump>"
     );
 
@@ -170,6 +177,12 @@ fn test_gpscan_with_output_file() {
         predicate::str::contains(r#"<File name="file1.txt""#).eval(&output_xml),
         "Output file XML does not contain file1.txt"
     );
+    assert!(
+        predicate::str::is_match(r#"<ScanSummary scanDuration="\d+\.\d{3}""#)
+            .unwrap()
+            .eval(&output_xml),
+        "Output file XML does not record the scan duration"
+    );
 
     // Ensure correct XML structure
     assert!(
@@ -181,11 +194,285 @@ fn test_gpscan_with_output_file() {
         "Output file does not start with correct XML declaration and root"
     );
     assert!(
-        predicate::str::ends_with("</GrandPerspectiveScanDump>").eval(&output_xml.trim_end()),
+        predicate::str::ends_with("</GrandPerspectiveScanDump>").eval(output_xml.trim_end()),
         "Output file XML does not end with </GrandPerspectiveScanDump>"
     );
 }
 
+#[test]
+fn test_gpscan_output_placeholders() {
+    let temp_dir = TempDir::new("gpscan_placeholders").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    let out_dir = temp_dir.path().join("out");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::create_dir(&out_dir).expect("Failed to create dir");
+    fs::write(dir_path.join("file.txt"), "data").expect("Failed to write file");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-o")
+        .arg(out_dir.join("scan-{hostname}-{date}-{timestamp}.gpscan"));
+    cmd.assert().success();
+
+    let names: Vec<String> = fs::read_dir(&out_dir)
+        .expect("Failed to read output dir")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names.len(), 1, "{:?}", names);
+    assert!(
+        predicate::str::is_match(r"^scan-.+-\d{4}-\d{2}-\d{2}-\d{8}T\d{6}Z\.gpscan$")
+            .unwrap()
+            .eval(&names[0]),
+        "Unexpected output name {}",
+        names[0]
+    );
+    assert!(!names[0].contains('{'));
+}
+
+#[test]
+fn test_gpscan_output_to_stdout() {
+    let temp_dir = TempDir::new("gpscan_stdout").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("file.txt"), "data").expect("Failed to write file");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.current_dir(dir_path).arg(".").arg("-o").arg("-");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"<File name="file.txt""#));
+    assert!(!dir_path.join("-").exists());
+
+    // Modes that write a file per scan need a file name
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path).arg("--daemon").arg("-o").arg("-");
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("--daemon cannot write to stdout"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gpscan_output_fd() {
+    let temp_dir = TempDir::new("gpscan_output_fd").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("file.txt"), "data").expect("Failed to write file");
+
+    // Reports move to stderr when the dump takes stdout
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path)
+        .arg("--output-fd")
+        .arg("1")
+        .arg("--report")
+        .arg("extensions");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"<File name="file.txt""#))
+        .stdout(predicate::str::contains("Extensions").not())
+        .stderr(predicate::str::contains("Extensions"));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path).arg("--output-fd").arg("99");
+    cmd.assert()
+        .code(3)
+        .stderr(predicate::str::contains("output file descriptor 99"));
+
+    // A device cannot be split into parts or replaced
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path)
+        .arg("-o")
+        .arg("/dev/stdout")
+        .arg("--split-size")
+        .arg("1M");
+    cmd.assert().code(1).stderr(predicate::str::contains(
+        "--split-size cannot write to a pipe or device (/dev/stdout)",
+    ));
+}
+
+#[test]
+fn test_gpscan_split_size() {
+    let temp_dir = TempDir::new("gpscan_split").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    let out_dir = temp_dir.path().join("out");
+    fs::create_dir_all(dir_path.join("subdir")).expect("Failed to create dir");
+    fs::create_dir(&out_dir).expect("Failed to create dir");
+    for i in 0..20 {
+        fs::write(
+            dir_path.join("subdir").join(format!("file{}.txt", i)),
+            "data",
+        )
+        .expect("Failed to write file");
+    }
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--split-size")
+        .arg("1K")
+        .arg("-o")
+        .arg(out_dir.join("scan.gpscan"));
+    cmd.assert().success();
+
+    let mut names: Vec<String> = fs::read_dir(&out_dir)
+        .expect("Failed to read output dir")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert!(names.len() > 1, "{:?}", names);
+    assert_eq!(names[0], "scan.gpscan.001");
+    // Parts are renamed as soon as they are complete
+    assert!(
+        names.iter().all(|name| !name.ends_with(".tmp")),
+        "{:?}",
+        names
+    );
+
+    // Every part is a complete dump, and each file is in exactly one of them
+    let mut files = 0;
+    for name in &names {
+        let path = out_dir.join(name);
+        let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+        cmd.arg("validate").arg(&path);
+        cmd.assert().success();
+        let xml_output = fs::read_to_string(&path).expect("Failed to read dump");
+        assert!(xml_output.contains(r#"<Folder name="subdir""#));
+        files += xml_output.matches("<File ").count();
+    }
+    assert_eq!(files, 20);
+
+    // The limit applies after compression, so the small tree fits in one part
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--split-size")
+        .arg("1K")
+        .arg("--compress")
+        .arg("-o")
+        .arg(out_dir.join("scan.gpscan.gz"));
+    cmd.assert().success();
+    let compressed = out_dir.join("scan.gpscan.gz.001");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("validate").arg(&compressed);
+    cmd.assert().success();
+    assert!(!out_dir.join("scan.gpscan.gz.002").exists());
+    assert!(!out_dir.join("scan.gpscan.gz.001.tmp").exists());
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--split-size")
+        .arg("1X")
+        .arg("-o")
+        .arg(out_dir.join("scan.gpscan"));
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("invalid size unit"));
+}
+
+#[test]
+fn test_gpscan_writer_thread_compress() {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let temp_dir = TempDir::new("gpscan_writer_thread").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    for i in 0..50 {
+        let sub_dir = dir_path.join(format!("dir{}", i));
+        fs::create_dir_all(&sub_dir).expect("Failed to create dir");
+        fs::write(sub_dir.join("a.txt"), "abc").expect("Failed to write a.txt");
+    }
+    let output_path = temp_dir.path().join("scan.gpscan.gz");
+
+    // The entries are compressed on the calling thread as the traversal hands them over
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--writer-thread")
+        .arg("--compress")
+        .arg("-o")
+        .arg(&output_path);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("validate").arg(&output_path);
+    cmd.assert().success();
+    let mut xml_output = String::new();
+    GzDecoder::new(File::open(&output_path).expect("Output not written"))
+        .read_to_string(&mut xml_output)
+        .expect("Output is not gzip-compressed");
+    assert_eq!(xml_output.matches(r#"<File name="a.txt""#).count(), 50);
+    assert!(xml_output
+        .trim_end()
+        .ends_with("</GrandPerspectiveScanDump>"));
+}
+
+#[test]
+fn test_gpscan_checksum() {
+    use sha2::{Digest, Sha256};
+
+    let temp_dir = TempDir::new("gpscan_checksum").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    let output_path = temp_dir.path().join("scan.gpscan.gz");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--compress")
+        .arg("--checksum")
+        .arg("sha256");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+
+    // The digest covers the file as written, in the format of sha256sum
+    let digest = format!("{:x}", Sha256::digest(fs::read(&output_path).unwrap()));
+    let line = format!("{}  scan.gpscan.gz\n", digest);
+    let checksum = fs::read_to_string(temp_dir.path().join("scan.gpscan.gz.sha256"))
+        .expect("Failed to read checksum file");
+    assert_eq!(checksum, line);
+    assert_eq!(String::from_utf8(stdout).unwrap(), line);
+
+    // Without an output file, the digest is printed after the dump on stderr
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--checksum").arg("sha256").arg("-q");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::is_match(r"(?m)^[0-9a-f]{64}  -$").unwrap());
+}
+
+#[cfg(feature = "encrypt")]
+#[test]
+fn test_gpscan_encrypt() {
+    use age::x25519;
+
+    let temp_dir = TempDir::new("gpscan_encrypt").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+
+    let recipient = format!("age:{}", x25519::Identity::generate().to_public());
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--compress")
+        .arg("--encrypt")
+        .arg(&recipient);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert!(output.starts_with(b"age-encryption.org/v1\n"));
+
+    // Invalid recipients and a missing password fail before the scan
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--encrypt").arg("age:age1invalid");
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("Invalid age recipient"));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.env_remove("GPSCAN_PASSWORD")
+        .arg(&dir_path)
+        .arg("--encrypt")
+        .arg("password");
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("GPSCAN_PASSWORD"));
+}
+
 #[test]
 fn test_gpscan_invalid_output_path() {
     let temp_dir = TempDir::new("gpscan_invalid_output").expect("Failed to create temp dir");
@@ -210,6 +497,1400 @@ fn test_gpscan_invalid_output_path() {
     let expected_error = "No such file or directory";
 
     cmd.assert()
-        .failure()
+        .code(3)
         .stderr(predicate::str::contains(expected_error));
 }
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_gpscan_output_disk_full() {
+    let temp_dir = TempDir::new("gpscan_output_full").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("file1.txt"), "data").expect("Failed to write file");
+
+    // Every write to /dev/full fails with ENOSPC; being a device, it is written as a stream
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path)
+        .arg("--compress")
+        .arg("-o")
+        .arg("/dev/full");
+    cmd.assert()
+        .code(8)
+        .stderr(predicate::str::contains("No space left on device"));
+}
+
+#[test]
+fn test_gpscan_single_file() {
+    let temp_dir = TempDir::new("gpscan_single_file").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("file1.txt"), "data").expect("Failed to write file");
+    fs::write(dir_path.join("file2.txt"), "data").expect("Failed to write file");
+
+    // The file is the only entry of its parent folder
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path.join("file1.txt"));
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(&format!(r#"<Folder name="{}""#, dir_path.display())));
+    assert!(xml_output.contains(r#"<File name="file1.txt""#));
+    assert!(!xml_output.contains("file2.txt"));
+
+    // A relative path without a directory is in the current directory
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.current_dir(dir_path).arg("file2.txt");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"<Folder name=".""#))
+        .stdout(predicate::str::contains(r#"<File name="file2.txt""#));
+}
+
+#[test]
+fn test_gpscan_embed_warnings() {
+    let temp_dir = TempDir::new("gpscan_embed_warnings").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    File::create(dir_path.join("empty.txt")).expect("Failed to create empty.txt");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--embed-warnings").arg("--quiet");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("<!-- Skipping zero-byte file: "))
+        .stdout(predicate::str::contains("empty.txt -->"));
+
+    // Without the flag, skipped entries are only logged
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("<!--").not());
+}
+
+#[test]
+fn test_gpscan_log_each_skip() {
+    let temp_dir = TempDir::new("gpscan_log_each_skip").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    for i in 0..25 {
+        File::create(dir_path.join(format!("empty{}.txt", i))).expect("Failed to create file");
+    }
+    let logged = |output: &[u8]| {
+        String::from_utf8_lossy(output)
+            .matches("Skipping zero-byte file")
+            .count()
+    };
+
+    // Only the first entries skipped for each reason are logged by default
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    let output = cmd.arg(dir_path).output().expect("Failed to run gpscan");
+    assert!(output.status.success());
+    assert_eq!(logged(&output.stderr), 10);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("zero-size=25"));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    let output = cmd
+        .arg(dir_path)
+        .arg("--log-each-skip")
+        .output()
+        .expect("Failed to run gpscan");
+    assert!(output.status.success());
+    assert_eq!(logged(&output.stderr), 25);
+}
+
+#[test]
+fn test_gpscan_progress_json() {
+    let temp_dir = TempDir::new("gpscan_progress_json").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+
+    // The last record is always the finish event
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("--progress-json").arg(&dir_path).arg("--quiet");
+    let output = cmd.assert().success().get_output().stderr.clone();
+    let stderr = String::from_utf8(output).expect("stderr is not UTF-8");
+    let last = stderr
+        .lines()
+        .rfind(|line| line.starts_with('{'))
+        .expect("No progress records");
+    let record: serde_json::Value = serde_json::from_str(last).expect("Invalid progress record");
+    assert_eq!(record["event"], "finish");
+    assert_eq!(record["files"], 1);
+    assert_eq!(record["folders"], 1);
+    assert!(record["bytes_per_second"].is_number());
+
+    // The records can also go to a file, keeping stderr for the log
+    let progress_path = temp_dir.path().join("progress.jsonl");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(format!("--progress-json={}", progress_path.display()))
+        .arg(&dir_path)
+        .arg("--quiet");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("\"event\"").not());
+    let records = fs::read_to_string(&progress_path).expect("Failed to read progress records");
+    assert!(records
+        .lines()
+        .last()
+        .unwrap()
+        .contains("\"event\":\"finish\""));
+}
+
+#[test]
+fn test_gpscan_root_name() {
+    let temp_dir = TempDir::new("gpscan_root_name").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.current_dir(temp_dir.path())
+        .arg("data")
+        .arg("--root-name")
+        .arg("absolute");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(r#"<Folder name="[^"]+[/\\]data""#).unwrap());
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.current_dir(&dir_path)
+        .arg(".")
+        .arg("--root-name")
+        .arg("basename");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"<Folder name="data""#));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--root-name").arg("nas");
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("invalid root name"));
+}
+
+#[test]
+fn test_gpscan_invalid_root() {
+    let temp_dir = TempDir::new("gpscan_invalid_root").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    File::create(dir_path.join("file1.txt")).expect("Failed to create file1");
+
+    // Non-existent path
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path.join("missing").to_str().unwrap());
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("does not exist"));
+
+    // Usage error
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path.to_str().unwrap()).arg("--no-such-option");
+    cmd.assert().code(1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gpscan_strict_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new("gpscan_strict").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+
+    let mut file1 = File::create(dir_path.join("file1.txt")).expect("Failed to create file1");
+    writeln!(file1, "Content for file1").expect("Failed to write to file1");
+
+    // Create a directory that cannot be read
+    let locked_dir = dir_path.join("locked");
+    fs::create_dir(&locked_dir).expect("Failed to create locked dir");
+    File::create(locked_dir.join("hidden.txt")).expect("Failed to create hidden file");
+    fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o000))
+        .expect("Failed to change permissions");
+
+    // Permissions are not enforced for privileged users
+    let readable = fs::read_dir(&locked_dir).is_ok();
+
+    // Without --strict, skipped entries do not affect the exit status
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path.to_str().unwrap());
+    cmd.assert().success();
+
+    // With --strict, the partial dump is still written but the exit status is non-zero
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path.to_str().unwrap()).arg("--strict");
+    let output = cmd.output().expect("Failed to execute gpscan");
+    let xml_output = String::from_utf8_lossy(&output.stdout);
+
+    fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o755))
+        .expect("Failed to restore permissions");
+
+    assert!(
+        predicate::str::contains(r#"<File name="file1.txt""#).eval(&xml_output),
+        "XML output does not contain file1.txt"
+    );
+    if readable {
+        assert!(output.status.success());
+    } else {
+        assert_eq!(output.status.code(), Some(4));
+
+        // The unreadable directory is kept as an empty, marked folder
+        assert!(
+            predicate::str::is_match(r#"<Folder name="locked"[^>]*unreadable="true"/>"#)
+                .unwrap()
+                .eval(&xml_output),
+            "XML output does not mark the locked folder as unreadable"
+        );
+    }
+}
+
+#[test]
+fn test_gpscan_sort_order() {
+    let temp_dir = TempDir::new("gpscan_sort").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+
+    // "B.txt" sorts before "a.txt" in byte order, but is the smallest file
+    fs::write(dir_path.join("a.txt"), vec![b'a'; 2000]).expect("Failed to write a.txt");
+    fs::write(dir_path.join("b.txt"), vec![b'b'; 3000]).expect("Failed to write b.txt");
+    fs::write(dir_path.join("B.txt"), vec![b'B'; 1000]).expect("Failed to write B.txt");
+
+    let scan = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+        cmd.arg(dir_path.to_str().unwrap())
+            .arg("--apparent-size")
+            .args(args);
+        let output = cmd.output().expect("Failed to execute gpscan");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+    let position = |xml: &str, name: &str| {
+        xml.find(&format!(r#"<File name="{}""#, name))
+            .unwrap_or_else(|| panic!("XML output does not contain {}", name))
+    };
+
+    // Default: byte order
+    let xml_output = scan(&[]);
+    assert!(position(&xml_output, "B.txt") < position(&xml_output, "a.txt"));
+    assert!(position(&xml_output, "a.txt") < position(&xml_output, "b.txt"));
+
+    // Largest first
+    let xml_output = scan(&["--sort", "size"]);
+    assert!(position(&xml_output, "b.txt") < position(&xml_output, "a.txt"));
+    assert!(position(&xml_output, "a.txt") < position(&xml_output, "B.txt"));
+}
+
+#[test]
+fn test_gpscan_validate() {
+    let temp_dir = TempDir::new("gpscan_validate").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+
+    let scan_dir = dir_path.join("data");
+    fs::create_dir(&scan_dir).expect("Failed to create data dir");
+    let mut file1 = File::create(scan_dir.join("file1.txt")).expect("Failed to create file1");
+    writeln!(file1, "Content for file1").expect("Failed to write to file1");
+
+    let output_file_path = dir_path.join("output.gpscan");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(scan_dir.to_str().unwrap())
+        .arg("-o")
+        .arg(output_file_path.to_str().unwrap());
+    cmd.assert().success();
+
+    // A complete dump is valid
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("validate").arg(output_file_path.to_str().unwrap());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("OK (1 folders, 1 files"));
+
+    // A truncated dump is rejected
+    let output_xml = fs::read_to_string(&output_file_path).expect("Failed to read output file");
+    let truncated_path = dir_path.join("truncated.gpscan");
+    fs::write(&truncated_path, &output_xml[..output_xml.len() - 30])
+        .expect("Failed to write truncated file");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("validate").arg(truncated_path.to_str().unwrap());
+    cmd.assert()
+        .code(5)
+        .stderr(predicate::str::contains("truncated.gpscan: "));
+}
+
+#[cfg(target_os = "windows")]
+#[test]
+fn test_gpscan_skips_directory_junctions() {
+    let temp_dir = TempDir::new("gpscan_junction").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+
+    // Create a directory and a junction pointing back to the scan root
+    fs::create_dir(dir_path.join("subdir")).expect("Failed to create subdir");
+    let mut file1 =
+        File::create(dir_path.join("subdir").join("file1.txt")).expect("Failed to create file1");
+    writeln!(file1, "Content for file1").expect("Failed to write to file1");
+
+    let status = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(dir_path.join("junction_to_root"))
+        .arg(dir_path)
+        .status()
+        .expect("Failed to run mklink");
+    assert!(status.success(), "Failed to create junction");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path.to_str().unwrap());
+    let output = cmd.output().expect("Failed to execute gpscan");
+    let xml_output = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert!(
+        predicate::str::contains(r#"<Folder name="subdir""#).eval(&xml_output),
+        "XML output does not contain subdir"
+    );
+    assert!(
+        !predicate::str::contains(r#"name="junction_to_root""#).eval(&xml_output),
+        "XML output contains junction_to_root"
+    );
+}
+
+#[test]
+fn test_gpscan_merge() {
+    let temp_dir = TempDir::new("gpscan_merge").expect("Failed to create temp dir");
+    let base = temp_dir.path();
+
+    for (dir, file, content) in [("one", "a.txt", "abc"), ("two", "b.txt", "defg")] {
+        let dir_path = base.join(dir);
+        fs::create_dir(&dir_path).expect("Failed to create dir");
+        fs::write(dir_path.join(file), content).expect("Failed to write file");
+
+        let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+        cmd.arg(&dir_path)
+            .arg("-A")
+            .arg("-o")
+            .arg(base.join(format!("{}.gpscan", dir)));
+        cmd.assert().success();
+    }
+
+    let merged_path = base.join("merged.gpscan");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("merge")
+        .arg(base.join("one.gpscan"))
+        .arg(base.join("two.gpscan"))
+        .arg("--root-name")
+        .arg("estate")
+        .arg("-o")
+        .arg(&merged_path);
+    cmd.assert().success();
+
+    let xml_output = fs::read_to_string(&merged_path).expect("Failed to read merged dump");
+    assert!(xml_output.contains(r#"<Folder name="estate""#));
+    assert!(xml_output.contains(r#"<File name="a.txt" size="3""#));
+    assert!(xml_output.contains(r#"<File name="b.txt" size="4""#));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("validate").arg(&merged_path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("3 folders, 2 files, 7 bytes"));
+
+    // Unreadable inputs are reported with the invalid dump exit code
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("merge").arg(base.join("missing.gpscan"));
+    cmd.assert().code(5);
+}
+
+#[test]
+fn test_gpscan_top() {
+    let temp_dir = TempDir::new("gpscan_top").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(dir_path.join("big")).expect("Failed to create dir");
+    fs::write(dir_path.join("big").join("large.bin"), vec![0u8; 3000]).expect("Failed to write");
+    fs::write(dir_path.join("medium.bin"), vec![0u8; 2000]).expect("Failed to write");
+    fs::write(dir_path.join("small.bin"), vec![0u8; 1000]).expect("Failed to write");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("top").arg(&dir_path).arg("-A").arg("-n").arg("2");
+    let output = cmd.output().expect("Failed to execute gpscan");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("Output is not UTF-8");
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "Largest files:");
+    assert!(lines[1].ends_with("large.bin") && lines[1].contains("2.9 KiB"));
+    assert!(lines[2].ends_with("medium.bin"));
+    assert_eq!(lines[3], "Largest directories:");
+    assert!(lines[4].ends_with("data") && lines[4].contains("5.9 KiB"));
+    assert!(lines[5].ends_with("big"));
+    assert_eq!(lines.len(), 6);
+
+    // The same list can be produced from an existing dump
+    let dump_path = temp_dir.path().join("data.gpscan");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("-A").arg("-o").arg(&dump_path);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("top").arg(&dump_path).arg("-n").arg("2");
+    cmd.assert().success().stdout(stdout);
+}
+
+#[test]
+fn test_gpscan_import() {
+    let temp_dir = TempDir::new("gpscan_import").expect("Failed to create temp dir");
+    let base = temp_dir.path();
+
+    let ncdu_path = base.join("export.json");
+    fs::write(
+        &ncdu_path,
+        r#"[1,2,{"progname":"ncdu","progver":"1.19","timestamp":1700000000},
+[{"name":"/data","asize":4096,"dsize":4096},
+ {"name":"a.txt","asize":3,"dsize":4096},
+ [{"name":"sub","asize":4096,"dsize":4096},
+  {"name":"b.bin","asize":5000,"dsize":8192,"ino":7,"hlnkc":true},
+  {"name":"c.bin","asize":5000,"dsize":8192,"ino":7,"hlnkc":true}]]]"#,
+    )
+    .expect("Failed to write export");
+
+    let dump_path = base.join("ncdu.gpscan");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("import")
+        .arg("--from")
+        .arg("ncdu")
+        .arg(&ncdu_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(&dump_path);
+    cmd.assert().success();
+
+    let xml_output = fs::read_to_string(&dump_path).expect("Failed to read dump");
+    assert!(xml_output.contains(r#"scanTime="2023-11-14T22:13:20Z""#));
+    assert!(xml_output.contains(r#"<Folder name="/data""#));
+    assert!(xml_output.contains(r#"<File name="a.txt" size="3""#));
+    assert!(xml_output.contains(r#"<File name="b.bin" size="5000""#));
+    // The second link to the same inode is not counted again
+    assert!(!xml_output.contains(r#"name="c.bin""#));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("validate").arg(&dump_path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("2 folders, 2 files, 5003 bytes"));
+
+    // du output, read from stdin, without -a
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("import").arg("--from").arg("du").arg("-");
+    cmd.write_stdin("8\t./logs/old\n12\t./logs\n4\t./tmp\n20\t.\n");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"<Folder name=".""#))
+        .stdout(predicate::str::contains(r#"<File name="old" size="8192""#))
+        .stdout(predicate::str::contains(
+            r#"<File name="(files)" size="4096""#,
+        ))
+        .stdout(predicate::str::contains(r#"<File name="tmp" size="4096""#));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("import").arg("--from").arg("du").arg("-");
+    cmd.write_stdin("not du output\n");
+    cmd.assert().code(5);
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_gpscan_output_url() {
+    use flate2::read::GzDecoder;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    let temp_dir = TempDir::new("gpscan_output_url").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("upload.txt"), "uploaded").expect("Failed to write file");
+
+    // Accept a single PUT request and return its request line and body
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let url = format!(
+        "http://{}/dumps/scan.gpscan",
+        listener.local_addr().unwrap()
+    );
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("Failed to accept");
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        (request_line, body)
+    });
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path).arg("-A").arg("--output-url").arg(&url);
+    cmd.assert().success().stdout("");
+
+    let (request_line, body) = server.join().expect("Server failed");
+    assert!(request_line.starts_with("PUT /dumps/scan.gpscan HTTP/1.1"));
+    let mut xml_output = String::new();
+    GzDecoder::new(body.as_slice())
+        .read_to_string(&mut xml_output)
+        .expect("Upload is not gzip-compressed");
+    assert!(xml_output.contains(r#"<File name="upload.txt" size="8""#));
+    assert!(xml_output
+        .trim_end()
+        .ends_with("</GrandPerspectiveScanDump>"));
+
+    // Output to a file and to a URL cannot be combined
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path)
+        .arg("-o")
+        .arg("x")
+        .arg("--output-url")
+        .arg(&url);
+    cmd.assert().code(1);
+}
+
+#[test]
+fn test_gpscan_daemon() {
+    use assert_cmd::cargo::CommandCargoExt;
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new("gpscan_daemon").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    let out_dir = temp_dir.path().join("out");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::create_dir(&out_dir).expect("Failed to create dir");
+    fs::write(dir_path.join("file.txt"), "data").expect("Failed to write file");
+
+    // A stale dump from an earlier run, which is rotated out
+    fs::write(out_dir.join("scan-20000101T000000Z.gpscan"), "old").expect("Failed to write");
+
+    let mut child = std::process::Command::cargo_bin("gpscan")
+        .expect("Failed to build gpscan")
+        .arg(&dir_path)
+        .arg("-q")
+        .arg("--daemon")
+        .arg("--interval")
+        .arg("1s")
+        .arg("--keep")
+        .arg("2")
+        .arg("-o")
+        .arg(out_dir.join("scan.gpscan"))
+        .spawn()
+        .expect("Failed to start gpscan");
+    std::thread::sleep(Duration::from_millis(3500));
+    child.kill().expect("Failed to stop gpscan");
+    child.wait().expect("Failed to wait for gpscan");
+
+    let mut names: Vec<String> = fs::read_dir(&out_dir)
+        .expect("Failed to read output dir")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.ends_with(".tmp"))
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 2, "{:?}", names);
+    for name in &names {
+        assert!(name.starts_with("scan-20") && name.ends_with("Z.gpscan"));
+        assert_ne!(name, "scan-20000101T000000Z.gpscan");
+        let xml_output = fs::read_to_string(out_dir.join(name)).expect("Failed to read dump");
+        assert!(xml_output.contains(r#"<File name="file.txt""#));
+    }
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--daemon")
+        .arg("--interval")
+        .arg("6x")
+        .arg("-o")
+        .arg(out_dir.join("scan.gpscan"));
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("invalid interval unit"));
+}
+
+#[test]
+fn test_gpscan_metrics() {
+    use assert_cmd::cargo::CommandCargoExt;
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new("gpscan_metrics").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(dir_path.join("logs")).expect("Failed to create dir");
+    fs::write(dir_path.join("logs").join("app.log"), "0123456789").expect("Failed to write");
+    fs::write(dir_path.join("top.txt"), "abc").expect("Failed to write file");
+    let metrics_path = temp_dir.path().join("gpscan.prom");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--apparent-size")
+        .arg("-o")
+        .arg(temp_dir.path().join("scan.gpscan"))
+        .arg("--metrics-textfile")
+        .arg(&metrics_path);
+    cmd.assert().success();
+
+    let metrics = fs::read_to_string(&metrics_path).expect("Failed to read metrics");
+    let root = dir_path.to_string_lossy();
+    assert!(metrics.contains("# TYPE gpscan_size_bytes gauge"));
+    assert!(metrics.contains(&format!("gpscan_size_bytes{{root=\"{}\"}} 13\n", root)));
+    assert!(metrics.contains(&format!("gpscan_files{{root=\"{}\"}} 2\n", root)));
+    assert!(metrics.contains(&format!(
+        "gpscan_directory_size_bytes{{root=\"{}\",directory=\"logs\"}} 10\n",
+        root
+    )));
+    assert!(metrics.contains("gpscan_scan_duration_seconds{"));
+
+    // A file that cannot be moved into place leaves no temporary file behind
+    let blocked_path = temp_dir.path().join("blocked.prom");
+    fs::create_dir(&blocked_path).expect("Failed to create dir");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-o")
+        .arg(temp_dir.path().join("scan.gpscan"))
+        .arg("--metrics-textfile")
+        .arg(&blocked_path);
+    cmd.assert()
+        .code(3)
+        .stderr(predicate::str::contains("Failed to move the output"));
+    assert!(!temp_dir.path().join("blocked.prom.tmp").exists());
+
+    // In daemon mode, the metrics of the latest scan are served over HTTP
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to find a free port");
+    let mut child = std::process::Command::cargo_bin("gpscan")
+        .expect("Failed to build gpscan")
+        .arg(&dir_path)
+        .arg("-q")
+        .arg("--daemon")
+        .arg("-o")
+        .arg(temp_dir.path().join("daemon.gpscan"))
+        .arg("--metrics-listen")
+        .arg(address.to_string())
+        .spawn()
+        .expect("Failed to start gpscan");
+
+    let mut response = String::new();
+    for _ in 0..50 {
+        std::thread::sleep(Duration::from_millis(100));
+        if let Ok(mut stream) = TcpStream::connect(address) {
+            stream
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .expect("Failed to send request");
+            response.clear();
+            stream
+                .read_to_string(&mut response)
+                .expect("Failed to read response");
+            if response.contains("gpscan_files") {
+                break;
+            }
+        }
+    }
+    child.kill().expect("Failed to stop gpscan");
+    child.wait().expect("Failed to wait for gpscan");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.contains(&format!("gpscan_files{{root=\"{}\"}} 2\n", root)));
+
+    // The endpoint is only available in daemon mode
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--metrics-listen")
+        .arg("127.0.0.1:0");
+    cmd.assert().code(1);
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_gpscan_notify_cmd() {
+    let temp_dir = TempDir::new("gpscan_notify_cmd").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("file.txt"), "12345").expect("Failed to write file");
+    let output = temp_dir.path().join("scan.gpscan");
+    let env_file = temp_dir.path().join("env.txt");
+    let command = format!(
+        "echo \"$GPSCAN_STATUS $GPSCAN_FILES $GPSCAN_BYTES $GPSCAN_OUTPUT\" > '{}'",
+        env_file.display()
+    );
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(&output)
+        .arg("--notify-cmd")
+        .arg(&command);
+    cmd.assert().success();
+    let summary = fs::read_to_string(&env_file).expect("Command did not run");
+    assert_eq!(summary.trim(), format!("ok 1 5 {}", output.display()));
+
+    // Failed scans are reported too, with the error message
+    let command = format!(
+        "echo \"$GPSCAN_STATUS $GPSCAN_ERROR\" > '{}'",
+        env_file.display()
+    );
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(temp_dir.path().join("missing"))
+        .arg("--notify-cmd")
+        .arg(&command);
+    cmd.assert().code(2);
+    let summary = fs::read_to_string(&env_file).expect("Command did not run");
+    assert!(summary.starts_with("failed The specified path does not exist"));
+
+    // A failing command is logged, but does not fail the scan
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--notify-cmd").arg("exit 3");
+    cmd.assert().success().stderr(predicate::str::contains(
+        "Notification command 'exit 3' failed",
+    ));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_gpscan_notify_url() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    let temp_dir = TempDir::new("gpscan_notify_url").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("file.txt"), "12345").expect("Failed to write file");
+
+    // Accept a single POST request and return its request line and body
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let url = format!("http://{}/hooks/gpscan", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("Failed to accept");
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        (request_line, body)
+    });
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(dir_path).arg("-A").arg("--notify-url").arg(&url);
+    cmd.assert().success();
+
+    let (request_line, body) = server.join().expect("Server failed");
+    assert!(request_line.starts_with("POST /hooks/gpscan HTTP/1.1"));
+    let summary: serde_json::Value = serde_json::from_slice(&body).expect("Invalid JSON");
+    assert_eq!(summary["status"], "ok");
+    assert_eq!(summary["root"], dir_path.to_string_lossy().as_ref());
+    assert_eq!(summary["files"], 1);
+    assert_eq!(summary["bytes"], 5);
+    assert_eq!(summary["output"], serde_json::Value::Null);
+    assert!(summary["duration_seconds"].is_number());
+}
+
+#[test]
+fn test_gpscan_profile() {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let temp_dir = TempDir::new("gpscan_profile").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("nas");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("keep.txt"), "keep").expect("Failed to write file");
+    fs::write(dir_path.join("skip.tmp"), "skip").expect("Failed to write file");
+    let output = temp_dir.path().join("nas.gpscan.gz");
+
+    let config = temp_dir.path().join("config.toml");
+    fs::write(
+        &config,
+        format!(
+            r#"
+[profiles.nas-weekly]
+root = '{}'
+exclude = ["*.tmp"]
+output = '{}'
+compress = true
+apparent-size = true
+
+[profiles.broken]
+root = "/"
+colour = true
+"#,
+            dir_path.display(),
+            output.display()
+        ),
+    )
+    .expect("Failed to write config");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("--config")
+        .arg(&config)
+        .arg("--profile")
+        .arg("nas-weekly");
+    cmd.assert().success().stdout("");
+
+    let mut xml_output = String::new();
+    GzDecoder::new(File::open(&output).expect("Output not written"))
+        .read_to_string(&mut xml_output)
+        .expect("Output is not gzip-compressed");
+    assert!(xml_output.contains(r#"<File name="keep.txt" size="4""#));
+    assert!(!xml_output.contains("skip.tmp"));
+
+    // The root and options on the command line override those of the profile
+    let other_output = temp_dir.path().join("other.gpscan.gz");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.env("GPSCAN_CONFIG", &config)
+        .arg(temp_dir.path())
+        .arg("--profile")
+        .arg("nas-weekly")
+        .arg("-o")
+        .arg(&other_output);
+    cmd.assert().success();
+    let mut xml_output = String::new();
+    GzDecoder::new(File::open(&other_output).expect("Output not written"))
+        .read_to_string(&mut xml_output)
+        .expect("Output is not gzip-compressed");
+    assert!(xml_output.contains(r#"<File name="config.toml""#));
+
+    // Unknown profiles and options are usage errors
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("--config")
+        .arg(&config)
+        .arg("--profile")
+        .arg("daily");
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("Profile 'daily' not found"));
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg("--config")
+        .arg(&config)
+        .arg("--profile")
+        .arg("broken");
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("unknown option 'colour'"));
+}
+
+#[test]
+fn test_gpscan_all_volumes_conflicts_with_directory() {
+    let temp_dir = TempDir::new("gpscan_all_volumes_test").expect("Failed to create temp dir");
+
+    Command::cargo_bin("gpscan")
+        .unwrap()
+        .arg("--all-volumes")
+        .arg(temp_dir.path())
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Command::cargo_bin("gpscan")
+        .unwrap()
+        .arg("--parallel-volumes")
+        .arg(temp_dir.path())
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("--all-volumes"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_gpscan_all_drives_unsupported() {
+    let temp_dir = TempDir::new("gpscan_all_drives_test").expect("Failed to create temp dir");
+
+    Command::cargo_bin("gpscan")
+        .unwrap()
+        .arg("--all-drives")
+        .arg("-o")
+        .arg(temp_dir.path().join("scan.gpscan"))
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("only available on Windows"));
+}
+
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn test_gpscan_snapshot_unsupported() {
+    let temp_dir = TempDir::new("gpscan_snapshot_test").expect("Failed to create temp dir");
+
+    Command::cargo_bin("gpscan")
+        .unwrap()
+        .arg(temp_dir.path())
+        .args([
+            "--snapshot",
+            "com.apple.TimeMachine.2024-03-01-120000.local",
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("only available on macOS"));
+}
+
+#[test]
+fn test_gpscan_lock() {
+    use assert_cmd::cargo::CommandCargoExt;
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new("gpscan_lock").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("file.txt"), "data").expect("Failed to write file");
+    let output = temp_dir.path().join("scan.gpscan");
+
+    // A daemon holds the lock for as long as it runs
+    let mut daemon = std::process::Command::cargo_bin("gpscan")
+        .expect("Failed to build gpscan")
+        .env("TMPDIR", temp_dir.path())
+        .arg(&dir_path)
+        .arg("-q")
+        .arg("--lock")
+        .arg("--daemon")
+        .arg("-o")
+        .arg(temp_dir.path().join("daemon.gpscan"))
+        .spawn()
+        .expect("Failed to start gpscan");
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !fs::read_dir(temp_dir.path()).unwrap().any(|entry| {
+        entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .ends_with(".lock")
+    }) {
+        assert!(Instant::now() < deadline, "Lock file was not created");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    std::thread::sleep(Duration::from_millis(200));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.env("TMPDIR", temp_dir.path())
+        .arg(&dir_path)
+        .arg("--lock")
+        .arg("-o")
+        .arg(&output);
+    cmd.assert().code(6).stderr(predicate::str::contains(
+        "Another gpscan process is scanning",
+    ));
+    assert!(!output.exists());
+
+    // Without --lock, scans are not coordinated
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.env("TMPDIR", temp_dir.path()).arg(&dir_path);
+    cmd.assert().success();
+
+    // With --lock-wait, the scan starts once the daemon has stopped
+    let mut waiting = std::process::Command::cargo_bin("gpscan")
+        .expect("Failed to build gpscan")
+        .env("TMPDIR", temp_dir.path())
+        .arg(&dir_path)
+        .arg("-q")
+        .arg("--lock-wait")
+        .arg("-o")
+        .arg(&output)
+        .spawn()
+        .expect("Failed to start gpscan");
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(waiting.try_wait().unwrap().is_none());
+    assert!(!output.exists());
+
+    daemon.kill().expect("Failed to stop gpscan");
+    daemon.wait().expect("Failed to wait for gpscan");
+    let status = waiting.wait().expect("Failed to wait for gpscan");
+    assert!(status.success());
+    assert!(output.exists());
+}
+
+#[test]
+fn test_gpscan_report() {
+    let temp_dir = TempDir::new("gpscan_report").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(dir_path.join("videos")).expect("Failed to create dir");
+    fs::write(dir_path.join("videos").join("a.mkv"), vec![0; 2048]).expect("Failed to write");
+    fs::write(dir_path.join("notes.txt"), "notes").expect("Failed to write file");
+    let output = temp_dir.path().join("scan.gpscan");
+
+    // With an output file, the report is printed to stdout
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(&output)
+        .arg("--report")
+        .arg("extensions");
+    cmd.assert()
+        .success()
+        .stdout("Extensions:\n   2.0 KiB         1  mkv\n       5 B         1  txt\n");
+
+    // Otherwise it goes to stderr, so that the dump on stdout stays intact
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("--report")
+        .arg("extensions")
+        .arg("--report-format")
+        .arg("json");
+    let assert = cmd.assert().success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(stdout.trim_end().ends_with("</GrandPerspectiveScanDump>"));
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    let line = stderr
+        .lines()
+        .find(|line| line.starts_with('{'))
+        .expect("No JSON report");
+    let report: serde_json::Value = serde_json::from_str(line).expect("Invalid JSON");
+    assert_eq!(report["extensions"][0]["key"], "mkv");
+    assert_eq!(report["extensions"][0]["size"], 2048);
+    assert_eq!(report["extensions"][1]["files"], 1);
+
+    // Several reports can be combined
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-o")
+        .arg(&output)
+        .arg("--report")
+        .arg("owners")
+        .arg("--report")
+        .arg("extensions");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("Owners:\n"))
+        .stdout(predicate::str::contains("\nExtensions:\n"));
+}
+
+#[test]
+fn test_gpscan_baseline() {
+    let temp_dir = TempDir::new("gpscan_baseline").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("srv");
+    fs::create_dir_all(dir_path.join("logs")).expect("Failed to create dir");
+    fs::create_dir_all(dir_path.join("db")).expect("Failed to create dir");
+    fs::write(dir_path.join("logs").join("app.log"), vec![0; 1024]).expect("Failed to write");
+    fs::write(dir_path.join("db").join("data.bin"), vec![0; 4096]).expect("Failed to write");
+    let baseline = temp_dir.path().join("baseline.gpscan");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("-A").arg("-o").arg(&baseline);
+    cmd.assert().success();
+
+    fs::write(dir_path.join("logs").join("app.log"), vec![0; 3072]).expect("Failed to write");
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(temp_dir.path().join("current.gpscan"))
+        .arg("--baseline")
+        .arg(&baseline);
+    let root = dir_path.display().to_string();
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("Largest growth since "))
+        .stdout(predicate::str::contains(format!(
+            "   +2.0 KiB     7.0 KiB  {}\n",
+            root
+        )))
+        .stdout(predicate::str::contains(format!(
+            "   +2.0 KiB     3.0 KiB  {}\n",
+            dir_path.join("logs").display()
+        )))
+        .stdout(predicate::str::contains("db").not());
+
+    // An unreadable baseline fails before scanning
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--baseline")
+        .arg(temp_dir.path().join("missing.gpscan"));
+    cmd.assert().code(5).stdout("");
+}
+
+#[test]
+fn test_gpscan_largest_files() {
+    let temp_dir = TempDir::new("gpscan_largest").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(dir_path.join("videos")).expect("Failed to create dir");
+    fs::write(dir_path.join("videos").join("a.mkv"), vec![0; 2048]).expect("Failed to write");
+    fs::write(dir_path.join("notes.txt"), "notes").expect("Failed to write file");
+    let output = temp_dir.path().join("scan.gpscan");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(&output)
+        .arg("--largest-files")
+        .arg("1");
+    cmd.assert().success().stdout("");
+    let dump = fs::read_to_string(&output).expect("Failed to read output");
+    let section = &dump[dump.find("</ScanInfo>").expect("No ScanInfo")..];
+    assert!(section.contains(&format!(
+        "<LargestFile path=\"{}\" size=\"2048\"/>",
+        dir_path.join("videos").join("a.mkv").display()
+    )));
+    assert!(!section.contains("notes.txt"));
+
+    // Printed instead of recorded
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(&output)
+        .arg("--largest-files")
+        .arg("2")
+        .arg("--print-largest-files");
+    cmd.assert().success().stdout(format!(
+        "Largest files:\n   2.0 KiB  {}\n       5 B  {}\n",
+        dir_path.join("videos").join("a.mkv").display(),
+        dir_path.join("notes.txt").display()
+    ));
+    let dump = fs::read_to_string(&output).expect("Failed to read output");
+    assert!(!dump.contains("<LargestFiles>"));
+}
+
+#[test]
+fn test_gpscan_classify() {
+    let temp_dir = TempDir::new("gpscan_classify").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("a.mkv"), vec![0; 2048]).expect("Failed to write");
+    fs::write(dir_path.join("notes.txt"), "notes").expect("Failed to write file");
+    let output = temp_dir.path().join("scan.gpscan");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(&output)
+        .arg("--classify");
+    cmd.assert()
+        .success()
+        .stdout("Kinds:\n   2.0 KiB         1  video\n       5 B         1  document\n");
+    let dump = fs::read_to_string(&output).expect("Failed to read output");
+    assert!(dump.contains("name=\"a.mkv\" size=\"2048\""));
+    assert!(dump.contains("kind=\"video\""));
+    assert!(dump.contains("kind=\"document\""));
+}
+
+#[test]
+fn test_gpscan_verify_totals() {
+    let temp_dir = TempDir::new("gpscan_totals").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("a.bin"), vec![0; 2048]).expect("Failed to write");
+    let output = temp_dir.path().join("scan.gpscan");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-A")
+        .arg("-o")
+        .arg(&output)
+        .arg("--verify-totals")
+        .arg("--report-format")
+        .arg("json");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let json: serde_json::Value = serde_json::from_slice(&stdout).expect("Invalid JSON");
+    let totals = &json["totals"];
+    assert_eq!(totals["scanned"], 2048);
+    let causes = totals["causes"].as_array().expect("No causes");
+    // A temporary directory is never the root of its volume
+    assert!(causes[0]
+        .as_str()
+        .is_some_and(|cause| cause.starts_with("Only ")));
+    assert!(causes.iter().any(|cause| cause
+        .as_str()
+        .is_some_and(|cause| cause.contains("Apparent sizes"))));
+}
+
+#[test]
+fn test_gpscan_profile_dirs() {
+    let temp_dir = TempDir::new("gpscan_profile").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(dir_path.join("sub")).expect("Failed to create dir");
+    fs::write(dir_path.join("sub").join("file.txt"), "x").expect("Failed to write file");
+    let output = temp_dir.path().join("scan.gpscan");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-o")
+        .arg(&output)
+        .arg("--profile-dirs=1");
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())
+        .expect("Invalid UTF-8");
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "Slowest directories (total / own time):");
+    assert!(lines[1].ends_with(&format!("  {}", dir_path.display())));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("-o")
+        .arg(&output)
+        .arg("--profile-dirs")
+        .arg("--report-format")
+        .arg("json");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let json: serde_json::Value = serde_json::from_slice(&stdout).expect("Invalid JSON");
+    let dirs = json["slowest_dirs"].as_array().expect("No slowest_dirs");
+    assert_eq!(dirs.len(), 2);
+    assert_eq!(
+        dirs[1]["path"],
+        dir_path.join("sub").to_string_lossy().as_ref()
+    );
+}
+
+#[test]
+fn test_gpscan_dir_entries() {
+    let temp_dir = TempDir::new("gpscan_dir_entries").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(dir_path.join("spool")).expect("Failed to create dir");
+    for i in 0..5 {
+        fs::write(dir_path.join("spool").join(i.to_string()), "mail").expect("Failed to write");
+    }
+    fs::write(dir_path.join("notes.txt"), "notes").expect("Failed to write file");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--warn-dir-entries").arg("4");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("<Folder name=\"spool\""))
+        .stdout(predicate::str::contains("<File name=\"0\""))
+        .stderr(predicate::str::contains(format!(
+            "[WARN] {} has 5 entries",
+            dir_path.join("spool").display()
+        )));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--skip-dir-entries").arg("4");
+    cmd.assert()
+        .success()
+        .stdout(
+            predicate::str::is_match("<Folder name=\"spool\"[^>]* skippedEntries=\"5\"/>").unwrap(),
+        )
+        .stdout(predicate::str::contains("<File name=\"0\"").not())
+        .stdout(predicate::str::contains("<File name=\"notes.txt\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gpscan_root_symlink() {
+    let temp_dir = TempDir::new("gpscan_root_symlink").expect("Failed to create temp dir");
+    let target = temp_dir.path().join("target");
+    fs::create_dir(&target).expect("Failed to create dir");
+    fs::write(target.join("a.txt"), "abc").expect("Failed to write a.txt");
+    let link = temp_dir.path().join("link");
+    symlink(&target, &link).expect("Failed to create symlink");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&link).arg("-A");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let xml_output = String::from_utf8(stdout).expect("Output is not UTF-8");
+    assert!(xml_output.contains(&format!(r#"<Folder name="{}""#, link.display())));
+    assert!(xml_output.contains(r#"<File name="a.txt" size="3""#));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&link).arg("--follow-root-symlink=false");
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("is a symbolic link"));
+}
+
+#[test]
+fn test_gpscan_absolute_paths() {
+    let temp_dir = TempDir::new("gpscan_absolute_paths").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::create_dir(dir_path.join("logs")).expect("Failed to create logs");
+    fs::write(dir_path.join("logs").join("app.log"), "abc").expect("Failed to write app.log");
+    let absolute = fs::canonicalize(dir_path).expect("Failed to resolve temp dir");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.current_dir(&absolute).arg(".").arg("--absolute-paths");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let xml_output = String::from_utf8(stdout).expect("Output is not UTF-8");
+    // The root keeps the name given on the command line
+    assert!(xml_output.contains(r#"<Folder name=".""#));
+    let app_log = absolute.join("logs").join("app.log");
+    assert!(xml_output.contains(&format!(r#"path="{}""#, app_log.display())));
+    assert!(xml_output.contains(&format!(r#"path="{}""#, absolute.display())));
+}
+
+#[test]
+fn test_gpscan_relative_root() {
+    let temp_dir = TempDir::new("gpscan_relative_root").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("mnt").join("data");
+    fs::create_dir_all(&dir_path).expect("Failed to create dir");
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--relative-root");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let xml_output = String::from_utf8(stdout).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#"<Folder name=".""#));
+    assert!(!xml_output.contains(&dir_path.display().to_string()));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--relative-root=data");
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let xml_output = String::from_utf8(stdout).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#"<Folder name="data""#));
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg("--relative-root")
+        .arg("--absolute-paths");
+    cmd.assert().code(1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_gpscan_filter_cmd() {
+    let temp_dir = TempDir::new("gpscan_filter_cmd").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("data");
+    fs::create_dir_all(dir_path.join("secret_dir")).expect("Failed to create dir");
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    fs::write(dir_path.join("secret.txt"), "abc").expect("Failed to write secret.txt");
+    fs::write(dir_path.join("secret_dir").join("b.txt"), "abc").expect("Failed to write b.txt");
+    let script = temp_dir.path().join("filter.sh");
+    fs::write(
+        &script,
+        r#"while IFS= read -r path; do
+  case "$path" in
+    "") ;;
+    *secret*) echo skip ;;
+    *) echo keep ;;
+  esac
+done
+"#,
+    )
+    .expect("Failed to write filter.sh");
+
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path)
+        .arg(format!("--filter-cmd=sh {}", script.display()));
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let xml_output = String::from_utf8(stdout).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#"name="a.txt""#));
+    assert!(!xml_output.contains("secret"));
+    assert!(!xml_output.contains(r#"name="b.txt""#));
+    assert!(xml_output.contains(r#"skippedFilterCommand="2""#));
+
+    // A command that does not answer fails the scan
+    let mut cmd = Command::cargo_bin("gpscan").expect("Failed to build gpscan");
+    cmd.arg(&dir_path).arg("--filter-cmd=true");
+    cmd.assert()
+        .code(3)
+        .stderr(predicate::str::contains("Filter command 'true' failed"));
+}
@@ -0,0 +1,2536 @@
+use gpscan::filters;
+use gpscan::{
+    read_scan, read_scan_from, scan, scan_to_tree, scan_with_formatter, scan_with_visitor,
+    BloomFilter, CancellationToken, EntryKind, EntryMetadata, Error, ErrorAction, ErrorPolicy,
+    InodeFilter, MemorySource, NodeKind, Options, OutputFormatter, PlaceholderMode, Progress,
+    ProgressSink, ScanEntry, ScanInfo, ScanIter, ScanSource, ScanVisitor, SkipReason, SortOrder,
+};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tempdir::TempDir;
+
+#[test]
+fn test_scan_with_builder_options() {
+    // Set up a temporary directory structure for testing:
+    //
+    // gpscan_lib_test
+    // ├── file1.txt
+    // ├── empty_file.txt
+    // └── node_modules
+    //     └── package.js
+    //
+    let temp_dir = TempDir::new("gpscan_lib_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+
+    let mut file1 = File::create(dir_path.join("file1.txt")).expect("Failed to create file1");
+    writeln!(file1, "This is a test file.").expect("Failed to write to file1");
+    File::create(dir_path.join("empty_file.txt")).expect("Failed to create empty_file");
+    fs::create_dir(dir_path.join("node_modules")).expect("Failed to create node_modules");
+    let mut package =
+        File::create(dir_path.join("node_modules").join("package.js")).expect("Failed to create");
+    writeln!(package, "module.exports = {{}};").expect("Failed to write to package.js");
+
+    let options = Options::builder()
+        .apparent_size(true)
+        .include_zero_files(true)
+        .sort_order(SortOrder::Name)
+        .exclude("node_*")
+        .build();
+
+    let mut output = Vec::new();
+    let stats = scan(dir_path, &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+
+    assert_eq!(stats.errors, 0);
+    assert!(xml_output.contains(r#"<File name="file1.txt" size="21""#));
+    assert!(xml_output.contains(r#"<File name="empty_file.txt" size="0""#));
+    assert!(!xml_output.contains("node_modules"));
+    assert!(!xml_output.contains("package.js"));
+}
+
+#[test]
+fn test_scan_info_counts() {
+    let temp_dir = TempDir::new("gpscan_counts_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    File::create(dir_path.join("empty1.txt")).expect("Failed to create empty1.txt");
+    File::create(dir_path.join("empty2.txt")).expect("Failed to create empty2.txt");
+    fs::write(dir_path.join("b.tmp"), "abc").expect("Failed to write b.tmp");
+
+    let options = Options::builder().exclude("*.tmp").build();
+    let mut output = Vec::new();
+    scan(dir_path, &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#" errors="0" skippedExcluded="1" skippedZeroSize="2"/>"#));
+
+    let info = read_scan_from(xml_output.as_bytes())
+        .expect("Failed to read dump")
+        .info;
+    assert_eq!(info.errors, Some(0));
+    let skipped = info.skipped.expect("No skip counts");
+    assert_eq!(skipped.get(filters::SkipReason::ZeroSize), 2);
+    assert_eq!(skipped.get(filters::SkipReason::Excluded), 1);
+    assert_eq!(skipped.total(), 3);
+}
+
+#[test]
+fn test_scan_duration_and_free_space() {
+    let temp_dir = TempDir::new("gpscan_free_space_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+
+    let mut output = Vec::new();
+    scan(dir_path, &Options::default(), &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains("</ScanInfo>\n<ScanSummary scanDuration=\""));
+    assert!(xml_output.contains(" freeSpaceAfter=\""));
+
+    let info = read_scan_from(xml_output.as_bytes())
+        .expect("Failed to read dump")
+        .info;
+    assert!(info.scan_duration.is_some());
+    let free_space_after = info.free_space_after.expect("No free space after the scan");
+    if info.volume_size > 0 {
+        assert!(free_space_after <= info.volume_size);
+    }
+
+    // The dump is written as the scan goes, with the summary at the end
+    let mut formatter = gpscan::XmlFormatter::new(Vec::new());
+    formatter.begin_scan(&info).expect("Failed to begin");
+    let entry = ScanEntry {
+        path: dir_path.join("a.txt"),
+        name: "a.txt".to_string(),
+        depth: 1,
+        size: 3,
+        metadata: EntryMetadata::new(EntryKind::File, 3),
+        attributes: Vec::new(),
+    };
+    formatter.file(&entry).expect("Failed to write a file");
+    assert!(formatter.tree_len() > 0);
+    let partial = String::from_utf8(formatter.into_inner()).expect("Output is not UTF-8");
+    assert!(partial.contains(r#"<File name="a.txt" size="3""#));
+    assert!(!partial.contains("scanDuration"));
+}
+
+#[test]
+fn test_scan_rejects_missing_root() {
+    let temp_dir = TempDir::new("gpscan_lib_missing").expect("Failed to create temp dir");
+    let missing = temp_dir.path().join("missing");
+
+    let result = scan(&missing, &Options::default(), Vec::new());
+    match result.expect_err("Scan of a missing root succeeded") {
+        Error::RootNotFound(path) => assert_eq!(path, missing),
+        e => panic!("Unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_read_scan_errors() {
+    let error = read_scan_from(&b"<GrandPerspectiveScanDump><Folder name=\"a\"></File>"[..])
+        .expect_err("Malformed dump was read");
+    assert!(matches!(error, Error::Xml { .. }), "{:?}", error);
+    assert_eq!(error.exit_code(), gpscan::exit_code::INVALID_DUMP);
+
+    let error = read_scan_from(&b"<GrandPerspectiveScanDump></GrandPerspectiveScanDump>"[..])
+        .expect_err("Empty dump was read");
+    assert!(matches!(error, Error::InvalidDump(_)), "{:?}", error);
+
+    // Errors can still be handled as I/O errors
+    let error = io::Error::from(error);
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_scan_to_tree() {
+    // gpscan_tree_test
+    // ├── a.txt
+    // └── sub
+    //     └── b.txt
+    let temp_dir = TempDir::new("gpscan_tree_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+
+    fs::write(dir_path.join("a.txt"), "hello").expect("Failed to write a.txt");
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("b.txt"), "world!").expect("Failed to write b.txt");
+
+    let options = Options::builder().apparent_size(true).build();
+    let root = scan_to_tree(dir_path, &options).expect("Failed to scan");
+
+    assert_eq!(root.name, dir_path.display().to_string());
+    assert!(root.is_folder());
+    assert_eq!(root.file_count(), 2);
+    assert_eq!(root.total_size(), 11);
+
+    let names: Vec<&str> = root.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["a.txt", "sub"]);
+    assert_eq!(root.children[0].kind, NodeKind::File);
+    assert!(root.children[0].modified.is_some());
+    assert_eq!(root.children[1].children[0].name, "b.txt");
+    assert_eq!(root.children[1].children[0].size, 6);
+}
+
+#[derive(Default)]
+struct RecordingVisitor {
+    events: Vec<String>,
+}
+
+impl ScanVisitor for RecordingVisitor {
+    fn enter_dir(&mut self, entry: &ScanEntry) -> std::io::Result<()> {
+        if entry.depth > 0 {
+            self.events.push(format!("enter {}", entry.name));
+        }
+        Ok(())
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> std::io::Result<()> {
+        self.events.push(format!(
+            "file {} {} {}",
+            entry.name, entry.depth, entry.size
+        ));
+        Ok(())
+    }
+
+    fn leave_dir(&mut self, entry: &ScanEntry) -> std::io::Result<()> {
+        if entry.depth > 0 {
+            self.events.push(format!("leave {}", entry.name));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_scan_with_visitor() {
+    let temp_dir = TempDir::new("gpscan_visitor_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("b.txt"), "de").expect("Failed to write b.txt");
+
+    let options = Options::builder().apparent_size(true).build();
+    let mut visitor = RecordingVisitor::default();
+    let stats = scan_with_visitor(dir_path, &options, &mut visitor).expect("Failed to scan");
+
+    assert_eq!(stats.errors, 0);
+    assert_eq!(
+        visitor.events,
+        ["file a.txt 1 3", "enter sub", "file b.txt 2 2", "leave sub"]
+    );
+}
+
+#[test]
+fn test_scan_iter() {
+    let temp_dir = TempDir::new("gpscan_iter_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("b.txt"), "de").expect("Failed to write b.txt");
+
+    let entries: Vec<ScanEntry> = ScanIter::new(dir_path, &Options::default())
+        .collect::<Result<_, _>>()
+        .expect("Failed to scan");
+    let names: Vec<(usize, &str)> = entries.iter().map(|e| (e.depth, e.name.as_str())).collect();
+
+    assert_eq!(names[1..], [(1, "a.txt"), (1, "sub"), (2, "b.txt")]);
+    assert_eq!(names[0].0, 0);
+
+    // A missing root is reported as the only item
+    let mut iter = ScanIter::new(dir_path.join("missing"), &Options::default());
+    assert!(iter.next().expect("No error reported").is_err());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_scan_cancelled() {
+    let temp_dir = TempDir::new("gpscan_cancel_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("a.txt"), "abc").expect("Failed to write a.txt");
+
+    let token = CancellationToken::new();
+    let options = Options::builder().cancellation_token(token.clone()).build();
+    token.cancel();
+
+    let mut output = Vec::new();
+    let stats = scan(dir_path, &options, &mut output).expect("Failed to scan");
+    assert!(stats.cancelled);
+
+    // The partial dump is still well-formed
+    let report = gpscan::validate::validate(output.as_slice()).expect("Invalid partial dump");
+    assert_eq!(report.folders, 1);
+    assert_eq!(report.files, 0);
+}
+
+#[derive(Default)]
+struct CountingProgress {
+    updates: AtomicU64,
+    finished: Mutex<Option<(u64, u64, u64)>>,
+}
+
+impl ProgressSink for CountingProgress {
+    fn update(&self, _progress: &Progress) {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn finish(&self, progress: &Progress) {
+        *self.finished.lock().unwrap() = Some((progress.folders, progress.files, progress.bytes));
+    }
+}
+
+#[test]
+fn test_scan_progress() {
+    let temp_dir = TempDir::new("gpscan_progress_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("b.txt"), "de").expect("Failed to write b.txt");
+
+    let sink = Arc::new(CountingProgress::default());
+    let options = Options::builder()
+        .apparent_size(true)
+        .progress(sink.clone(), Duration::ZERO)
+        .build();
+    scan_to_tree(dir_path, &options).expect("Failed to scan");
+
+    assert_eq!(sink.updates.load(Ordering::Relaxed), 4);
+    assert_eq!(*sink.finished.lock().unwrap(), Some((2, 2, 5)));
+}
+
+struct AbortPolicy;
+
+impl ErrorPolicy for AbortPolicy {
+    fn on_error(&self, _path: &Path, _error: &std::io::Error, _attempt: u32) -> ErrorAction {
+        ErrorAction::Abort
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scan_error_policy_abort() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new("gpscan_policy_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    let locked_dir = dir_path.join("locked");
+    fs::create_dir(&locked_dir).expect("Failed to create locked dir");
+    fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o000))
+        .expect("Failed to change permissions");
+
+    // Permissions are not enforced for privileged users
+    let readable = fs::read_dir(&locked_dir).is_ok();
+
+    let options = Options::builder()
+        .error_policy(Arc::new(AbortPolicy))
+        .build();
+    let result = scan_to_tree(dir_path, &options);
+
+    fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o755))
+        .expect("Failed to restore permissions");
+
+    if readable {
+        assert!(result.is_ok());
+    } else {
+        match result.expect_err("Scan was not aborted") {
+            Error::Io(e) => assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
+}
+
+/// A tree in which the directories named `bad*` cannot be read.
+struct FailingSource(MemorySource);
+
+impl ScanSource for FailingSource {
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.0.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.0.symlink_metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("bad")
+        {
+            return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+        }
+        self.0.read_dir(path)
+    }
+}
+
+/// A tree in which every directory lists a `gone.txt` that was removed before it is read.
+struct VanishingSource(MemorySource);
+
+impl ScanSource for VanishingSource {
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.0.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.0.symlink_metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = self.0.read_dir(path)?;
+        entries.push(path.join("gone.txt"));
+        Ok(entries)
+    }
+}
+
+#[test]
+fn test_vanished_entries() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100);
+    source.add_file("/data/logs/b.log", 100);
+
+    // Removed entries are neither errors nor handed to the error policy
+    let options = Options::builder()
+        .source(Arc::new(VanishingSource(source)))
+        .error_policy(Arc::new(AbortPolicy))
+        .build();
+    let mut output = Vec::new();
+    let stats = scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    assert_eq!((stats.vanished, stats.errors), (2, 0));
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#" errors="0""#));
+    assert!(xml_output.contains(r#"<File name="b.log""#));
+    assert!(!xml_output.contains("gone.txt"));
+}
+
+/// A tree in which reading the metadata of `flaky.txt` fails with `kind` `failures` times.
+struct FlakySource {
+    inner: MemorySource,
+    kind: io::ErrorKind,
+    failures: u32,
+    attempts: AtomicU64,
+}
+
+impl ScanSource for FlakySource {
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        if path.ends_with("flaky.txt")
+            && self.attempts.fetch_add(1, Ordering::SeqCst) < u64::from(self.failures)
+        {
+            return Err(self.kind.into());
+        }
+        self.inner.symlink_metadata(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.inner.read_dir(path)
+    }
+}
+
+#[test]
+fn test_retry_transient_errors() {
+    let scan_with_retries = |kind, retries| {
+        let mut inner = MemorySource::new("/data");
+        inner.add_file("/data/flaky.txt", 100);
+        let source = FlakySource {
+            inner,
+            kind,
+            failures: 2,
+            attempts: AtomicU64::new(0),
+        };
+        let options = Options::builder()
+            .source(Arc::new(source))
+            .retries(retries)
+            .retry_delay(Duration::ZERO)
+            .build();
+        let mut output = Vec::new();
+        let stats = scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+        let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+        (
+            stats.errors,
+            xml_output.contains(r#"<File name="flaky.txt""#),
+        )
+    };
+
+    // The entry is read once the errors are over
+    let (timed_out, stale) = (
+        io::ErrorKind::TimedOut,
+        io::ErrorKind::StaleNetworkFileHandle,
+    );
+    assert_eq!(scan_with_retries(timed_out, 2), (0, true));
+    assert_eq!(scan_with_retries(stale, 3), (0, true));
+    // Otherwise it is counted as an error
+    assert_eq!(scan_with_retries(timed_out, 1), (1, false));
+    assert_eq!(scan_with_retries(stale, 0), (1, false));
+    // Other errors are not retried
+    assert_eq!(
+        scan_with_retries(io::ErrorKind::PermissionDenied, 5),
+        (1, false)
+    );
+}
+
+#[test]
+fn test_placeholders() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/local.txt", 100);
+    source.add_file("/data/cloud.txt", 4096).placeholder = true;
+    let source = Arc::new(source);
+    let scan_placeholders = |mode| {
+        let options = Options::builder()
+            .source(source.clone())
+            .placeholders(mode)
+            .include_zero_files(true)
+            .build();
+        let mut output = Vec::new();
+        scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+        String::from_utf8(output).expect("Output is not UTF-8")
+    };
+
+    let xml_output = scan_placeholders(PlaceholderMode::Zero);
+    assert!(xml_output.contains(r#"<File name="cloud.txt" size="0""#));
+    assert!(xml_output.contains(r#"<File name="local.txt" size="100""#));
+    assert!(!xml_output.contains("placeholder="));
+
+    let xml_output = scan_placeholders(PlaceholderMode::Mark);
+    let cloud = xml_output
+        .lines()
+        .find(|line| line.contains(r#"name="cloud.txt""#))
+        .expect("No placeholder file");
+    assert!(cloud.contains(r#"size="4096""#));
+    assert!(cloud.contains(r#"placeholder="true""#));
+    assert!(!xml_output.contains(r#"name="local.txt" size="100" placeholder"#));
+
+    let xml_output = scan_placeholders(PlaceholderMode::Keep);
+    assert!(xml_output.contains(r#"<File name="cloud.txt" size="4096""#));
+    assert!(!xml_output.contains("placeholder="));
+}
+
+#[test]
+fn test_directory_loop() {
+    let mut source = MemorySource::new("/data");
+    source.add_dir("/data/a").inode = 500;
+    source.add_file("/data/a/x.txt", 100);
+    // A bind mount of its own ancestor
+    source.add_dir("/data/a/b/loop").inode = 500;
+    source.add_file("/data/a/b/loop/y.txt", 100);
+
+    let options = Options::builder().source(Arc::new(source)).build();
+    let mut output = Vec::new();
+    let stats = scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    assert_eq!(stats.skipped.get(SkipReason::VisitedDirectory), 1);
+    assert_eq!(stats.skipped.total(), 1);
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert_eq!(xml_output.matches(r#"<File name="x.txt""#).count(), 1);
+    assert!(!xml_output.contains(r#"name="loop""#));
+    assert!(!xml_output.contains("y.txt"));
+}
+
+#[test]
+fn test_record_junctions() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100);
+    source.add(
+        PathBuf::from("/data/link"),
+        EntryMetadata::new(EntryKind::Junction, 0),
+    );
+    let source = Arc::new(source);
+
+    let options = Options::builder().source(source.clone()).build();
+    let mut output = Vec::new();
+    let stats = scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    assert_eq!(stats.skipped.get(SkipReason::Junction), 1);
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(!xml_output.contains(r#"name="link""#));
+
+    // Recorded junctions are empty folders, which are not skipped as such
+    let options = Options::builder()
+        .source(source)
+        .record_junctions(true)
+        .build();
+    let mut output = Vec::new();
+    let stats = scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    assert_eq!(stats.skipped.total(), 0);
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    let link = xml_output
+        .lines()
+        .find(|line| line.contains(r#"<Folder name="link""#))
+        .expect("No junction");
+    assert!(link.contains(r#"junction="true""#) && link.ends_with("/>"));
+}
+
+#[test]
+fn test_max_errors() {
+    let mut source = MemorySource::new("/data");
+    for name in ["bad1", "bad2", "bad3"] {
+        source.add_file(format!("/data/{}/a.txt", name), 100);
+    }
+    source.add_file("/data/good/a.txt", 100);
+    let source = Arc::new(FailingSource(source));
+
+    let options = Options::builder().source(source.clone()).build();
+    let stats = scan(Path::new("/data"), &options, io::sink()).expect("Failed to scan");
+    assert_eq!(stats.errors, 3);
+    assert!(!stats.too_many_errors);
+
+    // The scan stops at the second error, but the dump is still complete
+    let options = Options::builder().source(source).max_errors(2).build();
+    let mut output = Vec::new();
+    let stats = scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    assert_eq!(stats.errors, 2);
+    assert!(stats.too_many_errors);
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#"name="bad2""#));
+    assert!(!xml_output.contains(r#"name="bad3""#));
+    assert!(!xml_output.contains(r#"name="good""#));
+    gpscan::validate::validate(xml_output.as_bytes()).expect("Invalid dump");
+}
+
+/// Writes one `depth,name,size` line per entry.
+#[derive(Default)]
+struct LineFormatter {
+    lines: Vec<String>,
+    measure: Option<String>,
+    finished: bool,
+}
+
+impl OutputFormatter for LineFormatter {
+    fn begin_scan(&mut self, info: &ScanInfo) -> std::io::Result<()> {
+        self.measure = Some(info.file_size_measure.clone());
+        Ok(())
+    }
+
+    fn begin_folder(&mut self, entry: &ScanEntry) -> std::io::Result<()> {
+        if entry.depth > 0 {
+            self.lines.push(format!("{},{}/", entry.depth, entry.name));
+        }
+        Ok(())
+    }
+
+    fn file(&mut self, entry: &ScanEntry) -> std::io::Result<()> {
+        self.lines
+            .push(format!("{},{},{}", entry.depth, entry.name, entry.size));
+        Ok(())
+    }
+
+    fn end_folder(&mut self, _entry: &ScanEntry) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn end_scan(&mut self, info: &ScanInfo) -> std::io::Result<()> {
+        self.finished = info.scan_duration.is_some();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_scan_with_formatter() {
+    let temp_dir = TempDir::new("gpscan_formatter_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("b.txt"), "de").expect("Failed to write b.txt");
+
+    let options = Options::builder().apparent_size(true).build();
+    let mut formatter = LineFormatter::default();
+    scan_with_formatter(dir_path, &options, &mut formatter).expect("Failed to scan");
+
+    assert_eq!(formatter.lines, ["1,a.txt,3", "1,sub/", "2,b.txt,2"]);
+    assert_eq!(formatter.measure.as_deref(), Some("logical"));
+    assert!(formatter.finished);
+}
+
+#[test]
+fn test_scan_memory_source() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/logs/app.log", 2048);
+    source.add_file("/data/logs/empty.log", 0);
+    source.add_file("/data/big.bin", 1 << 20).modified = Some(SystemTime::UNIX_EPOCH);
+    source.add_dir("/data/cache");
+    source.add(
+        "/data/link".into(),
+        EntryMetadata::new(EntryKind::Symlink, 0),
+    );
+
+    let options = Options::builder().source(Arc::new(source)).build();
+    let root = scan_to_tree(Path::new("/data"), &options).expect("Failed to scan");
+
+    assert_eq!(root.name, "/data");
+    let names: Vec<&str> = root.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["big.bin", "logs"]);
+    assert_eq!(root.children[1].children.len(), 1);
+    assert_eq!(root.total_size(), 2048 + (1 << 20));
+
+    // The dump of a mock tree is valid, too
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(
+        r#"<File name="big.bin" size="1048576" created="1970-01-01T00:00:00Z" modified="1970-01-01T00:00:00Z""#
+    ));
+    gpscan::validate::validate(xml_output.as_bytes()).expect("Invalid dump");
+}
+
+#[test]
+fn test_root_name() {
+    let mut source = MemorySource::new("/mnt/snapshot/data");
+    source.add_file("/mnt/snapshot/data/a.txt", 100);
+
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .root_name("/data")
+        .build();
+    let root = scan_to_tree(Path::new("/mnt/snapshot/data"), &options).expect("Failed to scan");
+
+    assert_eq!(root.name, "/data");
+    assert_eq!(root.children[0].name, "a.txt");
+}
+
+#[test]
+fn test_root_name_modes() {
+    use gpscan::RootName;
+
+    let mut source = MemorySource::new("/mnt/data");
+    source.add_file("/mnt/data/a.txt", 100);
+    let source = Arc::new(source);
+    let root_name = |name: RootName| {
+        let options = Options::builder()
+            .source(source.clone())
+            .root_name(name)
+            .build();
+        scan_to_tree(Path::new("/mnt/data"), &options)
+            .expect("Failed to scan")
+            .name
+    };
+
+    assert_eq!(root_name(RootName::Path), "/mnt/data");
+    assert_eq!(root_name(RootName::Basename), "data");
+    assert_eq!(root_name(RootName::Custom("nas".to_string())), "nas");
+    assert_eq!(
+        "custom:a b".parse(),
+        Ok(RootName::Custom("a b".to_string()))
+    );
+    assert!("custom:".parse::<RootName>().is_err());
+}
+
+#[test]
+fn test_empty_root_folder() {
+    let temp_dir = TempDir::new("gpscan_empty_root").expect("Failed to create temp dir");
+
+    // The root is written even though empty folders are skipped
+    let mut output = Vec::new();
+    scan(temp_dir.path(), &Options::default(), &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert_eq!(xml_output.matches("<Folder ").count(), 1);
+    gpscan::validate::validate(xml_output.as_bytes()).expect("Invalid dump");
+}
+
+#[test]
+fn test_skip_counts() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100).inode = 7;
+    source.add_file("/data/b.txt", 100).inode = 7;
+    source.add_file("/data/empty.log", 0);
+    source.add_file("/data/node_modules/package.js", 10);
+    source.add_dir("/data/cache");
+    source.add(
+        "/data/link".into(),
+        EntryMetadata::new(EntryKind::Symlink, 0),
+    );
+
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .exclude("node_*")
+        .build();
+    let stats = scan(Path::new("/data"), &options, io::sink()).expect("Failed to scan");
+
+    let skipped = &stats.skipped;
+    assert_eq!(skipped.get(SkipReason::HardLink), 1);
+    assert_eq!(skipped.get(SkipReason::ZeroSize), 1);
+    assert_eq!(skipped.get(SkipReason::Excluded), 1);
+    assert_eq!(skipped.get(SkipReason::EmptyFolder), 1);
+    assert_eq!(skipped.get(SkipReason::Symlink), 1);
+    assert_eq!(skipped.total(), 5);
+    assert_eq!(
+        skipped.to_string(),
+        "excluded=1 symlink=1 empty-folder=1 hard-link=1 zero-size=1"
+    );
+}
+
+#[test]
+fn test_embed_warnings() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100);
+    source.add_file("/data/sub/old--backup.log", 0);
+
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .embed_warnings(true)
+        .build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+
+    // The comment is written where the entry would be, and cannot contain "--"
+    let comment = "<!-- Skipping zero-byte file: /data/sub/old- -backup.log -->";
+    let position = xml_output.find(comment).expect("Missing comment");
+    assert!(xml_output.find("<Folder name=\"sub\"").unwrap() < position);
+    gpscan::validate::validate(xml_output.as_bytes()).expect("Invalid dump");
+}
+
+#[test]
+fn test_max_memory() {
+    let mut source = MemorySource::new("/data");
+    for i in 1..=5 {
+        source.add_file(format!("/data/a{}.txt", i), 100).links = 1;
+    }
+    let x = source.add_file("/data/x.txt", 100);
+    x.inode = 99;
+    x.links = 2;
+    let y = source.add_file("/data/y.txt", 100);
+    y.inode = 99;
+    y.links = 2;
+    let source = Arc::new(source);
+
+    // Close to the limit, files with a single link are no longer remembered
+    let options = Options::builder()
+        .source(source.clone())
+        .max_memory(300)
+        .build();
+    let stats = scan(Path::new("/data"), &options, io::sink()).expect("Failed to scan");
+    assert_eq!(stats.hard_links, 1);
+
+    // At the limit, hard links are no longer detected
+    let options = Options::builder()
+        .source(source.clone())
+        .max_memory(100)
+        .build();
+    let stats = scan(Path::new("/data"), &options, io::sink()).expect("Failed to scan");
+    assert_eq!(stats.hard_links, 0);
+
+    // Sorting by size holds the whole tree, which the limit does not cover
+    let options = Options::builder()
+        .source(source)
+        .max_memory(100)
+        .sort_order(SortOrder::Size)
+        .build();
+    match scan(Path::new("/data"), &options, io::sink()) {
+        Err(e @ Error::InvalidOptions(_)) => {
+            assert_eq!(e.exit_code(), gpscan::exit_code::USAGE_ERROR)
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_inode_filter_bloom() {
+    let mut bloom = BloomFilter::new(64 * 1024);
+    for inode in 1..=10_000 {
+        assert!(bloom.insert((1, inode)));
+    }
+    assert!((1..=10_000).all(|inode| bloom.contains((1, inode))));
+    assert!(!bloom.insert((1, 1)));
+    assert!(bloom.false_positive_rate() < 1e-3);
+
+    let mut source = MemorySource::new("/data");
+    for i in 1..=5 {
+        source.add_file(format!("/data/a{}.txt", i), 100).links = 1;
+    }
+    for name in ["x", "y", "z"] {
+        let file = source.add_file(format!("/data/{}.txt", name), 100);
+        file.inode = 99;
+        file.links = 3;
+    }
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .inode_filter(InodeFilter::Bloom(1024))
+        .build();
+    let stats = scan(Path::new("/data"), &options, io::sink()).expect("Failed to scan");
+    assert_eq!(stats.hard_links, 2);
+    assert_eq!(stats.hard_link_bytes, 200);
+}
+
+#[test]
+fn test_trust_dir_mtime() {
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let source = |files: &[&str]| {
+        let mut source = MemorySource::new("/data");
+        source.add_dir("/data/archive/2020").modified = Some(old);
+        source.add_dir("/data/archive").modified = Some(SystemTime::now());
+        for file in files {
+            source.add_file(format!("/data/archive/{}", file), 100);
+        }
+        Arc::new(source)
+    };
+    let options = Options::builder()
+        .source(source(&["2020/a.dat", "2021/b.dat"]))
+        .build();
+    let (cache, _) = gpscan::scan_to_result(Path::new("/data"), &options).expect("Failed to scan");
+
+    // The new file in the old directory is missed, the one in the recent directory is not
+    let options = Options::builder()
+        .source(source(&[
+            "2020/a.dat",
+            "2020/late.dat",
+            "2021/b.dat",
+            "2021/c.dat",
+        ]))
+        .trust_dir_mtime(cache, SystemTime::now() - Duration::from_secs(86400))
+        .build();
+    let (result, stats) =
+        gpscan::scan_to_result(Path::new("/data"), &options).expect("Failed to scan");
+    assert_eq!(stats.cached_folders, 1);
+    let archive = &result.root.children[0];
+    let names = |folder: &str| {
+        let folder = archive.children.iter().find(|child| child.name == folder);
+        let mut names: Vec<_> = folder
+            .unwrap()
+            .children
+            .iter()
+            .map(|c| c.name.clone())
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names("2020"), ["a.dat"]);
+    assert_eq!(names("2021"), ["b.dat", "c.dat"]);
+}
+
+/// Changes the tree once the scan has left the root folder.
+struct ChangingVisitor<'a>(&'a Path);
+
+impl ScanVisitor for ChangingVisitor<'_> {
+    fn leave_dir(&mut self, entry: &ScanEntry) -> std::io::Result<()> {
+        if entry.depth == 0 {
+            fs::write(self.0.join("a.txt"), "grown")?;
+            fs::remove_file(self.0.join("b.txt"))?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_verify() {
+    let temp_dir = TempDir::new("gpscan_verify").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(dir_path.join(name), "x").expect("Failed to write");
+    }
+
+    let options = Options::builder().verify(2).build();
+    let (result, stats) = gpscan::scan_to_result(dir_path, &options).expect("Failed to scan");
+    let consistency = stats.consistency.expect("No consistency");
+    assert_eq!((consistency.checked, consistency.changed), (2, 0));
+    assert_eq!(consistency.level(), "stable");
+    let mut output = Vec::new();
+    gpscan::write_result(&result, gpscan::XmlFormatter::new(&mut output)).expect("Failed to write");
+    let read = read_scan_from(output.as_slice()).expect("Failed to read");
+    assert_eq!(read.info.consistency, Some(consistency));
+
+    // The root folder and c.txt may or may not have changed
+    let options = Options::builder().verify(100).build();
+    let stats = scan_with_visitor(dir_path, &options, &mut ChangingVisitor(dir_path))
+        .expect("Failed to scan");
+    let consistency = stats.consistency.expect("No consistency");
+    assert_eq!(consistency.checked, 4);
+    assert!(consistency.changed >= 2);
+    assert_eq!(consistency.level(), "unstable");
+}
+
+#[test]
+fn test_sample() {
+    let source_at = |root: &str| {
+        let mut source = MemorySource::new(root);
+        for dir in 0..20 {
+            for file in 0..100 {
+                source.add_file(format!("{}/d{}/f{}.bin", root, dir, file), 1000);
+            }
+        }
+        Arc::new(source)
+    };
+    let options = Options::builder()
+        .source(source_at("/data"))
+        .apparent_size(true)
+        .sample(0.1)
+        .build();
+    let (result, stats) =
+        gpscan::scan_to_result(Path::new("/data"), &options).expect("Failed to scan");
+    let sampled = 2000 - stats.unsampled;
+    assert!((150..250).contains(&sampled), "{} files sampled", sampled);
+    // Each sampled file stands for ten
+    assert_eq!(result.root.total_size(), sampled * 10_000);
+    assert_eq!(result.root.children.len(), 20);
+    assert_eq!(result.info.sample_rate, Some(0.1));
+
+    let mut output = Vec::new();
+    gpscan::write_result(&result, gpscan::XmlFormatter::new(&mut output)).expect("Failed to write");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#" estimate="true" sampleRate="0.1""#));
+    let read = read_scan_from(xml_output.as_bytes()).expect("Failed to read");
+    assert_eq!(read.info.sample_rate, Some(0.1));
+
+    // The same files are sampled wherever the tree is mounted
+    let options = Options::builder()
+        .source(source_at("/mnt/data"))
+        .apparent_size(true)
+        .sample(0.1)
+        .root_name("data")
+        .build();
+    let (moved, _) =
+        gpscan::scan_to_result(Path::new("/mnt/data"), &options).expect("Failed to scan");
+    let names = |root: &gpscan::FileNode| -> Vec<String> {
+        root.children
+            .iter()
+            .flat_map(|dir| dir.children.iter().map(|file| file.name.clone()))
+            .collect()
+    };
+    assert_eq!(names(&moved.root), names(&result.root));
+
+    // Rates outside (0, 1] are rejected when the scan starts
+    for rate in [0.0, 1.5, f64::NAN] {
+        let options = Options::builder()
+            .source(source_at("/data"))
+            .sample(rate)
+            .build();
+        match gpscan::scan_to_result(Path::new("/data"), &options) {
+            Err(e @ Error::InvalidOptions(_)) => {
+                assert_eq!(e.exit_code(), gpscan::exit_code::USAGE_ERROR)
+            }
+            result => panic!("Unexpected result for {}: {:?}", rate, result.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn test_sort_by_total_size() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/small/a.txt", 10);
+    source.add_file("/data/big/b.txt", 600);
+    source.add_file("/data/big/c.txt", 600);
+    source.add_file("/data/d.txt", 1000);
+
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .sort_order(SortOrder::Size)
+        .build();
+
+    // Folders are ordered by their total size, not the size of the directory itself
+    let root = scan_to_tree(Path::new("/data"), &options).expect("Failed to scan");
+    let names: Vec<&str> = root.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["big", "d.txt", "small"]);
+
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    let big = xml_output.find(r#"name="big""#).unwrap();
+    let d = xml_output.find(r#"name="d.txt""#).unwrap();
+    let small = xml_output.find(r#"name="small""#).unwrap();
+    assert!(big < d && d < small);
+}
+
+#[test]
+fn test_sort_natural() {
+    let mut source = MemorySource::new("/data");
+    for name in [
+        "file10.txt",
+        "file2.txt",
+        "File3.txt",
+        "file02.txt",
+        "notes",
+    ] {
+        source.add_file(format!("/data/{}", name), 100);
+    }
+
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .sort_order(SortOrder::Natural)
+        .build();
+    let root = scan_to_tree(Path::new("/data"), &options).expect("Failed to scan");
+    let names: Vec<&str> = root.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "file02.txt",
+            "file2.txt",
+            "File3.txt",
+            "file10.txt",
+            "notes"
+        ]
+    );
+}
+
+#[test]
+fn test_writer_thread() {
+    let temp_dir = TempDir::new("gpscan_writer_thread").expect("Failed to create temp dir");
+    for i in 0..50 {
+        let dir_path = temp_dir.path().join(format!("dir{}", i));
+        fs::create_dir(&dir_path).expect("Failed to create dir");
+        fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    }
+
+    // The output does not depend on the thread the formatter runs on
+    let mut lines = Vec::new();
+    for writer_thread in [false, true] {
+        let options = Options::builder().writer_thread(writer_thread).build();
+        let mut formatter = LineFormatter::default();
+        scan_with_formatter(temp_dir.path(), &options, &mut formatter).expect("Failed to scan");
+        assert!(formatter.finished);
+        lines.push(formatter.lines);
+    }
+    assert_eq!(lines[0].len(), 100);
+    assert_eq!(lines[0], lines[1]);
+}
+
+#[test]
+fn test_filter_rules() {
+    let options = Options::builder().exclude("*.tmp").build();
+    assert_eq!(
+        filters::skip_by_name("a.tmp", &options),
+        Some(SkipReason::Excluded)
+    );
+    assert_eq!(filters::skip_by_name("a.txt", &options), None);
+
+    // `?` matches one character, not one byte
+    let options = Options::builder().exclude("caf?").build();
+    assert_eq!(
+        filters::skip_by_name("café", &options),
+        Some(SkipReason::Excluded)
+    );
+    assert_eq!(filters::skip_by_name("cafés", &options), None);
+    assert_eq!(
+        filters::skip_by_kind(EntryKind::Junction),
+        Some(SkipReason::Junction)
+    );
+    assert_eq!(filters::skip_by_kind(EntryKind::Dir), None);
+    assert_eq!(
+        filters::skip_folder(0, &options),
+        Some(SkipReason::EmptyFolder)
+    );
+    assert_eq!(filters::skip_file(0, &options), Some(SkipReason::ZeroSize));
+
+    let options = Options::builder()
+        .include_empty_folders(true)
+        .include_zero_files(true)
+        .build();
+    assert_eq!(filters::skip_folder(0, &options), None);
+    assert_eq!(filters::skip_file(0, &options), None);
+}
+
+#[test]
+fn test_owner_filters() {
+    let mut source = MemorySource::new("/home");
+    source.add_file("/home/alice/a.txt", 100).owner = Some(1000);
+    source.add_file("/home/alice/shared/b.txt", 100).owner = Some(1001);
+    source.add_file("/home/bob/c.txt", 100).owner = Some(1001);
+    source.add_file("/home/unknown.txt", 100);
+    let source = Arc::new(source);
+    let names = |options: Options| {
+        let mut names = Vec::new();
+        for entry in ScanIter::new("/home", &options) {
+            let entry = entry.expect("Failed to scan");
+            if entry.metadata.is_file() {
+                names.push(entry.name);
+            }
+        }
+        names.sort();
+        names
+    };
+
+    let options = Options::builder()
+        .source(source.clone())
+        .only_user(1001)
+        .build();
+    assert_eq!(names(options), ["b.txt", "c.txt"]);
+    let options = Options::builder()
+        .source(source.clone())
+        .exclude_user(1001)
+        .build();
+    assert_eq!(names(options), ["a.txt", "unknown.txt"]);
+
+    let options = Options::builder().source(source).only_user(1000).build();
+    assert_eq!(
+        filters::skip_by_owner(Some(1001), &options),
+        Some(SkipReason::Owner)
+    );
+    assert_eq!(
+        filters::skip_by_owner(None, &options),
+        Some(SkipReason::Owner)
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_scan_keeps_access_times() {
+    let temp_dir = TempDir::new("gpscan_atime_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path().join("sub");
+    fs::create_dir(&dir_path).expect("Failed to create sub");
+    fs::write(dir_path.join("a.txt"), "abc").expect("Failed to write a.txt");
+    // Old enough that relatime would update it on the next read
+    let status = std::process::Command::new("touch")
+        .args(["-a", "-d", "2001-02-03 04:05:06"])
+        .arg(&dir_path)
+        .status()
+        .expect("Failed to run touch");
+    assert!(status.success());
+    let before = fs::metadata(&dir_path).unwrap().accessed().unwrap();
+
+    scan(temp_dir.path(), &Options::default(), io::sink()).expect("Failed to scan");
+
+    assert_eq!(fs::metadata(&dir_path).unwrap().accessed().unwrap(), before);
+}
+
+#[test]
+fn test_hide_access_times() {
+    let temp_dir = TempDir::new("gpscan_no_atime_test").expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("sub")).expect("Failed to create sub");
+    fs::write(temp_dir.path().join("sub").join("a.txt"), "abc").expect("Failed to write a.txt");
+
+    let options = Options::builder().hide_access_times(true).build();
+    let mut output = Vec::new();
+    scan(temp_dir.path(), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+
+    assert_eq!(xml_output.matches(" accessed=").count(), 3);
+    assert_eq!(
+        xml_output
+            .matches(r#" accessed="1970-01-01T00:00:00Z""#)
+            .count(),
+        3
+    );
+    // Other times are kept
+    assert!(!xml_output.contains(r#" modified="1970-01-01T00:00:00Z""#));
+
+    // The epoch is written when only known times are, too
+    let options = Options::builder()
+        .hide_access_times(true)
+        .timestamps(gpscan::Timestamps::All)
+        .build();
+    let mut output = Vec::new();
+    scan(temp_dir.path(), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert_eq!(
+        xml_output
+            .matches(r#" accessed="1970-01-01T00:00:00Z""#)
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn test_timestamps() {
+    use gpscan::Timestamps;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mut source = MemorySource::new("/data");
+    let file = source.add_file("/data/a.txt", 100);
+    file.modified = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    file.accessed = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_100));
+    let source = Arc::new(source);
+
+    let dump = |timestamps| {
+        let options = Options::builder()
+            .source(source.clone())
+            .timestamps(timestamps)
+            .build();
+        let mut output = Vec::new();
+        scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+        let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+        let start = xml_output.find("<File ").expect("No file");
+        xml_output[start..xml_output[start..].find('\n').unwrap() + start].to_string()
+    };
+    assert_eq!(dump(Timestamps::None), r#"<File name="a.txt" size="100"/>"#);
+    assert_eq!(
+        dump(Timestamps::Modified),
+        r#"<File name="a.txt" size="100" modified="2023-11-14T22:13:20Z"/>"#
+    );
+    // The unknown creation time is left out instead of written as the epoch
+    assert_eq!(
+        dump(Timestamps::All),
+        r#"<File name="a.txt" size="100" modified="2023-11-14T22:13:20Z" accessed="2023-11-14T22:15:00Z"/>"#
+    );
+}
+
+#[test]
+fn test_sanitize_names() {
+    use gpscan::xml_output::sanitize_xml_text;
+
+    assert_eq!(sanitize_xml_text("plain name.txt"), "plain name.txt");
+    assert_eq!(
+        sanitize_xml_text("a\x01b\nc\u{fffe}"),
+        "a\u{2401}b\u{240a}c\u{fffd}"
+    );
+
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/bell\x07.txt", 100);
+    let source = Arc::new(source);
+
+    let options = Options::builder().source(source.clone()).build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains("<File name=\"bell\u{2407}.txt\""));
+    let result = read_scan_from(xml_output.as_bytes()).expect("Failed to read dump");
+    assert_eq!(result.root.children[0].name, "bell\u{2407}.txt");
+
+    let options = Options::builder().source(source).strict_names(true).build();
+    match scan(Path::new("/data"), &options, io::sink()).expect_err("Scan succeeded") {
+        Error::Io(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        e => panic!("Unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_read_scan_round_trip() {
+    let temp_dir = TempDir::new("gpscan_reader_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a&b <c>.txt"), "abc").expect("Failed to write file");
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("d\"e'.txt"), "de").expect("Failed to write file");
+
+    let options = Options::builder().apparent_size(true).build();
+    let dump_path = temp_dir.path().with_extension("gpscan");
+    let file = File::create(&dump_path).expect("Failed to create dump");
+    scan(dir_path, &options, file).expect("Failed to scan");
+
+    let result = read_scan(&dump_path).expect("Failed to read dump");
+    fs::remove_file(&dump_path).expect("Failed to remove dump");
+
+    assert_eq!(result.info.file_size_measure, "logical");
+    assert!(result.info.scan_duration.is_some());
+    assert_eq!(result.root.name, dir_path.display().to_string());
+    assert_eq!(result.root.total_size(), 5);
+    assert_eq!(result.root.children[0].name, "a&b <c>.txt");
+    assert_eq!(result.root.children[1].children[0].name, "d\"e'.txt");
+}
+
+#[test]
+fn test_volume_attributes_round_trip() {
+    use gpscan::{read_scan_from, write_result, XmlFormatter};
+
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100);
+    let options = Options::builder().source(Arc::new(source)).build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let mut result = read_scan_from(output.as_slice()).expect("Failed to read dump");
+
+    result.info.volume_name = Some("Backup Disk".to_string());
+    result.info.file_system = Some("ext4".to_string());
+    result.info.volume_id = Some("0b1d6c1e-3f4a-4d5e-9a8b-7c6d5e4f3a2b".to_string());
+    let mut output = Vec::new();
+    write_result(&result, XmlFormatter::new(&mut output)).expect("Failed to write dump");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(
+        xml_output.contains(r#" volumeName="Backup Disk" fileSystem="ext4" volumeId="0b1d6c1e-"#)
+    );
+
+    let read_back = read_scan_from(xml_output.as_bytes()).expect("Failed to read dump");
+    assert_eq!(read_back.info, result.info);
+}
+
+#[test]
+fn test_scan_comment() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100);
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .comment(r#"pre-migration snapshot of "/srv" & <co>"#)
+        .build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output
+        .contains(r#" comment="pre-migration snapshot of &quot;/srv&quot; &amp; &lt;co&gt;""#));
+
+    let result = read_scan_from(xml_output.as_bytes()).expect("Failed to read dump");
+    assert_eq!(
+        result.info.comment.as_deref(),
+        Some(r#"pre-migration snapshot of "/srv" & <co>"#)
+    );
+}
+
+#[test]
+fn test_both_sizes() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100).size = 4096;
+    let source = Arc::new(source);
+
+    let options = Options::builder()
+        .source(source.clone())
+        .both_sizes(true)
+        .build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#"<File name="a.txt" size="4096""#));
+    assert!(xml_output.contains(r#" logicalSize="100""#));
+    assert!(!xml_output.contains("physicalSize"));
+
+    let options = Options::builder()
+        .source(source)
+        .both_sizes(true)
+        .apparent_size(true)
+        .build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#"<File name="a.txt" size="100""#));
+    assert!(xml_output.contains(r#" physicalSize="4096""#));
+    assert!(!xml_output.contains("logicalSize"));
+}
+
+#[test]
+fn test_allocation_unit() {
+    let temp_dir = TempDir::new("gpscan_allocation_unit").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("small.txt"), "abc").expect("Failed to write small.txt");
+    fs::write(dir_path.join("large.bin"), vec![1u8; 70_000]).expect("Failed to write large.bin");
+
+    let unit = gpscan::platform::allocation_unit(dir_path).expect("No allocation unit");
+    assert!(unit >= 512);
+
+    let options = Options::builder().allocation_unit(true).build();
+    let root = gpscan::scan_to_tree(dir_path, &options).expect("Failed to scan");
+    assert_eq!(root.children.len(), 2);
+    for file in &root.children {
+        assert!(file.size > 0);
+        assert_eq!(file.size % unit, 0, "{} is not rounded", file.name);
+    }
+}
+
+#[test]
+fn test_windows_output_names() {
+    use gpscan::platform::{extended_length, windows_name_problem};
+
+    for name in [
+        "CON",
+        "con.xml",
+        "Nul",
+        "COM1.gpscan.gz",
+        "lpt9",
+        "AUX .txt",
+    ] {
+        assert_eq!(
+            windows_name_problem(name),
+            Some("is a reserved device name"),
+            "{}",
+            name
+        );
+    }
+    assert!(windows_name_problem("scan?.xml").is_some());
+    assert!(windows_name_problem("scan.xml.").is_some());
+    assert!(windows_name_problem("scan ").is_some());
+    for name in ["scan.xml", "console.xml", "COM10", "lpt", ".gpscan"] {
+        assert_eq!(windows_name_problem(name), None, "{}", name);
+    }
+
+    assert_eq!(extended_length(r"C:\scans\a.xml"), r"\\?\C:\scans\a.xml");
+    assert_eq!(
+        extended_length(r"\\server\share\a.xml"),
+        r"\\?\UNC\server\share\a.xml"
+    );
+    assert_eq!(extended_length(r"\\?\D:\a.xml"), r"\\?\D:\a.xml");
+}
+
+#[test]
+fn test_dir_entry_size() {
+    let mut source = MemorySource::new("/data");
+    source.add_dir("/data/logs").size = 8192;
+    source.add_file("/data/logs/app.log", 100);
+    let source = Arc::new(source);
+
+    let options = Options::builder().source(source.clone()).build();
+    let root = gpscan::scan_to_tree(Path::new("/data"), &options).expect("Failed to scan");
+    assert_eq!(root.total_size(), 100);
+
+    let options = Options::builder()
+        .source(source)
+        .dir_entry_size(true)
+        .build();
+    let root = gpscan::scan_to_tree(Path::new("/data"), &options).expect("Failed to scan");
+    assert_eq!(root.total_size(), 8292);
+    // Directories that take no space of their own get no entry
+    assert_eq!(root.children.len(), 1);
+    let logs = &root.children[0];
+    let entry = logs
+        .children
+        .iter()
+        .find(|child| child.name == ".")
+        .expect("No directory entry");
+    assert!(!entry.is_folder());
+    assert_eq!(entry.size, 8192);
+    assert_eq!(
+        entry.attributes,
+        [("directoryEntry".to_string(), "true".to_string())]
+    );
+}
+
+#[test]
+fn test_totals_check() {
+    use gpscan::volume::VolumeInfo;
+    use gpscan::TotalsCheck;
+
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/a.txt", 100);
+    source.add_file("/data/b.tmp", 50);
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .exclude("*.tmp")
+        .build();
+    let (result, stats) =
+        gpscan::scan_to_result(Path::new("/data"), &options).expect("Failed to scan");
+    let volume = VolumeInfo {
+        path: "/data".to_string(),
+        size: 1000,
+        free_space: 700,
+        name: None,
+        file_system: None,
+        id: None,
+    };
+
+    let check = TotalsCheck::new(
+        Path::new("/data"),
+        &volume,
+        result.root.total_size(),
+        &stats,
+        &options,
+    );
+    assert_eq!(check.scanned, 100);
+    assert_eq!(check.used, 300);
+    assert_eq!(check.difference(), -200);
+    assert_eq!(check.causes.len(), 3);
+    assert_eq!(check.causes[0], "1 entries were excluded by patterns");
+    assert!(check.causes[1].contains("--dir-entry-size"));
+    assert!(check.causes[2].contains("reserved blocks"));
+
+    // Part of a volume
+    let check = TotalsCheck::new(
+        Path::new("/data/sub"),
+        &volume,
+        result.root.total_size(),
+        &stats,
+        &options,
+    );
+    assert_eq!(
+        check.causes[0],
+        "Only /data/sub was scanned, not the whole volume mounted at /data"
+    );
+}
+
+#[test]
+fn test_metrics_collector() {
+    use gpscan::{MetricsCollector, XmlFormatter};
+
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/logs/2024/app.log", 2048);
+    source.add_file("/data/logs/app.log", 1024);
+    source.add_file("/data/cache/blob", 500);
+    source.add_file("/data/top.txt", 10);
+
+    let options = Options::builder().source(Arc::new(source)).build();
+    let mut xml_output = Vec::new();
+    let mut collector = MetricsCollector::new(XmlFormatter::new(&mut xml_output));
+    scan_with_formatter(Path::new("/data"), &options, &mut collector).expect("Failed to scan");
+    let (metrics, _) = collector.into_parts();
+
+    // The wrapped formatter still receives every entry
+    let xml_output = String::from_utf8(xml_output).unwrap();
+    assert!(xml_output.contains(r#"<File name="blob" size="500""#));
+
+    assert_eq!(metrics.root, "/data");
+    assert_eq!(metrics.files, 4);
+    assert_eq!(metrics.folders, 4);
+    assert_eq!(metrics.bytes, 3582);
+    let mut top_level = metrics.top_level.clone();
+    top_level.sort();
+    assert_eq!(
+        top_level,
+        vec![("cache".to_string(), 500), ("logs".to_string(), 3072)]
+    );
+    assert!(metrics.duration.is_some());
+
+    let text = metrics.to_prometheus();
+    assert!(text.contains("# HELP gpscan_size_bytes "));
+    assert!(text.contains("gpscan_size_bytes{root=\"/data\"} 3582\n"));
+    assert!(text.contains("gpscan_directory_size_bytes{root=\"/data\",directory=\"logs\"} 3072\n"));
+}
+
+#[test]
+fn test_hard_link_savings() {
+    let mut source = MemorySource::new("/data");
+    let mut original = EntryMetadata::new(EntryKind::File, 4096);
+    original.device = 1;
+    original.inode = 100;
+    source.add("/data/a/original.bin".into(), original.clone());
+    source.add("/data/b/link.bin".into(), original);
+    source.add_file("/data/other.bin", 10);
+
+    let options = Options::builder().source(Arc::new(source)).build();
+    let stats = scan(Path::new("/data"), &options, io::sink()).expect("Failed to scan");
+
+    assert_eq!(stats.hard_links, 1);
+    assert_eq!(stats.hard_link_bytes, 4096);
+}
+
+#[test]
+fn test_mount_points() {
+    let mount_points = gpscan::volume::mount_points(&sysinfo::Disks::new_with_refreshed_list());
+
+    let mut sorted = mount_points.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(mount_points, sorted);
+    assert!(mount_points.iter().all(|mount_point| mount_point.is_dir()));
+}
+
+#[test]
+fn test_physical_disk() {
+    use gpscan::volume::physical_disk;
+
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    assert_eq!(physical_disk(Path::new("/no/such/volume"), &disks), None);
+    for mount_point in gpscan::volume::mount_points(&disks) {
+        if let Some(disk) = physical_disk(&mount_point, &disks) {
+            assert!(!disk.is_empty());
+            assert!(!disk.contains('/'));
+        }
+    }
+}
+
+#[test]
+fn test_find_mount() {
+    use gpscan::volume::find_mount;
+
+    let mounts = |list: &[(&str, u64)]| -> Vec<(PathBuf, Option<u64>)> {
+        list.iter()
+            .map(|&(path, device)| (PathBuf::from(path), Some(device)))
+            .collect()
+    };
+
+    // Nested mounts: the deepest mount point on the same device
+    let nested = mounts(&[("/", 1), ("/srv", 2), ("/srv/data", 3)]);
+    assert_eq!(
+        find_mount(Path::new("/srv/data/x"), Some(3), &nested),
+        Some(2)
+    );
+    assert_eq!(
+        find_mount(Path::new("/srv/other"), Some(2), &nested),
+        Some(1)
+    );
+    assert_eq!(find_mount(Path::new("/home"), Some(1), &nested), Some(0));
+
+    // A bind mount of a directory of /mnt/disk2 at /data belongs to /mnt/disk2
+    let bind = mounts(&[("/", 1), ("/mnt/disk2", 2)]);
+    assert_eq!(find_mount(Path::new("/data/logs"), Some(2), &bind), Some(1));
+
+    // A volume mounted over another one on the same mount point
+    let stacked = mounts(&[("/", 1), ("/mnt", 2), ("/mnt", 3)]);
+    assert_eq!(find_mount(Path::new("/mnt/a"), Some(3), &stacked), Some(2));
+    assert_eq!(find_mount(Path::new("/mnt/a"), Some(2), &stacked), Some(1));
+
+    // Unknown devices, such as btrfs subvolumes, fall back to the mount point
+    assert_eq!(
+        find_mount(Path::new("/srv/data/x"), Some(9), &nested),
+        Some(2)
+    );
+    assert_eq!(find_mount(Path::new("/srv/x"), None, &nested), Some(1));
+    assert_eq!(find_mount(Path::new("/srv"), None, &[]), None);
+}
+
+#[test]
+fn test_mount_point_name() {
+    use gpscan::volume::mount_point_name;
+
+    assert_eq!(mount_point_name(Path::new("/")), "root");
+    assert_eq!(mount_point_name(Path::new("/mnt/data")), "mnt_data");
+    assert_eq!(mount_point_name(Path::new(r"C:\")), "C");
+    assert_eq!(
+        mount_point_name(Path::new(r"D:\Volumes\Backup")),
+        "D__Volumes_Backup"
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_placeholder_template() {
+    use gpscan::filesystem::placeholder_template;
+
+    assert_eq!(
+        placeholder_template("scan.gpscan", "{volume}"),
+        "scan-{volume}.gpscan"
+    );
+    // Compound extensions stay together
+    assert_eq!(
+        placeholder_template("out/scan.gpscan.gz", "{timestamp}"),
+        "out/scan-{timestamp}.gpscan.gz"
+    );
+    assert_eq!(placeholder_template("scan", "{volume}"), "scan-{volume}");
+    // A leading dot is not an extension
+    assert_eq!(
+        placeholder_template(".gpscan", "{volume}"),
+        ".gpscan-{volume}"
+    );
+    // An output name with the placeholder is kept as it is
+    assert_eq!(
+        placeholder_template("{volume}/scan.gpscan", "{volume}"),
+        "{volume}/scan.gpscan"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_btrfs_subvolume_mount_table() {
+    use gpscan::volume::{is_in_btrfs_mount, parse_mount_table};
+
+    let mounts = parse_mount_table(
+        "22 1 254:0 / / rw,relatime shared:1 - ext4 /dev/vda rw\n\
+         30 22 0:40 / /home rw,relatime shared:5 - btrfs /dev/vdb rw,subvol=/\n\
+         31 30 0:41 /snap /home/snap\\040shots rw shared:6 - btrfs /dev/vdb rw\n\
+         32 30 0:42 / /home/nfs rw shared:7 - nfs4 server:/export rw\n\
+         33 22 0:43 / /mnt rw shared:8 - btrfs /dev/vdc rw\n\
+         34 22 0:44 / /mnt rw shared:9 - ext4 /dev/vdd rw\n",
+    );
+    assert_eq!(mounts.len(), 6);
+    assert_eq!(
+        mounts[2],
+        (PathBuf::from("/home/snap shots"), "btrfs".to_string())
+    );
+
+    // A directory inside a btrfs mount, but not the mount point itself
+    assert!(is_in_btrfs_mount(Path::new("/home/alice/vm"), &mounts));
+    assert!(!is_in_btrfs_mount(Path::new("/home"), &mounts));
+    assert!(is_in_btrfs_mount(
+        Path::new("/home/snap shots/daily"),
+        &mounts
+    ));
+    assert!(!is_in_btrfs_mount(Path::new("/home/snap shots"), &mounts));
+    // The deepest mount point decides, and the last of stacked mounts is on top
+    assert!(!is_in_btrfs_mount(Path::new("/home/nfs/a"), &mounts));
+    assert!(!is_in_btrfs_mount(Path::new("/mnt/a"), &mounts));
+    assert!(!is_in_btrfs_mount(Path::new("/var/lib"), &mounts));
+}
+
+#[test]
+fn test_unc_share_root() {
+    use gpscan::volume::unc_share_root;
+
+    let share = Some(r"\\server\share\".to_string());
+    assert_eq!(unc_share_root(r"\\server\share"), share);
+    assert_eq!(unc_share_root(r"\\server\share\dir\file.txt"), share);
+    assert_eq!(unc_share_root(r"\\?\UNC\server\share\dir"), share);
+    for path in [
+        r"C:\Users",
+        r"\\?\C:\Users",
+        r"\\.\PhysicalDrive0",
+        r"\\server",
+        "/srv",
+    ] {
+        assert_eq!(unc_share_root(path), None, "{}", path);
+    }
+}
+
+#[test]
+fn test_network_file_systems() {
+    use gpscan::volume::is_network_file_system;
+
+    for file_system in ["nfs4", "cifs", "smbfs", "fuse.sshfs", "fuse"] {
+        assert!(is_network_file_system(file_system), "{}", file_system);
+    }
+    for file_system in ["ext4", "NTFS", "apfs", "fuseblk", "tmpfs"] {
+        assert!(!is_network_file_system(file_system), "{}", file_system);
+    }
+
+    // Every mounted volume is found by the device ID of its files
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let device = fs::metadata("/").expect("Failed to stat /").dev();
+        assert!(gpscan::volume::file_system_types().contains_key(&device));
+    }
+}
+
+#[test]
+fn test_extension_report() {
+    use gpscan::{ReportCollector, ReportKind, ReportRow, XmlFormatter};
+
+    let mut source = MemorySource::new("/media");
+    source.add_file("/media/movies/a.MP4", 3000);
+    source.add_file("/media/movies/b.mp4", 2000);
+    source.add_file("/media/vm/disk.qcow2", 4000);
+    source.add_file("/media/backup.tar.gz", 100);
+    source.add_file("/media/.bashrc", 10);
+    source.add_file("/media/README", 10);
+
+    let options = Options::builder().source(Arc::new(source)).build();
+    let mut collector = ReportCollector::new(
+        XmlFormatter::new(std::io::sink()),
+        &[ReportKind::Extensions],
+    );
+    scan_with_formatter(Path::new("/media"), &options, &mut collector).expect("Failed to scan");
+
+    let reports = collector.reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].kind, ReportKind::Extensions);
+    let row = |key: &str, size, files| ReportRow {
+        key: key.to_string(),
+        size,
+        files,
+    };
+    // Extensions are case-insensitive, and only the last one counts
+    assert_eq!(
+        reports[0].rows,
+        vec![
+            row("mp4", 5000, 2),
+            row("qcow2", 4000, 1),
+            row("gz", 100, 1),
+            row("(none)", 20, 2),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_owner_report() {
+    use gpscan::{ReportCollector, ReportKind, XmlFormatter};
+
+    let mut source = MemorySource::new("/home");
+    source.add_file("/home/root/a.bin", 700).owner = Some(0);
+    source.add_file("/home/root/b.bin", 300).owner = Some(0);
+    source.add_file("/home/gone/c.bin", 50).owner = Some(4_000_000_000);
+    source.add_file("/home/d.bin", 5);
+
+    let options = Options::builder().source(Arc::new(source)).build();
+    let mut collector = ReportCollector::new(
+        XmlFormatter::new(std::io::sink()),
+        &[ReportKind::Owners, ReportKind::Extensions],
+    );
+    scan_with_formatter(Path::new("/home"), &options, &mut collector).expect("Failed to scan");
+
+    let reports = collector.reports();
+    assert_eq!(reports[0].kind, ReportKind::Owners);
+    assert_eq!(reports[1].kind, ReportKind::Extensions);
+    // Users without a name are reported by ID, and files without an owner separately
+    let rows: Vec<(&str, u64, u64)> = reports[0]
+        .rows
+        .iter()
+        .map(|row| (row.key.as_str(), row.size, row.files))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            ("root", 1000, 2),
+            ("4000000000", 50, 1),
+            ("(unknown)", 5, 1)
+        ]
+    );
+}
+
+#[test]
+fn test_folder_totals() {
+    let mut source = MemorySource::new("/data");
+    source
+        .add_file("/data/logs/2024/app.log", 4096)
+        .apparent_size = 1000;
+    source.add_file("/data/logs/app.log", 8192).apparent_size = 5000;
+    source.add_file("/data/top.txt", 4096).apparent_size = 10;
+    source.add_dir("/data/logs/empty");
+
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .include_empty_folders(true)
+        .folder_totals(true)
+        .build();
+    let mut xml_output = Vec::new();
+    scan(Path::new("/data"), &options, &mut xml_output).expect("Failed to scan");
+    let result = read_scan_from(xml_output.as_slice()).expect("Failed to read dump");
+
+    let attribute = |node: &gpscan::FileNode, key: &str| {
+        node.attributes
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.clone())
+    };
+    let root = &result.root;
+    assert_eq!(
+        attribute(root, "totalPhysicalSize").as_deref(),
+        Some("16384")
+    );
+    assert_eq!(attribute(root, "totalLogicalSize").as_deref(), Some("6010"));
+    assert_eq!(attribute(root, "totalFiles").as_deref(), Some("3"));
+    assert_eq!(attribute(root, "totalFolders").as_deref(), Some("3"));
+
+    let logs = root
+        .children
+        .iter()
+        .find(|child| child.name == "logs")
+        .expect("Missing folder");
+    assert_eq!(
+        attribute(logs, "totalPhysicalSize").as_deref(),
+        Some("12288")
+    );
+    assert_eq!(attribute(logs, "totalFiles").as_deref(), Some("2"));
+    assert_eq!(attribute(logs, "totalFolders").as_deref(), Some("2"));
+    // The totals do not change the tree itself
+    assert_eq!(root.total_size(), 16384);
+}
+
+#[test]
+fn test_largest_growth() {
+    use gpscan::{folder_sizes, largest_growth, FolderSizeCollector, XmlFormatter};
+    use std::path::PathBuf;
+
+    let mut source = MemorySource::new("/srv");
+    source.add_file("/srv/logs/app.log", 1000);
+    source.add_file("/srv/db/data.bin", 5000);
+    let options = Options::builder().source(Arc::new(source)).build();
+    let baseline = folder_sizes(&scan_to_tree(Path::new("/srv"), &options).unwrap());
+
+    let mut source = MemorySource::new("/srv");
+    source.add_file("/srv/logs/app.log", 4000);
+    source.add_file("/srv/logs/old/app.1.log", 500);
+    source.add_file("/srv/db/data.bin", 2000);
+    let options = Options::builder().source(Arc::new(source)).build();
+    let mut collector = FolderSizeCollector::new(XmlFormatter::new(std::io::sink()));
+    scan_with_formatter(Path::new("/srv"), &options, &mut collector).expect("Failed to scan");
+    let current = collector.into_sizes();
+
+    // Sizes collected during a scan match those of the tree
+    assert_eq!(
+        current,
+        folder_sizes(&scan_to_tree(Path::new("/srv"), &options).unwrap())
+    );
+
+    // Shrinking folders are left out, and new folders grow from 0. The root is ""
+    let growth = largest_growth(&baseline, &current, 10);
+    let rows: Vec<(PathBuf, u64, u64)> = growth
+        .iter()
+        .map(|delta| (delta.path.clone(), delta.before, delta.after))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (PathBuf::from("logs"), 1000, 4500),
+            (PathBuf::new(), 6000, 6500),
+            (PathBuf::from("logs/old"), 0, 500),
+        ]
+    );
+    assert_eq!(growth[0].growth(), 3500);
+    assert_eq!(largest_growth(&baseline, &current, 1).len(), 1);
+}
+
+#[test]
+fn test_read_grandperspective_dump() {
+    let dump = r#"<?xml version="1.0" encoding="UTF-8"?>
+<GrandPerspectiveScanDump appVersion="3.4.1" formatVersion="6">
+<ScanInfo volumePath="/Volumes/Data" volumeSize="500000000000" freeSpace="100000000000" scanTime="2023-05-01T10:20:30Z" fileSizeMeasure="physical">
+<Folder name="/Volumes/Data/Projects" created="2023-01-01T00:00:00Z" modified="2023-04-01T00:00:00Z" accessed="2023-05-01T00:00:00Z">
+<Folder name="src" created="2023-01-01T00:00:00Z" modified="2023-04-01T00:00:00Z" accessed="2023-05-01T00:00:00Z">
+<File name="main.c" size="4096" created="2023-01-01T00:00:00Z" modified="2023-04-01T00:00:00Z" accessed="2023-05-01T00:00:00Z"/>
+</Folder>
+<Folder name="empty" created="2023-01-01T00:00:00Z" modified="2023-04-01T00:00:00Z" accessed="2023-05-01T00:00:00Z"/>
+<File name="Fish &amp; Chips.txt" size="8192" created="2023-01-01T00:00:00Z" modified="2023-04-01T00:00:00Z" accessed="2023-05-01T00:00:00Z"/>
+</Folder>
+</ScanInfo>
+</GrandPerspectiveScanDump>
+"#;
+
+    let result = read_scan_from(dump.as_bytes()).expect("Failed to read dump");
+
+    assert_eq!(result.info.volume_path, "/Volumes/Data");
+    assert_eq!(result.info.free_space, 100_000_000_000);
+    assert_eq!(result.info.scan_duration, None);
+    assert_eq!(result.root.file_count(), 2);
+    assert_eq!(result.root.total_size(), 12288);
+    assert_eq!(result.root.children[1].name, "empty");
+    assert_eq!(result.root.children[2].name, "Fish & Chips.txt");
+    assert_eq!(
+        result.root.children[0].children[0]
+            .modified
+            .map(|t| t.to_rfc3339()),
+        Some("2023-04-01T00:00:00+00:00".to_string())
+    );
+}
+
+#[test]
+fn test_scan_archives() {
+    let temp_dir = TempDir::new("gpscan_archives").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+
+    // backup.tar.gz: docs/readme.txt (5 bytes), data.bin (3000 bytes)
+    let tar_gz = File::create(dir_path.join("backup.tar.gz")).expect("Failed to create archive");
+    let encoder = flate2::write::GzEncoder::new(tar_gz, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, content) in [
+        ("docs/readme.txt", b"hello".to_vec()),
+        ("data.bin", vec![0u8; 3000]),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(1_700_000_000);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, content.as_slice())
+            .expect("Failed to append to archive");
+    }
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .expect("Failed to finish archive");
+
+    // photos.zip: a/b.jpg (100 bytes)
+    let zip_file = File::create(dir_path.join("photos.zip")).expect("Failed to create archive");
+    let mut writer = zip::ZipWriter::new(zip_file);
+    writer
+        .start_file(
+            "a/b.jpg",
+            zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored),
+        )
+        .expect("Failed to start zip entry");
+    writer
+        .write_all(&[1u8; 100])
+        .expect("Failed to write zip entry");
+    writer.finish().expect("Failed to finish archive");
+
+    let options = Options::builder()
+        .apparent_size(true)
+        .scan_archives(true)
+        .build();
+    let root = scan_to_tree(dir_path, &options).expect("Failed to scan");
+
+    let backup = &root.children[0];
+    assert_eq!(backup.name, "backup.tar.gz");
+    assert_eq!(backup.kind, NodeKind::Folder);
+    assert_eq!(backup.attribute("archive"), Some("tar.gz"));
+    assert_eq!(backup.total_size(), 3005);
+    assert_eq!(backup.children[0].name, "data.bin");
+    assert_eq!(backup.children[1].name, "docs");
+    assert_eq!(backup.children[1].children[0].name, "readme.txt");
+    assert_eq!(
+        backup.children[0].modified.map(|t| t.timestamp()),
+        Some(1_700_000_000)
+    );
+
+    let photos = &root.children[1];
+    assert_eq!(photos.attribute("archive"), Some("zip"));
+    assert_eq!(photos.children[0].name, "a");
+    assert_eq!(photos.children[0].children[0].size, 100);
+
+    // Without the option, archives are plain files
+    let options = Options::builder().apparent_size(true).build();
+    let root = scan_to_tree(dir_path, &options).expect("Failed to scan");
+    assert!(root.children.iter().all(|child| !child.is_folder()));
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn test_tree_watcher() {
+    use gpscan::TreeWatcher;
+
+    let temp_dir = TempDir::new("gpscan_watch").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "aaa").expect("Failed to write file");
+
+    let options = Options::builder().apparent_size(true).build();
+    let mut watcher = TreeWatcher::new(dir_path, &options).expect("Failed to watch");
+    assert_eq!(watcher.tree().total_size(), 3);
+
+    // Applies notifications until the tree has the expected size
+    let mut wait_for_size = |size: u64| {
+        for _ in 0..50 {
+            watcher
+                .update(Duration::from_millis(100))
+                .expect("Failed to update");
+            if watcher.tree().total_size() == size {
+                return watcher.result();
+            }
+        }
+        panic!(
+            "Tree size is {}, expected {}",
+            watcher.tree().total_size(),
+            size
+        );
+    };
+
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create dir");
+    fs::write(dir_path.join("sub").join("b.txt"), "bbbbb").expect("Failed to write file");
+    let result = wait_for_size(8);
+    assert_eq!(result.root.children[1].name, "sub");
+    assert_eq!(result.root.children[1].children[0].name, "b.txt");
+
+    fs::remove_file(dir_path.join("a.txt")).expect("Failed to remove file");
+    let result = wait_for_size(5);
+    assert_eq!(result.root.children.len(), 1);
+}
+
+#[cfg(feature = "encrypt")]
+#[test]
+fn test_encryption() {
+    use age::secrecy::SecretString;
+    use age::{scrypt, x25519, Decryptor, Identity};
+    use gpscan::Encryption;
+    use std::io::Read;
+
+    let temp_dir = TempDir::new("gpscan_encrypt").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("secret-plans.txt"), "data").expect("Failed to write file");
+
+    let decrypt = |encrypted: &[u8], identity: &dyn Identity| {
+        let decryptor = Decryptor::new(encrypted).expect("Not an age file");
+        let mut reader = decryptor
+            .decrypt(std::iter::once(identity))
+            .expect("Failed to decrypt");
+        let mut xml = Vec::new();
+        reader.read_to_end(&mut xml).expect("Failed to read");
+        read_scan_from(&xml[..]).expect("Failed to read dump")
+    };
+
+    // Any of the recipients can decrypt the dump
+    let first = x25519::Identity::generate();
+    let second = x25519::Identity::generate();
+    let encryption = Encryption::parse([
+        format!("age:{}", first.to_public()).as_str(),
+        format!("age:{}", second.to_public()).as_str(),
+    ])
+    .expect("Failed to parse recipients");
+    let mut encrypted = Vec::new();
+    encryption
+        .encrypt(&mut encrypted, |handle| {
+            Ok(scan(dir_path, &Options::default(), handle)?)
+        })
+        .expect("Failed to scan");
+    assert!(!String::from_utf8_lossy(&encrypted).contains("secret-plans"));
+    for identity in [&first, &second] {
+        let result = decrypt(&encrypted, identity);
+        assert_eq!(result.root.children[0].name, "secret-plans.txt");
+    }
+
+    let encryption = Encryption::with_password(SecretString::from("hunter2".to_string()));
+    let mut encrypted = Vec::new();
+    encryption
+        .encrypt(&mut encrypted, |handle| {
+            Ok(scan(dir_path, &Options::default(), handle)?)
+        })
+        .expect("Failed to scan");
+    let identity = scrypt::Identity::new(SecretString::from("hunter2".to_string()));
+    let result = decrypt(&encrypted, &identity);
+    assert_eq!(result.root.children.len(), 1);
+
+    assert!(Encryption::parse(["age:not-a-key"]).is_err());
+    assert!(Encryption::parse(["rot13"]).is_err());
+    let key = format!("age:{}", first.to_public());
+    assert!(Encryption::parse(["password", key.as_str()]).is_err());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_scan_async() {
+    use gpscan::{scan_async, scan_to_result};
+
+    let temp_dir = TempDir::new("gpscan_async").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create dir");
+    fs::write(dir_path.join("sub").join("a.txt"), "aaa").expect("Failed to write file");
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to build runtime");
+    let options = Options::builder().apparent_size(true).build();
+    let (result, stats) = runtime
+        .block_on(scan_async(dir_path, &options))
+        .expect("Failed to scan");
+    let (expected, _) = scan_to_result(dir_path, &options).expect("Failed to scan");
+    assert_eq!(result.root.total_size(), 3);
+    assert_eq!(result.root.children[0].name, expected.root.children[0].name);
+    assert_eq!(stats.errors, 0);
+    assert!(!stats.cancelled);
+
+    let missing = dir_path.join("missing");
+    match runtime.block_on(scan_async(&missing, &options)) {
+        Err(Error::RootNotFound(path)) => assert_eq!(path, missing),
+        other => panic!("Expected RootNotFound, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[cfg(feature = "gpscan-capi")]
+#[test]
+fn test_capi_scan() {
+    use gpscan::capi::*;
+    use std::ffi::{c_int, c_void, CStr, CString};
+
+    unsafe extern "C" fn collect(entry: *const gpscan_entry, user_data: *mut c_void) -> c_int {
+        let entry = &*entry;
+        let names = &mut *(user_data as *mut Vec<(gpscan_event, String, u64)>);
+        let name = CStr::from_ptr(entry.name).to_string_lossy().into_owned();
+        names.push((entry.event, name, entry.size));
+        0
+    }
+
+    unsafe extern "C" fn stop(_entry: *const gpscan_entry, _user_data: *mut c_void) -> c_int {
+        1
+    }
+
+    let temp_dir = TempDir::new("gpscan_capi").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("a.txt"), "aaa").expect("Failed to write file");
+    fs::write(dir_path.join("skip.log"), "log").expect("Failed to write file");
+    let path = CString::new(dir_path.to_str().unwrap()).unwrap();
+
+    unsafe {
+        let options = gpscan_options_new();
+        gpscan_options_set_apparent_size(options, true);
+        assert_eq!(gpscan_options_add_exclude(options, c"*.log".as_ptr()), 0);
+        let mut entries: Vec<(gpscan_event, String, u64)> = Vec::new();
+        let status = gpscan_scan(
+            path.as_ptr(),
+            options,
+            Some(collect),
+            &mut entries as *mut _ as *mut c_void,
+        );
+        gpscan_options_free(options);
+        assert_eq!(status, 0);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].0, gpscan_event::GPSCAN_ENTER_DIR);
+        assert_eq!(
+            entries[1],
+            (gpscan_event::GPSCAN_FILE, "a.txt".to_string(), 3)
+        );
+        assert_eq!(entries[2].0, gpscan_event::GPSCAN_LEAVE_DIR);
+
+        let status = gpscan_scan(
+            path.as_ptr(),
+            std::ptr::null(),
+            Some(stop),
+            std::ptr::null_mut(),
+        );
+        assert_eq!(status, GPSCAN_STOPPED);
+
+        let missing = CString::new(dir_path.join("missing").to_str().unwrap()).unwrap();
+        let status = gpscan_scan(
+            missing.as_ptr(),
+            std::ptr::null(),
+            Some(stop),
+            std::ptr::null_mut(),
+        );
+        assert_eq!(status, gpscan::exit_code::INVALID_ROOT);
+        let message = CStr::from_ptr(gpscan_last_error()).to_string_lossy();
+        assert!(message.contains("does not exist"));
+    }
+}
+
+#[test]
+fn test_largest_files() {
+    use gpscan::{LargestFiles, LargestFilesCollector, XmlFormatter};
+
+    let mut largest = LargestFiles::new(2);
+    for (name, size) in [("a", 5), ("b", 50), ("c", 1), ("d", 50), ("e", 20)] {
+        largest.push(Path::new(name), size);
+    }
+    assert_eq!(
+        largest.to_vec(),
+        vec![(PathBuf::from("b"), 50), (PathBuf::from("d"), 50)]
+    );
+    let mut none = LargestFiles::new(0);
+    none.push(Path::new("a"), 1);
+    assert!(none.to_vec().is_empty());
+
+    let temp_dir = TempDir::new("gpscan_largest").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create dir");
+    fs::write(dir_path.join("small.txt"), "a").expect("Failed to write file");
+    fs::write(dir_path.join("sub").join("big.txt"), "a".repeat(100)).expect("Failed to write file");
+    fs::write(dir_path.join("mid.txt"), "a".repeat(10)).expect("Failed to write file");
+
+    // The section is recorded in the dump, which can still be read
+    let options = Options::builder().apparent_size(true).build();
+    let mut dump = Vec::new();
+    let formatter = XmlFormatter::new(&mut dump).largest_files(2);
+    let mut collector = LargestFilesCollector::new(formatter, 1);
+    scan_with_formatter(dir_path, &options, &mut collector).expect("Failed to scan");
+    assert_eq!(
+        collector.largest().to_vec(),
+        vec![(dir_path.join("sub").join("big.txt"), 100)]
+    );
+    drop(collector);
+    let xml = String::from_utf8(dump).expect("Invalid UTF-8");
+    let section = &xml[xml.find("<LargestFiles>").expect("No LargestFiles section")..];
+    assert!(section.contains(&format!(
+        "path=\"{}\" size=\"100\"",
+        dir_path.join("sub").join("big.txt").display()
+    )));
+    assert!(section.contains("size=\"10\""));
+    assert!(!section.contains("small.txt"));
+    let result = read_scan_from(xml.as_bytes()).expect("Failed to read dump");
+    assert_eq!(result.root.file_count(), 3);
+}
+
+#[test]
+fn test_tree_shape() {
+    let temp_dir = TempDir::new("gpscan_shape").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::create_dir_all(dir_path.join("a").join("deep")).expect("Failed to create dir");
+    fs::write(dir_path.join("a").join("deep").join("file.txt"), "x").expect("Failed to write");
+    fs::create_dir(dir_path.join("wide")).expect("Failed to create dir");
+    for i in 0..5 {
+        fs::write(dir_path.join("wide").join(format!("{}", i)), "x").expect("Failed to write");
+    }
+    // Excluded entries still count towards the width of their folder
+    fs::write(dir_path.join("wide").join("skip.tmp"), "x").expect("Failed to write");
+
+    let options = Options::builder().exclude("*.tmp").build();
+    let (_, stats) = gpscan::scan_to_result(dir_path, &options).expect("Failed to scan");
+    let shape = &stats.shape;
+    // The root, 3 folders, and 6 files
+    assert_eq!(shape.entries, 10);
+    assert_eq!(shape.max_depth, 3);
+    assert_eq!(shape.depth_total, 1 + 2 + 3 + 1 + 5 * 2);
+    assert!((shape.average_depth() - 1.7).abs() < 1e-9);
+    assert_eq!(shape.widest_dir, Some((dir_path.join("wide"), 6)));
+    assert_eq!(
+        shape.longest_path,
+        Some(dir_path.join("a").join("deep").join("file.txt"))
+    );
+    assert!(shape
+        .to_string()
+        .starts_with("max depth 3, average depth 1.7"));
+}
+
+#[test]
+fn test_classify() {
+    use gpscan::{ClassifyMode, FileKind};
+
+    assert_eq!(FileKind::from_name("Holiday.JPG"), FileKind::Image);
+    assert_eq!(FileKind::from_name("backup.tar.gz"), FileKind::Archive);
+    assert_eq!(FileKind::from_name("Makefile"), FileKind::Other);
+    assert_eq!(FileKind::from_magic(b"%PDF-1.7"), FileKind::Document);
+    assert_eq!(FileKind::from_magic(b"\0\0\0\x18ftypmp42"), FileKind::Video);
+    assert_eq!(FileKind::from_magic(b""), FileKind::Other);
+    assert_eq!("audio".parse::<FileKind>(), Ok(FileKind::Audio));
+
+    let temp_dir = TempDir::new("gpscan_classify").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::write(dir_path.join("main.rs"), "fn main() {}").expect("Failed to write");
+    fs::write(dir_path.join("scan"), b"\x89PNG\r\n\x1a\n").expect("Failed to write");
+
+    let kinds = |mode| {
+        let options = Options::builder().classify(mode).build();
+        let root = gpscan::scan_to_tree(dir_path, &options).expect("Failed to scan");
+        let mut kinds: Vec<(String, String)> = root
+            .children
+            .iter()
+            .map(|child| (child.name.clone(), child.attributes[0].1.clone()))
+            .collect();
+        kinds.sort();
+        kinds
+    };
+    assert_eq!(
+        kinds(ClassifyMode::Extension),
+        [
+            ("main.rs".to_string(), "code".to_string()),
+            ("scan".to_string(), "other".to_string())
+        ]
+    );
+    // Magic bytes are only read for files whose extension is not known
+    assert_eq!(
+        kinds(ClassifyMode::Magic),
+        [
+            ("main.rs".to_string(), "code".to_string()),
+            ("scan".to_string(), "image".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_uniform_types() {
+    assert_eq!(
+        gpscan::classify::uniform_type("IMG_0001.JPG"),
+        Some("public.jpeg")
+    );
+    assert_eq!(gpscan::classify::uniform_type("data.unknown"), None);
+
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/movie.mov", 100);
+    source.add_file("/data/README", 10);
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .uniform_types(true)
+        .build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    assert!(xml_output.contains(r#" type="com.apple.quicktime-movie"/>"#));
+    // Files without a known extension have no type
+    assert_eq!(xml_output.matches(" type=").count(), 1);
+}
+
+#[test]
+fn test_custom_attributes() {
+    let mut source = MemorySource::new("/data");
+    source.add_file("/data/projects/alpha/report.pdf", 100);
+    source.add_file("/data/scratch/tmp.bin", 10);
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .attributes(|path, metadata| {
+            let mut attributes = vec![
+                ("size".to_string(), "1".to_string()),
+                ("bad name".to_string(), "x".to_string()),
+            ];
+            if path.starts_with("/data/projects") {
+                attributes.push(("project".to_string(), "alpha & co".to_string()));
+            }
+            if metadata.kind == EntryKind::File {
+                attributes.push(("retention".to_string(), "1y".to_string()));
+            }
+            attributes
+        })
+        .build();
+    let mut output = Vec::new();
+    scan(Path::new("/data"), &options, &mut output).expect("Failed to scan");
+    let xml_output = String::from_utf8(output).expect("Output is not UTF-8");
+    gpscan::validate::validate(xml_output.as_bytes()).expect("Invalid dump");
+    assert!(xml_output.contains(r#"<Folder name="alpha" "#));
+    // Both folders below projects and the file in it
+    assert_eq!(
+        xml_output.matches(r#" project="alpha &amp; co""#).count(),
+        3
+    );
+    assert_eq!(xml_output.matches(r#" retention="1y""#).count(), 2);
+    assert!(!xml_output.contains(r#" size="1""#));
+    assert!(!xml_output.contains("bad name"));
+}
+
+#[test]
+fn test_profile_dirs() {
+    let temp_dir = TempDir::new("gpscan_profile").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::create_dir_all(dir_path.join("a").join("b")).expect("Failed to create dir");
+    fs::write(dir_path.join("a").join("b").join("file.txt"), "x").expect("Failed to write");
+    fs::create_dir(dir_path.join("c")).expect("Failed to create dir");
+
+    let (_, stats) = gpscan::scan_to_result(dir_path, &Options::default()).expect("Failed to scan");
+    assert!(stats.slowest_dirs.is_empty());
+
+    let options = Options::builder().profile_dirs(2).build();
+    let (_, stats) = gpscan::scan_to_result(dir_path, &options).expect("Failed to scan");
+    // The subtree of the root contains all others, so it is the slowest
+    assert_eq!(stats.slowest_dirs.len(), 2);
+    assert_eq!(stats.slowest_dirs[0].path, dir_path);
+    assert!(stats.slowest_dirs[0].total >= stats.slowest_dirs[1].total);
+    for dir in &stats.slowest_dirs {
+        assert!(dir.own <= dir.total);
+    }
+}
+
+#[test]
+fn test_skip_dir_entries() {
+    let mut source = MemorySource::new("/data");
+    for i in 0..5 {
+        source.add_file(format!("/data/spool/{}", i), 10);
+    }
+    source.add_file("/data/docs/a.txt", 10);
+
+    let options = Options::builder()
+        .source(Arc::new(source))
+        .skip_dir_entries(4)
+        .build();
+    assert_eq!(
+        filters::skip_folder_entries(5, &options),
+        Some(SkipReason::LargeFolder)
+    );
+    assert_eq!(filters::skip_folder_entries(4, &options), None);
+
+    let (result, stats) =
+        gpscan::scan_to_result(Path::new("/data"), &options).expect("Failed to scan");
+    let spool = result
+        .root
+        .children
+        .iter()
+        .find(|child| child.name == "spool")
+        .expect("Large folder is missing");
+    assert!(spool.children.is_empty());
+    assert_eq!(spool.attribute("skippedEntries"), Some("5"));
+    assert_eq!(result.root.file_count(), 1);
+    assert_eq!(stats.skipped.get(SkipReason::LargeFolder), 1);
+}
+
+/// A file on a disk that fills up after `limit` bytes.
+#[cfg(feature = "cli")]
+struct FillingFile {
+    file: File,
+    limit: usize,
+}
+
+#[cfg(feature = "cli")]
+impl Write for FillingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.limit == 0 {
+            return Err(io::ErrorKind::StorageFull.into());
+        }
+        let written = self.file.write(&buf[..buf.len().min(self.limit)])?;
+        self.limit -= written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_output_file_disk_full() {
+    use gpscan::output_file::OutputFile;
+
+    let temp_dir = TempDir::new("gpscan_output_file").expect("Failed to create temp dir");
+    let path = temp_dir.path().join("scan.gpscan");
+    let fallback = temp_dir.path().join("fallback.gpscan");
+    let data: Vec<u8> = (0..100u8).collect();
+    let filling = |limit| FillingFile {
+        file: File::create(&path).expect("Failed to create the output"),
+        limit,
+    };
+
+    // The bytes written so far are copied to the fallback, and the rest follows
+    let mut output = OutputFile::new(filling(40), &path, Some(&fallback));
+    output
+        .write_all(&data)
+        .expect("Failed to continue in the fallback");
+    output.flush().expect("Failed to flush");
+    assert_eq!(output.path(), fallback);
+    drop(output);
+    assert_eq!(
+        fs::read(&fallback).expect("Failed to read the fallback"),
+        data
+    );
+    assert!(!path.exists());
+    fs::remove_file(&fallback).expect("Failed to remove the fallback");
+
+    // Without a fallback, the incomplete file is removed
+    let mut output = OutputFile::new(filling(40), &path, None);
+    let error = output.write_all(&data).expect_err("Wrote to a full disk");
+    assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+    drop(output);
+    assert!(!path.exists());
+
+    // Only the disk of the output is reported as full, not that of a temporary file
+    assert_eq!(
+        gpscan::Error::Output(error).exit_code(),
+        gpscan::exit_code::OUTPUT_FULL
+    );
+    let temp_error = io::Error::from(io::ErrorKind::StorageFull);
+    assert_eq!(
+        gpscan::Error::Io(temp_error).exit_code(),
+        gpscan::exit_code::OUTPUT_ERROR
+    );
+
+    // A complete file is kept
+    let mut output = OutputFile::new(filling(100), &path, None);
+    output.write_all(&data).expect("Failed to write");
+    drop(output);
+    assert_eq!(fs::read(&path).expect("Failed to read the output"), data);
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "s3")]
+
+use gpscan::s3::{put_object, S3Credentials};
+use gpscan::{scan_s3, NodeKind, Options, S3Config, S3Location};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Serves the given responses to consecutive requests and returns the request heads.
+fn serve(responses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for body in responses {
+            let (mut stream, _) = listener.accept().expect("Failed to accept");
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).expect("Failed to read request");
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            requests.push(head);
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .expect("Failed to write response");
+        }
+        requests
+    });
+    (endpoint, handle)
+}
+
+#[test]
+fn test_scan_s3_bucket() {
+    let first_page = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+<Name>backups</Name><Prefix>hosts/</Prefix><IsTruncated>true</IsTruncated>
+<NextContinuationToken>page 2</NextContinuationToken>
+<Contents><Key>hosts/web/db.sql.gz</Key><LastModified>2024-03-01T12:00:00.000Z</LastModified><Size>5000</Size></Contents>
+<Contents><Key>hosts/empty/</Key><LastModified>2024-03-01T12:00:00.000Z</LastModified><Size>0</Size></Contents>
+</ListBucketResult>"#;
+    let second_page = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+<Name>backups</Name><Prefix>hosts/</Prefix><IsTruncated>false</IsTruncated>
+<Contents><Key>hosts/web/logs/a &amp; b.log</Key><LastModified>2024-03-02T08:30:00.000Z</LastModified><Size>300</Size></Contents>
+</ListBucketResult>"#;
+    let (endpoint, server) = serve(vec![first_page, second_page]);
+
+    let location: S3Location = "s3://backups/hosts/".parse().expect("Invalid location");
+    assert_eq!(location.prefix, "hosts");
+    let config = S3Config {
+        region: "eu-west-1".to_string(),
+        endpoint: Some(endpoint),
+        credentials: Some(S3Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: None,
+        }),
+    };
+
+    let result = scan_s3(&location, &config, &Options::default()).expect("Failed to scan");
+    assert_eq!(result.info.volume_path, "s3://backups");
+    assert_eq!(result.info.volume_size, 5300);
+    assert_eq!(result.info.file_size_measure, "logical");
+
+    // Keys are folded into folders below the prefix, and folder markers are dropped when empty
+    let root = &result.root;
+    assert_eq!(root.name, "s3://backups/hosts");
+    assert_eq!(root.total_size(), 5300);
+    assert_eq!(root.children.len(), 1);
+    let web = &root.children[0];
+    assert_eq!(web.name, "web");
+    assert_eq!(web.children[0].name, "db.sql.gz");
+    assert_eq!(web.children[1].kind, NodeKind::Folder);
+    assert_eq!(web.children[1].children[0].name, "a & b.log");
+    assert_eq!(
+        web.children[0].modified.map(|t| t.to_rfc3339()),
+        Some("2024-03-01T12:00:00+00:00".to_string())
+    );
+
+    let requests = server.join().expect("Server failed");
+    assert!(requests[0].starts_with("GET /backups?list-type=2&prefix=hosts%2F HTTP/1.1"));
+    assert!(requests[0].contains("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+    assert!(requests[0].contains("/eu-west-1/s3/aws4_request"));
+    assert!(requests[1].contains("continuation-token=page%202&list-type=2"));
+}
+
+#[test]
+fn test_put_object() {
+    use sha2::{Digest, Sha256};
+    use std::io::{Read, Seek};
+
+    let (endpoint, server) = serve(vec![""]);
+    let config = S3Config {
+        region: "us-east-1".to_string(),
+        endpoint: Some(endpoint),
+        credentials: Some(S3Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: Some("token".to_string()),
+        }),
+    };
+
+    let mut file = tempfile::tempfile().expect("Failed to create file");
+    file.write_all(b"dump contents").unwrap();
+    let location: S3Location = "s3://results/daily/scan 1.gpscan.gz".parse().unwrap();
+    put_object(&location, &config, &mut file).expect("Failed to upload");
+
+    let requests = server.join().expect("Server failed");
+    assert!(requests[0].starts_with("PUT /results/daily/scan%201.gpscan.gz HTTP/1.1"));
+    let payload_hash = hex::encode(Sha256::digest(b"dump contents"));
+    assert!(requests[0].contains(&format!("x-amz-content-sha256: {}", payload_hash)));
+    assert!(requests[0].contains("x-amz-security-token: token"));
+    assert!(requests[0]
+        .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token"));
+
+    // The file is left readable for the caller
+    file.rewind().unwrap();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "dump contents");
+
+    // An object key is required
+    let bucket: S3Location = "s3://results".parse().unwrap();
+    assert!(put_object(&bucket, &config, &mut file).is_err());
+}
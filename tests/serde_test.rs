@@ -0,0 +1,24 @@
+#![cfg(feature = "serde")]
+
+use gpscan::{scan_to_tree, FileNode, NodeKind, Options};
+use std::fs;
+use tempdir::TempDir;
+
+#[test]
+fn test_file_node_json_round_trip() {
+    let temp_dir = TempDir::new("gpscan_serde_test").expect("Failed to create temp dir");
+    let dir_path = temp_dir.path();
+    fs::create_dir(dir_path.join("sub")).expect("Failed to create sub");
+    fs::write(dir_path.join("sub").join("data.bin"), "12345").expect("Failed to write file");
+
+    let options = Options::builder().apparent_size(true).build();
+    let root = scan_to_tree(dir_path, &options).expect("Failed to scan");
+
+    let json = serde_json::to_string(&root).expect("Failed to serialize");
+    assert!(json.contains(r#""kind":"folder""#));
+    assert!(json.contains(r#""name":"data.bin""#));
+
+    let parsed: FileNode = serde_json::from_str(&json).expect("Failed to deserialize");
+    assert_eq!(parsed, root);
+    assert_eq!(parsed.children[0].children[0].kind, NodeKind::File);
+}